@@ -0,0 +1,12 @@
+fn main() {
+  // only the grpc feature has anything to generate
+  #[cfg(feature = "grpc")]
+  {
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("no protoc for this platform"));
+
+    tonic_build::configure()
+      .build_client(false)
+      .compile(&["proto/spotify_info.proto"], &["proto"])
+      .expect("failed to compile proto/spotify_info.proto");
+  }
+}
@@ -0,0 +1,18 @@
+use spotify_info::{SpotifyEvent, SpotifyListener, SpotifyMessage};
+
+#[tokio::main]
+async fn main() {
+  // Create listener, it keeps accepting connections in the background
+  let (mut events, messages) = SpotifyListener::bind_default().await.unwrap().into_channel(64);
+
+  // Every event in order, nothing gets skipped
+  while let Some(event) = events.recv().await {
+    match event {
+      SpotifyEvent::TrackChanged(info) => println!("Changed track to {}", info.title),
+      SpotifyEvent::StateChanged(state) => println!("Changed state to {}", state),
+      // Sends messages back from anywhere, this likes the track again whenever it gets unliked
+      SpotifyEvent::LikedChanged(false) => messages.send(SpotifyMessage::SetLiked(true)).await.unwrap(),
+      event => println!("Got {}", event.kind()),
+    }
+  }
+}
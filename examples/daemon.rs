@@ -0,0 +1,26 @@
+use futures_util::{pin_mut, StreamExt};
+use spotify_info::{Backoff, ListenerState, SpotifyEvent, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  let listener = SpotifyListener::builder()
+    // Only matters if the listener itself breaks, reconnecting spotify is instant
+    .backoff(Backoff::default())
+    .bind()
+    .await
+    .unwrap();
+
+  // Never ends, so there's no retry loop around it
+  let states = listener.states();
+  pin_mut!(states);
+
+  while let Some(state) = states.next().await {
+    match state {
+      ListenerState::WaitingForSpotify => println!("Waiting for spotify"),
+      ListenerState::Connected(info) => println!("Connected {}", info),
+      ListenerState::Lost(reason) => println!("Lost connection ({})", reason),
+      ListenerState::Event(SpotifyEvent::TrackChanged(info)) => println!("Changed track to {}", info.title),
+      ListenerState::Event(_) => {}
+    }
+  }
+}
@@ -7,9 +7,22 @@ async fn main() {
   let listener = SpotifyListener::bind_default().await.unwrap();
 
   // Listen for incoming connections, if spotify closes, the loop keeps listening
-  while let Ok(mut connection) = listener.get_connection().await {
+  loop {
+    let mut connection = match listener.get_connection().await {
+      Ok(connection) => connection,
+      // For example an outdated extension, wait for the next one
+      Err(err) if err.is_connection_error() => {
+        eprintln!("{}", err);
+        continue;
+      }
+      Err(err) => panic!("{}", err),
+    };
+
+    println!("Connected {} with protocol version {}", connection.info(), connection.protocol_version());
 
     connection.set_progress_interval(Duration::from_secs(1)).await.unwrap();
+    // Get the current state right away instead of waiting for something to change
+    connection.request_state().await.unwrap();
 
     while let Some(Ok(event)) = connection.next().await {
       match event {
@@ -19,7 +32,31 @@ async fn main() {
         SpotifyEvent::StateChanged(state) => println!("Changed state to {}", state),
         // Gets called on a set interval, wont get called if player is paused or stopped,
         // Value is a percentage of the position between 0 and 1
-        SpotifyEvent::ProgressChanged(time) => println!("Changed progress to {}", time)
+        SpotifyEvent::ProgressChanged { percent, .. } => println!("Changed progress to {}", percent),
+        // Gets called when user changes the volume
+        SpotifyEvent::VolumeChanged(volume) => println!("Changed volume to {}", volume),
+        // Gets called after requesting the state
+        SpotifyEvent::StateSnapshot(snapshot) => println!("Player is {}", snapshot.state),
+        // Gets called when a podcast plays faster or slower
+        SpotifyEvent::PlaybackRateChanged(rate) => println!("Changed playback rate to {}", rate),
+        // Gets called when user turns shuffle on or off
+        SpotifyEvent::ShuffleChanged(shuffle) => println!("Changed shuffle to {}", shuffle),
+        // Gets called when user changes the repeat mode
+        SpotifyEvent::RepeatChanged(repeat) => println!("Changed repeat to {}", repeat),
+        // Gets called when playback moves to another device
+        SpotifyEvent::DeviceChanged { name, .. } => println!("Playing on {}", name),
+        // Gets called when the track plays from a different playlist, album, etc.
+        SpotifyEvent::ContextChanged(context) => println!("Playing from {}", context.uri),
+        // Gets called with the lyrics of the new track
+        SpotifyEvent::LyricsChanged(lines) => println!("Got {} lines of lyrics", lines.len()),
+        // Gets called when the current line of the lyrics changes
+        SpotifyEvent::LyricLineChanged(line) => println!("Changed lyrics line to {}", line),
+        // Gets called with colors from the cover art of the new track
+        SpotifyEvent::ColorsChanged(colors) => println!("Changed colors to {:?}", colors.vibrant),
+        // Gets called when user likes or unlikes the track
+        SpotifyEvent::LikedChanged(liked) => println!("Changed liked to {}", liked),
+        // Events added after this example was written
+        event => println!("Got {}", event.kind()),
       }
     }
   }
@@ -0,0 +1,14 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use spotify_info::{GrpcServer, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  // Create listener and share its events
+  let events = SpotifyListener::bind_default().await.unwrap().events(64);
+
+  // Try it with `grpcurl -plaintext -import-path proto -proto spotify_info.proto 127.0.0.1:19533 spotify_info.Spotify/WatchEvents`
+  let server = GrpcServer::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 19533))).await.unwrap();
+
+  // Commands sent with SendCommand go to spotify while it's connected
+  server.serve(events).await.unwrap();
+}
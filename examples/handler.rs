@@ -0,0 +1,35 @@
+use std::time::Duration;
+use spotify_info::{ConnectionInfo, DisconnectReason, SpotifyEventHandler, SpotifyListener, TrackInfo, TrackState};
+
+// Only the callbacks that are implemented do anything
+struct Printer;
+
+impl SpotifyEventHandler for Printer {
+  fn on_connect(&mut self, info: &ConnectionInfo) {
+    println!("Connected {}", info);
+  }
+
+  fn on_disconnect(&mut self, reason: &DisconnectReason) {
+    println!("Disconnected: {}", reason);
+  }
+
+  fn on_track_changed(&mut self, info: &TrackInfo) {
+    println!("Changed track to {}", info.title);
+  }
+
+  fn on_state_changed(&mut self, state: TrackState) {
+    println!("Changed state to {}", state);
+  }
+
+  fn on_progress(&mut self, position: Duration, _: f64) {
+    println!("At {}s", position.as_secs());
+  }
+}
+
+#[tokio::main]
+async fn main() {
+  let listener = SpotifyListener::bind_default().await.unwrap();
+
+  // Calls the handler for every connection, one at a time
+  listener.run(Printer).await.unwrap();
+}
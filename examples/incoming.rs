@@ -0,0 +1,28 @@
+use spotify_info::SpotifyEvent;
+
+fn main() {
+  // Create a blocking listener, no async runtime needed
+  let server = spotify_info::Listener::new().unwrap();
+
+  // Blocks until the next event, if spotify closes, it waits for it to connect again
+  for event in server.incoming() {
+    match event {
+      Ok(SpotifyEvent::TrackChanged(info)) => println!("Changed track to {}", info.title),
+      Ok(SpotifyEvent::StateChanged(state)) => println!("Changed state to {}", state),
+      Ok(SpotifyEvent::ProgressChanged { percent, .. }) => println!("Changed progress to {}", percent),
+      Ok(SpotifyEvent::VolumeChanged(volume)) => println!("Changed volume to {}", volume),
+      Ok(SpotifyEvent::StateSnapshot(snapshot)) => println!("Player is {}", snapshot.state),
+      Ok(SpotifyEvent::PlaybackRateChanged(rate)) => println!("Changed playback rate to {}", rate),
+      Ok(SpotifyEvent::ShuffleChanged(shuffle)) => println!("Changed shuffle to {}", shuffle),
+      Ok(SpotifyEvent::RepeatChanged(repeat)) => println!("Changed repeat to {}", repeat),
+      Ok(SpotifyEvent::DeviceChanged { name, .. }) => println!("Playing on {}", name),
+      Ok(SpotifyEvent::ContextChanged(context)) => println!("Playing from {}", context.uri),
+      Ok(SpotifyEvent::LyricsChanged(lines)) => println!("Got {} lines of lyrics", lines.len()),
+      Ok(SpotifyEvent::LyricLineChanged(line)) => println!("Changed lyrics line to {}", line),
+      Ok(SpotifyEvent::ColorsChanged(colors)) => println!("Changed colors to {:?}", colors.vibrant),
+      Ok(SpotifyEvent::LikedChanged(liked)) => println!("Changed liked to {}", liked),
+      Ok(event) => println!("Got {}", event.kind()),
+      Err(err) => eprintln!("{}", err),
+    }
+  }
+}
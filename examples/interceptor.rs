@@ -0,0 +1,53 @@
+use spotify_info::{Interceptor, SpotifyEvent, SpotifyListener, SpotifyMessage};
+
+// Skips explicit tracks before the app ever sees them
+#[derive(Clone)]
+struct NoExplicit;
+
+impl Interceptor for NoExplicit {
+  fn on_event(&mut self, event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+    match &event {
+      SpotifyEvent::TrackChanged(info) if info.explicit == Some(true) => println!("Dropped {}", info.title),
+      _ => events.push(event),
+    }
+  }
+}
+
+// Asks for bigger covers and logs everything that gets sent
+#[derive(Clone)]
+struct BigCovers;
+
+impl Interceptor for BigCovers {
+  fn on_event(&mut self, mut event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+    if let SpotifyEvent::TrackChanged(info) = &mut event {
+      if let Some(cover) = info.cover_art() {
+        info.cover_url = Some(cover.large());
+      }
+    }
+
+    events.push(event);
+  }
+
+  fn on_message(&mut self, message: SpotifyMessage) -> Option<SpotifyMessage> {
+    println!("Sending {:?}", message);
+    Some(message)
+  }
+}
+
+#[tokio::main]
+async fn main() {
+  let listener = SpotifyListener::builder()
+    .interceptor(NoExplicit)
+    .interceptor(BigCovers)
+    .bind()
+    .await
+    .unwrap();
+
+  let mut connection = listener.get_connection().await.unwrap();
+
+  connection.request_state().await.unwrap();
+
+  while let Some(Ok(event)) = connection.next().await {
+    println!("{:?}", event);
+  }
+}
@@ -0,0 +1,29 @@
+use futures_util::{pin_mut, StreamExt};
+use spotify_info::{ConnectionEvent, Keepalive, SpotifyEvent, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  // Create listener, the builder also has things like an auth token or TLS
+  let listener = SpotifyListener::builder()
+    // Notice when spotify freezes or the machine goes to sleep
+    .keepalive(Some(Keepalive::default()))
+    .max_connections(Some(1))
+    .bind()
+    .await
+    .unwrap();
+
+  // One stream for connects, disconnects and every event in between
+  let events = listener.connection_events();
+  pin_mut!(events);
+
+  println!("Spotify not running");
+
+  while let Some(event) = events.next().await {
+    match event {
+      ConnectionEvent::Connected => println!("Spotify connected"),
+      ConnectionEvent::Disconnected { reason } => println!("Spotify not running ({})", reason),
+      ConnectionEvent::Event(SpotifyEvent::TrackChanged(info)) => println!("Changed track to {}", info.title),
+      ConnectionEvent::Event(_) => {}
+    }
+  }
+}
@@ -0,0 +1,25 @@
+use std::time::Duration;
+use spotify_info::{SpotifyListener, TrackHandle};
+
+#[tokio::main]
+async fn main() {
+  // Create listener
+  let listener = SpotifyListener::bind_default().await.unwrap();
+
+  // Handles are cheap to clone, every clone reads the same track
+  let handle = TrackHandle::default();
+  let main_handle = handle.clone();
+
+  // Read the current track from another thread without owning the connection
+  std::thread::spawn(move || loop {
+    let info = handle.read();
+
+    println!("{} by {} is {}", info.title, info.artist(), info.state);
+
+    drop(info);
+    std::thread::sleep(Duration::from_secs(1));
+  });
+
+  // Keeps listening for connections and updates the handle with every event
+  listener.listen(main_handle).await.unwrap();
+}
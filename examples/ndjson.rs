@@ -0,0 +1,14 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use spotify_info::{NdjsonServer, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  // Create listener and share its events
+  let events = SpotifyListener::bind_default().await.unwrap().events(64);
+
+  // Try it with `nc 127.0.0.1 19533`
+  let server = NdjsonServer::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 19533))).await.unwrap();
+
+  // Every event gets sent as one line of json to everything connected
+  server.serve(events).await.unwrap();
+}
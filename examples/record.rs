@@ -0,0 +1,16 @@
+use spotify_info::{EventRecorder, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  let path = std::env::args().nth(1).unwrap_or_else(|| "session.jsonl".to_string());
+
+  // Starts a new file every 10 MiB and keeps the last 3
+  let mut recorder = EventRecorder::create(&path).unwrap().rotate(10 * 1024 * 1024, 3);
+  let (mut events, _) = SpotifyListener::bind_default().await.unwrap().into_channel(64);
+
+  println!("Recording to {}", path);
+
+  while let Some(event) = events.recv().await {
+    recorder.record(&event).unwrap();
+  }
+}
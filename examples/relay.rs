@@ -0,0 +1,14 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use spotify_info::{RelayServer, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  // Create listener and share its events
+  let events = SpotifyListener::bind_default().await.unwrap().events(64);
+
+  // Point an OBS browser source (or anything else) at ws://127.0.0.1:19533
+  let server = RelayServer::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 19533))).await.unwrap();
+
+  // Every client gets the current state when it connects, then every event
+  server.serve(events).await.unwrap();
+}
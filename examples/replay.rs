@@ -0,0 +1,19 @@
+use spotify_info::{EventReplayer, SpotifyEvent};
+
+#[tokio::main]
+async fn main() {
+  // Made with examples/record.rs
+  let path = std::env::args().nth(1).unwrap_or_else(|| "session.jsonl".to_string());
+
+  // Twice as fast, starts over at the end
+  let mut connection = EventReplayer::open(&path).unwrap().speed(2.0).repeat(true).connection().await.unwrap();
+
+  // Same as a connection from the extension
+  while let Some(event) = connection.next().await {
+    match event.unwrap() {
+      SpotifyEvent::TrackChanged(info) => println!("Changed track to {}", info.title),
+      SpotifyEvent::StateChanged(state) => println!("Changed state to {}", state),
+      event => println!("Got {}", event.kind()),
+    }
+  }
+}
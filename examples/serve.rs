@@ -0,0 +1,15 @@
+use spotify_info::{SpotifyEvent, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  let listener = SpotifyListener::bind_default().await.unwrap();
+
+  // Reconnecting is handled for you, this only returns if the listener breaks
+  listener.serve(|event| async move {
+    match event {
+      SpotifyEvent::TrackChanged(info) => println!("Changed track to {} by {}", info.title, info.artist()),
+      SpotifyEvent::StateChanged(state) => println!("Changed state to {}", state),
+      _ => {}
+    }
+  }).await.unwrap();
+}
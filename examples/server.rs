@@ -0,0 +1,35 @@
+use std::time::Duration;
+use spotify_info::{Aggregation, SpotifyEvent, SpotifyListener, SpotifyMessage, SpotifyServer};
+
+#[tokio::main]
+async fn main() {
+  // Every spotify that connects stays connected, instead of only the latest one
+  let mut server = SpotifyServer::new(SpotifyListener::bind_default().await.unwrap());
+
+  // Show whatever is playing when there's more than one
+  server.set_aggregation(Aggregation::PreferPlaying);
+
+  while let Some((id, event)) = server.next().await {
+    match event {
+      SpotifyEvent::TrackChanged(info) => println!("{} changed track to {}", id, info.title),
+      SpotifyEvent::StateChanged(state) => println!("{} changed state to {}", id, state),
+      SpotifyEvent::VolumeChanged(_) => {
+        // Commands go to one connection
+        let message = SpotifyMessage::SetProgressUpdateInterval(Duration::from_secs(5));
+        let _ = server.send(id, message).await;
+      }
+      SpotifyEvent::LikedChanged(true) => {
+        // Or to all of them
+        let count = server.broadcast(SpotifyMessage::Pause).await;
+        println!("Paused {} connections", count);
+      }
+      _ => {}
+    }
+
+    println!("Connections: {:?}", server.connections());
+
+    if let Some((id, player)) = server.current() {
+      println!("Showing {} which is {}", id, player.state);
+    }
+  }
+}
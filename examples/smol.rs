@@ -0,0 +1,50 @@
+use std::time::Duration;
+use smol::net::TcpListener;
+use spotify_info::{BoxFuture, Runtime, SpotifyListener, SpotifyEvent};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+// Everything the listener needs from smol
+struct Smol;
+
+impl Runtime for Smol {
+  fn spawn(&self, task: BoxFuture) {
+    smol::spawn(task).detach();
+  }
+
+  fn sleep(&self, duration: Duration) -> BoxFuture {
+    Box::pin(async move {
+      smol::Timer::after(duration).await;
+    })
+  }
+}
+
+fn main() {
+  smol::block_on(async {
+    // Binding needs tokio, so the socket comes from smol and the acceptor does the rest
+    let acceptor = SpotifyListener::builder().runtime(Smol).acceptor().unwrap();
+    let listener = TcpListener::bind("127.0.0.1:19532").await.unwrap();
+
+    loop {
+      let (stream, peer) = listener.accept().await.unwrap();
+
+      // smol streams use the futures io traits, compat turns them into tokio ones
+      let mut connection = match acceptor.accept(stream.compat(), Some(peer)).await {
+        Ok(connection) => connection,
+        Err(err) => {
+          eprintln!("{}", err);
+          continue;
+        }
+      };
+
+      println!("Connected {}", connection.info());
+
+      while let Some(Ok(event)) = connection.next().await {
+        match event {
+          SpotifyEvent::TrackChanged(info) => println!("Changed track to {}", info.title),
+          SpotifyEvent::StateChanged(state) => println!("Changed state to {}", state),
+          event => println!("Got {}", event.kind()),
+        }
+      }
+    }
+  });
+}
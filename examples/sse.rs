@@ -0,0 +1,14 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use spotify_info::{HttpServer, SpotifyListener};
+
+#[tokio::main]
+async fn main() {
+  // Create listener and share its events
+  let events = SpotifyListener::bind_default().await.unwrap().events(64);
+
+  // Open http://127.0.0.1:19533/events in a browser or use EventSource,
+  // http://127.0.0.1:19533/now-playing has the current state as json
+  let server = HttpServer::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 19533))).await.unwrap();
+
+  server.serve(events).await.unwrap();
+}
@@ -0,0 +1,9 @@
+// cargo run --example typescript --features ts-rs
+fn main() {
+  // Next to the extension, so editors pick it up for spotify_info.js
+  let path = std::env::args().nth(1).unwrap_or_else(|| "extension/spotify_info.d.ts".to_string());
+
+  std::fs::write(&path, spotify_info::typescript()).unwrap();
+
+  println!("Wrote {}", path);
+}
@@ -0,0 +1,259 @@
+// Generated by spotify_info 0.5.0 for schema version 1, don't edit it by hand
+
+export type SpotifyEvent = { "type": "TrackChanged", "data": TrackInfo } | { "type": "StateChanged", "data": TrackState } | { "type": "ProgressChanged", "data": { 
+/**
+ * Position in the current track
+ */
+position: number, 
+/**
+ * Percentage of the position between 0 and 1
+ */
+percent: number, 
+/**
+ * When the position was measured by the extension
+ */
+timestamp: number, } } | { "type": "VolumeChanged", "data": number } | { "type": "StateSnapshot", "data": PlayerSnapshot } | { "type": "PlaybackRateChanged", "data": number } | { "type": "ShuffleChanged", "data": boolean } | { "type": "RepeatChanged", "data": RepeatMode } | { "type": "DeviceChanged", "data": { 
+/**
+ * Name of the device, like "Kitchen Speaker"
+ */
+name: string, 
+/**
+ * ID of the device
+ */
+id: string, 
+/**
+ * If it's the spotify client the extension runs in
+ */
+is_local: boolean, 
+/**
+ * Volume of the device between 0 and 1
+ */
+volume: number, } } | { "type": "ContextChanged", "data": PlaybackContext } | { "type": "LyricsChanged", "data": Array<LyricLine> } | { "type": "LyricLineChanged", "data": number } | { "type": "ColorsChanged", "data": CoverColors } | { "type": "LikedChanged", "data": boolean } | { "type": "TrackFinished", "data": TrackInfo } | { "type": "TrackSkipped", "data": { 
+/**
+ * The track that got skipped
+ */
+track: TrackInfo, 
+/**
+ * How far into the track it got skipped
+ */
+at: number, } } | { "type": "ScrobblePoint", "data": TrackInfo } | { "type": "SessionStarted" } | { "type": "SessionEnded", "data": { 
+/**
+ * From when the session started to when playback stopped, without the gap
+ */
+duration: number, 
+/**
+ * How many different tracks played in the session
+ */
+track_count: number, } };
+
+export type SpotifyMessage = { "type": "SetProgressUpdateInterval", "data": number } | { "type": "Play" } | { "type": "Pause" } | { "type": "TogglePlayback" } | { "type": "Next" } | { "type": "Previous" } | { "type": "Seek", "data": { position_ms: number, } } | { "type": "SeekPercent", "data": { percent: number, } } | { "type": "SetVolume", "data": number } | { "type": "SetShuffle", "data": boolean } | { "type": "SetRepeat", "data": RepeatMode } | { "type": "SetLiked", "data": boolean } | { "type": "RequestState" } | { "type": "SetSubscriptions", "data": EventMask };
+
+export type TrackInfo = { 
+/**
+ * UID of track
+ */
+uid: string, 
+/**
+ * URI of track
+ */
+uri: SpotifyUri, 
+/**
+ * State of the track
+ */
+state: TrackState, 
+/**
+ * Duration of the track
+ */
+duration: number, 
+/**
+ * Title of the track
+ */
+title: string, 
+/**
+ * Album of the track
+ */
+album: Album, 
+/**
+ * Vec since there can be multiple artists, in the order spotify lists them
+ */
+artists: Array<Artist>, 
+/**
+ * Cover art of the track, option because it may not exist
+ */
+cover_url: string | null, 
+/**
+ * Background art of the track, option because it may nto exist
+ * (when you hit the "full screen" thing in the bottom-right corner of spotify)
+ */
+background_url: string | null, 
+/**
+ * Looping "Canvas" video of the track, option because most tracks don't have one
+ */
+canvas: string | null, 
+/**
+ * If the track is in Liked Songs, option because older extensions don't send it
+ */
+liked: boolean | null, 
+/**
+ * Position of the track on its disc, starting at 1
+ */
+track_number: number | null, 
+/**
+ * Disc of the album the track is on, starting at 1
+ */
+disc_number: number | null, 
+/**
+ * If the track is marked as explicit
+ */
+explicit: boolean | null, 
+/**
+ * Popularity between 0 and 100, higher is more popular
+ */
+popularity: number | null, 
+/**
+ * If it's a song or a podcast episode
+ */
+kind: MediaKind, 
+/**
+ * If it's a local file instead of something from spotify,
+ * local files don't have a cover, album or artist URIs
+ */
+is_local: boolean, 
+/**
+ * Only exists for podcast episodes,
+ * [Self::album] is the show and [Self::artists] is the publisher for those
+ */
+episode: EpisodeInfo | null, };
+
+export type TrackState = 0 | 1 | 2;
+
+export type RepeatMode = 0 | 1 | 2;
+
+export type MediaKind = "track" | "episode";
+
+export type Artist = { 
+/**
+ * Name of the artist
+ */
+name: string, 
+/**
+ * URI of the artist, empty if it's not known (like for local files)
+ */
+uri: SpotifyUri, };
+
+export type Album = { 
+/**
+ * Name of the album
+ */
+name: string, 
+/**
+ * URI of the album, empty if it's not known (like for local files)
+ */
+uri: SpotifyUri, 
+/**
+ * Release date as spotify gives it, can be just the year (`2012`),
+ * the month (`2012-03`) or the full date (`2012-03-14`)
+ */
+release_date: string | null, 
+/**
+ * Cover art at different sizes
+ */
+covers: AlbumCovers, };
+
+export type AlbumCovers = { 
+/**
+ * Usually 64x64
+ */
+small: string | null, 
+/**
+ * Usually 300x300
+ */
+medium: string | null, 
+/**
+ * Usually 640x640
+ */
+large: string | null, };
+
+export type EpisodeInfo = { 
+/**
+ * Name of the show (podcast) the episode belongs to
+ */
+show: string, 
+/**
+ * URI of the show
+ */
+show_uri: SpotifyUri, 
+/**
+ * Publisher of the show
+ */
+publisher: string | null, 
+/**
+ * Description of the episode, plain text
+ */
+description: string | null, };
+
+export type PlayerSnapshot = { 
+/**
+ * Current track, [None] if nothing has played yet
+ */
+track: TrackInfo | null, 
+/**
+ * State of the player
+ */
+state: TrackState, 
+/**
+ * Position in the current track
+ */
+position: number, 
+/**
+ * Volume between 0 and 1
+ */
+volume: number, 
+/**
+ * If shuffle is on
+ */
+shuffle: boolean, 
+/**
+ * Repeat mode
+ */
+repeat: RepeatMode, 
+/**
+ * How fast it's playing, 1 is normal speed, only podcasts can change it
+ */
+playback_rate: number, 
+/**
+ * What the track is playing from
+ */
+context: PlaybackContext | null, };
+
+export type PlaybackContext = { kind: ContextKind, 
+/**
+ * URI of the context
+ */
+uri: SpotifyUri, 
+/**
+ * Name of the context, like "Discover Weekly", may not exist
+ */
+name: string | null, };
+
+export type ContextKind = "playlist" | "album" | "artist" | "show" | "collection" | "other";
+
+export type LyricLine = { 
+/**
+ * When the line starts in the track
+ */
+start: number, 
+/**
+ * Text of the line, empty for instrumental breaks
+ */
+text: string, };
+
+export type CoverColors = { vibrant: string | null, dark_vibrant: string | null, light_vibrant: string | null, prominent: string | null, desaturated: string | null, 
+/**
+ * Vibrant but less saturated, good for backgrounds behind text
+ */
+vibrant_non_alarming: string | null, };
+
+export type SpotifyUri = string;
+
+export type EventMask = number;
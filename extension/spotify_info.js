@@ -6,12 +6,30 @@
 
 // ----- SETTINGS -----
 
+// Change this if the other end runs on a different machine,
+// or to "[::1]" on machines without IPv4
+//
+// default: "127.0.0.1"
+const host = "127.0.0.1";
+
+// Use wss:// instead of ws://, only works if the other end has TLS set up
+// with a certificate that spotify trusts
+//
+// default: false
+const secure = false;
+
 // Change this if you want to use a custom port
-// make sure to also change it on the other end
+// make sure to also change it on the other end,
+// if the other end lets the OS pick a port it's in spotify_info/listener.json in the cache directory
 //
 // default: 19532
 const port = 19532;
 
+// Token to send when connecting, only needed if the other end was set up with one
+//
+// default: ""
+const authToken = "";
+
 // How often should this check for connections?
 //
 // default: 1000
@@ -24,8 +42,50 @@ const checkConnectionInterval = 1000;
 // default: 1000
 let progressUpdateInterval = 1000;
 
+// How often should this check which line of the lyrics is playing in milliseconds
+//
+// default: 100
+const lyricsUpdateInterval = 100;
+
 // --------------------
 
+// Sent when connecting, has to be between MIN_PROTOCOL_VERSION and PROTOCOL_VERSION on the other end
+const PROTOCOL_VERSION = 3;
+
+// Sent when connecting so the other end can tell extensions apart, same as the crate version
+const EXTENSION_VERSION = "0.5.0";
+
+// Has to match EventMask on the other end,
+// events that aren't in here are always sent
+const EVENT_MASK = {
+  TrackChanged: 1 << 0,
+  StateChanged: 1 << 1,
+  ProgressChanged: 1 << 2,
+  VolumeChanged: 1 << 3,
+  PlaybackRateChanged: 1 << 4,
+  ShuffleChanged: 1 << 5,
+  RepeatChanged: 1 << 6,
+  DeviceChanged: 1 << 7,
+  ContextChanged: 1 << 8,
+  LyricsChanged: 1 << 9,
+  LyricLineChanged: 1 << 10,
+  ColorsChanged: 1 << 11,
+  LikedChanged: 1 << 12,
+};
+
+// Which events to send, can be changed by the other end
+let subscriptions = 0xFFFFFFFF;
+
+// Messages at least this long get gzipped when the other end can read them,
+// smaller ones aren't worth it
+const COMPRESSION_THRESHOLD = 1024;
+
+// If the other end said it can read gzipped messages
+let compression = false;
+
+// Compressing is async, everything gets sent through this so nothing overtakes anything else
+let sendQueue = Promise.resolve();
+
 function SpotifyInfo() {
   if (!Spicetify.CosmosAsync || !Spicetify.Platform) {
     setTimeout(SpotifyInfo, 500);
@@ -42,10 +102,93 @@ function SpotifyInfo() {
     duration: undefined,
     title: undefined,
     album: undefined,
-    artist: undefined,
+    artists: undefined,
     cover: undefined,
     background: undefined
   };
+  let volume;
+  let playbackRate = 1;
+  let shuffle;
+  let repeat;
+  let device;
+  let context;
+  let lyrics = [];
+  let lyricLine = -1;
+  let colors;
+
+  async function updateColors(uri) {
+    let extracted;
+
+    try {
+      extracted = await Spicetify.colorExtractor(uri);
+    } catch (e) {
+      extracted = undefined;
+    }
+
+    colors = {
+      vibrant: extracted?.VIBRANT ?? null,
+      dark_vibrant: extracted?.DARK_VIBRANT ?? null,
+      light_vibrant: extracted?.LIGHT_VIBRANT ?? null,
+      prominent: extracted?.PROMINENT ?? null,
+      desaturated: extracted?.DESATURATED ?? null,
+      vibrant_non_alarming: extracted?.VIBRANT_NON_ALARMING ?? null
+    };
+
+    // the track might have changed again while waiting
+    if (uri === storage.uri) {
+      send("ColorsChanged", colors);
+    }
+  }
+
+  async function updateLyrics(uri) {
+    lyrics = [];
+    lyricLine = -1;
+
+    const id = uri?.startsWith("spotify:track:") ? uri.split(":")[2] : undefined;
+
+    if (id) {
+      try {
+        const res = await Spicetify.CosmosAsync.get(`https://spclient.wg.spotify.com/color-lyrics/v2/track/${id}?format=json&vocalRemoval=false`);
+
+        if (res?.lyrics?.syncType === "LINE_SYNCED") {
+          lyrics = res.lyrics.lines.map(line => ({ start: Number(line.startTimeMs), text: line.words === "♪" ? "" : line.words }));
+        }
+      } catch (e) {
+        lyrics = [];
+      }
+    }
+
+    // the track might have changed again while waiting
+    if (uri === storage.uri) {
+      send("LyricsChanged", lyrics);
+    }
+  }
+
+  // spotify:playlist:<id>, spotify:user:<name>:collection, ...
+  function contextKind(uri) {
+    const parts = uri.split(":");
+
+    if (parts[1] === "user" && parts[3] === "collection") {
+      return "collection";
+    }
+
+    return ["playlist", "album", "artist", "show", "collection"].includes(parts[1]) ? parts[1] : "other";
+  }
+
+  // spotify:image:<id> to a url, null for local files or missing images
+  function imageUrl(image) {
+    if (!image || image.indexOf("localfile") !== -1) {
+      return null;
+    }
+
+    return "https://i.scdn.co/image/" + image.substring(image.lastIndexOf(":") + 1);
+  }
+
+  function optionalNumber(value) {
+    const n = Number.parseInt(value);
+
+    return isNaN(n) ? null : n;
+  }
 
   async function updateStorage(data) {
     if (!data?.track?.metadata) {
@@ -53,6 +196,23 @@ function SpotifyInfo() {
     }
 
     const meta = data.track.metadata;
+
+    if (data.context_uri && data.context_uri !== context?.uri) {
+      context = {
+        kind: contextKind(data.context_uri),
+        uri: data.context_uri,
+        name: data.context_metadata?.context_description ?? null
+      };
+
+      send("ContextChanged", context);
+    }
+    const rate = Number(data.playback_speed ?? data.speed ?? 1) || 1;
+
+    if (rate !== playbackRate) {
+      playbackRate = rate;
+      send("PlaybackRateChanged", playbackRate);
+    }
+
     const local = {
       uid: undefined,
       uri: undefined,
@@ -60,9 +220,18 @@ function SpotifyInfo() {
       duration: undefined,
       title: undefined,
       album: undefined,
-      artist: undefined,
+      artists: undefined,
       cover: undefined,
-      background: undefined
+      background: undefined,
+      track_number: undefined,
+      disc_number: undefined,
+      explicit: undefined,
+      popularity: undefined,
+      kind: undefined,
+      episode: undefined,
+      is_local: undefined,
+      canvas: undefined,
+      liked: undefined
     };
 
     local.uid = data.track.uid;
@@ -70,12 +239,89 @@ function SpotifyInfo() {
     local.state = data.is_paused ? 1 : 2;
     local.duration = meta.duration;
     local.title = meta.title;
-    local.album = meta.album_title;
-    local.artist = meta.artist_name;
+    local.album = {
+      name: meta.album_title,
+      uri: meta.album_uri ?? "",
+      release_date: null,
+      covers: {
+        small: imageUrl(meta.image_small_url),
+        medium: imageUrl(meta.image_url),
+        large: imageUrl(meta.image_large_url ?? meta.image_xlarge_url)
+      }
+    };
+    local.artists = [];
+
+    // extra artists are stored as artist_name:1, artist_name:2, ...
+    for (let i = 0; ; i++) {
+      const suffix = i === 0 ? "" : `:${i}`;
+      const name = meta[`artist_name${suffix}`];
+
+      if (name === undefined) {
+        break;
+      }
 
-    const cover = meta.image_xlarge_url;
+      local.artists.push({ name, uri: meta[`artist_uri${suffix}`] ?? "" });
+    }
 
-    local.cover = cover?.indexOf("localfile") === -1 ? "https://i.scdn.co/image/" + cover.substring(cover.lastIndexOf(":") + 1) : undefined;
+    local.cover = imageUrl(meta.image_xlarge_url) ?? undefined;
+    // metadata values are all strings
+    local.track_number = optionalNumber(meta.album_track_number);
+    local.disc_number = optionalNumber(meta.album_disc_number);
+    local.explicit = meta.is_explicit === undefined ? null : meta.is_explicit === "true";
+    local.popularity = optionalNumber(meta.popularity);
+    // only tracks with a canvas have these
+    local.liked = Spicetify.Player.getHeart?.() ?? null;
+    local.canvas = meta["canvas.type"]?.startsWith("VIDEO") ? meta["canvas.url"] ?? null : null;
+    local.kind = local.uri?.startsWith("spotify:episode:") ? "episode" : "track";
+    local.is_local = Boolean(local.uri?.startsWith("spotify:local:"));
+
+    // spotify:local:<artist>:<album>:<title>:<duration in seconds>
+    if (local.is_local) {
+      const [artist, album, title, seconds] = local.uri.split(":").slice(2).map(it => decodeURIComponent(it.replace(/\+/g, " ")));
+
+      local.title = local.title || title || "";
+      local.album.name = local.album.name || album || "";
+      local.duration = local.duration || Number(seconds) * 1000 || 0;
+
+      if (local.artists.length === 0 && artist) {
+        local.artists.push({ name: artist, uri: "" });
+      }
+    }
+    local.episode = null;
+
+    // the player doesn't know the description, so ask the web api
+    if (local.kind === "episode" && local.uid !== storage.uid) {
+      local.episode = {
+        show: meta.album_title ?? "",
+        show_uri: meta.album_uri ?? "",
+        publisher: meta.artist_name ?? null,
+        description: null
+      };
+
+      try {
+        const res = await Spicetify.CosmosAsync.get(`https://api.spotify.com/v1/episodes/${local.uri.split(":")[2]}`);
+
+        local.episode.show = res.show?.name ?? local.episode.show;
+        local.episode.show_uri = res.show?.uri ?? local.episode.show_uri;
+        local.episode.publisher = res.show?.publisher ?? local.episode.publisher;
+        local.episode.description = res.description ?? null;
+      } catch (e) {
+        local.episode.description = null;
+      }
+    }
+
+    try {
+      const albumId = meta.album_uri?.split(":")[2];
+
+      // only needed when the track changes
+      if (albumId && local.uid !== storage.uid) {
+        const res = await Spicetify.CosmosAsync.get(`https://api.spotify.com/v1/albums/${albumId}`);
+
+        local.album.release_date = res.release_date ?? null;
+      }
+    } catch (e) {
+      local.album.release_date = null;
+    }
 
     try {
       const res = await Spicetify.CosmosAsync.get(
@@ -90,67 +336,235 @@ function SpotifyInfo() {
     // so it doesn't spam multiple messages
     if (local.uid !== storage.uid) {
       storage = local;
-      ws_data = [
-        local.uid,
-        local.uri,
-        local.state ?? 0,
-        local.duration,
-        // just for some weird edge case it messes things up
-        local.title.replace(";", "${#{#{SEMI_COLON}#}#}$"),
-        local.album.replace(";", "${#{#{SEMI_COLON}#}#}$"),
-        local.artist.replace(";", "${#{#{SEMI_COLON}#}#}$"),
-        local.cover ?? "NONE",
-        local.background ?? "NONE"
-      ].join(";");
-
-      if (ws_connected) {
-        ws.send(`TRACK_CHANGED;${ws_data}`);
-      }
+      ws_data = {
+        uid: local.uid,
+        uri: local.uri,
+        state: local.state ?? 0,
+        duration: Number(local.duration),
+        title: local.title,
+        album: local.album,
+        artists: local.artists,
+        cover_url: local.cover ?? null,
+        background_url: local.background ?? null,
+        track_number: local.track_number,
+        disc_number: local.disc_number,
+        explicit: local.explicit,
+        popularity: local.popularity,
+        kind: local.kind,
+        episode: local.episode,
+        is_local: local.is_local,
+        canvas: local.canvas,
+        liked: local.liked
+      };
+
+      send("TrackChanged", ws_data);
+      updateLyrics(local.uri);
+      updateColors(local.uri);
     } else if (local.state !== storage.state) {
       storage.state = local.state;
 
-      if (ws_connected) {
-        ws.send(`STATE_CHANGED;${local.state ?? 0}`);
+      send("StateChanged", local.state ?? 0);
 
-        if (storage.state !== 2) {
-          ws.send(`PROGRESS_CHANGED;${Spicetify.Player.getProgressPercent()}`);
-        }
+      if (storage.state !== 2) {
+        sendProgress();
       }
     }
   }
 
+  // types are generated by the crate, `cargo run --example typescript --features ts-rs`
+  /** @param {import("./spotify_info").SpotifyEvent["type"]} type */
+  function send(type, data) {
+    const mask = EVENT_MASK[type];
+
+    if (mask !== undefined && (subscriptions & mask) === 0) {
+      return;
+    }
+
+    if (ws_connected) {
+      sendText(JSON.stringify({ type, data }));
+    }
+  }
+
+  function sendText(text) {
+    const payload = compression && text.length >= COMPRESSION_THRESHOLD ? gzip(text) : text;
+
+    sendQueue = sendQueue
+      .then(() => payload)
+      .then((payload) => ws_connected && ws.send(payload))
+      .catch((e) => console.error("spotify_info: failed to send a message", e));
+  }
+
+  function gzip(text) {
+    const stream = new Blob([text]).stream().pipeThrough(new CompressionStream("gzip"));
+
+    return new Response(stream).arrayBuffer();
+  }
+
+  function sendProgress() {
+    send("ProgressChanged", {
+      position: Spicetify.Player.getProgress(),
+      percent: Spicetify.Player.getProgressPercent(),
+      timestamp: Date.now()
+    });
+  }
+
   Spicetify.CosmosAsync.sub("sp://player/v2/main", updateStorage);
 
   function init() {
     ws_connected = false;
-    ws = new WebSocket(`ws://127.0.0.1:${port}`);
+    ws = new WebSocket(`${secure ? "wss" : "ws"}://${host}:${port}`);
 
     ws.onopen = () => {
       ws_connected = true;
-      if (ws_data) ws.send(`TRACK_CHANGED;${ws_data}`);
+      // has to be the first message
+      ws.send(JSON.stringify({
+        type: "Hello",
+        data: {
+          protocol_version: PROTOCOL_VERSION,
+          token: authToken || undefined,
+          compression: typeof CompressionStream !== "undefined" ? ["gzip"] : undefined,
+          extension_version: EXTENSION_VERSION,
+          client: Spicetify.Platform?.version ? `Spotify ${Spicetify.Platform.version}` : "Spotify"
+        }
+      }));
+      if (ws_data) send("TrackChanged", ws_data);
+      if (volume !== undefined) send("VolumeChanged", volume);
+      if (playbackRate !== 1) send("PlaybackRateChanged", playbackRate);
+      if (shuffle !== undefined) send("ShuffleChanged", shuffle);
+      if (repeat !== undefined) send("RepeatChanged", repeat);
+      if (device !== undefined) send("DeviceChanged", device);
+      if (context !== undefined) send("ContextChanged", context);
+      if (lyrics.length !== 0) send("LyricsChanged", lyrics);
+      if (colors !== undefined) send("ColorsChanged", colors);
     };
 
-    ws.onclose = () => {
+    ws.onclose = (event) => {
       ws_connected = false;
+
+      // policy violation, the token is wrong
+      if (event.code === 1008) {
+        console.error(`spotify_info: connection rejected, ${event.reason}`);
+      }
+
+      // try again later, the other end already has as many connections as it allows
+      if (event.code === 1013) {
+        console.warn(`spotify_info: connection rejected, ${event.reason}`);
+      }
+
+      // the next connection might want different events
+      subscriptions = 0xFFFFFFFF;
+      compression = false;
       setTimeout(init, checkConnectionInterval);
     };
 
 
     ws.onmessage = (message) => {
-      /**
-       * @type {Array<string>}
-       */
-      let data = message.data.split(";");
+      let msg;
 
-      if (data.length === 0) {
+      try {
+        msg = JSON.parse(message.data);
+      } catch (e) {
         return;
       }
 
-      if (data[0] === "SET_PROGRESS_INTERVAL") {
-        let n = Number.parseInt(data[1]);
+      switch (msg?.type) {
+        case "Hello": {
+          const { min_version, max_version } = msg.data ?? {};
+
+          compression = msg.data?.compression === "gzip";
+
+          if (PROTOCOL_VERSION < min_version || PROTOCOL_VERSION > max_version) {
+            console.error(`spotify_info: protocol version ${PROTOCOL_VERSION} isn't supported, the other end supports ${min_version} to ${max_version}`);
+          }
+          break;
+        }
+        case "SetProgressUpdateInterval": {
+          let n = Number.parseInt(msg.data);
+
+          if (!isNaN(n)) {
+            progressUpdateInterval = n;
+          }
+          break;
+        }
+        case "Play":
+          Spicetify.Player.play();
+          break;
+        case "Pause":
+          Spicetify.Player.pause();
+          break;
+        case "TogglePlayback":
+          Spicetify.Player.togglePlay();
+          break;
+        case "Next":
+          Spicetify.Player.next();
+          break;
+        case "Previous":
+          Spicetify.Player.back();
+          break;
+        case "Seek": {
+          const ms = Number(msg.data?.position_ms);
+
+          // Player.seek treats anything <= 1 as a percentage
+          if (!isNaN(ms)) {
+            Spicetify.Player.origin?.seekTo ? Spicetify.Player.origin.seekTo(ms) : Spicetify.Player.seek(Math.max(ms, 2));
+          }
+          break;
+        }
+        case "SetVolume": {
+          const volume = Number(msg.data);
 
-        if (!isNaN(n)) {
-          progressUpdateInterval = n;
+          if (!isNaN(volume)) {
+            Spicetify.Player.setVolume(Math.min(Math.max(volume, 0), 1));
+          }
+          break;
+        }
+        case "SetShuffle":
+          Spicetify.Player.setShuffle(Boolean(msg.data));
+          break;
+        case "SetRepeat": {
+          const mode = Number(msg.data);
+
+          // 0 = off, 1 = context, 2 = track
+          Spicetify.Player.setRepeat(mode === 1 || mode === 2 ? mode : 0);
+          break;
+        }
+        case "SetLiked": {
+          const liked = Boolean(msg.data);
+
+          if (Spicetify.Player.setHeart) {
+            Spicetify.Player.setHeart(liked);
+          } else if (Spicetify.Player.getHeart() !== liked) {
+            Spicetify.Player.toggleHeart();
+          }
+          break;
+        }
+        case "RequestState":
+          send("StateSnapshot", {
+            track: ws_data ? { ...ws_data, state: storage.state ?? 0 } : null,
+            state: storage.state ?? 0,
+            position: Spicetify.Player.getProgress(),
+            volume: Spicetify.Player.getVolume(),
+            shuffle: Spicetify.Player.getShuffle(),
+            repeat: Spicetify.Player.getRepeat(),
+            playback_rate: playbackRate,
+            context: context ?? null
+          });
+          break;
+        case "SetSubscriptions": {
+          const n = Number(msg.data);
+
+          if (!isNaN(n)) {
+            subscriptions = n;
+          }
+          break;
+        }
+        case "SeekPercent": {
+          const percent = Number(msg.data?.percent);
+
+          if (!isNaN(percent)) {
+            Spicetify.Player.seek(Math.min(Math.max(percent, 0), 1));
+          }
+          break;
         }
       }
     };
@@ -159,8 +573,52 @@ function SpotifyInfo() {
   init();
 
   const progressInterval = () => {
-    if (ws_connected && storage.state === 2) {
-      ws.send(`PROGRESS_CHANGED;${Spicetify.Player.getProgressPercent()}`)
+    if (storage.state === 2) {
+      sendProgress();
+    }
+
+    // there's no event for volume, shuffle or repeat changes, so check them along with progress
+    const currentVolume = Spicetify.Player.getVolume();
+    const currentShuffle = Spicetify.Player.getShuffle();
+    const currentRepeat = Spicetify.Player.getRepeat();
+
+    if (currentVolume !== volume) {
+      volume = currentVolume;
+      send("VolumeChanged", volume);
+    }
+
+    if (currentShuffle !== shuffle) {
+      shuffle = currentShuffle;
+      send("ShuffleChanged", shuffle);
+    }
+
+    if (currentRepeat !== repeat) {
+      repeat = currentRepeat;
+      send("RepeatChanged", repeat);
+    }
+
+    const currentLiked = Spicetify.Player.getHeart?.();
+
+    // changing track already sends it with the track
+    if (ws_data && currentLiked !== undefined && currentLiked !== ws_data.liked) {
+      ws_data.liked = currentLiked;
+      send("LikedChanged", currentLiked);
+    }
+
+    const activeDevice = Spicetify.Platform?.ConnectAPI?.state?.activeDevice;
+
+    if (activeDevice && activeDevice.id !== device?.id) {
+      // older clients give the volume between 0 and 65535
+      const deviceVolume = Number(activeDevice.volume ?? 0);
+
+      device = {
+        name: activeDevice.name ?? "",
+        id: activeDevice.id,
+        is_local: Boolean(activeDevice.isLocal ?? activeDevice.is_local),
+        volume: deviceVolume > 1 ? deviceVolume / 65535 : deviceVolume
+      };
+
+      send("DeviceChanged", device);
     }
 
     setTimeout(progressInterval, progressUpdateInterval)
@@ -168,6 +626,27 @@ function SpotifyInfo() {
 
   setTimeout(progressInterval, progressUpdateInterval);
 
+  setInterval(() => {
+    if (lyrics.length === 0 || storage.state !== 2) {
+      return;
+    }
+
+    const position = Spicetify.Player.getProgress();
+    let line = -1;
+
+    while (line + 1 < lyrics.length && lyrics[line + 1].start <= position) {
+      line++;
+    }
+
+    if (line !== lyricLine) {
+      lyricLine = line;
+
+      if (line !== -1) {
+        send("LyricLineChanged", line);
+      }
+    }
+  }, lyricsUpdateInterval);
+
   window.onbeforeunload = () => {
     ws_connected = false;
     ws.onclose = null;
@@ -0,0 +1,206 @@
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::future::{join, select, Either};
+use futures_util::{SinkExt, StreamExt};
+use ipnet::IpNet;
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream};
+use tokio_tungstenite::{accept_hdr_async_with_config, WebSocketStream};
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::Message;
+
+use crate::intercept::Layers;
+use crate::keepalive::{IdleTimer, KeepaliveTimer};
+use crate::runtime::Runtime;
+use crate::session::SessionTracker;
+use crate::throttle::ProgressThrottle;
+use crate::transport::Accepted;
+use crate::{handshake, Codec, ConnectionSlot, Keepalive, ParseMode, SpotifyConnection, SpotifyError, SpotifyResult, SpotifyStream};
+
+/// Everything a [SpotifyListener](crate::SpotifyListener) does after accepting a stream,
+/// without the part that accepts it, made with [SpotifyListenerBuilder::acceptor](crate::SpotifyListenerBuilder::acceptor)
+///
+/// Works with any stream on any [Runtime], so it's what to use when the listener
+/// can't bind the socket itself, like with a runtime other than tokio
+pub struct SpotifyAcceptor {
+  pub(crate) keepalive: Option<Keepalive>,
+  pub(crate) idle_timeout: Option<Duration>,
+  pub(crate) progress_throttle: Option<Duration>,
+  pub(crate) parse_mode: ParseMode,
+  pub(crate) session_gap: Option<Duration>,
+  pub(crate) auth_token: Option<String>,
+  pub(crate) handshake_timeout: Duration,
+  pub(crate) allowed_origins: Vec<String>,
+  pub(crate) allowed_peers: Vec<IpNet>,
+  pub(crate) max_connections: Option<usize>,
+  pub(crate) codecs: Vec<Codec>,
+  pub(crate) connections: Arc<AtomicUsize>,
+  pub(crate) ws_config: WebSocketConfig,
+  pub(crate) runtime: Arc<dyn Runtime>,
+  pub(crate) layers: Layers,
+  #[cfg(any(feature = "tls", feature = "native-tls"))]
+  pub(crate) tls: Option<crate::tls::TlsAcceptor>,
+}
+
+impl SpotifyAcceptor {
+  /// Does the websocket handshake on `stream` and waits for the extension to say hello,
+  /// fails the same ways as [SpotifyListener::get_connection](crate::SpotifyListener::get_connection)
+  ///
+  /// `peer` gets checked against [SpotifyListenerBuilder::allowed_peers](crate::SpotifyListenerBuilder::allowed_peers),
+  /// tls from the builder isn't used here, wrap the stream before if it needs it
+  pub async fn accept<S>(&self, stream: S, peer: Option<SocketAddr>) -> SpotifyResult<SpotifyConnection<Codec, S>>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    self.with_timeout(peer, |slot| self.handshake(stream, peer, slot)).await
+  }
+
+  /// A connection over an in-memory stream instead of a socket, for tests that shouldn't bind ports
+  ///
+  /// The other end is the extension's websocket, the hellos are already exchanged so everything sent on it
+  /// has to be in `codec` (or be json, that always works), it comes from the first allowed origin and has the token
+  ///
+  /// ```text
+  /// let (mut connection, mut extension) = acceptor.duplex(Codec::Json).await?;
+  ///
+  /// extension.send(Message::Text(r#"{"type":"StateChanged","data":2}"#.into())).await?;
+  /// connection.next().await;
+  /// ```
+  pub async fn duplex(&self, codec: Codec) -> SpotifyResult<(SpotifyConnection<Codec, DuplexStream>, WebSocketStream<DuplexStream>)> {
+    let (listener, extension) = tokio::io::duplex(64 * 1024);
+    let extension = handshake::connect_client(extension, "ws://duplex/", &self.allowed_origins, self.auth_token.as_deref(), "duplex", codec);
+
+    match join(self.accept(listener, None), extension).await {
+      (Ok(connection), Ok(extension)) => Ok((connection, extension)),
+      (Err(err), _) | (_, Err(err)) => Err(err),
+    }
+  }
+
+  /// Same as [Self::accept] but with tls from the builder, for streams from the listener's own socket
+  pub(crate) async fn accept_transport(&self, stream: Accepted) -> SpotifyResult<SpotifyConnection> {
+    let peer = stream.peer();
+
+    self.with_timeout(peer, |slot| async move {
+      let stream = self.wrap(stream).await?;
+
+      self.handshake(stream, peer, slot).await
+    }).await
+  }
+
+  /// How many connections are open right now,
+  /// including ones still in the handshake, they stop counting once they're dropped
+  pub fn connection_count(&self) -> usize {
+    self.connections.load(Ordering::Acquire)
+  }
+
+  async fn with_timeout<T, F, Fut>(&self, peer: Option<SocketAddr>, handshake: F) -> SpotifyResult<T>
+  where
+    F: FnOnce(Option<ConnectionSlot>) -> Fut,
+    Fut: Future<Output = SpotifyResult<T>>,
+  {
+    // unix sockets are only limited by file permissions
+    if let Some(peer) = peer {
+      if !handshake::is_peer_allowed(peer, &self.allowed_peers) {
+        return Err(SpotifyError::PeerNotAllowed(peer));
+      }
+    }
+
+    let handshake = std::pin::pin!(handshake(self.reserve_slot()));
+
+    match select(handshake, self.runtime.sleep(self.handshake_timeout)).await {
+      Either::Left((connection, _)) => connection,
+      Either::Right(_) => Err(SpotifyError::Timeout),
+    }
+  }
+
+  /// [None] when there's already [SpotifyListenerBuilder::max_connections](crate::SpotifyListenerBuilder::max_connections)
+  fn reserve_slot(&self) -> Option<ConnectionSlot> {
+    ConnectionSlot::reserve(&self.connections, self.max_connections)
+  }
+
+  async fn wrap(&self, stream: Accepted) -> SpotifyResult<SpotifyStream> {
+    let stream = match stream {
+      #[cfg(any(feature = "tls", feature = "native-tls"))]
+      Accepted::Tcp(stream, _) if self.tls.is_some() => self.tls.as_ref().expect("checked above").accept(stream).await?,
+      Accepted::Tcp(stream, _) => SpotifyStream::Plain(stream),
+      #[cfg(all(unix, feature = "unix"))]
+      Accepted::Unix(stream) => SpotifyStream::Unix(stream),
+      #[cfg(all(windows, feature = "named-pipe"))]
+      Accepted::NamedPipe(pipe) => SpotifyStream::NamedPipe(pipe),
+    };
+
+    Ok(stream)
+  }
+
+  async fn handshake<S>(&self, stream: S, peer: Option<SocketAddr>, slot: Option<ConnectionSlot>) -> SpotifyResult<SpotifyConnection<Codec, S>>
+  where
+    S: AsyncRead + AsyncWrite + Unpin,
+  {
+    let mut rejected = None;
+    // the error type comes from tungstenite
+    #[allow(clippy::result_large_err)]
+    let check_origin = |request: &_, response| match handshake::check_origin(request, &self.allowed_origins) {
+      Ok(()) => Ok(response),
+      Err(origin) => {
+        rejected = Some(origin);
+        Err(handshake::forbidden())
+      }
+    };
+
+    let mut ws = match accept_hdr_async_with_config(stream, check_origin, Some(self.ws_config)).await {
+      Ok(ws) => ws,
+      Err(_) if rejected.is_some() => return Err(SpotifyError::OriginNotAllowed(rejected.flatten())),
+      Err(err) => return Err(SpotifyError::Handshake(Box::new(err))),
+    };
+
+    // upgrade first so it gets a close code it understands instead of a failed upgrade
+    let slot = match slot {
+      Some(slot) => slot,
+      None => {
+        let _ = ws.close(Some(handshake::too_many_connections())).await;
+        return Err(SpotifyError::TooManyConnections);
+      }
+    };
+
+    let hello = loop {
+      match ws.next().await {
+        Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+        Some(Ok(Message::Close(_))) | None => return Err(SpotifyError::Closed),
+        Some(Ok(message)) => break handshake::parse(&message),
+        Some(Err(err)) => return Err(err.into()),
+      }
+    };
+
+    if let Err(err) = handshake::authenticate(hello.as_ref(), self.auth_token.as_deref()) {
+      let _ = ws.close(Some(handshake::unauthorized())).await;
+      return Err(err);
+    }
+
+    let codec = handshake::codec(hello.as_ref(), &self.codecs);
+
+    ws.send(handshake::reply(hello.as_ref(), codec)).await?;
+
+    match handshake::check(hello.as_ref()) {
+      Ok(protocol_version) => Ok(SpotifyConnection {
+        ws,
+        info: handshake::info(hello, peer, protocol_version),
+        codec,
+        parse_mode: self.parse_mode,
+        keepalive: self.keepalive.map(|config| KeepaliveTimer::new(config, self.runtime.clone())),
+        idle: self.idle_timeout.map(|timeout| IdleTimer::new(timeout, self.runtime.clone())),
+        interceptors: self.layers.build(),
+        throttle: self.progress_throttle.map(|spacing| ProgressThrottle::new(spacing, self.runtime.clone())),
+        sessions: self.session_gap.map(|gap| SessionTracker::new(gap, self.runtime.clone())),
+        _slot: slot,
+      }),
+      Err(err) => {
+        // the extension already knows why from the reply
+        let _ = ws.close(None).await;
+        Err(err)
+      }
+    }
+  }
+}
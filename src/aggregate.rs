@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+
+use crate::{ConnectionId, PlayerSnapshot, SpotifyEvent, TrackState};
+
+/// How [SpotifyServer::current](crate::SpotifyServer::current) picks one connection
+/// when more than one spotify is connected
+///
+/// Default: LastActive
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Aggregation {
+  /// Whichever connection changed something last, progress updates don't count
+  #[default]
+  LastActive,
+  /// Whichever connection is playing, the last active one if more than one is,
+  /// same as [Self::LastActive] when nothing is playing
+  PreferPlaying,
+  /// Only ever this connection, nothing while it isn't connected
+  Pinned(ConnectionId),
+}
+
+#[derive(Debug, Default)]
+struct Player {
+  snapshot: PlayerSnapshot,
+  last_active: u64,
+}
+
+/// Keeps the state of every connection to pick one with [Aggregation]
+#[derive(Debug, Default)]
+pub(crate) struct Aggregator {
+  pub(crate) policy: Aggregation,
+  players: HashMap<ConnectionId, Player>,
+  /// Goes up with every event, so the last active connection has the highest
+  tick: u64,
+}
+
+impl Aggregator {
+  pub(crate) fn insert(&mut self, id: ConnectionId) {
+    self.players.insert(id, Player::default());
+  }
+
+  pub(crate) fn remove(&mut self, id: ConnectionId) {
+    self.players.remove(&id);
+  }
+
+  pub(crate) fn apply(&mut self, id: ConnectionId, event: &SpotifyEvent) {
+    let player = match self.players.get_mut(&id) {
+      Some(player) => player,
+      None => return,
+    };
+
+    player.snapshot.apply(event);
+
+    // a playing connection sends these all the time, so it'd always look active
+    if !matches!(event, SpotifyEvent::ProgressChanged { .. }) {
+      self.tick += 1;
+      player.last_active = self.tick;
+    }
+  }
+
+  pub(crate) fn current(&self) -> Option<(ConnectionId, &PlayerSnapshot)> {
+    match self.policy {
+      Aggregation::LastActive => last_active(self.players.iter()),
+      Aggregation::PreferPlaying => {
+        let playing = self.players.iter().filter(|(_, player)| player.snapshot.state == TrackState::Playing);
+
+        last_active(playing).or_else(|| last_active(self.players.iter()))
+      }
+      Aggregation::Pinned(id) => self.players.get(&id).map(|player| (id, &player.snapshot)),
+    }
+  }
+}
+
+/// Ties go to the newer connection
+fn last_active<'a>(players: impl Iterator<Item = (&'a ConnectionId, &'a Player)>) -> Option<(ConnectionId, &'a PlayerSnapshot)> {
+  players
+    .max_by_key(|(id, player)| (player.last_active, **id))
+    .map(|(id, player)| (*id, &player.snapshot))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, SystemTime};
+
+  use super::*;
+
+  fn aggregator(policy: Aggregation) -> (Aggregator, ConnectionId, ConnectionId) {
+    let mut aggregator = Aggregator { policy, ..Aggregator::default() };
+    let (first, second) = (ConnectionId::next(), ConnectionId::next());
+
+    aggregator.insert(first);
+    aggregator.insert(second);
+
+    (aggregator, first, second)
+  }
+
+  fn current(aggregator: &Aggregator) -> Option<ConnectionId> {
+    aggregator.current().map(|(id, _)| id)
+  }
+
+  fn state(state: TrackState) -> SpotifyEvent {
+    SpotifyEvent::StateChanged(state)
+  }
+
+  fn progress() -> SpotifyEvent {
+    SpotifyEvent::ProgressChanged { position: Duration::from_secs(1), percent: 0.0, timestamp: SystemTime::now() }
+  }
+
+  #[test]
+  fn last_active_wins() {
+    let (mut aggregator, first, second) = aggregator(Aggregation::LastActive);
+
+    aggregator.apply(second, &SpotifyEvent::VolumeChanged(0.5));
+    aggregator.apply(first, &SpotifyEvent::VolumeChanged(0.5));
+    assert_eq!(current(&aggregator), Some(first));
+
+    aggregator.apply(second, &state(TrackState::Paused));
+    assert_eq!(current(&aggregator), Some(second));
+    assert_eq!(aggregator.current().unwrap().1.state, TrackState::Paused);
+  }
+
+  #[test]
+  fn progress_doesnt_count() {
+    let (mut aggregator, first, second) = aggregator(Aggregation::LastActive);
+
+    aggregator.apply(first, &state(TrackState::Playing));
+    aggregator.apply(second, &state(TrackState::Paused));
+    aggregator.apply(first, &progress());
+
+    assert_eq!(current(&aggregator), Some(second));
+    // it's still applied though
+    assert_eq!(aggregator.players[&first].snapshot.position, Duration::from_secs(1));
+  }
+
+  #[test]
+  fn ties_go_to_the_newer_connection() {
+    let (aggregator, _, second) = aggregator(Aggregation::LastActive);
+
+    assert_eq!(current(&aggregator), Some(second));
+  }
+
+  #[test]
+  fn prefers_playing() {
+    let (mut aggregator, first, second) = aggregator(Aggregation::PreferPlaying);
+
+    aggregator.apply(first, &state(TrackState::Playing));
+    aggregator.apply(second, &state(TrackState::Paused));
+    assert_eq!(current(&aggregator), Some(first));
+
+    // both playing is the last active of them
+    aggregator.apply(second, &state(TrackState::Playing));
+    assert_eq!(current(&aggregator), Some(second));
+
+    // nothing playing is the last active one
+    aggregator.apply(second, &state(TrackState::Stopped));
+    aggregator.apply(first, &state(TrackState::Paused));
+    assert_eq!(current(&aggregator), Some(first));
+  }
+
+  #[test]
+  fn pinned_is_only_that_one() {
+    // the ids only exist once they're connected
+    let (mut aggregator, first, second) = aggregator(Aggregation::LastActive);
+    aggregator.policy = Aggregation::Pinned(first);
+
+    aggregator.apply(second, &state(TrackState::Playing));
+    assert_eq!(current(&aggregator), Some(first));
+
+    aggregator.remove(first);
+    assert_eq!(current(&aggregator), None);
+  }
+
+  #[test]
+  fn removed_and_unknown_connections() {
+    let (mut aggregator, first, second) = aggregator(Aggregation::LastActive);
+
+    aggregator.apply(second, &state(TrackState::Playing));
+    aggregator.remove(second);
+    assert_eq!(current(&aggregator), Some(first));
+
+    // events for connections it doesn't know about are ignored
+    aggregator.apply(second, &state(TrackState::Playing));
+    assert_eq!(current(&aggregator), Some(first));
+
+    aggregator.remove(first);
+    assert_eq!(current(&aggregator), None);
+  }
+}
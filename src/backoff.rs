@@ -0,0 +1,61 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How long [SpotifyListener::states](crate::SpotifyListener::states) waits before accepting again
+/// after the listener itself failed, doubles every time it fails in a row
+///
+/// Every wait is somewhere between half and all of the current delay,
+/// so listeners that failed at the same time don't all retry at the same time
+///
+/// Default: starts at 100 milliseconds and goes up to 30 seconds
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Backoff {
+  /// Delay after the first failure
+  pub initial: Duration,
+  /// Delay never goes above this
+  pub max: Duration,
+}
+
+impl Default for Backoff {
+  fn default() -> Self {
+    Self {
+      initial: Duration::from_millis(100),
+      max: Duration::from_secs(30),
+    }
+  }
+}
+
+/// Counts failures in a row for a [Backoff]
+#[derive(Debug)]
+pub(crate) struct BackoffTimer {
+  backoff: Backoff,
+  failures: u32,
+}
+
+impl BackoffTimer {
+  pub(crate) fn new(backoff: Backoff) -> Self {
+    Self { backoff, failures: 0 }
+  }
+
+  pub(crate) fn reset(&mut self) {
+    self.failures = 0;
+  }
+
+  /// How long to wait after another failure
+  pub(crate) fn next_delay(&mut self) -> Duration {
+    let delay = self.backoff.initial.saturating_mul(1 << self.failures.min(31)).min(self.backoff.max);
+
+    self.failures = self.failures.saturating_add(1);
+
+    delay / 2 + delay.mul_f64(random() / 2.0)
+  }
+}
+
+/// Between 0 and 1, only good enough for jitter
+fn random() -> f64 {
+  // RandomState gets new random keys every time, so hashing nothing is random
+  let bits = RandomState::new().build_hasher().finish();
+
+  (bits >> 11) as f64 / (1u64 << 53) as f64
+}
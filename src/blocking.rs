@@ -0,0 +1,330 @@
+//! Blocking version of [SpotifyListener](crate::SpotifyListener)
+//! for programs that don't want to use async, only with the `blocking` feature
+//!
+//! Only needs std and tungstenite, so with `default-features = false` tokio doesn't get pulled in
+
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ipnet::IpNet;
+use tungstenite::protocol::WebSocketConfig;
+use tungstenite::{accept_hdr_with_config, Error, HandshakeError, Message, WebSocket};
+
+use crate::{codec, handshake, Codec, ConnectionSlot, ConnectionInfo, EventCodec, ParseMode, SpotifyError, SpotifyEvent, SpotifyMessage, SpotifyResult};
+
+/// Blocking listener, uses [std::net::TcpListener] so no async runtime is needed
+///
+/// Default: no token, any origin or peer, no connection limit, a 5 second handshake timeout,
+/// the same max message size as tungstenite (64 MiB) and every [Codec], same as [SpotifyListenerBuilder](crate::SpotifyListenerBuilder)
+#[derive(Debug)]
+pub struct Listener {
+  pub listener: TcpListener,
+  auth_token: Option<String>,
+  parse_mode: ParseMode,
+  handshake_timeout: Duration,
+  allowed_origins: Vec<String>,
+  allowed_peers: Vec<IpNet>,
+  codecs: Vec<Codec>,
+  max_connections: Option<usize>,
+  connections: Arc<AtomicUsize>,
+  ws_config: WebSocketConfig,
+}
+
+impl Listener {
+  /// Binds to 127.0.0.1:19532, or \[::1\]:19532 on machines without IPv4
+  pub fn new() -> SpotifyResult<Self> {
+    Self::bind_local(19532)
+  }
+
+  /// Binds to 127.0.0.1 with a custom port, or \[::1\] on machines without IPv4
+  pub fn bind_local(port: u16) -> SpotifyResult<Self> {
+    match Self::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port))) {
+      Err(SpotifyError::Bind(err)) if err.kind() == ErrorKind::AddrNotAvailable => Self::bind_local_v6(port),
+      listener => listener,
+    }
+  }
+
+  /// Binds to \[::1\] with a custom port
+  pub fn bind_local_v6(port: u16) -> SpotifyResult<Self> {
+    Self::bind(SocketAddr::from((Ipv6Addr::LOCALHOST, port)))
+  }
+
+  /// Binds to the given address, same as calling [TcpListener::bind(addr)]
+  pub fn bind(addr: SocketAddr) -> SpotifyResult<Self> {
+    let listener = TcpListener::bind(addr).map_err(SpotifyError::Bind)?;
+
+    Ok(Self {
+      listener,
+      auth_token: None,
+      parse_mode: ParseMode::default(),
+      handshake_timeout: Duration::from_secs(5),
+      allowed_origins: Vec::new(),
+      allowed_peers: Vec::new(),
+      codecs: Codec::SUPPORTED.to_vec(),
+      max_connections: None,
+      connections: Arc::default(),
+      ws_config: WebSocketConfig::default(),
+    })
+  }
+
+  /// Only accepts extensions that send this token in their hello,
+  /// same as [SpotifyListenerBuilder::auth_token](crate::SpotifyListenerBuilder::auth_token)
+  pub fn auth_token(mut self, token: impl Into<String>) -> Self {
+    self.auth_token = Some(token.into());
+    self
+  }
+
+  /// Same as [SpotifyListenerBuilder::parse_mode](crate::SpotifyListenerBuilder::parse_mode)
+  pub fn parse_mode(mut self, mode: ParseMode) -> Self {
+    self.parse_mode = mode;
+    self
+  }
+
+  /// Same as [SpotifyListenerBuilder::handshake_timeout](crate::SpotifyListenerBuilder::handshake_timeout),
+  /// nothing else gets accepted in the meantime since it's all on one thread
+  pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+    self.handshake_timeout = timeout.min(crate::FAR_FUTURE);
+    self
+  }
+
+  /// Same as [SpotifyListenerBuilder::allowed_origins](crate::SpotifyListenerBuilder::allowed_origins)
+  pub fn allowed_origins<T: Into<String>>(mut self, origins: impl IntoIterator<Item = T>) -> Self {
+    self.allowed_origins = origins.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Same as [SpotifyListenerBuilder::allowed_peers](crate::SpotifyListenerBuilder::allowed_peers)
+  pub fn allowed_peers<T: Into<IpNet>>(mut self, peers: impl IntoIterator<Item = T>) -> Self {
+    self.allowed_peers = peers.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Same as [SpotifyListenerBuilder::codecs](crate::SpotifyListenerBuilder::codecs)
+  pub fn codecs(mut self, codecs: impl IntoIterator<Item = Codec>) -> Self {
+    self.codecs = codecs.into_iter().collect();
+    self
+  }
+
+  /// Same as [SpotifyListenerBuilder::max_connections](crate::SpotifyListenerBuilder::max_connections),
+  /// a [Connection] counts until it's dropped
+  pub fn max_connections(mut self, max: Option<usize>) -> Self {
+    self.max_connections = max;
+    self
+  }
+
+  /// Same as [SpotifyListenerBuilder::max_message_size](crate::SpotifyListenerBuilder::max_message_size)
+  pub fn max_message_size(mut self, max: Option<usize>) -> Self {
+    self.ws_config = WebSocketConfig {
+      max_message_size: max.or(WebSocketConfig::default().max_message_size),
+      max_frame_size: max.or(WebSocketConfig::default().max_frame_size),
+      ..WebSocketConfig::default()
+    };
+    self
+  }
+
+  /// How many connections are open right now, they stop counting once they're dropped
+  pub fn connection_count(&self) -> usize {
+    self.connections.load(Ordering::Acquire)
+  }
+
+  /// Blocks until the extension connects and says hello,
+  /// fails the same ways as [SpotifyListener::get_connection](crate::SpotifyListener::get_connection)
+  pub fn accept(&self) -> SpotifyResult<Connection> {
+    let (stream, peer) = self.listener.accept().map_err(SpotifyError::Accept)?;
+
+    if !handshake::is_peer_allowed(peer, &self.allowed_peers) {
+      return Err(SpotifyError::PeerNotAllowed(peer));
+    }
+
+    let deadline = crate::instant_after(self.handshake_timeout);
+    set_timeout(&stream, Some(deadline))?;
+
+    let mut rejected = None;
+    // the error type comes from tungstenite
+    #[allow(clippy::result_large_err)]
+    let check_origin = |request: &_, response| match handshake::check_origin(request, &self.allowed_origins) {
+      Ok(()) => Ok(response),
+      Err(origin) => {
+        rejected = Some(origin);
+        Err(handshake::forbidden())
+      }
+    };
+
+    // the half done handshake in the error holds on to `check_origin`, so it has to be gone before `rejected` is looked at
+    let accepted = match accept_hdr_with_config(stream, check_origin, Some(self.ws_config)) {
+      Ok(ws) => Ok(ws),
+      // the read timeout makes the socket act like a non-blocking one
+      Err(HandshakeError::Interrupted(_)) => Err(SpotifyError::Timeout),
+      Err(HandshakeError::Failure(Error::Io(err))) if is_timeout(&err) => Err(SpotifyError::Timeout),
+      Err(HandshakeError::Failure(err)) => Err(SpotifyError::Handshake(Box::new(err))),
+    };
+
+    let mut ws = match accepted {
+      Ok(ws) => ws,
+      Err(_) if rejected.is_some() => return Err(SpotifyError::OriginNotAllowed(rejected.flatten())),
+      Err(err) => return Err(err),
+    };
+
+    // upgrade first so it gets a close code it understands instead of a failed upgrade
+    let slot = match ConnectionSlot::reserve(&self.connections, self.max_connections) {
+      Some(slot) => slot,
+      None => {
+        let _ = ws.close(Some(handshake::too_many_connections()));
+        let _ = ws.write_pending();
+        return Err(SpotifyError::TooManyConnections);
+      }
+    };
+
+    let hello = loop {
+      set_timeout(ws.get_ref(), Some(deadline))?;
+
+      match ws.read_message() {
+        Ok(Message::Ping(_) | Message::Pong(_)) => continue,
+        Ok(Message::Close(_)) => return Err(SpotifyError::Closed),
+        Ok(message) => break handshake::parse(&message),
+        Err(Error::Io(err)) if is_timeout(&err) => return Err(SpotifyError::Timeout),
+        Err(err) => return Err(err.into()),
+      }
+    };
+
+    // events can take as long as they want once it's connected
+    set_timeout(ws.get_ref(), None)?;
+
+    if let Err(err) = handshake::authenticate(hello.as_ref(), self.auth_token.as_deref()) {
+      let _ = ws.close(Some(handshake::unauthorized()));
+      return Err(err);
+    }
+
+    let codec = handshake::codec(hello.as_ref(), &self.codecs);
+
+    ws.write_message(handshake::reply(hello.as_ref(), codec))?;
+
+    match handshake::check(hello.as_ref()) {
+      Ok(protocol_version) => Ok(Connection {
+        ws,
+        info: handshake::info(hello, Some(peer), protocol_version),
+        codec,
+        parse_mode: self.parse_mode,
+        _slot: slot,
+      }),
+      Err(err) => {
+        // the extension already knows why from the reply
+        let _ = ws.close(None);
+        Err(err)
+      }
+    }
+  }
+
+  /// Returns an iterator over every event received,
+  /// blocks while waiting for spotify to connect or send something
+  ///
+  /// When spotify disconnects it waits for it to connect again, so it never ends
+  pub fn incoming(&self) -> Incoming<'_> {
+    Incoming { listener: self, connection: None }
+  }
+}
+
+/// Puts a read and write timeout on `stream` that ends at `deadline`, [None] to remove it
+fn set_timeout(stream: &TcpStream, deadline: Option<Instant>) -> SpotifyResult<()> {
+  let timeout = match deadline {
+    // a zero timeout is an error, so time that's already up is too
+    Some(deadline) => match deadline.checked_duration_since(Instant::now()).filter(|left| !left.is_zero()) {
+      Some(left) => Some(left),
+      None => return Err(SpotifyError::Timeout),
+    },
+    None => None,
+  };
+
+  stream.set_read_timeout(timeout).and_then(|_| stream.set_write_timeout(timeout)).map_err(|err| Error::Io(err).into())
+}
+
+/// What a read that hit its timeout fails with, it's different on windows
+fn is_timeout(err: &io::Error) -> bool {
+  matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut)
+}
+
+/// One extension connected to a [Listener], iterating over it blocks until the next event
+/// and ends when the extension disconnects
+#[derive(Debug)]
+pub struct Connection {
+  pub ws: WebSocket<TcpStream>,
+  info: ConnectionInfo,
+  codec: Codec,
+  parse_mode: ParseMode,
+  _slot: ConnectionSlot,
+}
+
+impl Connection {
+  /// Id of the connection and what it said about itself when it connected
+  pub fn info(&self) -> &ConnectionInfo {
+    &self.info
+  }
+
+  /// Format everything after the hello is sent in, whatever the extension asked for
+  pub fn codec(&self) -> Codec {
+    self.codec
+  }
+
+  /// Sends a message to the spotify extension
+  pub fn send(&mut self, message: SpotifyMessage) -> SpotifyResult<()> {
+    let message = self.codec.encode(&message)?;
+
+    Ok(self.ws.write_message(message)?)
+  }
+}
+
+impl Iterator for Connection {
+  type Item = SpotifyResult<SpotifyEvent>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      match self.ws.read_message() {
+        Ok(message) => match codec::decode_event(&self.codec, message, self.parse_mode) {
+          Some(event) => return Some(event),
+          None => continue,
+        },
+        Err(Error::ConnectionClosed | Error::AlreadyClosed) => return None,
+        Err(err) => return Some(Err(err.into())),
+      }
+    }
+  }
+}
+
+/// Iterator returned by [Listener::incoming]
+#[derive(Debug)]
+pub struct Incoming<'a> {
+  listener: &'a Listener,
+  connection: Option<Connection>,
+}
+
+impl Iterator for Incoming<'_> {
+  type Item = SpotifyResult<SpotifyEvent>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let connection = match &mut self.connection {
+        Some(connection) => connection,
+        None => match self.listener.accept() {
+          Ok(connection) => self.connection.insert(connection),
+          Err(err) => return Some(Err(err)),
+        },
+      };
+
+      match connection.next() {
+        Some(Ok(event)) => return Some(Ok(event)),
+        None => self.connection = None,
+        Some(Err(err)) => {
+          // unreadable messages don't break the connection, anything else does
+          if !err.is_message_error() {
+            self.connection = None;
+          }
+
+          return Some(Err(err));
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,481 @@
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ipnet::IpNet;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tungstenite::protocol::WebSocketConfig;
+
+use crate::derived::{ScrobbleTimer, TrackLifecycle};
+use crate::intercept::{DedupTracks, Layers};
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+use crate::tls::TlsSource;
+use crate::transport::Transport;
+#[cfg(all(windows, feature = "named-pipe"))]
+use crate::transport::NamedPipe;
+#[cfg(all(unix, feature = "unix"))]
+use crate::transport::UnixSocket;
+use crate::{Backoff, Codec, DiscoveryFile, Interceptor, Keepalive, ParseMode, Runtime, SpotifyAcceptor, SpotifyError, SpotifyListener, SpotifyResult, TokioRuntime, FAR_FUTURE};
+
+/// Configures a [SpotifyListener], created with [SpotifyListener::builder]
+///
+/// Default: 127.0.0.1:19532 (or \[::1\]:19532 when there's no IPv4) with the default [Keepalive] and [Backoff], no token, any origin or peer,
+/// no connection limit, a 5 second handshake timeout, the same max message size as tungstenite (64 MiB) and every [Codec]
+///
+/// Timeouts and intervals over 30 years count as 30 years, which is as good as never
+#[derive(Debug, Clone)]
+pub struct SpotifyListenerBuilder {
+  addr: SocketAddr,
+  fallback: bool,
+  dual_stack: bool,
+  keepalive: Option<Keepalive>,
+  idle_timeout: Option<Duration>,
+  progress_throttle: Option<Duration>,
+  parse_mode: ParseMode,
+  session_gap: Option<Duration>,
+  dedup_tracks: bool,
+  track_lifecycle: bool,
+  scrobble_points: bool,
+  backoff: Backoff,
+  auth_token: Option<String>,
+  handshake_timeout: Duration,
+  allowed_origins: Vec<String>,
+  allowed_peers: Vec<IpNet>,
+  max_connections: Option<usize>,
+  max_message_size: Option<usize>,
+  codecs: Vec<Codec>,
+  discovery_file: Option<PathBuf>,
+  runtime: Arc<dyn Runtime>,
+  layers: Layers,
+  #[cfg(any(feature = "tls", feature = "native-tls"))]
+  tls: Option<TlsSource>,
+}
+
+impl Default for SpotifyListenerBuilder {
+  fn default() -> Self {
+    Self {
+      addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 19532)),
+      fallback: true,
+      dual_stack: false,
+      keepalive: Some(Keepalive::default()),
+      idle_timeout: None,
+      progress_throttle: None,
+      parse_mode: ParseMode::default(),
+      session_gap: None,
+      dedup_tracks: false,
+      track_lifecycle: false,
+      scrobble_points: false,
+      backoff: Backoff::default(),
+      auth_token: None,
+      handshake_timeout: Duration::from_secs(5),
+      allowed_origins: Vec::new(),
+      allowed_peers: Vec::new(),
+      max_connections: None,
+      max_message_size: None,
+      codecs: Codec::SUPPORTED.to_vec(),
+      discovery_file: None,
+      runtime: Arc::new(TokioRuntime),
+      layers: Layers::default(),
+      #[cfg(any(feature = "tls", feature = "native-tls"))]
+      tls: None,
+    }
+  }
+}
+
+impl SpotifyListenerBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Address to bind to, replaces the port set with [Self::port]
+  ///
+  /// Unlike the default address this one doesn't fall back to IPv6 if it isn't available
+  pub fn addr(mut self, addr: SocketAddr) -> Self {
+    self.addr = addr;
+    self.fallback = false;
+    self
+  }
+
+  /// When binding to an IPv6 address also accept IPv4 connections,
+  /// only does something for `[::]` since IPv4 connections can't reach any other IPv6 address
+  ///
+  /// Off by default, some systems already do this for `[::]` on their own
+  pub fn dual_stack(mut self, dual_stack: bool) -> Self {
+    self.dual_stack = dual_stack;
+    self
+  }
+
+  /// Port to bind to, keeps the address
+  pub fn port(mut self, port: u16) -> Self {
+    self.addr.set_port(port);
+    self
+  }
+
+  /// Lets the OS pick a free port, same as `port(0)`,
+  /// use [Self::discovery_file] so other programs can find out which one it picked
+  pub fn free_port(self) -> Self {
+    self.port(0)
+  }
+
+  /// Writes the address the listener ended up on to this file when it binds,
+  /// it gets removed again when the listener is dropped, see [DiscoveryFile]
+  pub fn discovery_file(mut self, path: impl Into<PathBuf>) -> Self {
+    self.discovery_file = Some(path.into());
+    self
+  }
+
+  /// How often to ping connections and how long to wait for them to answer,
+  /// [None] never pings, so a dead connection only ends when the OS notices
+  pub fn keepalive(mut self, keepalive: Option<Keepalive>) -> Self {
+    self.keepalive = keepalive.map(|keepalive| Keepalive {
+      interval: keepalive.interval.min(FAR_FUTURE),
+      timeout: keepalive.timeout.min(FAR_FUTURE),
+    });
+    self
+  }
+
+  /// Ends connections that don't send any events for this long with [SpotifyError::Idle],
+  /// for when spotify hangs but still answers pings
+  ///
+  /// Paused spotify doesn't send anything either, so this should be longer than it's expected to stay paused
+  ///
+  /// Default: [None], connections can be quiet forever
+  pub fn idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+    self.idle_timeout = timeout.map(|timeout| timeout.min(FAR_FUTURE));
+    self
+  }
+
+  /// Lets through at most one [SpotifyEvent::ProgressChanged](crate::SpotifyEvent::ProgressChanged) per `spacing` on every connection,
+  /// for extensions that send bursts of them (like after seeking) even with
+  /// [SpotifyConnection::set_progress_interval](crate::SpotifyConnection::set_progress_interval)
+  ///
+  /// One that comes in too early waits until it's allowed, if a newer one comes in before that it replaces it,
+  /// so only the latest position gets through
+  ///
+  /// Default: [None], everything gets through as soon as it's received
+  pub fn progress_throttle(mut self, spacing: Option<Duration>) -> Self {
+    self.progress_throttle = spacing.map(|spacing| spacing.min(FAR_FUTURE));
+    self
+  }
+
+  /// What happens with events that can't be decoded or have fields the crate doesn't know about,
+  /// can be changed for one connection with [SpotifyConnection::with_parse_mode](crate::SpotifyConnection::with_parse_mode)
+  ///
+  /// Default: [ParseMode::Lenient], they get logged and skipped
+  pub fn parse_mode(mut self, mode: ParseMode) -> Self {
+    self.parse_mode = mode;
+    self
+  }
+
+  /// Drops [SpotifyEvent::TrackChanged](crate::SpotifyEvent::TrackChanged) when it's the same track as the one before,
+  /// the extension sometimes sends it again (like when spotify gets focused), which would re-trigger
+  /// animations or scrobbles downstream
+  ///
+  /// Same means the same uid, uri and title, other fields like [TrackInfo::liked](crate::TrackInfo::liked) aren't compared,
+  /// runs before any [Self::interceptor]
+  ///
+  /// **NOTE**: Repeating one track sends the same track again too, so that gets dropped as well
+  ///
+  /// Default: `false`
+  pub fn dedup_tracks(mut self, dedup: bool) -> Self {
+    self.dedup_tracks = dedup;
+    self
+  }
+
+  /// Adds [SpotifyEvent::TrackFinished](crate::SpotifyEvent::TrackFinished) and [SpotifyEvent::TrackSkipped](crate::SpotifyEvent::TrackSkipped)
+  /// when the track changes, based on how far the track got, which is what scrobblers and stats need
+  /// and the extension doesn't send
+  ///
+  /// It counts as finished within 3 seconds of the end, nothing is added for tracks without a duration,
+  /// runs after [Self::dedup_tracks] and before any [Self::interceptor]
+  ///
+  /// Default: `false`
+  pub fn track_lifecycle(mut self, lifecycle: bool) -> Self {
+    self.track_lifecycle = lifecycle;
+    self
+  }
+
+  /// Adds [SpotifyEvent::ScrobblePoint](crate::SpotifyEvent::ScrobblePoint) once a track has been played
+  /// for half its duration or 4 minutes, whichever comes first, the same rule Last.fm uses
+  ///
+  /// Only time spent playing counts, so pausing or seeking ahead doesn't get it there sooner,
+  /// podcasts played faster get there sooner since it counts time in the track,
+  /// tracks shorter than 30 seconds or without a duration never get one
+  ///
+  /// It gets checked whenever an event comes in, so with progress events turned off
+  /// with [EventMask](crate::EventMask) it can come late
+  ///
+  /// Default: `false`
+  pub fn scrobble_points(mut self, scrobble: bool) -> Self {
+    self.scrobble_points = scrobble;
+    self
+  }
+
+  /// Groups playback into listening sessions, adding [SpotifyEvent::SessionStarted](crate::SpotifyEvent::SessionStarted)
+  /// when something starts playing and [SpotifyEvent::SessionEnded](crate::SpotifyEvent::SessionEnded)
+  /// once nothing played for `gap`, like when the user walked away
+  ///
+  /// Sessions are per connection, so one also ends when its connection does
+  ///
+  /// Default: [None], no session events
+  pub fn session_gap(mut self, gap: Option<Duration>) -> Self {
+    self.session_gap = gap.map(|gap| gap.min(FAR_FUTURE));
+    self
+  }
+
+  /// How long [SpotifyListener::states] waits before accepting again when the listener fails
+  pub fn backoff(mut self, backoff: Backoff) -> Self {
+    self.backoff = backoff;
+    self
+  }
+
+  /// Only accepts connections from extensions that send this token,
+  /// set the same one as `authToken` in the extension
+  pub fn auth_token(mut self, token: impl Into<String>) -> Self {
+    self.auth_token = Some(token.into());
+    self
+  }
+
+  /// How long a connection has to say hello (and send the token) before it gets dropped,
+  /// other connections still get accepted in the meantime
+  pub fn handshake_timeout(mut self, timeout: Duration) -> Self {
+    self.handshake_timeout = timeout.min(FAR_FUTURE);
+    self
+  }
+
+  /// Only accepts connections whose `Origin` header is one of these,
+  /// so webpages open in a browser can't connect, an empty list allows any origin
+  ///
+  /// Upgrades without an origin get rejected too, browsers always send one
+  ///
+  /// `[SPOTIFY_ORIGIN](crate::SPOTIFY_ORIGIN)` in the list only allows the spotify client
+  pub fn allowed_origins<T: Into<String>>(mut self, origins: impl IntoIterator<Item = T>) -> Self {
+    self.allowed_origins = origins.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// Only accepts connections from these addresses or networks,
+  /// everything else gets dropped before the websocket handshake, an empty list allows anyone
+  ///
+  /// Mostly useful when binding to `0.0.0.0`, for example `["192.168.1.0/24".parse().unwrap()]`
+  /// or a single address with `IpAddr::into`
+  pub fn allowed_peers<T: Into<IpNet>>(mut self, peers: impl IntoIterator<Item = T>) -> Self {
+    self.allowed_peers = peers.into_iter().map(Into::into).collect();
+    self
+  }
+
+  /// How many connections can be open at once, [None] for no limit,
+  /// connections over the limit get closed with 1013 (try again later) right after connecting
+  ///
+  /// A [SpotifyConnection](crate::SpotifyConnection) counts until it's dropped,
+  /// see [SpotifyListener::connection_count]
+  pub fn max_connections(mut self, max: Option<usize>) -> Self {
+    self.max_connections = max;
+    self
+  }
+
+  /// Biggest message the extension can send in bytes, [None] for the default,
+  /// anything bigger breaks the connection
+  ///
+  /// Lyrics are the biggest thing it sends, which are a few KiB at most
+  pub fn max_message_size(mut self, max: Option<usize>) -> Self {
+    self.max_message_size = max;
+    self
+  }
+
+  /// Codecs the extension can get, it gets the first one from its hello that's in here,
+  /// json if there's none since every extension speaks it
+  ///
+  /// Default: [Codec::SUPPORTED], so every codec the enabled features have
+  pub fn codecs(mut self, codecs: impl IntoIterator<Item = Codec>) -> Self {
+    self.codecs = codecs.into_iter().collect();
+    self
+  }
+
+  /// Only accepts `wss://` connections, using the certificate and private key from these PEM files,
+  /// they get loaded in [Self::bind] which fails with [SpotifyError::Tls] if they're invalid
+  ///
+  /// Spotify has to trust the certificate, so a self-signed one has to be added to the system first
+  #[cfg(feature = "tls")]
+  pub fn tls_pem(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+    self.tls = Some(TlsSource::Pem { cert: cert.into(), key: key.into() });
+    self
+  }
+
+  /// Same as [Self::tls_pem] but with a config you made yourself
+  #[cfg(feature = "tls")]
+  pub fn tls_config(mut self, config: Arc<crate::rustls::ServerConfig>) -> Self {
+    self.tls = Some(TlsSource::Config(config));
+    self
+  }
+
+  /// Same as [Self::tls_pem] but with the platform tls stack (openssl, schannel or security framework)
+  /// instead of rustls, for places that need it, replaces whatever tls was set before
+  ///
+  /// The key has to be PKCS#8, [Self::native_tls_pkcs12] takes anything
+  #[cfg(feature = "native-tls")]
+  pub fn native_tls_pem(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+    self.tls = Some(TlsSource::NativePem { cert: cert.into(), key: key.into() });
+    self
+  }
+
+  /// Same as [Self::native_tls_pem] but with a PKCS#12 (`.pfx` / `.p12`) file,
+  /// which is what windows exports certificates from its store as
+  #[cfg(feature = "native-tls")]
+  pub fn native_tls_pkcs12(mut self, path: impl Into<PathBuf>, password: impl Into<String>) -> Self {
+    self.tls = Some(TlsSource::NativePkcs12 { path: path.into(), password: password.into() });
+    self
+  }
+
+  /// Same as [Self::native_tls_pem] but with an acceptor you made yourself
+  #[cfg(feature = "native-tls")]
+  pub fn native_tls_acceptor(mut self, acceptor: crate::native_tls::TlsAcceptor) -> Self {
+    self.tls = Some(TlsSource::NativeAcceptor(acceptor));
+    self
+  }
+
+  /// What spawns background tasks and runs timers like [Keepalive],
+  /// binding still needs a tokio reactor so other runtimes use [Self::acceptor] instead
+  ///
+  /// Default: [TokioRuntime]
+  pub fn runtime(mut self, runtime: impl Runtime) -> Self {
+    self.runtime = Arc::new(runtime);
+    self
+  }
+
+  /// Adds an [Interceptor] that sees every event and message of every connection,
+  /// for logging, filtering or changing them before the app gets them
+  ///
+  /// They run in the order they were added, every connection gets its own clone
+  pub fn interceptor(mut self, interceptor: impl Interceptor + Clone + Sync) -> Self {
+    self.layers.push(interceptor);
+    self
+  }
+
+  /// Binds the listener with this configuration
+  pub async fn bind(mut self) -> SpotifyResult<SpotifyListener> {
+    let listener = match bind_tcp(self.addr, self.dual_stack).await {
+      // IPv6 only machine, the extension has to use [::1] as its host
+      Err(err) if self.fallback && err.kind() == ErrorKind::AddrNotAvailable => {
+        bind_tcp(SocketAddr::from((Ipv6Addr::LOCALHOST, self.addr.port())), self.dual_stack).await
+      }
+      listener => listener,
+    };
+    let listener = listener.map_err(SpotifyError::Bind)?;
+    let discovery = match self.discovery_file.take() {
+      Some(path) => {
+        let addr = listener.local_addr().map_err(SpotifyError::Bind)?;
+        Some(DiscoveryFile::write(path, addr).map_err(SpotifyError::Discovery)?)
+      }
+      None => None,
+    };
+
+    self.finish(Transport::Tcp(listener), discovery)
+  }
+
+  /// Binds to a unix socket instead of the address,
+  /// see [SpotifyListener::bind_unix]
+  ///
+  /// TLS, allowed peers and the discovery file only apply to tcp so they're ignored
+  #[cfg(all(unix, feature = "unix"))]
+  pub async fn bind_unix(self, path: impl AsRef<std::path::Path>) -> SpotifyResult<SpotifyListener> {
+    let socket = UnixSocket::bind(path.as_ref()).map_err(SpotifyError::Bind)?;
+
+    self.finish(Transport::Unix(socket), None)
+  }
+
+  /// Creates a windows named pipe instead of binding to the address,
+  /// see [SpotifyListener::bind_named_pipe]
+  ///
+  /// TLS, allowed peers and the discovery file only apply to tcp so they're ignored
+  #[cfg(all(windows, feature = "named-pipe"))]
+  pub async fn bind_named_pipe(self, name: impl AsRef<str>) -> SpotifyResult<SpotifyListener> {
+    let pipe = NamedPipe::bind(name.as_ref()).map_err(SpotifyError::Bind)?;
+
+    self.finish(Transport::NamedPipe(pipe), None)
+  }
+
+  /// Only what the listener does after accepting a stream, for streams accepted some other way,
+  /// like with a runtime other than tokio, the address and discovery file aren't used
+  ///
+  /// Fails with [SpotifyError::Tls] if the certificate can't be loaded
+  pub fn acceptor(self) -> SpotifyResult<SpotifyAcceptor> {
+    let mut layers = Layers::default();
+
+    if self.dedup_tracks {
+      layers.push(DedupTracks::default());
+    }
+
+    if self.track_lifecycle {
+      layers.push(TrackLifecycle::default());
+    }
+
+    if self.scrobble_points {
+      layers.push(ScrobbleTimer::default());
+    }
+
+    layers.extend(self.layers);
+
+    #[cfg(any(feature = "tls", feature = "native-tls"))]
+    let tls = self.tls.map(TlsSource::acceptor).transpose()?;
+
+    Ok(SpotifyAcceptor {
+      keepalive: self.keepalive,
+      idle_timeout: self.idle_timeout,
+      progress_throttle: self.progress_throttle,
+      parse_mode: self.parse_mode,
+      session_gap: self.session_gap,
+      auth_token: self.auth_token,
+      handshake_timeout: self.handshake_timeout,
+      allowed_origins: self.allowed_origins,
+      allowed_peers: self.allowed_peers,
+      max_connections: self.max_connections,
+      codecs: self.codecs,
+      connections: Arc::default(),
+      ws_config: WebSocketConfig {
+        max_message_size: self.max_message_size.or(WebSocketConfig::default().max_message_size),
+        max_frame_size: self.max_message_size.or(WebSocketConfig::default().max_frame_size),
+        ..WebSocketConfig::default()
+      },
+      runtime: self.runtime,
+      layers,
+      #[cfg(any(feature = "tls", feature = "native-tls"))]
+      tls,
+    })
+  }
+
+  fn finish(self, transport: Transport, discovery: Option<DiscoveryFile>) -> SpotifyResult<SpotifyListener> {
+    let backoff = self.backoff;
+    let (finished, handshakes) = mpsc::unbounded_channel();
+
+    Ok(SpotifyListener {
+      transport,
+      acceptor: Arc::new(self.acceptor()?),
+      backoff,
+      discovery,
+      finished,
+      handshakes: tokio::sync::Mutex::new(handshakes),
+    })
+  }
+}
+
+async fn bind_tcp(addr: SocketAddr, dual_stack: bool) -> io::Result<TcpListener> {
+  if !dual_stack || !addr.is_ipv6() {
+    return TcpListener::bind(addr).await;
+  }
+
+  let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+  socket.set_only_v6(false)?;
+  // same as what TcpListener::bind does
+  #[cfg(not(windows))]
+  socket.set_reuse_address(true)?;
+  socket.set_nonblocking(true)?;
+  socket.bind(&addr.into())?;
+  socket.listen(1024)?;
+
+  TcpListener::from_std(socket.into())
+}
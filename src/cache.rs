@@ -0,0 +1,262 @@
+//! Keeps downloaded covers on disk, only with the `http-client` feature
+
+use std::fs::{self, File};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::{Image, ImageClient, SpotifyError, SpotifyResult, TrackInfo};
+
+/// Stores covers on disk so programs that restart often (like overlays)
+/// don't download the same ones again, the least recently used ones get removed
+/// once there's more than [Self::max_size]
+///
+/// Covers are stored by url, so every track of an album shares one
+///
+/// Default: `spotify_info/covers` in the user's cache directory, at most 100 MiB
+#[derive(Debug, Clone)]
+pub struct CoverCache {
+  dir: PathBuf,
+  max_size: u64,
+  client: ImageClient,
+}
+
+impl CoverCache {
+  /// Uses `spotify_info/covers` in the user's cache directory,
+  /// fails with [SpotifyError::Cache] if the platform doesn't have one or it can't be created
+  pub fn new() -> SpotifyResult<Self> {
+    let dir = Self::default_dir().ok_or_else(|| SpotifyError::Cache(io::Error::new(ErrorKind::NotFound, "no cache directory")))?;
+
+    Self::with_dir(dir)
+  }
+
+  /// Stores covers in `dir` instead, creates it if it doesn't exist
+  pub fn with_dir(dir: impl Into<PathBuf>) -> SpotifyResult<Self> {
+    let dir = dir.into();
+
+    fs::create_dir_all(&dir).map_err(SpotifyError::Cache)?;
+
+    Ok(Self {
+      dir,
+      max_size: 100 * 1024 * 1024,
+      client: ImageClient::default(),
+    })
+  }
+
+  /// `spotify_info/covers` in the user's cache directory,
+  /// [None] if the platform doesn't have one
+  pub fn default_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("spotify_info").join("covers"))
+  }
+
+  /// How many bytes of covers to keep at most
+  pub fn max_size(mut self, max: u64) -> Self {
+    self.max_size = max;
+    self
+  }
+
+  /// Downloads missing covers with this client instead of the default one
+  pub fn client(mut self, client: ImageClient) -> Self {
+    self.client = client;
+    self
+  }
+
+  pub fn dir(&self) -> &Path {
+    &self.dir
+  }
+
+  /// The image at `url` from the cache, or downloads it and stores it if it isn't cached yet
+  ///
+  /// A cache that can't be written to doesn't fail this, it just downloads it again next time
+  pub async fn get(&self, url: &str) -> SpotifyResult<Image> {
+    let path = self.dir.join(format!("{:016x}", fnv1a(url.as_bytes())));
+    let cached = path.clone();
+
+    if let Some(image) = blocking(move || read(&cached)).await {
+      return Ok(image);
+    }
+
+    let image = self.client.fetch(url).await?;
+    let (dir, max_size, stored) = (self.dir.clone(), self.max_size, image.clone());
+
+    blocking(move || write(&path, &stored).and_then(|_| evict(&dir, max_size)).ok()).await;
+
+    Ok(image)
+  }
+
+  /// [Self::get] for [TrackInfo::cover], [None] if the track doesn't have one
+  pub async fn cover(&self, info: &TrackInfo) -> SpotifyResult<Option<Image>> {
+    match info.cover() {
+      Some(url) => self.get(url).await.map(Some),
+      None => Ok(None),
+    }
+  }
+
+  /// Removes every cached cover
+  pub fn clear(&self) -> SpotifyResult<()> {
+    for entry in fs::read_dir(&self.dir).map_err(SpotifyError::Cache)? {
+      fs::remove_file(entry.map_err(SpotifyError::Cache)?.path()).map_err(SpotifyError::Cache)?;
+    }
+
+    Ok(())
+  }
+}
+
+/// Runs file system stuff without blocking the runtime, [None] if it failed
+async fn blocking<T: Send + 'static>(task: impl FnOnce() -> Option<T> + Send + 'static) -> Option<T> {
+  tokio::task::spawn_blocking(task).await.ok().flatten()
+}
+
+/// Stable between versions and platforms unlike [std::hash::DefaultHasher], so the file names stay the same
+fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Files are the content type, a newline, then the image
+fn read(path: &Path) -> Option<Image> {
+  let file = fs::read(path).ok()?;
+  let split = file.iter().position(|byte| *byte == b'\n')?;
+  let content_type = std::str::from_utf8(&file[..split]).ok()?;
+
+  // marks it as just used for [evict]
+  let _ = File::options().append(true).open(path).and_then(|file| file.set_modified(SystemTime::now()));
+
+  Some(Image {
+    content_type: Some(content_type.to_string()).filter(|content_type| !content_type.is_empty()),
+    bytes: file[split + 1..].to_vec(),
+  })
+}
+
+fn write(path: &Path, image: &Image) -> io::Result<()> {
+  let mut file = image.content_type.as_deref().unwrap_or_default().replace('\n', "").into_bytes();
+  file.push(b'\n');
+  file.extend_from_slice(&image.bytes);
+
+  // somewhere else first so readers never see half a file
+  let tmp = path.with_extension("tmp");
+  fs::write(&tmp, file)?;
+  fs::rename(&tmp, path)
+}
+
+/// Removes the least recently used covers until there's at most `max_size` bytes left
+fn evict(dir: &Path, max_size: u64) -> io::Result<()> {
+  let mut files = fs::read_dir(dir)?
+    .filter_map(|entry| {
+      let entry = entry.ok()?;
+      let metadata = entry.metadata().ok()?;
+
+      // covers that are still being written, removing those would make the rename in [write] fail
+      if !metadata.is_file() || entry.path().extension().is_some_and(|extension| extension == "tmp") {
+        return None;
+      }
+
+      Some((metadata.modified().ok()?, metadata.len(), entry.path()))
+    })
+    .collect::<Vec<_>>();
+
+  let mut size = files.iter().map(|(_, len, _)| len).sum::<u64>();
+
+  files.sort();
+
+  for (_, len, path) in files {
+    if size <= max_size {
+      break;
+    }
+
+    fs::remove_file(path)?;
+    size -= len;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  fn image(bytes: &[u8]) -> Image {
+    Image { bytes: bytes.to_vec(), content_type: Some("image/jpeg".to_string()) }
+  }
+
+  /// Writes a cover of `len` bytes that was last used `secs` after the epoch
+  fn cover(dir: &Path, name: &str, len: usize, secs: u64) -> PathBuf {
+    let path = dir.join(name);
+
+    fs::write(&path, vec![0; len]).unwrap();
+    File::options().append(true).open(&path).unwrap().set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)).unwrap();
+
+    path
+  }
+
+  #[test]
+  fn reads_what_got_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cover");
+
+    write(&path, &image(b"\n\xff\xd8")).unwrap();
+
+    let cached = read(&path).unwrap();
+    assert_eq!(cached.bytes, b"\n\xff\xd8");
+    assert_eq!(cached.content_type.as_deref(), Some("image/jpeg"));
+    assert!(!path.with_extension("tmp").exists());
+
+    write(&path, &Image { bytes: vec![1], content_type: None }).unwrap();
+    assert_eq!(read(&path).unwrap().content_type, None);
+  }
+
+  #[test]
+  fn reading_marks_as_used() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cover");
+    write(&path, &image(b"")).unwrap();
+    File::options().append(true).open(&path).unwrap().set_modified(SystemTime::UNIX_EPOCH).unwrap();
+
+    read(&path).unwrap();
+
+    assert!(fs::metadata(&path).unwrap().modified().unwrap() > SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+  }
+
+  #[test]
+  fn evicts_least_recently_used_first() {
+    let dir = tempfile::tempdir().unwrap();
+    let oldest = cover(dir.path(), "a", 10, 1);
+    let older = cover(dir.path(), "b", 10, 2);
+    let newest = cover(dir.path(), "c", 10, 3);
+
+    evict(dir.path(), 20).unwrap();
+    assert!(!oldest.exists());
+    assert!(older.exists() && newest.exists());
+
+    evict(dir.path(), 15).unwrap();
+    assert!(!older.exists());
+    assert!(newest.exists());
+  }
+
+  #[test]
+  fn keeps_everything_under_max_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let covers = [cover(dir.path(), "a", 10, 1), cover(dir.path(), "b", 10, 2)];
+
+    evict(dir.path(), 20).unwrap();
+    assert!(covers.iter().all(|cover| cover.exists()));
+
+    evict(dir.path(), 0).unwrap();
+    assert!(covers.iter().all(|cover| !cover.exists()));
+  }
+
+  #[test]
+  fn skips_covers_being_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let tmp = cover(dir.path(), "a.tmp", 100, 1);
+    let newest = cover(dir.path(), "b", 10, 2);
+
+    // the tmp file neither gets removed nor counts towards the size
+    evict(dir.path(), 10).unwrap();
+    assert!(tmp.exists() && newest.exists());
+
+    evict(dir.path(), 0).unwrap();
+    assert!(tmp.exists());
+    assert!(!newest.exists());
+  }
+}
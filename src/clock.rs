@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{clamp_playback_rate, scale_by_rate, SpotifyEvent, TrackState};
+
+/// Estimates the position of the current track between progress events,
+/// so progress bars can move smoothly instead of jumping every interval
+///
+/// Feed it every event with [PlaybackClock::ingest],
+/// the position moves forward while playing (faster or slower for podcasts played at a different speed)
+/// and freezes while paused or stopped
+#[derive(Debug, Clone)]
+pub struct PlaybackClock {
+  /// Last known position
+  position: Duration,
+  /// When [Self::position] was measured, only set while playing
+  anchor: Option<Instant>,
+  duration: Duration,
+  state: TrackState,
+  rate: f32,
+}
+
+impl Default for PlaybackClock {
+  fn default() -> Self {
+    Self {
+      position: Duration::ZERO,
+      anchor: None,
+      duration: Duration::ZERO,
+      state: TrackState::default(),
+      rate: 1.0,
+    }
+  }
+}
+
+impl PlaybackClock {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Updates the clock with an event, events that don't affect the position are ignored
+  pub fn ingest(&mut self, event: &SpotifyEvent) {
+    self.ingest_at(event, Instant::now());
+  }
+
+  /// Same as [Self::ingest] with `now` as the time it arrived
+  pub(crate) fn ingest_at(&mut self, event: &SpotifyEvent, now: Instant) {
+    match event {
+      SpotifyEvent::TrackChanged(info) => {
+        self.duration = info.duration;
+        self.set(Duration::ZERO, now, info.state);
+      }
+      SpotifyEvent::StateChanged(state) => {
+        self.set(self.position_at(now), now, *state);
+      }
+      SpotifyEvent::ProgressChanged { position, timestamp, .. } => {
+        // account for the time it took the event to get here
+        let latency = SystemTime::now().duration_since(*timestamp).unwrap_or(Duration::ZERO);
+        let measured = now.checked_sub(latency).unwrap_or(now);
+
+        self.set(*position, measured, self.state);
+      }
+      SpotifyEvent::StateSnapshot(snapshot) => {
+        if let Some(track) = &snapshot.track {
+          self.duration = track.duration;
+        }
+
+        self.rate = clamp_playback_rate(snapshot.playback_rate);
+        self.set(snapshot.position, now, snapshot.state);
+      }
+      SpotifyEvent::PlaybackRateChanged(rate) => {
+        // everything before this played at the old rate
+        self.set(self.position_at(now), now, self.state);
+        self.rate = clamp_playback_rate(*rate);
+      }
+      _ => {}
+    }
+  }
+
+  fn set(&mut self, position: Duration, measured: Instant, state: TrackState) {
+    self.position = position;
+    self.state = state;
+    self.anchor = Some(measured).filter(|_| state == TrackState::Playing);
+  }
+
+  /// Estimated position in the current track, never goes past the duration of the track
+  pub fn estimated_position(&self) -> Duration {
+    self.position_at(Instant::now())
+  }
+
+  /// Same as [Self::estimated_position] but at `now`
+  pub(crate) fn position_at(&self, now: Instant) -> Duration {
+    let position = match self.anchor {
+      Some(anchor) => self.position.saturating_add(scale_by_rate(now.saturating_duration_since(anchor), self.rate)),
+      None => self.position,
+    };
+
+    if self.duration.is_zero() {
+      position
+    } else {
+      position.min(self.duration)
+    }
+  }
+
+  /// Estimated position as a percentage between 0 and 1,
+  /// 0 if the duration isn't known
+  pub fn estimated_percent(&self) -> f64 {
+    if self.duration.is_zero() {
+      return 0.0;
+    }
+
+    self.estimated_position().as_secs_f64() / self.duration.as_secs_f64()
+  }
+
+  /// Duration of the current track
+  pub fn duration(&self) -> Duration {
+    self.duration
+  }
+
+  /// How fast the position moves, 1 is normal speed
+  pub fn playback_rate(&self) -> f32 {
+    self.rate
+  }
+
+  /// Current state, the position only moves while [TrackState::Playing]
+  pub fn state(&self) -> TrackState {
+    self.state
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::TrackInfo;
+
+  fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+  }
+
+  fn playing(clock: &mut PlaybackClock, now: Instant) {
+    let track = TrackInfo::builder().uid("a").duration(secs(60)).build().unwrap();
+
+    clock.ingest_at(&SpotifyEvent::TrackChanged(track), now);
+    clock.ingest_at(&SpotifyEvent::StateChanged(TrackState::Playing), now);
+  }
+
+  #[test]
+  fn moves_while_playing() {
+    let start = Instant::now();
+    let mut clock = PlaybackClock::new();
+    playing(&mut clock, start);
+
+    assert_eq!(clock.position_at(start), Duration::ZERO);
+    assert_eq!(clock.position_at(start + secs(10)), secs(10));
+    // before it was measured doesn't go backwards
+    assert_eq!(clock.position_at(start - secs(1)), Duration::ZERO);
+  }
+
+  #[test]
+  fn freezes_while_paused() {
+    let start = Instant::now();
+    let mut clock = PlaybackClock::new();
+    playing(&mut clock, start);
+
+    clock.ingest_at(&SpotifyEvent::StateChanged(TrackState::Paused), start + secs(10));
+    assert_eq!(clock.position_at(start + secs(30)), secs(10));
+
+    clock.ingest_at(&SpotifyEvent::StateChanged(TrackState::Playing), start + secs(30));
+    assert_eq!(clock.position_at(start + secs(35)), secs(15));
+
+    clock.ingest_at(&SpotifyEvent::StateChanged(TrackState::Stopped), start + secs(35));
+    assert_eq!(clock.position_at(start + secs(50)), secs(15));
+  }
+
+  #[test]
+  fn rate_changes_mid_track() {
+    let start = Instant::now();
+    let mut clock = PlaybackClock::new();
+    playing(&mut clock, start);
+
+    clock.ingest_at(&SpotifyEvent::PlaybackRateChanged(2.0), start + secs(10));
+    assert_eq!(clock.position_at(start + secs(15)), secs(20));
+
+    clock.ingest_at(&SpotifyEvent::PlaybackRateChanged(0.5), start + secs(15));
+    assert_eq!(clock.position_at(start + secs(19)), secs(22));
+    assert_eq!(clock.playback_rate(), 0.5);
+
+    // nonsense from the socket is normal speed
+    clock.ingest_at(&SpotifyEvent::PlaybackRateChanged(f32::NAN), start + secs(19));
+    assert_eq!(clock.position_at(start + secs(20)), secs(23));
+  }
+
+  #[test]
+  fn progress_moves_it() {
+    let start = Instant::now();
+    let mut clock = PlaybackClock::new();
+    playing(&mut clock, start);
+
+    // from the future so there's no latency to take away
+    let progress = SpotifyEvent::ProgressChanged { position: secs(40), percent: 40.0 / 60.0, timestamp: SystemTime::now() + secs(60) };
+    clock.ingest_at(&progress, start + secs(1));
+
+    assert_eq!(clock.position_at(start + secs(3)), secs(42));
+  }
+
+  #[test]
+  fn clamps_to_the_duration() {
+    let start = Instant::now();
+    let mut clock = PlaybackClock::new();
+    playing(&mut clock, start);
+
+    assert_eq!(clock.position_at(start + secs(600)), secs(60));
+    assert_eq!(clock.position_at(start + crate::FAR_FUTURE), secs(60));
+
+    // without a duration it keeps going
+    let mut clock = PlaybackClock::new();
+    clock.ingest_at(&SpotifyEvent::StateChanged(TrackState::Playing), start);
+    assert_eq!(clock.position_at(start + secs(600)), secs(600));
+  }
+}
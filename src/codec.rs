@@ -0,0 +1,455 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tungstenite::Message;
+
+use crate::{SpotifyError, SpotifyEvent, SpotifyMessage, SpotifyResult};
+
+/// Every gzip payload starts with these, none of the codecs can start an event with them
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Most a gzip payload can grow to, so a tiny frame can't take all the memory
+#[cfg(feature = "gzip")]
+const MAX_DECOMPRESSED_SIZE: u64 = 64 << 20;
+
+/// Turns messages into websocket frames and frames back into events,
+/// implement it to use a wire format this crate doesn't know about with
+/// [SpotifyConnection::with_codec](crate::SpotifyConnection::with_codec)
+pub trait EventCodec {
+  /// Turns a message for the extension into the frame that gets sent
+  fn encode(&self, message: &SpotifyMessage) -> SpotifyResult<Message>;
+
+  /// Turns a frame from the extension into an event,
+  /// only gets called with text and binary frames since the rest don't carry events
+  fn decode(&self, message: Message) -> SpotifyResult<SpotifyEvent>;
+
+  /// Same as [Self::decode] but fails on fields it doesn't know about, used with [ParseMode::Strict]
+  /// (unknown events get checked after this),
+  /// does the same as [Self::decode] unless it's implemented
+  fn decode_strict(&self, message: Message) -> SpotifyResult<SpotifyEvent> {
+    self.decode(message)
+  }
+}
+
+/// What happens with events that don't look like what the crate expects,
+/// set with [SpotifyListenerBuilder::parse_mode](crate::SpotifyListenerBuilder::parse_mode)
+///
+/// Default: Lenient
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ParseMode {
+  /// Unknown fields get ignored and unknown events become [SpotifyEvent::Unknown],
+  /// events that can't be decoded get logged with the `log` crate and skipped
+  /// so one bad event doesn't end a `while let Some(Ok(event))` loop
+  #[default]
+  Lenient,
+  /// Unknown fields, unknown events and events that can't be decoded are all errors,
+  /// for making sure an extension sends exactly what the crate expects
+  ///
+  /// Fields only get checked with [Codec] or an [EventCodec] that implements [EventCodec::decode_strict],
+  /// camelCase and the other old names from [SCHEMA_VERSION](crate::SCHEMA_VERSION) are still allowed
+  Strict,
+}
+
+/// Format of everything sent after the hello, the extension asks for one when it connects
+/// and [SpotifyConnection::codec](crate::SpotifyConnection::codec) says which one it got,
+/// [SpotifyListenerBuilder::codecs](crate::SpotifyListenerBuilder::codecs) limits which ones it can get
+///
+/// The hellos themselves are always json, so both ends can understand them
+///
+/// Default: Json
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum Codec {
+  /// Json in text frames, what every extension speaks, binary frames with json work too
+  #[default]
+  #[serde(rename = "json")]
+  Json,
+  /// [MessagePack](https://msgpack.org) in binary frames, structs are maps with their field names
+  /// so it looks the same as the json, only with the `msgpack` feature
+  #[cfg(feature = "msgpack")]
+  #[serde(rename = "msgpack")]
+  MessagePack,
+  /// [CBOR](https://cbor.io) in binary frames, structs are maps with their field names
+  /// same as [Self::MessagePack], only with the `cbor` feature
+  #[cfg(feature = "cbor")]
+  #[serde(rename = "cbor")]
+  Cbor,
+}
+
+impl Codec {
+  /// Every codec this build supports, in the order the listener prefers them
+  pub const SUPPORTED: &'static [Codec] = &[
+    #[cfg(feature = "msgpack")]
+    Codec::MessagePack,
+    #[cfg(feature = "cbor")]
+    Codec::Cbor,
+    Codec::Json,
+  ];
+
+  /// Name of the codec, same as it's sent in the hello
+  pub fn name(self) -> &'static str {
+    match self {
+      Codec::Json => "json",
+      #[cfg(feature = "msgpack")]
+      Codec::MessagePack => "msgpack",
+      #[cfg(feature = "cbor")]
+      Codec::Cbor => "cbor",
+    }
+  }
+
+  #[cfg(any(feature = "async", feature = "blocking"))]
+  /// The first codec in `requested` that's in `allowed`, [Self::Json] if there's none,
+  /// unknown names get skipped since they're from newer extensions
+  pub(crate) fn negotiate(requested: &[String], allowed: &[Codec]) -> Self {
+    requested
+      .iter()
+      .find_map(|name| allowed.iter().copied().find(|codec| codec.name() == name))
+      .unwrap_or_default()
+  }
+}
+
+impl EventCodec for Codec {
+  fn encode(&self, message: &SpotifyMessage) -> SpotifyResult<Message> {
+    let message = match self {
+      Codec::Json => Message::Text(serde_json::to_string(message).expect("messages always serialize")),
+      #[cfg(feature = "msgpack")]
+      Codec::MessagePack => Message::Binary(rmp_serde::to_vec_named(message).expect("messages always serialize")),
+      #[cfg(feature = "cbor")]
+      Codec::Cbor => {
+        let mut bytes = Vec::new();
+        ciborium::into_writer(message, &mut bytes).expect("messages always serialize");
+        Message::Binary(bytes)
+      }
+    };
+
+    Ok(message)
+  }
+
+  /// Binary frames can be gzipped with every codec, see [decompress]
+  fn decode(&self, message: Message) -> SpotifyResult<SpotifyEvent> {
+    self.decode_as(message)
+  }
+
+  /// Decodes it without a type first, so every field can be checked against what the event has
+  fn decode_strict(&self, message: Message) -> SpotifyResult<SpotifyEvent> {
+    let value = self.decode_as::<Value>(message)?;
+    let event = serde_json::from_value::<SpotifyEvent>(value.clone()).map_err(SpotifyError::Deserialize)?;
+
+    let known = serde_json::to_value(&event).expect("events always serialize");
+
+    match unknown_field(&value, &known, "") {
+      Some(path) => Err(SpotifyError::Deserialize(serde::de::Error::custom(format!("unknown field {} in {}", path, event.kind())))),
+      None => Ok(event),
+    }
+  }
+}
+
+impl Codec {
+  fn decode_as<T: DeserializeOwned>(&self, message: Message) -> SpotifyResult<T> {
+    let bytes = match message {
+      Message::Text(text) if *self == Codec::Json => return serde_json::from_str(&text).map_err(SpotifyError::Deserialize),
+      Message::Text(_) => return Err(SpotifyError::Protocol(format!("text frames aren't used with {}", self.name()))),
+      Message::Binary(bytes) => decompress(bytes)?,
+      _ => return Err(SpotifyError::Protocol("control frames don't carry events".to_string())),
+    };
+
+    match self {
+      Codec::Json => serde_json::from_slice(&bytes).map_err(SpotifyError::Deserialize),
+      #[cfg(feature = "msgpack")]
+      Codec::MessagePack => rmp_serde::from_slice(&bytes).map_err(|err| {
+        SpotifyError::Deserialize(serde::de::Error::custom(err))
+      }),
+      #[cfg(feature = "cbor")]
+      Codec::Cbor => ciborium::from_reader(bytes.as_slice()).map_err(|err| {
+        SpotifyError::Deserialize(serde::de::Error::custom(err))
+      }),
+    }
+  }
+}
+
+/// Old names that are still accepted and aren't only the camelCase version of the field,
+/// the same ones [SCHEMA_VERSION](crate::SCHEMA_VERSION) talks about
+const OLD_NAMES: &[(&str, &str)] = &[("artist", "artists"), ("cover", "cover_url"), ("background", "background_url")];
+
+/// First field in `value` that isn't in `known` (the event serialized again) as its name or one of its aliases,
+/// like `data.album.foo`
+fn unknown_field(value: &Value, known: &Value, path: &str) -> Option<String> {
+  match (value, known) {
+    (Value::Object(fields), Value::Object(known)) => fields.iter().find_map(|(name, value)| {
+      let path = match path.is_empty() {
+        true => name.clone(),
+        false => format!("{}.{}", path, name),
+      };
+
+      let old = OLD_NAMES.iter().find(|(old, _)| old == name).map(|(_, name)| *name);
+      let known = known.get(name).or_else(|| known.get(&to_snake_case(name))).or_else(|| old.and_then(|name| known.get(name)));
+
+      match known {
+        Some(known) => unknown_field(value, known, &path),
+        None => Some(path),
+      }
+    }),
+    (Value::Array(values), Value::Array(known)) => values
+      .iter()
+      .zip(known)
+      .enumerate()
+      .find_map(|(n, (value, known))| unknown_field(value, known, &format!("{}[{}]", path, n))),
+    _ => None,
+  }
+}
+
+fn to_snake_case(name: &str) -> String {
+  let mut snake_case = String::with_capacity(name.len() + 4);
+
+  for char in name.chars() {
+    if char.is_ascii_uppercase() {
+      snake_case.push('_');
+    }
+
+    snake_case.push(char.to_ascii_lowercase());
+  }
+
+  snake_case
+}
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+/// Decodes text and binary frames, [None] for control frames (ping, pong, close) since they don't carry events
+/// and for events that couldn't be decoded with [ParseMode::Lenient]
+pub(crate) fn decode_event(codec: &impl EventCodec, message: Message, mode: ParseMode) -> Option<SpotifyResult<SpotifyEvent>> {
+  let event = match (message, mode) {
+    (message @ (Message::Text(_) | Message::Binary(_)), ParseMode::Lenient) => codec.decode(message),
+    (message @ (Message::Text(_) | Message::Binary(_)), ParseMode::Strict) => codec.decode_strict(message),
+    _ => return None,
+  };
+
+  match (event.and_then(check_event), mode) {
+    (Ok(SpotifyEvent::Unknown { kind, .. }), ParseMode::Strict) => {
+      Some(Err(SpotifyError::Deserialize(serde::de::Error::custom(format!("unknown event {}", kind)))))
+    }
+    (Err(err), ParseMode::Lenient) => {
+      log::warn!("skipped an event that couldn't be decoded: {}", err);
+      None
+    }
+    (event, _) => Some(event),
+  }
+}
+
+#[cfg(any(feature = "async", feature = "blocking"))]
+/// Known events only end up as [SpotifyEvent::Unknown] when their data is wrong
+fn check_event(event: SpotifyEvent) -> SpotifyResult<SpotifyEvent> {
+  match event {
+    SpotifyEvent::Unknown { kind, .. } if SpotifyEvent::KINDS.contains(&kind.as_str()) => {
+      let err = serde::de::Error::custom(format!("invalid data for {}", kind));
+
+      Err(SpotifyError::Deserialize(err))
+    }
+    event => Ok(event),
+  }
+}
+
+/// Extensions can gzip big payloads like lyrics with `CompressionStream("gzip")`,
+/// anything that isn't gzipped is returned as it is
+///
+/// Only with the `gzip` feature, otherwise gzipped payloads fail with [SpotifyError::Protocol]
+fn decompress(bytes: Vec<u8>) -> SpotifyResult<Vec<u8>> {
+  if !bytes.starts_with(&GZIP_MAGIC) {
+    return Ok(bytes);
+  }
+
+  #[cfg(feature = "gzip")]
+  {
+    use std::io::Read;
+
+    let mut decompressed = Vec::new();
+    let decoder = flate2::read::GzDecoder::new(bytes.as_slice());
+
+    decoder
+      .take(MAX_DECOMPRESSED_SIZE + 1)
+      .read_to_end(&mut decompressed)
+      .map_err(|err| SpotifyError::Protocol(format!("invalid gzip payload: {}", err)))?;
+
+    if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+      return Err(SpotifyError::Protocol("gzip payload is too big".to_string()));
+    }
+
+    Ok(decompressed)
+  }
+
+  #[cfg(not(feature = "gzip"))]
+  Err(SpotifyError::Protocol("gzipped payloads need the gzip feature".to_string()))
+}
+
+#[cfg(all(test, any(feature = "async", feature = "blocking")))]
+mod tests {
+  use super::*;
+
+  fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+  }
+
+  #[test]
+  fn negotiate() {
+    assert_eq!(Codec::negotiate(&[], Codec::SUPPORTED), Codec::Json);
+    assert_eq!(Codec::negotiate(&names(&["zstd", "json"]), Codec::SUPPORTED), Codec::Json);
+    assert_eq!(Codec::negotiate(&names(&["json"]), &[]), Codec::Json);
+
+    #[cfg(feature = "msgpack")]
+    {
+      assert_eq!(Codec::negotiate(&names(&["msgpack", "json"]), Codec::SUPPORTED), Codec::MessagePack);
+      assert_eq!(Codec::negotiate(&names(&["msgpack"]), &[Codec::Json]), Codec::Json);
+    }
+  }
+
+  fn track_changed() -> Value {
+    let track = crate::TrackInfo::builder()
+      .uid("a")
+      .title("Song")
+      .artist("Artist")
+      .album("Album")
+      .cover_url("https://i.scdn.co/image/a")
+      .track_number(1, 1)
+      .build()
+      .unwrap();
+
+    serde_json::to_value(SpotifyEvent::TrackChanged(track)).unwrap()
+  }
+
+  /// Moves `from` to `to` in the data of `event`
+  fn rename(event: &mut Value, from: &str, to: &str) {
+    let data = event["data"].as_object_mut().unwrap();
+    let value = data.remove(from).unwrap_or_else(|| panic!("no {} in {:?}", from, data));
+
+    data.insert(to.to_string(), value);
+  }
+
+  fn decode(event: &Value, mode: ParseMode) -> Option<SpotifyResult<SpotifyEvent>> {
+    decode_event(&Codec::Json, Message::Text(event.to_string()), mode)
+  }
+
+  #[test]
+  fn strict_allows_old_names() {
+    let mut event = track_changed();
+    rename(&mut event, "artists", "artist");
+    rename(&mut event, "cover_url", "coverUrl");
+    rename(&mut event, "track_number", "trackNumber");
+
+    assert!(matches!(decode(&event, ParseMode::Strict), Some(Ok(SpotifyEvent::TrackChanged(info))) if info.cover_url.as_deref() == Some("https://i.scdn.co/image/a")));
+
+    let mut event = track_changed();
+    rename(&mut event, "cover_url", "cover");
+    assert!(matches!(decode(&event, ParseMode::Strict), Some(Ok(SpotifyEvent::TrackChanged(_)))));
+  }
+
+  #[test]
+  fn strict_rejects_unknown_fields() {
+    let mut event = track_changed();
+    event["data"]["album"]["foo"] = Value::from(1);
+
+    match decode(&event, ParseMode::Strict) {
+      Some(Err(SpotifyError::Deserialize(err))) => assert!(err.to_string().contains("album.foo"), "{}", err),
+      event => panic!("expected an unknown field, got {:?}", event),
+    }
+
+    // lenient doesn't care
+    assert!(matches!(decode(&event, ParseMode::Lenient), Some(Ok(SpotifyEvent::TrackChanged(_)))));
+
+    let mut event = track_changed();
+    event["data"]["artists"][0]["foo"] = Value::from(1);
+    assert!(matches!(decode(&event, ParseMode::Strict), Some(Err(SpotifyError::Deserialize(_)))));
+
+    let mut event = track_changed();
+    event["foo"] = Value::from(1);
+    assert!(matches!(decode(&event, ParseMode::Strict), Some(Err(SpotifyError::Deserialize(_)))));
+  }
+
+  #[test]
+  fn unknown_events() {
+    let event = serde_json::json!({ "type": "SomethingNew", "data": { "a": 1 } });
+
+    assert!(matches!(decode(&event, ParseMode::Lenient), Some(Ok(SpotifyEvent::Unknown { kind, .. })) if kind == "SomethingNew"));
+    assert!(matches!(decode(&event, ParseMode::Strict), Some(Err(SpotifyError::Deserialize(_)))));
+  }
+
+  #[test]
+  fn known_events_with_wrong_data() {
+    let event = serde_json::json!({ "type": "VolumeChanged", "data": "loud" });
+
+    // skipped instead of showing up as unknown
+    assert!(decode(&event, ParseMode::Lenient).is_none());
+    assert!(matches!(decode(&event, ParseMode::Strict), Some(Err(SpotifyError::Deserialize(_)))));
+  }
+
+  #[test]
+  fn broken_json() {
+    let broken = || Message::Text("{\"type\":".to_string());
+
+    assert!(decode_event(&Codec::Json, broken(), ParseMode::Lenient).is_none());
+    assert!(matches!(decode_event(&Codec::Json, broken(), ParseMode::Strict), Some(Err(SpotifyError::Deserialize(_)))));
+  }
+
+  #[test]
+  fn control_frames_arent_events() {
+    for mode in [ParseMode::Lenient, ParseMode::Strict] {
+      assert!(decode_event(&Codec::Json, Message::Ping(Vec::new()), mode).is_none());
+      assert!(decode_event(&Codec::Json, Message::Close(None), mode).is_none());
+    }
+  }
+
+  fn volume() -> Vec<u8> {
+    serde_json::to_vec(&serde_json::json!({ "type": "VolumeChanged", "data": 0.5 })).unwrap()
+  }
+
+  #[cfg(feature = "gzip")]
+  fn gzip(bytes: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(bytes).unwrap();
+    encoder.finish().unwrap()
+  }
+
+  #[test]
+  fn binary_json() {
+    assert!(matches!(Codec::Json.decode(Message::Binary(volume())), Ok(SpotifyEvent::VolumeChanged(volume)) if volume == 0.5));
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn gzipped_binary_frames() {
+    assert!(matches!(Codec::Json.decode(Message::Binary(gzip(&volume()))), Ok(SpotifyEvent::VolumeChanged(_))));
+    assert!(matches!(Codec::Json.decode_strict(Message::Binary(gzip(&volume()))), Ok(SpotifyEvent::VolumeChanged(_))));
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn broken_gzip() {
+    let mut broken = gzip(&volume());
+    broken.truncate(broken.len() / 2);
+
+    assert!(matches!(Codec::Json.decode(Message::Binary(broken)), Err(SpotifyError::Protocol(_))));
+    assert!(matches!(Codec::Json.decode(Message::Binary(GZIP_MAGIC.to_vec())), Err(SpotifyError::Protocol(_))));
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn gzip_bombs() {
+    let bomb = gzip(&vec![b' '; MAX_DECOMPRESSED_SIZE as usize + 1]);
+
+    match Codec::Json.decode(Message::Binary(bomb)) {
+      Err(SpotifyError::Protocol(err)) => assert!(err.contains("too big"), "{}", err),
+      event => panic!("expected it to be too big, got {:?}", event),
+    }
+  }
+
+  #[cfg(not(feature = "gzip"))]
+  #[test]
+  fn gzip_needs_the_feature() {
+    let mut gzipped = GZIP_MAGIC.to_vec();
+    gzipped.extend(volume());
+
+    match Codec::Json.decode(Message::Binary(gzipped)) {
+      Err(SpotifyError::Protocol(err)) => assert!(err.contains("gzip feature"), "{}", err),
+      event => panic!("expected a protocol error, got {:?}", event),
+    }
+  }
+}
@@ -0,0 +1,182 @@
+use std::fmt::{Display, Formatter};
+
+/// Where spotify serves images from
+const IMAGE_URL: &str = "https://i.scdn.co/image/";
+
+/// Image ids start with 8 characters for what it's of and 8 for the size,
+/// these are the sizes for each of them from small to large, in pixels
+const FAMILIES: &[(&str, [(&str, u32); 3])] = &[
+  // albums
+  ("ab67616d", [("00004851", 64), ("00001e02", 300), ("0000b273", 640)]),
+  // artists
+  ("ab676161", [("0000f178", 160), ("00005174", 320), ("0000e5eb", 640)]),
+  // podcasts
+  ("ab676563", [("0000f68d", 64), ("00005f1f", 300), ("0000ba8a", 640)]),
+];
+
+/// Sizes every [CoverArt] is available at, the pixels depend on what it's of
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum CoverSize {
+  /// 64x64 for albums
+  Small,
+  /// 300x300 for albums
+  Medium,
+  /// 640x640 for albums
+  Large,
+}
+
+impl CoverSize {
+  const ALL: [Self; 3] = [Self::Small, Self::Medium, Self::Large];
+
+  fn index(self) -> usize {
+    self as usize
+  }
+}
+
+/// A cover from spotify's image CDN, the size is part of the url
+/// so any of the others can be made from the one that was received
+///
+/// Only works for images from `i.scdn.co` (or `spotify:image:` URIs), which is where every cover the extension sends is from
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct CoverArt {
+  family: usize,
+  size: CoverSize,
+  /// Everything after the size
+  hash: String,
+}
+
+impl CoverArt {
+  /// Takes a `https://i.scdn.co/image/...` url or a `spotify:image:...` URI,
+  /// [None] if it's from somewhere else or the size isn't one of the known ones
+  pub fn parse(url: &str) -> Option<Self> {
+    let id = url.strip_prefix(IMAGE_URL)
+      .or_else(|| url.strip_prefix("http://i.scdn.co/image/"))
+      .or_else(|| url.strip_prefix("spotify:image:"))?;
+
+    if id.len() <= 16 || !id.is_ascii() {
+      return None;
+    }
+
+    let (prefix, rest) = id.split_at(8);
+    let (size, hash) = rest.split_at(8);
+    let family = FAMILIES.iter().position(|(family, _)| *family == prefix)?;
+    let size = FAMILIES[family].1.iter().position(|(code, _)| *code == size)?;
+
+    Some(Self {
+      family,
+      size: CoverSize::ALL[size],
+      hash: hash.to_string(),
+    })
+  }
+
+  /// Size of the url this was made from
+  pub fn size(&self) -> CoverSize {
+    self.size
+  }
+
+  /// Width (and height) of the cover at `size`
+  pub fn pixels(&self, size: CoverSize) -> u32 {
+    FAMILIES[self.family].1[size.index()].1
+  }
+
+  /// Url of the cover at `size`
+  pub fn url(&self, size: CoverSize) -> String {
+    let (family, sizes) = FAMILIES[self.family];
+
+    format!("{}{}{}{}", IMAGE_URL, family, sizes[size.index()].0, self.hash)
+  }
+
+  pub fn small(&self) -> String {
+    self.url(CoverSize::Small)
+  }
+
+  pub fn medium(&self) -> String {
+    self.url(CoverSize::Medium)
+  }
+
+  pub fn large(&self) -> String {
+    self.url(CoverSize::Large)
+  }
+
+  /// Url of the smallest size that's at least `pixels` wide, or the largest one if none are,
+  /// for picking one that fits where it's shown
+  pub fn at_least(&self, pixels: u32) -> String {
+    let size = CoverSize::ALL.into_iter().find(|size| self.pixels(*size) >= pixels).unwrap_or(CoverSize::Large);
+
+    self.url(size)
+  }
+}
+
+/// The url at the size it was received at
+impl Display for CoverArt {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.url(self.size))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HASH: &str = "d3acd5d4ab4ab1a3e5c7e3c4a4b2ffb4f1e1a1b2";
+
+  fn album(size: &str) -> String {
+    format!("{}ab67616d{}{}", IMAGE_URL, size, HASH)
+  }
+
+  #[test]
+  fn parses_every_size() {
+    for (code, size) in [("00004851", CoverSize::Small), ("00001e02", CoverSize::Medium), ("0000b273", CoverSize::Large)] {
+      let cover = CoverArt::parse(&album(code)).unwrap();
+
+      assert_eq!(cover.size(), size);
+      assert_eq!(cover.to_string(), album(code));
+    }
+  }
+
+  #[test]
+  fn makes_the_other_sizes() {
+    let cover = CoverArt::parse(&album("00001e02")).unwrap();
+
+    assert_eq!(cover.small(), album("00004851"));
+    assert_eq!(cover.medium(), album("00001e02"));
+    assert_eq!(cover.large(), album("0000b273"));
+    assert_eq!([CoverSize::Small, CoverSize::Medium, CoverSize::Large].map(|size| cover.pixels(size)), [64, 300, 640]);
+  }
+
+  #[test]
+  fn sizes_depend_on_the_family() {
+    let artist = CoverArt::parse(&format!("spotify:image:ab6761610000f178{}", HASH)).unwrap();
+
+    assert_eq!(artist.pixels(CoverSize::Small), 160);
+    assert_eq!(artist.large(), format!("{}ab6761610000e5eb{}", IMAGE_URL, HASH));
+
+    let podcast = CoverArt::parse(&format!("http://i.scdn.co/image/ab6765630000ba8a{}", HASH)).unwrap();
+
+    assert_eq!(podcast.size(), CoverSize::Large);
+    assert_eq!(podcast.medium(), format!("{}ab67656300005f1f{}", IMAGE_URL, HASH));
+  }
+
+  #[test]
+  fn at_least() {
+    let cover = CoverArt::parse(&album("00004851")).unwrap();
+
+    assert_eq!(cover.at_least(0), album("00004851"));
+    assert_eq!(cover.at_least(64), album("00004851"));
+    assert_eq!(cover.at_least(65), album("00001e02"));
+    assert_eq!(cover.at_least(4000), album("0000b273"));
+  }
+
+  #[test]
+  fn rejects_other_urls() {
+    for url in [
+      format!("https://example.com/image/ab67616d00001e02{}", HASH),
+      format!("{}ab67616dffffffff{}", IMAGE_URL, HASH),
+      format!("{}ffffffff00001e02{}", IMAGE_URL, HASH),
+      format!("{}ab67616d00001e02", IMAGE_URL),
+      format!("{}ab67616d00001e02é", IMAGE_URL),
+    ] {
+      assert_eq!(CoverArt::parse(&url), None, "{}", url);
+    }
+  }
+}
@@ -0,0 +1,416 @@
+//! Events the extension doesn't send, made by the listener out of the ones it does
+
+use std::time::{Duration, Instant};
+
+use crate::{clamp_playback_rate, scale_by_rate, Interceptor, PlaybackClock, SpotifyEvent, TrackInfo, TrackState};
+
+/// How close to the end a track still counts as finished,
+/// progress only comes every second by default and spotify changes tracks a bit before the end
+const FINISH_MARGIN: Duration = Duration::from_secs(3);
+
+/// Tracks shorter than this never get scrobbled
+const MIN_SCROBBLE_DURATION: Duration = Duration::from_secs(30);
+
+/// Long tracks get scrobbled after this even if it's less than half of them
+const MAX_SCROBBLE_THRESHOLD: Duration = Duration::from_secs(4 * 60);
+
+/// Adds [SpotifyEvent::TrackFinished] and [SpotifyEvent::TrackSkipped] before track changes,
+/// see [SpotifyListenerBuilder::track_lifecycle](crate::SpotifyListenerBuilder::track_lifecycle)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrackLifecycle {
+  track: Option<TrackInfo>,
+  clock: PlaybackClock,
+}
+
+impl TrackLifecycle {
+  fn is_near_end(&self, now: Instant) -> bool {
+    let duration = self.clock.duration();
+
+    !duration.is_zero() && self.clock.position_at(now) + FINISH_MARGIN >= duration
+  }
+
+  /// The same track sent again in the middle of it, repeating it only counts once it got to the end
+  fn is_resent(&self, info: &TrackInfo, now: Instant) -> bool {
+    let same = self.track.as_ref().is_some_and(|track| track.eq_ignore_state(info) && track.uri == info.uri);
+
+    same && !self.is_near_end(now)
+  }
+
+  /// The current track is over, whichever way it ended
+  fn end(&mut self, now: Instant, events: &mut Vec<SpotifyEvent>) {
+    let track = match self.track.take() {
+      Some(track) if !track.duration.is_zero() => track,
+      // no way to tell how far it got
+      _ => return,
+    };
+
+    if self.is_near_end(now) {
+      events.push(SpotifyEvent::TrackFinished(track));
+    } else {
+      let at = self.clock.position_at(now);
+
+      events.push(SpotifyEvent::TrackSkipped { track, at });
+    }
+  }
+
+  /// [Interceptor::on_event] with `now` as the time `event` arrived
+  fn on_event_at(&mut self, event: SpotifyEvent, now: Instant, events: &mut Vec<SpotifyEvent>) {
+    match &event {
+      SpotifyEvent::TrackChanged(info) if self.is_resent(info, now) => {}
+      SpotifyEvent::TrackChanged(info) => {
+        self.end(now, events);
+        self.track = Some(info.clone());
+        self.clock.ingest_at(&event, now);
+      }
+      // the end of the queue, there's no next track to wait for
+      SpotifyEvent::StateChanged(TrackState::Stopped) if self.is_near_end(now) => {
+        self.end(now, events);
+        self.clock.ingest_at(&event, now);
+      }
+      SpotifyEvent::StateSnapshot(snapshot) => {
+        self.track = snapshot.track.clone();
+        self.clock.ingest_at(&event, now);
+      }
+      _ => self.clock.ingest_at(&event, now),
+    }
+
+    events.push(event);
+  }
+}
+
+impl Interceptor for TrackLifecycle {
+  fn on_event(&mut self, event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+    self.on_event_at(event, Instant::now(), events);
+  }
+}
+
+/// Adds [SpotifyEvent::ScrobblePoint] after enough of a track was played,
+/// see [SpotifyListenerBuilder::scrobble_points](crate::SpotifyListenerBuilder::scrobble_points)
+///
+/// Played time is time in the track, so a podcast at 2x gets there in half the time
+#[derive(Debug, Clone)]
+pub(crate) struct ScrobbleTimer {
+  track: Option<TrackInfo>,
+  /// Time played before [Self::playing_since]
+  played: Duration,
+  /// Only set while playing
+  playing_since: Option<Instant>,
+  rate: f32,
+  scrobbled: bool,
+}
+
+impl Default for ScrobbleTimer {
+  fn default() -> Self {
+    Self {
+      track: None,
+      played: Duration::ZERO,
+      playing_since: None,
+      rate: 1.0,
+      scrobbled: false,
+    }
+  }
+}
+
+impl ScrobbleTimer {
+  #[cfg(any(feature = "lastfm", feature = "listenbrainz"))]
+  pub(crate) fn track(&self) -> Option<&TrackInfo> {
+    self.track.as_ref()
+  }
+
+  #[cfg(any(feature = "lastfm", feature = "listenbrainz"))]
+  pub(crate) fn is_playing(&self) -> bool {
+    self.playing_since.is_some()
+  }
+
+  #[cfg(any(feature = "lastfm", feature = "listenbrainz"))]
+  pub(crate) fn played(&self) -> Duration {
+    self.played_at(Instant::now())
+  }
+
+  fn played_at(&self, now: Instant) -> Duration {
+    let since = self.playing_since.map_or(Duration::ZERO, |since| now.saturating_duration_since(since));
+
+    self.played.saturating_add(scale_by_rate(since, self.rate))
+  }
+
+  fn set_state(&mut self, state: TrackState, now: Instant) {
+    self.played = self.played_at(now);
+    self.playing_since = (state == TrackState::Playing).then_some(now);
+  }
+
+  fn set_rate(&mut self, rate: f32, now: Instant) {
+    // everything before this played at the old rate
+    self.played = self.played_at(now);
+    self.playing_since = self.playing_since.map(|_| now);
+    self.rate = clamp_playback_rate(rate);
+  }
+
+  fn start(&mut self, track: Option<TrackInfo>, state: TrackState, now: Instant) {
+    self.track = track;
+    self.played = Duration::ZERO;
+    self.scrobbled = false;
+    self.set_state(state, now);
+  }
+
+  /// The same track sent again in the middle of it, it counts as played again once all of it was played
+  #[cfg(any(feature = "lastfm", feature = "listenbrainz"))]
+  pub(crate) fn is_resent(&self, info: &TrackInfo) -> bool {
+    self.is_resent_at(info, Instant::now())
+  }
+
+  fn is_resent_at(&self, info: &TrackInfo, now: Instant) -> bool {
+    let same = self.track.as_ref().is_some_and(|track| track.eq_ignore_state(info) && track.uri == info.uri);
+
+    same && self.played_at(now) + FINISH_MARGIN < info.duration
+  }
+
+  fn threshold(track: &TrackInfo) -> Option<Duration> {
+    (track.duration >= MIN_SCROBBLE_DURATION).then(|| (track.duration / 2).min(MAX_SCROBBLE_THRESHOLD))
+  }
+
+  /// [Interceptor::on_event] with `now` as the time `event` arrived
+  fn on_event_at(&mut self, event: SpotifyEvent, now: Instant, events: &mut Vec<SpotifyEvent>) {
+    match &event {
+      SpotifyEvent::TrackChanged(info) if self.is_resent_at(info, now) => self.set_state(info.state, now),
+      SpotifyEvent::TrackChanged(info) => self.start(Some(info.clone()), info.state, now),
+      SpotifyEvent::StateChanged(state) => self.set_state(*state, now),
+      SpotifyEvent::PlaybackRateChanged(rate) => self.set_rate(*rate, now),
+      SpotifyEvent::StateSnapshot(snapshot) => {
+        self.set_rate(snapshot.playback_rate, now);
+
+        match &snapshot.track {
+          Some(info) if self.is_resent_at(info, now) => self.set_state(snapshot.state, now),
+          track => self.start(track.clone(), snapshot.state, now),
+        }
+      }
+      _ => {}
+    }
+
+    events.push(event);
+
+    if self.scrobbled {
+      return;
+    }
+
+    if let Some(track) = &self.track {
+      if Self::threshold(track).is_some_and(|threshold| self.played_at(now) >= threshold) {
+        self.scrobbled = true;
+        events.push(SpotifyEvent::ScrobblePoint(track.clone()));
+      }
+    }
+  }
+}
+
+impl Interceptor for ScrobbleTimer {
+  fn on_event(&mut self, event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+    self.on_event_at(event, Instant::now(), events);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn secs(secs: u64) -> Duration {
+    Duration::from_secs(secs)
+  }
+
+  fn track(uid: &str, state: TrackState) -> TrackInfo {
+    let mut info = TrackInfo::builder().uid(uid).title(uid).artist("artist").duration(secs(60)).build().unwrap();
+    info.state = state;
+    info
+  }
+
+  fn changed(uid: &str) -> SpotifyEvent {
+    SpotifyEvent::TrackChanged(track(uid, TrackState::Playing))
+  }
+
+  fn state(state: TrackState) -> SpotifyEvent {
+    SpotifyEvent::StateChanged(state)
+  }
+
+  /// Feeds `events` at their offsets from one start, returning only what was added
+  fn lifecycle(events: Vec<(u64, SpotifyEvent)>) -> Vec<SpotifyEvent> {
+    let start = Instant::now();
+    let mut lifecycle = TrackLifecycle::default();
+    let mut out = Vec::new();
+
+    for (at, event) in events {
+      let mut pushed = Vec::new();
+      lifecycle.on_event_at(event, start + secs(at), &mut pushed);
+      // the event itself always comes last
+      pushed.pop();
+      out.extend(pushed);
+    }
+
+    out
+  }
+
+  #[test]
+  fn finished_near_the_end() {
+    let out = lifecycle(vec![(0, changed("a")), (57, changed("b"))]);
+
+    assert!(matches!(&out[..], [SpotifyEvent::TrackFinished(track)] if track.uid == "a"));
+  }
+
+  #[test]
+  fn skipped_before_the_margin() {
+    let out = lifecycle(vec![(0, changed("a")), (56, changed("b"))]);
+
+    assert!(matches!(&out[..], [SpotifyEvent::TrackSkipped { track, at }] if track.uid == "a" && *at == secs(56)));
+  }
+
+  #[test]
+  fn paused_time_doesnt_count() {
+    let out = lifecycle(vec![
+      (0, changed("a")),
+      (10, state(TrackState::Paused)),
+      (100, state(TrackState::Playing)),
+      (110, changed("b")),
+    ]);
+
+    assert!(matches!(&out[..], [SpotifyEvent::TrackSkipped { at, .. }] if *at == secs(20)));
+  }
+
+  #[test]
+  fn first_track_ends_nothing() {
+    assert!(lifecycle(vec![(0, changed("a"))]).is_empty());
+  }
+
+  #[test]
+  fn resent_after_a_reconnect() {
+    // reconnecting sends the current track again, it keeps going where it was
+    let out = lifecycle(vec![(0, changed("a")), (20, changed("a")), (58, changed("b"))]);
+
+    assert!(matches!(&out[..], [SpotifyEvent::TrackFinished(track)] if track.uid == "a"));
+  }
+
+  #[test]
+  fn resent_at_the_end_is_a_repeat() {
+    let out = lifecycle(vec![(0, changed("a")), (58, changed("a")), (70, changed("b"))]);
+
+    assert!(matches!(&out[..], [
+      SpotifyEvent::TrackFinished(first),
+      SpotifyEvent::TrackSkipped { track: second, at },
+    ] if first.uid == "a" && second.uid == "a" && *at == secs(12)));
+  }
+
+  #[test]
+  fn stopped_at_the_end_finishes() {
+    let out = lifecycle(vec![(0, changed("a")), (59, state(TrackState::Stopped))]);
+
+    assert!(matches!(&out[..], [SpotifyEvent::TrackFinished(track)] if track.uid == "a"));
+  }
+
+  #[test]
+  fn stopped_in_the_middle_waits() {
+    // could be stopped and started again, it only ends with the next track
+    let out = lifecycle(vec![(0, changed("a")), (30, state(TrackState::Stopped))]);
+    assert!(out.is_empty());
+
+    let out = lifecycle(vec![(0, changed("a")), (30, state(TrackState::Stopped)), (40, changed("b"))]);
+    assert!(matches!(&out[..], [SpotifyEvent::TrackSkipped { at, .. }] if *at == secs(30)));
+  }
+
+  #[test]
+  fn stopped_twice_finishes_once() {
+    let out = lifecycle(vec![(0, changed("a")), (59, state(TrackState::Stopped)), (60, state(TrackState::Stopped))]);
+
+    assert_eq!(out.len(), 1);
+  }
+
+  fn scrobble_track(duration: u64) -> SpotifyEvent {
+    let info = TrackInfo::builder().uid("a").title("a").artist("artist").duration(secs(duration)).build().unwrap();
+
+    SpotifyEvent::TrackChanged(TrackInfo { state: TrackState::Playing, ..info })
+  }
+
+  /// When in `events` after `start` the scrobble point came, if it did
+  fn scrobbled_at(events: Vec<(u64, SpotifyEvent)>) -> Option<u64> {
+    let start = Instant::now();
+    let mut timer = ScrobbleTimer::default();
+    let last = events.last().map_or(0, |(at, _)| *at);
+
+    // check every second too, like progress events would
+    let mut events = events.into_iter().peekable();
+    for at in 0..=last {
+      let mut pushed = Vec::new();
+
+      while let Some((_, event)) = events.next_if(|(event_at, _)| *event_at == at) {
+        timer.on_event_at(event, start + secs(at), &mut pushed);
+      }
+      timer.on_event_at(SpotifyEvent::VolumeChanged(1.0), start + secs(at), &mut pushed);
+
+      if pushed.iter().any(|event| matches!(event, SpotifyEvent::ScrobblePoint(_))) {
+        return Some(at);
+      }
+    }
+
+    None
+  }
+
+  #[test]
+  fn scrobbles_at_half() {
+    assert_eq!(scrobbled_at(vec![(0, scrobble_track(200)), (300, state(TrackState::Playing))]), Some(100));
+  }
+
+  #[test]
+  fn scrobbles_long_tracks_after_four_minutes() {
+    assert_eq!(scrobbled_at(vec![(0, scrobble_track(3600)), (600, state(TrackState::Playing))]), Some(240));
+  }
+
+  #[test]
+  fn short_tracks_never_scrobble() {
+    assert_eq!(scrobbled_at(vec![(0, scrobble_track(29)), (100, state(TrackState::Playing))]), None);
+    assert_eq!(scrobbled_at(vec![(0, scrobble_track(30)), (100, state(TrackState::Playing))]), Some(15));
+  }
+
+  #[test]
+  fn paused_time_doesnt_scrobble() {
+    let events = vec![
+      (0, scrobble_track(200)),
+      (50, state(TrackState::Paused)),
+      (500, state(TrackState::Playing)),
+      (600, state(TrackState::Playing)),
+    ];
+
+    assert_eq!(scrobbled_at(events), Some(550));
+  }
+
+  #[test]
+  fn seeking_doesnt_count_as_played() {
+    let seek = SpotifyEvent::ProgressChanged { position: secs(190), percent: 0.95, timestamp: std::time::SystemTime::now() };
+    let events = vec![(0, scrobble_track(200)), (10, seek), (300, state(TrackState::Playing))];
+
+    assert_eq!(scrobbled_at(events), Some(100));
+  }
+
+  #[test]
+  fn counts_at_the_playback_rate() {
+    let events = vec![(0, scrobble_track(200)), (20, SpotifyEvent::PlaybackRateChanged(2.0)), (300, state(TrackState::Playing))];
+
+    // 20 at 1x and 40 at 2x
+    assert_eq!(scrobbled_at(events), Some(60));
+  }
+
+  #[test]
+  fn resent_keeps_counting() {
+    let events = vec![(0, scrobble_track(200)), (60, scrobble_track(200)), (300, state(TrackState::Playing))];
+
+    assert_eq!(scrobbled_at(events), Some(100));
+  }
+
+  #[test]
+  fn scrobbles_once() {
+    let start = Instant::now();
+    let mut timer = ScrobbleTimer::default();
+    let mut pushed = Vec::new();
+
+    timer.on_event_at(scrobble_track(200), start, &mut pushed);
+    for at in [100, 150, 199] {
+      timer.on_event_at(state(TrackState::Playing), start + secs(at), &mut pushed);
+    }
+
+    assert_eq!(pushed.iter().filter(|event| matches!(event, SpotifyEvent::ScrobblePoint(_))).count(), 1);
+  }
+}
@@ -0,0 +1,414 @@
+//! Discord Rich Presence, only with the `discord` feature
+
+use std::io;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::backoff::BackoffTimer;
+use crate::{Backoff, PlayerState, SpotifyEvent, SpotifyEvents, Template, TrackState};
+
+/// Discord only takes 5 updates every 20 seconds
+const MIN_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Progress events move the start time by a few milliseconds every time, only a seek should update it
+const SEEK_TOLERANCE_MS: u64 = 2000;
+
+/// How long discord gets to answer before the connection is given up on
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Biggest frame discord is expected to send, anything bigger means the stream is out of sync
+const MAX_FRAME: usize = 64 * 1024;
+
+const HANDSHAKE: u32 = 0;
+const FRAME: u32 = 1;
+const CLOSE: u32 = 2;
+const PING: u32 = 3;
+const PONG: u32 = 4;
+
+/// Shows what's playing on the user's Discord profile as "Listening to", with the cover and a progress bar
+///
+/// Needs the application id of an app from https://discord.com/developers/applications,
+/// the app's name is what shows after "Listening to". Discord has to run on the same machine,
+/// it's connected to when there's something to show and again after it restarts
+///
+/// Updates are held back so Discord's rate limit is never hit, only the latest one gets sent.
+/// The presence is cleared while paused or stopped, or shown without progress with [Self::clear_on_pause]
+///
+/// Default: `{title}`, `by {artist}` and the album as the cover's tooltip,
+/// `{title}` etc. are [Template]s with everything from [PlayerState]
+///
+/// ```text
+/// tokio::spawn(DiscordPresence::new(APP_ID).run(listener.events(64)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct DiscordPresence {
+  client_id: String,
+  details: Template,
+  state: Template,
+  large_text: Template,
+  fallback_image: Option<String>,
+  clear_on_pause: bool,
+  backoff: Backoff,
+}
+
+/// What discord is showing, with the start rounded to what gets compared
+#[derive(Debug, Clone, PartialEq)]
+struct Activity {
+  details: String,
+  state: String,
+  large_image: Option<String>,
+  large_text: String,
+  /// Start and end in unix milliseconds, only while playing
+  timestamps: Option<(u64, u64)>,
+}
+
+impl Activity {
+  /// The same except for the start moving a bit
+  fn is_same(&self, other: &Activity) -> bool {
+    let close = match (self.timestamps, other.timestamps) {
+      (Some((start, end)), Some((other_start, other_end))) => {
+        start.abs_diff(other_start) < SEEK_TOLERANCE_MS && end.abs_diff(other_end) < SEEK_TOLERANCE_MS
+      }
+      (None, None) => true,
+      _ => false,
+    };
+
+    close && Activity { timestamps: None, ..self.clone() } == Activity { timestamps: None, ..other.clone() }
+  }
+
+  fn to_json(&self) -> Value {
+    let mut activity = json!({ "type": 2 });
+
+    if let Some(details) = text(&self.details) {
+      activity["details"] = details.into();
+    }
+
+    if let Some(state) = text(&self.state) {
+      activity["state"] = state.into();
+    }
+
+    if let Some(image) = &self.large_image {
+      activity["assets"] = json!({ "large_image": image });
+
+      if let Some(large_text) = text(&self.large_text) {
+        activity["assets"]["large_text"] = large_text.into();
+      }
+    }
+
+    if let Some((start, end)) = self.timestamps {
+      activity["timestamps"] = json!({ "start": start, "end": end });
+    }
+
+    activity
+  }
+}
+
+impl DiscordPresence {
+  pub fn new(client_id: impl Into<String>) -> Self {
+    Self {
+      client_id: client_id.into(),
+      details: Template::parse("{title}").expect("valid template"),
+      state: Template::parse("{?artist}by {artist}{/artist}").expect("valid template"),
+      large_text: Template::parse("{album}").expect("valid template"),
+      fallback_image: None,
+      clear_on_pause: true,
+      backoff: Backoff {
+        initial: Duration::from_secs(5),
+        max: Duration::from_secs(60),
+      },
+    }
+  }
+
+  /// First line, under the app's name
+  pub fn details(mut self, template: Template) -> Self {
+    self.details = template;
+    self
+  }
+
+  /// Second line, under [Self::details]
+  pub fn state(mut self, template: Template) -> Self {
+    self.state = template;
+    self
+  }
+
+  /// What shows when hovering over the cover
+  pub fn large_text(mut self, template: Template) -> Self {
+    self.large_text = template;
+    self
+  }
+
+  /// Image for tracks without a cover (like local files), an asset key of the app or a url
+  pub fn fallback_image(mut self, image: impl Into<String>) -> Self {
+    self.fallback_image = Some(image.into());
+    self
+  }
+
+  /// Turn off to keep showing the track while paused, without a progress bar
+  pub fn clear_on_pause(mut self, clear: bool) -> Self {
+    self.clear_on_pause = clear;
+    self
+  }
+
+  /// How long to wait before connecting again when discord isn't running or the connection broke
+  pub fn backoff(mut self, backoff: Backoff) -> Self {
+    self.backoff = backoff;
+    self
+  }
+
+  /// Keeps the presence up to date with `events` until the listener is gone, then clears it
+  ///
+  /// Errors get logged with the `log` crate, discord not running only gets logged once until it connects
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn run(self, events: SpotifyEvents) {
+    let mut events = events.into_receiver();
+    let mut player = PlayerState::new();
+    let mut backoff = BackoffTimer::new(self.backoff);
+    let mut ipc: Option<Ipc> = None;
+    // [None] while it's unknown what discord shows, like before connecting
+    let mut shown: Option<Option<Activity>> = None;
+    let mut next_update = Instant::now();
+    let mut logged = false;
+
+    loop {
+      let wanted = self.activity(&player);
+      let outdated = match &shown {
+        Some(Some(shown)) => !wanted.as_ref().is_some_and(|wanted| wanted.is_same(shown)),
+        Some(None) => wanted.is_some(),
+        // nothing to clear without a connection
+        None => wanted.is_some() || ipc.is_some(),
+      };
+
+      let event = match outdated {
+        true => match tokio::time::timeout(next_update.saturating_duration_since(Instant::now()), events.recv()).await {
+          Ok(event) => event,
+          Err(_) => {
+            next_update = crate::instant_after(MIN_INTERVAL);
+
+            if ipc.is_none() {
+              match Ipc::connect(&self.client_id).await {
+                Ok(connected) => {
+                  ipc = Some(connected);
+                  logged = false;
+                }
+                Err(err) => {
+                  if !logged {
+                    log::warn!("couldn't connect to discord: {}", err);
+                    logged = true;
+                  }
+
+                  next_update = crate::instant_after(backoff.next_delay());
+                  continue;
+                }
+              }
+            }
+
+            let connected = ipc.as_mut().expect("just connected");
+
+            match connected.set_activity(wanted.as_ref().map(Activity::to_json)).await {
+              Ok(()) => {
+                backoff.reset();
+                shown = Some(wanted);
+              }
+              Err(err) => {
+                log::warn!("couldn't update discord presence: {}", err);
+                ipc = None;
+                shown = None;
+              }
+            }
+
+            continue;
+          }
+        },
+        false => events.recv().await,
+      };
+
+      match event {
+        Ok(SpotifyEvent::SessionEnded { .. }) => player = PlayerState::new(),
+        Ok(event) => player.apply(&event),
+        Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => break,
+      }
+    }
+
+    if let Some(mut ipc) = ipc {
+      let _ = ipc.set_activity(None).await;
+    }
+  }
+
+  /// What should be shown right now, [None] to clear it
+  fn activity(&self, player: &PlayerState) -> Option<Activity> {
+    let track = player.current_track()?;
+    let playing = player.state() == TrackState::Playing;
+
+    if !playing && (self.clear_on_pause || player.state() == TrackState::Stopped) {
+      return None;
+    }
+
+    // the progress bar is in real time, so podcasts at another speed end sooner or later
+    let timestamps = (playing && !track.duration.is_zero()).then(|| {
+      let rate = player.playback_rate().max(0.1) as f64;
+      let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+      let start = now.saturating_sub((player.position().as_millis() as f64 / rate) as u64);
+
+      (start, start + (track.duration.as_millis() as f64 / rate) as u64)
+    });
+
+    Some(Activity {
+      details: self.details.render(player),
+      state: self.state.render(player),
+      large_image: track.cover().map(str::to_string).or_else(|| self.fallback_image.clone()),
+      large_text: self.large_text.render(player),
+      timestamps,
+    })
+  }
+}
+
+/// Discord wants 2 to 128 characters, [None] for nothing at all
+fn text(text: &str) -> Option<String> {
+  let text = text.trim();
+
+  match text.chars().count() {
+    0 => None,
+    1 => Some(format!("{} ", text)),
+    _ => Some(text.chars().take(128).collect()),
+  }
+}
+
+#[cfg(unix)]
+type IpcStream = tokio::net::UnixStream;
+#[cfg(windows)]
+type IpcStream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+/// Discord's local rpc, frames of an opcode and a length followed by json
+#[derive(Debug)]
+struct Ipc {
+  stream: IpcStream,
+  nonce: u64,
+}
+
+impl Ipc {
+  async fn connect(client_id: &str) -> io::Result<Self> {
+    let mut ipc = Self { stream: open().await?, nonce: 0 };
+
+    ipc.send(HANDSHAKE, &json!({ "v": 1, "client_id": client_id })).await?;
+
+    match ipc.recv().await? {
+      (FRAME, ready) if ready["evt"] == "READY" => Ok(ipc),
+      (_, answer) => Err(rpc_error(&answer)),
+    }
+  }
+
+  async fn set_activity(&mut self, activity: Option<Value>) -> io::Result<()> {
+    self.nonce += 1;
+
+    let nonce = self.nonce.to_string();
+    let command = json!({
+      "cmd": "SET_ACTIVITY",
+      "args": { "pid": std::process::id(), "activity": activity },
+      "nonce": nonce,
+    });
+
+    self.send(FRAME, &command).await?;
+
+    // answers have to be read or they pile up, pings can come in between
+    loop {
+      match self.recv().await? {
+        (FRAME, answer) if answer["nonce"] == nonce.as_str() => {
+          return match answer["evt"] == "ERROR" {
+            true => Err(rpc_error(&answer)),
+            false => Ok(()),
+          };
+        }
+        (PING, ping) => self.send(PONG, &ping).await?,
+        (CLOSE, answer) => return Err(rpc_error(&answer)),
+        _ => continue,
+      }
+    }
+  }
+
+  async fn send(&mut self, opcode: u32, payload: &Value) -> io::Result<()> {
+    let payload = payload.to_string();
+    let mut frame = Vec::with_capacity(8 + payload.len());
+
+    frame.extend_from_slice(&opcode.to_le_bytes());
+    frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    frame.extend_from_slice(payload.as_bytes());
+
+    self.stream.write_all(&frame).await
+  }
+
+  async fn recv(&mut self) -> io::Result<(u32, Value)> {
+    let read = async {
+      let mut header = [0; 8];
+      self.stream.read_exact(&mut header).await?;
+
+      let opcode = u32::from_le_bytes(header[..4].try_into().expect("4 bytes"));
+      let length = u32::from_le_bytes(header[4..].try_into().expect("4 bytes")) as usize;
+
+      if length > MAX_FRAME {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "frame is too big"));
+      }
+
+      let mut payload = vec![0; length];
+      self.stream.read_exact(&mut payload).await?;
+
+      let payload = serde_json::from_slice(&payload).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+      Ok((opcode, payload))
+    };
+
+    tokio::time::timeout(TIMEOUT, read).await.unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into()))
+  }
+}
+
+fn rpc_error(answer: &Value) -> io::Error {
+  let message = answer["message"].as_str().or(answer["data"]["message"].as_str()).unwrap_or("unexpected answer");
+
+  io::Error::other(message.to_string())
+}
+
+/// The first of `discord-ipc-0` to `discord-ipc-9` that's there, in every place discord (or its flatpak or snap) puts it
+#[cfg(unix)]
+async fn open() -> io::Result<IpcStream> {
+  let dirs = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+    .iter()
+    .filter_map(std::env::var_os)
+    .map(std::path::PathBuf::from)
+    .chain([std::path::PathBuf::from("/tmp")]);
+
+  let mut last = io::Error::new(io::ErrorKind::NotFound, "discord isn't running");
+
+  for dir in dirs {
+    for sub in ["", "app/com.discordapp.Discord", "snap.discord"] {
+      for n in 0..10 {
+        match IpcStream::connect(dir.join(sub).join(format!("discord-ipc-{}", n))).await {
+          Ok(stream) => return Ok(stream),
+          Err(err) if err.kind() != io::ErrorKind::NotFound => last = err,
+          Err(_) => {}
+        }
+      }
+    }
+  }
+
+  Err(last)
+}
+
+#[cfg(windows)]
+async fn open() -> io::Result<IpcStream> {
+  use tokio::net::windows::named_pipe::ClientOptions;
+
+  let mut last = io::Error::new(io::ErrorKind::NotFound, "discord isn't running");
+
+  for n in 0..10 {
+    match ClientOptions::new().open(format!(r"\\.\pipe\discord-ipc-{}", n)) {
+      Ok(stream) => return Ok(stream),
+      Err(err) => last = err,
+    }
+  }
+
+  Err(last)
+}
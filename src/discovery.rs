@@ -0,0 +1,91 @@
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{PROTOCOL_VERSION, SCHEMA_VERSION};
+
+/// What gets written to the discovery file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Discovery {
+  /// Address the listener is bound to
+  pub addr: SocketAddr,
+  /// Same as the port in `addr`, so readers don't have to parse it
+  pub port: u16,
+  /// Process that owns the listener
+  pub pid: u32,
+  /// [PROTOCOL_VERSION] of the listener
+  pub protocol_version: u32,
+  /// [SCHEMA_VERSION] of the listener, 0 for files from before it existed
+  #[serde(default)]
+  pub schema_version: u32,
+}
+
+/// A file that says which port the listener ended up on,
+/// for when it binds to port 0 and lets the OS pick one
+///
+/// The extension runs inside spotify and can't read files,
+/// so this is for anything else that wants to find the listener, like a script that sets the extension's `port`
+///
+/// The file gets removed when this is dropped
+#[derive(Debug)]
+pub struct DiscoveryFile {
+  path: PathBuf,
+}
+
+impl DiscoveryFile {
+  /// `spotify_info/listener.json` in the user's cache directory,
+  /// [None] if the platform doesn't have one
+  pub fn default_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("spotify_info").join("listener.json"))
+  }
+
+  /// Writes the address to `path`, replacing what was there and creating missing directories
+  pub fn write(path: impl Into<PathBuf>, addr: SocketAddr) -> io::Result<Self> {
+    let path = path.into();
+    let discovery = Discovery {
+      addr,
+      port: addr.port(),
+      pid: std::process::id(),
+      protocol_version: PROTOCOL_VERSION,
+      schema_version: SCHEMA_VERSION,
+    };
+
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+
+    // write somewhere else first so readers never see half a file
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec(&discovery).expect("discovery always serializes"))?;
+    fs::rename(&tmp, &path)?;
+
+    Ok(Self { path })
+  }
+
+  /// Reads a file written by [Self::write], possibly from another process
+  pub fn read(path: impl AsRef<Path>) -> io::Result<Discovery> {
+    let bytes = fs::read(path)?;
+
+    serde_json::from_slice(&bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Removes the file now instead of when this gets dropped, so errors aren't ignored
+  pub fn remove(self) -> io::Result<()> {
+    let path = std::mem::take(&mut std::mem::ManuallyDrop::new(self).path);
+
+    fs::remove_file(path)
+  }
+}
+
+impl Drop for DiscoveryFile {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
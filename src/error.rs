@@ -0,0 +1,144 @@
+/// Result type used across the whole API
+pub type SpotifyResult<T> = Result<T, SpotifyError>;
+
+/// Errors that can happen while listening for or talking to the spotify extension
+#[derive(Debug, thiserror::Error)]
+pub enum SpotifyError {
+  /// Couldn't bind the listener to the address
+  #[error("failed to bind listener: {0}")]
+  Bind(#[source] std::io::Error),
+  /// Couldn't write the [DiscoveryFile](crate::DiscoveryFile)
+  #[error("failed to write discovery file: {0}")]
+  Discovery(#[source] std::io::Error),
+  /// Couldn't accept an incoming connection, usually means the listener itself is broken
+  #[error("failed to accept connection: {0}")]
+  Accept(#[source] std::io::Error),
+  /// Loading the certificate failed or something connected without speaking TLS,
+  /// only happens with the `tls` or `native-tls` feature
+  #[error("tls error: {0}")]
+  Tls(#[source] std::io::Error),
+  /// Something connected but the websocket handshake failed
+  #[error("websocket handshake failed: {0}")]
+  Handshake(#[source] Box<tungstenite::Error>),
+  /// The extension speaks a protocol version that isn't supported,
+  /// [None] when it didn't say which, meaning it's from before versions were exchanged
+  #[error("extension uses {}, supported versions are {}..={}", version_name(.0), crate::MIN_PROTOCOL_VERSION, crate::PROTOCOL_VERSION)]
+  IncompatibleVersion(Option<u32>),
+  /// Something connected from an address that isn't allowed
+  #[error("connection from {0} isn't allowed")]
+  PeerNotAllowed(std::net::SocketAddr),
+  /// Something tried to connect from an origin that isn't allowed, like a random webpage,
+  /// [None] if it didn't send an origin at all
+  #[error("connection from {} isn't allowed", origin_name(.0))]
+  OriginNotAllowed(Option<String>),
+  /// There's already as many connections open as the listener allows
+  #[error("too many connections")]
+  TooManyConnections,
+  /// The extension didn't send the token the listener was configured with
+  #[error("extension sent an invalid token")]
+  Unauthorized,
+  /// The extension sent something that doesn't follow the protocol, like a text frame with a binary codec
+  #[error("protocol error: {0}")]
+  Protocol(String),
+  /// A message isn't valid for the connection's [Codec](crate::Codec) or doesn't match any known event
+  #[error("failed to deserialize message: {0}")]
+  Deserialize(#[source] serde_json::Error),
+  /// Tried to send something with [SpotifyEvents::send](crate::SpotifyEvents::send) while spotify isn't connected
+  #[error("spotify isn't connected")]
+  NotConnected,
+  /// The connection is closed
+  #[error("connection closed")]
+  Closed,
+  /// The extension didn't answer in time, either a ping (see [Keepalive](crate::Keepalive))
+  /// or the handshake when connecting
+  #[error("connection timed out")]
+  Timeout,
+  /// The extension didn't send any events for longer than
+  /// [SpotifyListenerBuilder::idle_timeout](crate::SpotifyListenerBuilder::idle_timeout), so it's probably hung
+  #[error("connection was idle for too long")]
+  Idle,
+  /// Not a spotify URI or open.spotify.com link, from [SpotifyUri::parse](crate::SpotifyUri::parse)
+  #[error("`{0}` isn't a spotify uri or link")]
+  InvalidUri(String),
+  /// A [Template](crate::Template) that can't be parsed, like an unclosed `{`
+  #[error("invalid template: {0}")]
+  Template(String),
+  /// [TrackInfoBuilder::build](crate::TrackInfoBuilder::build) was given something the extension wouldn't send
+  #[error("invalid track: {0}")]
+  InvalidTrack(String),
+  /// Downloading an image failed, or the server answered with an error,
+  /// only happens with the `http-client` feature
+  #[cfg(feature = "http-client")]
+  #[error("failed to fetch image: {0}")]
+  Fetch(#[source] reqwest::Error),
+  /// The image is bigger than [ImageClient::max_size](crate::ImageClient::max_size)
+  #[cfg(feature = "http-client")]
+  #[error("image is bigger than {0} bytes")]
+  ImageTooLarge(usize),
+  /// Reading or writing the [CoverCache](crate::CoverCache) directory failed
+  #[cfg(feature = "http-client")]
+  #[error("cover cache error: {0}")]
+  Cache(#[source] std::io::Error),
+  /// Reading or writing the [HistoryStore](crate::HistoryStore) database failed,
+  /// only happens with the `history` feature
+  #[cfg(feature = "history")]
+  #[error("history database error: {0}")]
+  History(#[source] rusqlite::Error),
+  /// Writing [HistoryStore::export](crate::HistoryStore::export) failed
+  #[cfg(feature = "history")]
+  #[error("failed to export history: {0}")]
+  Export(#[source] std::io::Error),
+  /// A [ScrobbleService](crate::ScrobbleService) failed or didn't take something,
+  /// `retry` is set when it could work later (like without a network), only happens with the `lastfm` or `listenbrainz` feature
+  #[cfg(any(feature = "lastfm", feature = "listenbrainz"))]
+  #[error("{service} error: {message}")]
+  Scrobble { service: &'static str, message: String, retry: bool },
+  /// Connecting to the session bus or taking the name failed for [MprisServer](crate::MprisServer),
+  /// only happens with the `mpris` feature
+  #[cfg(all(feature = "mpris", target_os = "linux"))]
+  #[error("mpris error: {0}")]
+  Mpris(#[source] zbus::Error),
+  /// Windows didn't give out the media controls for [SmtcServer](crate::SmtcServer),
+  /// only happens with the `smtc` feature
+  #[cfg(all(feature = "smtc", windows))]
+  #[error("media controls error: {0}")]
+  Smtc(#[source] windows::core::Error),
+  /// Any other websocket error while reading or sending messages
+  #[error("websocket error: {0}")]
+  WebSocket(#[source] Box<tungstenite::Error>),
+}
+
+impl SpotifyError {
+  /// Errors that only affect a single message, the connection can still be used after these
+  pub fn is_message_error(&self) -> bool {
+    matches!(self, Self::Protocol(_) | Self::Deserialize(_))
+  }
+
+  /// Errors that only affect a single connection, the listener can keep accepting connections after these
+  pub fn is_connection_error(&self) -> bool {
+    !matches!(self, Self::Bind(_) | Self::Accept(_))
+  }
+}
+
+fn version_name(version: &Option<u32>) -> String {
+  match version {
+    Some(version) => format!("protocol version {}", version),
+    None => "an old protocol version".to_string(),
+  }
+}
+
+fn origin_name(origin: &Option<String>) -> String {
+  match origin {
+    Some(origin) => format!("origin {}", origin),
+    None => "no origin".to_string(),
+  }
+}
+
+impl From<tungstenite::Error> for SpotifyError {
+  fn from(err: tungstenite::Error) -> Self {
+    match err {
+      tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => Self::Closed,
+      err => Self::WebSocket(Box::new(err)),
+    }
+  }
+}
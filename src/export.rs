@@ -0,0 +1,177 @@
+//! Writing the history out for other tools, only with the `history` feature
+
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::stats::date;
+use crate::{HistoryStore, Play, SpotifyError, SpotifyResult};
+
+/// Format for [HistoryStore::export]
+///
+/// Default: Csv
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ExportFormat {
+  /// One row per play with a header, times are UTC like `2024-03-14T18:30:05Z`
+  /// and artists are joined with `; ` so it opens in any spreadsheet
+  #[default]
+  Csv,
+  /// An array of [Play], the same as serializing them, times are unix milliseconds
+  Json,
+}
+
+const CSV_HEADER: &str = "id,started_at,ended_at,uri,title,artists,album,album_uri,played_ms,duration_ms,finished,scrobbled";
+
+impl HistoryStore {
+  /// Writes every play to `writer` oldest first, fails with [SpotifyError::Export] if writing does
+  ///
+  /// ```text
+  /// history.export(ExportFormat::Csv, File::create("history.csv")?)?;
+  /// ```
+  pub fn export(&self, format: ExportFormat, writer: impl Write) -> SpotifyResult<()> {
+    let plays = self.plays(..)?;
+    let mut writer = io::BufWriter::new(writer);
+
+    let written = match format {
+      ExportFormat::Csv => write_csv(&plays, &mut writer),
+      ExportFormat::Json => serde_json::to_writer_pretty(&mut writer, &plays).map_err(io::Error::from),
+    };
+
+    written.and_then(|_| writer.flush()).map_err(SpotifyError::Export)
+  }
+}
+
+fn write_csv(plays: &[Play], writer: &mut impl Write) -> io::Result<()> {
+  writeln!(writer, "{}", CSV_HEADER)?;
+
+  for play in plays {
+    let artists = play.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>().join("; ");
+
+    writeln!(
+      writer,
+      "{},{},{},{},{},{},{},{},{},{},{},{}",
+      play.id,
+      datetime(play.started_at),
+      datetime(play.ended_at),
+      field(play.uri.as_str()),
+      field(&play.title),
+      field(&artists),
+      field(&play.album),
+      field(play.album_uri.as_str()),
+      play.played.as_millis(),
+      play.duration.as_millis(),
+      play.finished,
+      play.scrobbled,
+    )?;
+  }
+
+  Ok(())
+}
+
+/// Quoted if it has to be, with quotes in it doubled
+fn field(value: &str) -> String {
+  match value.contains([',', '"', '\n', '\r']) {
+    true => format!("\"{}\"", value.replace('"', "\"\"")),
+    false => value.to_string(),
+  }
+}
+
+fn datetime(time: SystemTime) -> String {
+  let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_secs() as i64);
+  let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+  format!("{}T{:02}:{:02}:{:02}Z", date(days), seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::Duration;
+
+  use super::*;
+  use crate::{Artist, SpotifyUri};
+
+  fn play(title: &str, artists: &[&str], album: &str) -> Play {
+    let started_at = UNIX_EPOCH + Duration::from_secs(1710441005);
+
+    Play {
+      id: 0,
+      uri: SpotifyUri::from("spotify:track:a"),
+      title: title.into(),
+      artists: artists.iter().map(|name| Artist { name: name.to_string(), uri: SpotifyUri::default() }).collect(),
+      album: album.into(),
+      album_uri: SpotifyUri::default(),
+      started_at,
+      ended_at: started_at + Duration::from_secs(90),
+      played: Duration::from_secs(90),
+      duration: Duration::from_secs(200),
+      finished: false,
+      scrobbled: true,
+    }
+  }
+
+  fn csv(plays: &[Play]) -> Vec<String> {
+    let history = HistoryStore::open_in_memory().unwrap();
+
+    for play in plays {
+      history.insert(play).unwrap();
+    }
+
+    let mut out = Vec::new();
+    history.export(ExportFormat::Csv, &mut out).unwrap();
+
+    String::from_utf8(out).unwrap().lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn writes_a_header_and_rows() {
+    let lines = csv(&[play("a", &["one", "two"], "x")]);
+
+    assert_eq!(lines[0], CSV_HEADER);
+    assert_eq!(lines[1], "1,2024-03-14T18:30:05Z,2024-03-14T18:31:35Z,spotify:track:a,a,one; two,x,,90000,200000,false,true");
+    assert_eq!(lines.len(), 2);
+  }
+
+  #[test]
+  fn quotes_commas_and_quotes() {
+    let lines = csv(&[play("a, b", &["say \"hi\""], "plain")]);
+
+    assert!(lines[1].contains(",\"a, b\",\"say \"\"hi\"\"\",plain,"), "{}", lines[1]);
+  }
+
+  #[test]
+  fn quotes_newlines() {
+    let history = HistoryStore::open_in_memory().unwrap();
+    history.insert(&play("line\nbreak", &["one"], "cr\r")).unwrap();
+
+    let mut out = Vec::new();
+    history.export(ExportFormat::Csv, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    assert!(out.contains(",\"line\nbreak\",one,\"cr\r\","), "{:?}", out);
+  }
+
+  #[test]
+  fn leaves_plain_fields_alone() {
+    assert_eq!(field("plain text"), "plain text");
+    assert_eq!(field(""), "");
+    assert_eq!(field("\""), "\"\"\"\"");
+  }
+
+  #[test]
+  fn writes_json() {
+    let history = HistoryStore::open_in_memory().unwrap();
+    history.insert(&play("a, b", &["one"], "x")).unwrap();
+
+    let mut out = Vec::new();
+    history.export(ExportFormat::Json, &mut out).unwrap();
+    let plays: Vec<Play> = serde_json::from_slice(&out).unwrap();
+
+    assert_eq!(plays.len(), 1);
+    assert_eq!(plays[0].title, "a, b");
+  }
+
+  #[test]
+  fn dates_before_1970_are_the_epoch() {
+    assert_eq!(datetime(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(datetime(UNIX_EPOCH - Duration::from_secs(1)), "1970-01-01T00:00:00Z");
+  }
+}
@@ -0,0 +1,112 @@
+//! Downloads covers, only with the `http-client` feature
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::{SpotifyError, SpotifyResult, TrackInfo};
+
+/// An image downloaded with [ImageClient::fetch]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Image {
+  pub bytes: Vec<u8>,
+  /// Whatever the server said it is, usually `image/jpeg`
+  pub content_type: Option<String>,
+}
+
+/// Downloads covers and backgrounds, cheap to clone since the connections are shared
+///
+/// Default: 10 second timeout and at most 10 MiB per image
+#[derive(Debug, Clone)]
+pub struct ImageClient {
+  client: reqwest::Client,
+  timeout: Duration,
+  max_size: usize,
+}
+
+impl Default for ImageClient {
+  fn default() -> Self {
+    Self {
+      client: reqwest::Client::new(),
+      timeout: Duration::from_secs(10),
+      max_size: 10 * 1024 * 1024,
+    }
+  }
+}
+
+impl ImageClient {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Uses an existing client instead of making a new one, to share its connections and settings
+  pub fn with_client(client: reqwest::Client) -> Self {
+    Self { client, ..Self::default() }
+  }
+
+  /// How long a whole download can take before it fails with [SpotifyError::Fetch]
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Images bigger than this fail with [SpotifyError::ImageTooLarge] without downloading the rest
+  pub fn max_size(mut self, max: usize) -> Self {
+    self.max_size = max;
+    self
+  }
+
+  /// Downloads the image at `url`
+  pub async fn fetch(&self, url: &str) -> SpotifyResult<Image> {
+    let mut response = self.client.get(url)
+      .timeout(self.timeout)
+      .send()
+      .await
+      .and_then(reqwest::Response::error_for_status)
+      .map_err(SpotifyError::Fetch)?;
+
+    if response.content_length().is_some_and(|length| length > self.max_size as u64) {
+      return Err(SpotifyError::ImageTooLarge(self.max_size));
+    }
+
+    let content_type = response.headers()
+      .get(reqwest::header::CONTENT_TYPE)
+      .and_then(|value| value.to_str().ok())
+      .map(str::to_string);
+
+    let mut bytes = Vec::new();
+
+    // the length can be missing or wrong, so it gets checked while downloading too
+    while let Some(chunk) = response.chunk().await.map_err(SpotifyError::Fetch)? {
+      if bytes.len() + chunk.len() > self.max_size {
+        return Err(SpotifyError::ImageTooLarge(self.max_size));
+      }
+
+      bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(Image { bytes, content_type })
+  }
+
+  /// Downloads [TrackInfo::cover], [None] if the track doesn't have one
+  pub async fn fetch_cover(&self, info: &TrackInfo) -> SpotifyResult<Option<Image>> {
+    match info.cover() {
+      Some(url) => self.fetch(url).await.map(Some),
+      None => Ok(None),
+    }
+  }
+
+  /// Downloads [TrackInfo::background_url], [None] if the track doesn't have one
+  pub async fn fetch_background(&self, info: &TrackInfo) -> SpotifyResult<Option<Image>> {
+    match &info.background_url {
+      Some(url) => self.fetch(url).await.map(Some),
+      None => Ok(None),
+    }
+  }
+}
+
+/// Used by [TrackInfo::fetch_cover] so every call shares the same connections
+pub(crate) fn shared() -> &'static ImageClient {
+  static CLIENT: OnceLock<ImageClient> = OnceLock::new();
+
+  CLIENT.get_or_init(ImageClient::default)
+}
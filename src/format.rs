@@ -0,0 +1,168 @@
+use std::fmt::{Display, Formatter};
+
+use crate::TrackInfo;
+
+/// How [TrackInfo::format] puts the track together, for status bars and logs
+///
+/// Default: `Artist – Title [Album]` with artists joined by `", "` and nothing cut off,
+/// which is also what [Display] for [TrackInfo] uses
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct FormatSpec {
+  /// Between the artists and the title
+  pub separator: String,
+  /// Between every artist when there's more than one
+  pub artist_separator: String,
+  /// Adds the album (or the show for podcasts) in brackets after the title, if there is one
+  pub album: bool,
+  /// Cuts off the artists, title and album on their own after this many characters
+  pub max_part_length: Option<usize>,
+  /// Cuts off the whole thing after this many characters, including the [Self::ellipsis]
+  /// (which gets cut off too if it's longer than this)
+  pub max_length: Option<usize>,
+  /// Added to the end of anything that got cut off
+  pub ellipsis: String,
+}
+
+impl Default for FormatSpec {
+  fn default() -> Self {
+    Self {
+      separator: " – ".to_string(),
+      artist_separator: ", ".to_string(),
+      album: true,
+      max_part_length: None,
+      max_length: None,
+      ellipsis: "…".to_string(),
+    }
+  }
+}
+
+impl FormatSpec {
+  /// Cuts `text` off after `max` characters, the [Self::ellipsis] counts towards it
+  fn truncate(&self, text: &str, max: Option<usize>) -> String {
+    let max = match max {
+      Some(max) if text.chars().count() > max => max,
+      _ => return text.to_string(),
+    };
+
+    let ellipsis = self.ellipsis.chars().count();
+
+    // no room for any of the text, as much of the ellipsis as fits
+    if max <= ellipsis {
+      return self.ellipsis.chars().take(max).collect();
+    }
+
+    let mut text = text.chars().take(max - ellipsis).collect::<String>();
+
+    text.truncate(text.trim_end().len());
+    text.push_str(&self.ellipsis);
+    text
+  }
+}
+
+impl TrackInfo {
+  /// The track as one line, like `Artist – Title [Album]`, see [FormatSpec]
+  pub fn format(&self, spec: &FormatSpec) -> String {
+    let artists = self.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>().join(&spec.artist_separator);
+    let mut line = String::new();
+
+    if !artists.is_empty() {
+      line.push_str(&spec.truncate(&artists, spec.max_part_length));
+      line.push_str(&spec.separator);
+    }
+
+    line.push_str(&spec.truncate(&self.title, spec.max_part_length));
+
+    if spec.album && !self.album.name.is_empty() {
+      line.push_str(" [");
+      line.push_str(&spec.truncate(&self.album.name, spec.max_part_length));
+      line.push(']');
+    }
+
+    spec.truncate(&line, spec.max_length)
+  }
+}
+
+/// `Artist – Title [Album]`, same as [TrackInfo::format] with the default [FormatSpec]
+impl Display for TrackInfo {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.format(&FormatSpec::default()))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn track(artists: &[&str], title: &str, album: &str) -> TrackInfo {
+    let track = artists.iter().fold(TrackInfo::builder().title(title).album(album), |track, artist| track.artist(*artist));
+
+    track.build().unwrap()
+  }
+
+  fn spec(max: usize, ellipsis: &str) -> FormatSpec {
+    FormatSpec { ellipsis: ellipsis.to_string(), max_length: Some(max), ..FormatSpec::default() }
+  }
+
+  #[test]
+  fn displays_artist_title_album() {
+    assert_eq!(track(&["Artist"], "Title", "Album").to_string(), "Artist – Title [Album]");
+    assert_eq!(track(&["One", "Two"], "Title", "Album").to_string(), "One, Two – Title [Album]");
+  }
+
+  #[test]
+  fn leaves_out_what_isnt_there() {
+    assert_eq!(track(&[], "Title", "Album").to_string(), "Title [Album]");
+    assert_eq!(track(&["Artist"], "Title", "").to_string(), "Artist – Title");
+
+    let spec = FormatSpec { album: false, ..FormatSpec::default() };
+    assert_eq!(track(&["Artist"], "Title", "Album").format(&spec), "Artist – Title");
+  }
+
+  #[test]
+  fn uses_the_separators() {
+    let spec = FormatSpec { separator: " - ".to_string(), artist_separator: " & ".to_string(), ..FormatSpec::default() };
+
+    assert_eq!(track(&["One", "Two"], "Title", "Album").format(&spec), "One & Two - Title [Album]");
+  }
+
+  #[test]
+  fn cuts_off_parts() {
+    let spec = FormatSpec { max_part_length: Some(4), ..FormatSpec::default() };
+
+    assert_eq!(track(&["Artist"], "Title", "Alb").format(&spec), "Art… – Tit… [Alb]");
+  }
+
+  #[test]
+  fn cuts_off_the_whole_line() {
+    let track = track(&["Artist"], "Title", "Album");
+
+    assert_eq!(track.format(&spec(10, "…")), "Artist –…");
+    assert_eq!(track.format(&spec(10, "...")), "Artist...");
+    // exactly as long as the limit
+    assert_eq!(track.format(&spec(22, "…")), "Artist – Title [Album]");
+    assert_eq!(track.format(&spec(21, "…")).chars().count(), 21);
+  }
+
+  #[test]
+  fn counts_characters_not_bytes() {
+    let track = track(&["Ünïcödé"], "タイトル", "");
+
+    assert_eq!(track.format(&spec(9, "…")), "Ünïcödé…");
+    assert_eq!(track.format(&spec(13, "…")), "Ünïcödé – タイ…");
+  }
+
+  #[test]
+  fn never_goes_over_the_limit() {
+    let track = track(&["Artist"], "Title", "Album");
+
+    assert_eq!(track.format(&spec(2, "...")), "..");
+    assert_eq!(track.format(&spec(3, "...")), "...");
+    assert_eq!(track.format(&spec(4, "...")), "A...");
+    assert_eq!(track.format(&spec(0, "...")), "");
+    assert_eq!(track.format(&spec(1, "")), "A");
+
+    for max in 0..30 {
+      assert!(track.format(&spec(max, "...")).chars().count() <= max, "{}", max);
+    }
+  }
+}
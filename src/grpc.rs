@@ -0,0 +1,176 @@
+//! Serving events and commands over grpc, only with the `grpc` feature
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::{Duration, UNIX_EPOCH};
+
+use futures_util::Stream;
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tonic::{Request, Response, Status};
+
+use crate::{EventMask, RepeatMode, SpotifyError, SpotifyEvent, SpotifyEvents, SpotifyMessage, SpotifyResult, TrackInfo, TrackState};
+
+use proto::command::Command;
+use proto::spotify_server::{Spotify, SpotifyServer};
+
+#[allow(clippy::all)]
+mod proto {
+  tonic::include_proto!("spotify_info");
+}
+
+/// Grpc server for things that aren't written in rust, see `proto/spotify_info.proto` for the service
+///
+/// `WatchEvents` streams every event, the most common ones are typed and every event also comes
+/// as the same json the extension sends, `SendCommand` sends a command to spotify
+#[derive(Debug)]
+pub struct GrpcServer {
+  listener: TcpListener,
+}
+
+impl GrpcServer {
+  /// Binds to the given address, this has to be a different port than the [SpotifyListener](crate::SpotifyListener)
+  pub async fn bind(addr: SocketAddr) -> SpotifyResult<Self> {
+    let listener = TcpListener::bind(addr).await.map_err(SpotifyError::Bind)?;
+
+    Ok(Self { listener })
+  }
+
+  pub fn local_addr(&self) -> SpotifyResult<SocketAddr> {
+    self.listener.local_addr().map_err(SpotifyError::Bind)
+  }
+
+  /// Keeps serving requests, commands go to the connection behind `events`
+  ///
+  /// Only returns when the listener stops accepting connections
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn serve(self, events: SpotifyEvents) -> SpotifyResult<()> {
+    let incoming = futures_util::stream::unfold(self.listener, |listener| async move {
+      let stream = listener.accept().await.map(|(stream, _)| stream);
+
+      Some((stream, listener))
+    });
+
+    tonic::transport::Server::builder()
+      .add_service(SpotifyServer::new(Service { events }))
+      .serve_with_incoming(incoming)
+      .await
+      .map_err(|err| SpotifyError::Accept(io::Error::other(err)))
+  }
+}
+
+struct Service {
+  events: SpotifyEvents,
+}
+
+#[tonic::async_trait]
+impl Spotify for Service {
+  type WatchEventsStream = Pin<Box<dyn Stream<Item = Result<proto::Event, Status>> + Send>>;
+
+  async fn watch_events(&self, request: Request<proto::WatchEventsRequest>) -> Result<Response<Self::WatchEventsStream>, Status> {
+    let mask = match request.into_inner().mask {
+      0 => EventMask::ALL,
+      bits => EventMask::from_bits(bits),
+    };
+
+    let stream = futures_util::stream::unfold(self.events.subscribe(), move |mut events| async move {
+      loop {
+        match events.recv().await {
+          Ok(event) if mask.matches(&event) => return Some((Ok(to_proto(&event)), events)),
+          Ok(_) | Err(RecvError::Lagged(_)) => continue,
+          Err(RecvError::Closed) => return None,
+        }
+      }
+    });
+
+    Ok(Response::new(Box::pin(stream)))
+  }
+
+  async fn send_command(&self, request: Request<proto::Command>) -> Result<Response<proto::CommandReply>, Status> {
+    let message = match request.into_inner().command {
+      Some(command) => from_proto(command),
+      None => return Err(Status::invalid_argument("missing command")),
+    };
+
+    match self.events.send(message).await {
+      Ok(()) => Ok(Response::new(proto::CommandReply {})),
+      Err(SpotifyError::NotConnected) => Err(Status::unavailable("spotify isn't connected")),
+      Err(err) => Err(Status::internal(err.to_string())),
+    }
+  }
+}
+
+fn from_proto(command: Command) -> SpotifyMessage {
+  match command {
+    Command::Play(_) => SpotifyMessage::Play,
+    Command::Pause(_) => SpotifyMessage::Pause,
+    Command::TogglePlayback(_) => SpotifyMessage::TogglePlayback,
+    Command::Next(_) => SpotifyMessage::Next,
+    Command::Previous(_) => SpotifyMessage::Previous,
+    Command::SeekMs(position_ms) => SpotifyMessage::Seek { position_ms },
+    Command::SeekPercent(percent) => SpotifyMessage::SeekPercent { percent: percent.clamp(0.0, 1.0) },
+    Command::SetVolume(volume) => SpotifyMessage::SetVolume(volume.clamp(0.0, 1.0)),
+    Command::SetShuffle(shuffle) => SpotifyMessage::SetShuffle(shuffle),
+    Command::SetRepeat(mode) => SpotifyMessage::SetRepeat(RepeatMode::from_u32(mode as u32)),
+    Command::SetLiked(liked) => SpotifyMessage::SetLiked(liked),
+    Command::RequestState(_) => SpotifyMessage::RequestState,
+    Command::SetProgressIntervalMs(ms) => SpotifyMessage::SetProgressUpdateInterval(Duration::from_millis(ms)),
+  }
+}
+
+fn to_proto(event: &SpotifyEvent) -> proto::Event {
+  use proto::event::Event;
+
+  let typed = match event {
+    SpotifyEvent::TrackChanged(info) => Some(Event::TrackChanged(track(info))),
+    SpotifyEvent::StateChanged(state) => Some(Event::StateChanged(track_state(*state) as i32)),
+    SpotifyEvent::ProgressChanged { position, percent, timestamp } => Some(Event::ProgressChanged(proto::Progress {
+      position_ms: position.as_millis() as u64,
+      percent: *percent,
+      timestamp_ms: timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+    })),
+    SpotifyEvent::VolumeChanged(volume) => Some(Event::VolumeChanged(*volume)),
+    SpotifyEvent::ShuffleChanged(shuffle) => Some(Event::ShuffleChanged(*shuffle)),
+    SpotifyEvent::RepeatChanged(mode) => Some(Event::RepeatChanged(repeat_mode(*mode) as i32)),
+    SpotifyEvent::LikedChanged(liked) => Some(Event::LikedChanged(*liked)),
+    // everything else is only in the json
+    _ => None,
+  };
+
+  proto::Event {
+    kind: event.kind().to_string(),
+    json: serde_json::to_string(event).expect("events always serialize"),
+    event: typed,
+  }
+}
+
+fn track(info: &TrackInfo) -> proto::Track {
+  proto::Track {
+    uri: info.uri.to_string(),
+    title: info.title.clone(),
+    artists: info.artists.iter().map(|artist| artist.name.clone()).collect(),
+    album: info.album.name.clone(),
+    duration_ms: info.duration.as_millis() as u64,
+    cover_url: info.cover().map(str::to_string),
+    state: track_state(info.state) as i32,
+    liked: info.liked,
+  }
+}
+
+fn track_state(state: TrackState) -> proto::TrackState {
+  match state {
+    TrackState::Playing => proto::TrackState::Playing,
+    TrackState::Paused => proto::TrackState::Paused,
+    TrackState::Stopped => proto::TrackState::Stopped,
+  }
+}
+
+fn repeat_mode(mode: RepeatMode) -> proto::RepeatMode {
+  match mode {
+    RepeatMode::Off => proto::RepeatMode::Off,
+    RepeatMode::Context => proto::RepeatMode::Context,
+    RepeatMode::Track => proto::RepeatMode::Track,
+  }
+}
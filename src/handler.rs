@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use crate::{ConnectionInfo, DisconnectReason, SpotifyEvent, TrackInfo, TrackState};
+
+/// Callbacks for [SpotifyListener::run](crate::SpotifyListener::run), for when a match over every event
+/// is too much, only implement the ones you need, the rest do nothing
+///
+/// ```text
+/// struct Printer;
+///
+/// impl SpotifyEventHandler for Printer {
+///   fn on_track_changed(&mut self, info: &TrackInfo) {
+///     println!("Now playing {}", info.title);
+///   }
+/// }
+/// ```
+pub trait SpotifyEventHandler {
+  /// Spotify connected, nothing has been sent yet
+  fn on_connect(&mut self, _info: &ConnectionInfo) {}
+
+  /// Spotify disconnected, the listener is waiting for it to connect again
+  fn on_disconnect(&mut self, _reason: &DisconnectReason) {}
+
+  /// The track changed, also gets called with the track from a [SpotifyEvent::StateSnapshot]
+  fn on_track_changed(&mut self, _info: &TrackInfo) {}
+
+  /// It started playing, paused or stopped, also gets called with the state from a [SpotifyEvent::StateSnapshot]
+  fn on_state_changed(&mut self, _state: TrackState) {}
+
+  /// Same as [SpotifyEvent::ProgressChanged], `percent` is between 0 and 1
+  fn on_progress(&mut self, _position: Duration, _percent: f64) {}
+
+  /// Every event, even ones that have their own callback, gets called before it
+  fn on_event(&mut self, _event: &SpotifyEvent) {}
+}
+
+/// Calls every callback that's for `event`
+pub(crate) fn dispatch(handler: &mut impl SpotifyEventHandler, event: &SpotifyEvent) {
+  handler.on_event(event);
+
+  match event {
+    SpotifyEvent::TrackChanged(info) => handler.on_track_changed(info),
+    SpotifyEvent::StateChanged(state) => handler.on_state_changed(*state),
+    SpotifyEvent::ProgressChanged { position, percent, .. } => handler.on_progress(*position, *percent),
+    SpotifyEvent::StateSnapshot(snapshot) => {
+      if let Some(info) = &snapshot.track {
+        handler.on_track_changed(info);
+      }
+
+      handler.on_state_changed(snapshot.state);
+    }
+    _ => {}
+  }
+}
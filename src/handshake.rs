@@ -0,0 +1,357 @@
+//! Hello messages exchanged right after the websocket connects,
+//! so both ends know they speak the same protocol before any events get parsed
+//!
+//! Hellos are always json text frames, the extension sends
+//! `{"type":"Hello","data":{"protocol_version":3,"token":"...","codecs":["msgpack","cbor","json"],"extension_version":"0.5.0","client":"Spotify 1.2.31"}}`
+//! where everything but `protocol_version` can be left out, `codecs` is in the order the extension prefers them
+//!
+//! The listener replies with `{"type":"Hello","data":{"min_version":3,"max_version":3,"codec":"msgpack"}}`,
+//! everything after that is sent with `codec` by both ends
+//!
+//! tungstenite can't do permessage-deflate, so compression is agreed on here instead,
+//! the extension adds `"compression":["gzip"]` and the reply has `"compression":"gzip"`
+//! when the listener has the `gzip` feature, after that the extension can gzip big payloads
+//! and send them as binary frames
+
+use serde::{Deserialize, Serialize};
+use ipnet::IpNet;
+use tungstenite::handshake::server::{ErrorResponse, Request};
+use tungstenite::http::{header, StatusCode};
+use tungstenite::protocol::frame::coding::CloseCode;
+use tungstenite::protocol::CloseFrame;
+use tungstenite::Message;
+
+use std::net::SocketAddr;
+
+use crate::{Codec, ConnectionId, ConnectionInfo, SpotifyError, SpotifyResult, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, SCHEMA_VERSION};
+
+/// What the extension sends first
+#[derive(Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub(crate) enum ExtensionHello {
+  Hello {
+    protocol_version: u32,
+    #[serde(default)]
+    token: Option<String>,
+    /// Names instead of [Codec] so names from newer extensions don't fail the whole hello
+    #[serde(default)]
+    codecs: Vec<String>,
+    #[serde(default)]
+    compression: Vec<String>,
+    #[serde(default)]
+    extension_version: Option<String>,
+    #[serde(default)]
+    client: Option<String>,
+  },
+}
+
+/// What gets sent back, so the extension can tell the user when it's outdated
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data")]
+enum ListenerHello {
+  Hello {
+    min_version: u32,
+    max_version: u32,
+    schema_version: u32,
+    codec: Codec,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compression: Option<&'static str>,
+  },
+}
+
+/// Makes sure the websocket upgrade comes from an allowed origin,
+/// anything is allowed when the list is empty
+///
+/// Errors with the rejected origin, [None] if there wasn't one
+pub(crate) fn check_origin(request: &Request, allowed: &[String]) -> Result<(), Option<String>> {
+  if allowed.is_empty() {
+    return Ok(());
+  }
+
+  let origin = request.headers().get(header::ORIGIN).and_then(|origin| origin.to_str().ok());
+
+  match origin {
+    Some(origin) if allowed.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin)) => Ok(()),
+    origin => Err(origin.map(str::to_string)),
+  }
+}
+
+/// Response for upgrades from origins that aren't allowed
+pub(crate) fn forbidden() -> ErrorResponse {
+  let mut response = ErrorResponse::new(Some("origin not allowed".to_string()));
+  *response.status_mut() = StatusCode::FORBIDDEN;
+  response
+}
+
+/// Anyone is allowed when the list is empty
+pub(crate) fn is_peer_allowed(peer: SocketAddr, allowed: &[IpNet]) -> bool {
+  // so ::ffff:127.0.0.1 still matches 127.0.0.1 on dual-stack sockets
+  let ip = peer.ip().to_canonical();
+
+  allowed.is_empty() || allowed.iter().any(|net| net.contains(&ip))
+}
+
+/// Reads the first message,
+/// [None] if it isn't a hello, which means the extension is from before the handshake existed
+pub(crate) fn parse(message: &Message) -> Option<ExtensionHello> {
+  match message {
+    Message::Text(text) => serde_json::from_str(text).ok(),
+    _ => None,
+  }
+}
+
+/// Makes sure the extension sent the right token, if the listener has one
+pub(crate) fn authenticate(hello: Option<&ExtensionHello>, token: Option<&str>) -> SpotifyResult<()> {
+  let expected = match token {
+    Some(expected) => expected,
+    None => return Ok(()),
+  };
+
+  match hello {
+    Some(ExtensionHello::Hello { token: Some(token), .. }) if tokens_match(token, expected) => Ok(()),
+    _ => Err(SpotifyError::Unauthorized),
+  }
+}
+
+/// Compares every byte, so how long it takes doesn't give away how much of the token matched
+fn tokens_match(a: &str, b: &str) -> bool {
+  a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Makes sure the version is in the supported range
+pub(crate) fn check(hello: Option<&ExtensionHello>) -> SpotifyResult<u32> {
+  match hello {
+    Some(ExtensionHello::Hello { protocol_version, .. }) if (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(protocol_version) => Ok(*protocol_version),
+    Some(ExtensionHello::Hello { protocol_version, .. }) => Err(SpotifyError::IncompatibleVersion(Some(*protocol_version))),
+    None => Err(SpotifyError::IncompatibleVersion(None)),
+  }
+}
+
+/// Picks the codec for everything after the hello out of `allowed`, json if the extension didn't ask for one of them
+pub(crate) fn codec(hello: Option<&ExtensionHello>, allowed: &[Codec]) -> Codec {
+  match hello {
+    Some(ExtensionHello::Hello { codecs, .. }) => Codec::negotiate(codecs, allowed),
+    None => Codec::Json,
+  }
+}
+
+/// Gives the connection its id and keeps what the extension said about itself
+pub(crate) fn info(hello: Option<ExtensionHello>, peer: Option<SocketAddr>, protocol_version: u32) -> ConnectionInfo {
+  let (extension_version, client) = match hello {
+    Some(ExtensionHello::Hello { extension_version, client, .. }) => (extension_version, client),
+    None => (None, None),
+  };
+
+  ConnectionInfo {
+    id: ConnectionId::next(),
+    peer,
+    protocol_version,
+    extension_version,
+    client,
+  }
+}
+
+/// Compression the extension can use for what it sends, only gzip exists right now
+fn compression(hello: Option<&ExtensionHello>) -> Option<&'static str> {
+  match hello {
+    Some(ExtensionHello::Hello { compression, .. }) if cfg!(feature = "gzip") && compression.iter().any(|name| name == "gzip") => Some("gzip"),
+    _ => None,
+  }
+}
+
+/// The reply to the extension's hello, gets sent even when the version isn't supported
+pub(crate) fn reply(hello: Option<&ExtensionHello>, codec: Codec) -> Message {
+  let hello = ListenerHello::Hello {
+    min_version: MIN_PROTOCOL_VERSION,
+    max_version: PROTOCOL_VERSION,
+    schema_version: SCHEMA_VERSION,
+    codec,
+    compression: compression(hello),
+  };
+
+  Message::Text(serde_json::to_string(&hello).expect("hello always serializes"))
+}
+
+/// Close frame for connections over [SpotifyListenerBuilder::max_connections](crate::SpotifyListenerBuilder::max_connections)
+pub(crate) fn too_many_connections() -> CloseFrame<'static> {
+  CloseFrame {
+    code: CloseCode::Again,
+    reason: "too many connections".into(),
+  }
+}
+
+/// Close frame for connections with the wrong token, they don't get a reply
+pub(crate) fn unauthorized() -> CloseFrame<'static> {
+  CloseFrame {
+    code: CloseCode::Policy,
+    reason: "invalid token".into(),
+  }
+}
+
+#[cfg(feature = "async")]
+/// Upgrade request for pretending to be the extension, from the first allowed origin or spotify's if any is allowed
+pub(crate) fn client_request(url: &str, allowed_origins: &[String]) -> SpotifyResult<Request> {
+  use tungstenite::client::IntoClientRequest;
+
+  let mut request = url.into_client_request().map_err(|err| SpotifyError::Handshake(Box::new(err)))?;
+  let origin = allowed_origins.first().map_or(crate::SPOTIFY_ORIGIN, String::as_str);
+
+  if let Ok(origin) = origin.parse() {
+    request.headers_mut().insert(header::ORIGIN, origin);
+  }
+
+  Ok(request)
+}
+
+#[cfg(feature = "async")]
+/// Hello for pretending to be the extension, `client` is what [ConnectionInfo::client] will be
+pub(crate) fn client_hello(token: Option<&str>, client: &str, codec: Codec) -> Message {
+  let hello = serde_json::json!({
+    "type": "Hello",
+    "data": {
+      "protocol_version": PROTOCOL_VERSION,
+      "token": token,
+      "codecs": [codec.name()],
+      "client": client,
+    },
+  });
+
+  Message::Text(hello.to_string())
+}
+
+#[cfg(feature = "async")]
+/// Does everything the extension does before sending events on `stream`,
+/// fails with [SpotifyError::Closed] when the listener closes it instead of replying, like with a wrong token
+pub(crate) async fn connect_client<S>(
+  stream: S,
+  url: &str,
+  allowed_origins: &[String],
+  token: Option<&str>,
+  client: &str,
+  codec: Codec,
+) -> SpotifyResult<tokio_tungstenite::WebSocketStream<S>>
+where
+  S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+  use futures_util::{SinkExt, StreamExt};
+
+  let request = client_request(url, allowed_origins)?;
+
+  let (mut ws, _) = tokio_tungstenite::client_async(request, stream)
+    .await
+    .map_err(|err| SpotifyError::Handshake(Box::new(err)))?;
+
+  ws.send(client_hello(token, client, codec)).await?;
+
+  match ws.next().await {
+    Some(Ok(Message::Text(_))) => Ok(ws),
+    Some(Err(err)) => Err(err.into()),
+    _ => Err(SpotifyError::Closed),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn hello(data: serde_json::Value) -> Option<ExtensionHello> {
+    parse(&Message::Text(serde_json::json!({ "type": "Hello", "data": data }).to_string()))
+  }
+
+  #[test]
+  fn parses_hellos() {
+    assert!(hello(serde_json::json!({ "protocol_version": PROTOCOL_VERSION })).is_some());
+    // everything but the version can be left out, and newer fields get ignored
+    assert!(hello(serde_json::json!({ "protocol_version": 3, "something_new": true })).is_some());
+
+    assert!(hello(serde_json::json!({})).is_none());
+    assert!(parse(&Message::Text("{\"type\":\"TrackChanged\",\"data\":{}}".to_string())).is_none());
+    assert!(parse(&Message::Binary(b"{}".to_vec())).is_none());
+  }
+
+  #[test]
+  fn checks_the_version() {
+    let version = |version: u32| check(hello(serde_json::json!({ "protocol_version": version })).as_ref());
+
+    assert_eq!(version(PROTOCOL_VERSION).unwrap(), PROTOCOL_VERSION);
+    assert_eq!(version(MIN_PROTOCOL_VERSION).unwrap(), MIN_PROTOCOL_VERSION);
+    assert!(matches!(version(MIN_PROTOCOL_VERSION - 1), Err(SpotifyError::IncompatibleVersion(Some(v))) if v == MIN_PROTOCOL_VERSION - 1));
+    assert!(matches!(version(PROTOCOL_VERSION + 1), Err(SpotifyError::IncompatibleVersion(Some(_)))));
+    // extensions from before the handshake
+    assert!(matches!(check(None), Err(SpotifyError::IncompatibleVersion(None))));
+  }
+
+  #[test]
+  fn reply_has_the_range() {
+    let reply = match reply(None, Codec::Json) {
+      Message::Text(text) => serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+      message => panic!("not text: {:?}", message),
+    };
+
+    assert_eq!(reply["type"], "Hello");
+    assert_eq!(reply["data"]["min_version"], MIN_PROTOCOL_VERSION);
+    assert_eq!(reply["data"]["max_version"], PROTOCOL_VERSION);
+    assert_eq!(reply["data"]["codec"], "json");
+  }
+
+  fn with_token(token: Option<&str>) -> Option<ExtensionHello> {
+    hello(serde_json::json!({ "protocol_version": PROTOCOL_VERSION, "token": token }))
+  }
+
+  #[test]
+  fn authenticates_the_token() {
+    assert!(authenticate(with_token(Some("secret")).as_ref(), Some("secret")).is_ok());
+
+    assert!(matches!(authenticate(with_token(Some("wrong!")).as_ref(), Some("secret")), Err(SpotifyError::Unauthorized)));
+    assert!(matches!(authenticate(with_token(Some("secret2")).as_ref(), Some("secret")), Err(SpotifyError::Unauthorized)));
+    assert!(matches!(authenticate(with_token(Some("")).as_ref(), Some("secret")), Err(SpotifyError::Unauthorized)));
+    assert!(matches!(authenticate(with_token(None).as_ref(), Some("secret")), Err(SpotifyError::Unauthorized)));
+    // no hello means no token either
+    assert!(matches!(authenticate(None, Some("secret")), Err(SpotifyError::Unauthorized)));
+  }
+
+  #[test]
+  fn no_token_lets_everyone_in() {
+    assert!(authenticate(with_token(None).as_ref(), None).is_ok());
+    assert!(authenticate(with_token(Some("anything")).as_ref(), None).is_ok());
+    assert!(authenticate(None, None).is_ok());
+  }
+
+  #[test]
+  fn compares_tokens() {
+    assert!(tokens_match("secret", "secret"));
+    assert!(tokens_match("", ""));
+
+    assert!(!tokens_match("secret", "secreT"));
+    assert!(!tokens_match("secret", "secre"));
+    assert!(!tokens_match("secre", "secret"));
+    // same bytes at the start isn't enough
+    assert!(!tokens_match("secret", "secretsecret"));
+    assert!(!tokens_match("", "secret"));
+  }
+
+  fn reply_compression(compression: &[&str]) -> serde_json::Value {
+    let hello = hello(serde_json::json!({ "protocol_version": PROTOCOL_VERSION, "compression": compression }));
+
+    match reply(hello.as_ref(), Codec::Json) {
+      Message::Text(text) => serde_json::from_str::<serde_json::Value>(&text).unwrap()["data"]["compression"].clone(),
+      message => panic!("not text: {:?}", message),
+    }
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn agrees_on_gzip() {
+    assert_eq!(reply_compression(&["gzip"]), "gzip");
+    assert_eq!(reply_compression(&["brotli", "gzip"]), "gzip");
+
+    assert!(reply_compression(&["brotli"]).is_null());
+    assert!(reply_compression(&[]).is_null());
+  }
+
+  #[cfg(not(feature = "gzip"))]
+  #[test]
+  fn no_gzip_without_the_feature() {
+    assert!(reply_compression(&["gzip"]).is_null());
+  }
+}
@@ -0,0 +1,760 @@
+//! Play history in SQLite, only with the `history` feature
+
+use std::ops::{Bound, RangeBounds};
+use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{serde_utils, Artist, SpotifyError, SpotifyEvent, SpotifyResult, SpotifyUri, TrackInfo};
+
+/// Goes up when the tables change, so older databases can be migrated when they're opened
+const VERSION: i32 = 1;
+
+const TABLES: &str = "
+  CREATE TABLE IF NOT EXISTS plays (
+    id INTEGER PRIMARY KEY,
+    uri TEXT NOT NULL,
+    title TEXT NOT NULL,
+    album TEXT NOT NULL,
+    album_uri TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    ended_at INTEGER NOT NULL,
+    played_ms INTEGER NOT NULL,
+    duration_ms INTEGER NOT NULL,
+    finished INTEGER NOT NULL,
+    scrobbled INTEGER NOT NULL
+  );
+
+  CREATE INDEX IF NOT EXISTS plays_started_at ON plays (started_at);
+
+  CREATE TABLE IF NOT EXISTS play_artists (
+    play_id INTEGER NOT NULL REFERENCES plays (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    uri TEXT NOT NULL,
+    PRIMARY KEY (play_id, position)
+  );
+
+  CREATE INDEX IF NOT EXISTS play_artists_name ON play_artists (name);
+";
+
+/// One time a track played, from when it started to when it finished or got skipped
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Play {
+  /// Row in the database, 0 for plays that weren't inserted yet
+  pub id: i64,
+  pub uri: SpotifyUri,
+  pub title: String,
+  /// In the order spotify lists them
+  pub artists: Vec<Artist>,
+  /// Name of the album
+  pub album: String,
+  pub album_uri: SpotifyUri,
+  #[serde(with = "serde_utils::timestamp_millis")]
+  pub started_at: SystemTime,
+  #[serde(with = "serde_utils::timestamp_millis")]
+  pub ended_at: SystemTime,
+  /// How far it got, the whole track if it finished
+  #[serde(with = "serde_utils::millis")]
+  pub played: Duration,
+  /// Length of the track
+  #[serde(with = "serde_utils::millis")]
+  pub duration: Duration,
+  /// If it played to the end instead of getting skipped
+  pub finished: bool,
+  /// If it got to [SpotifyEvent::ScrobblePoint] before it ended
+  pub scrobbled: bool,
+}
+
+impl Play {
+  /// A play of `track` that ended now after `played`,
+  /// started `played` ago since there's no way to know about pauses
+  pub fn new(track: &TrackInfo, played: Duration, finished: bool) -> Self {
+    let ended_at = SystemTime::now();
+
+    Self {
+      id: 0,
+      uri: track.uri.clone(),
+      title: track.title.clone(),
+      artists: track.artists.clone(),
+      album: track.album.name.clone(),
+      album_uri: track.album.uri.clone(),
+      started_at: ended_at.checked_sub(played).unwrap_or(ended_at),
+      ended_at,
+      played,
+      duration: track.duration,
+      finished,
+      scrobbled: false,
+    }
+  }
+}
+
+/// A track from [HistoryStore::top_tracks]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopTrack {
+  pub uri: SpotifyUri,
+  pub title: String,
+  /// From the latest play, in case they changed
+  pub artists: Vec<Artist>,
+  /// How many times it finished or got scrobbled
+  pub plays: u64,
+  /// How long it played in total for those
+  #[serde(with = "serde_utils::millis")]
+  pub played: Duration,
+}
+
+/// An artist from [HistoryStore::top_artists]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TopArtist {
+  pub name: String,
+  /// Empty if no play had it (like local files)
+  pub uri: SpotifyUri,
+  /// How many of their tracks finished or got scrobbled
+  pub plays: u64,
+  /// How long those played in total
+  #[serde(with = "serde_utils::millis")]
+  pub played: Duration,
+}
+
+/// Keeps every track that finished or got skipped in a SQLite database, to look at what was listened to later
+///
+/// Needs [SpotifyListenerBuilder::track_lifecycle](crate::SpotifyListenerBuilder::track_lifecycle)
+/// (and [SpotifyListenerBuilder::scrobble_points](crate::SpotifyListenerBuilder::scrobble_points) for [Play::scrobbled]),
+/// then [Self::run] records everything from [SpotifyEvents](crate::SpotifyEvents) in the background,
+/// or give it events some other way with [Self::record]
+///
+/// It also works as an [Interceptor](crate::Interceptor) with
+/// [SpotifyListenerBuilder::interceptor](crate::SpotifyListenerBuilder::interceptor),
+/// but then every insert blocks the task reading the connection (and the runtime thread under it) until it's on disk
+///
+/// Clones share the database but keep track of the current play on their own, so every connection gets its own,
+/// errors while recording get logged with the `log` crate
+///
+/// ```text
+/// let history = HistoryStore::open("history.db")?;
+///
+/// let listener = SpotifyListener::builder()
+///   .track_lifecycle(true)
+///   .scrobble_points(true)
+///   .bind()
+///   .await?;
+///
+/// tokio::spawn(history.clone().run(listener.events(64)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+  db: Arc<Mutex<Connection>>,
+  /// Uri of the track that's playing and when it started, plus if it got scrobbled
+  current: Option<(SpotifyUri, SystemTime, bool)>,
+}
+
+impl HistoryStore {
+  /// Opens the database at `path`, creating it and its tables if they don't exist yet
+  pub fn open(path: impl AsRef<Path>) -> SpotifyResult<Self> {
+    Self::new(Connection::open(path).map_err(SpotifyError::History)?)
+  }
+
+  /// A database that's gone once the last clone is dropped, for tests
+  pub fn open_in_memory() -> SpotifyResult<Self> {
+    Self::new(Connection::open_in_memory().map_err(SpotifyError::History)?)
+  }
+
+  fn new(db: Connection) -> SpotifyResult<Self> {
+    db.execute_batch("PRAGMA foreign_keys = ON;").map_err(SpotifyError::History)?;
+    db.execute_batch(TABLES).map_err(SpotifyError::History)?;
+    db.pragma_update(None, "user_version", VERSION).map_err(SpotifyError::History)?;
+
+    Ok(Self {
+      db: Arc::new(Mutex::new(db)),
+      current: None,
+    })
+  }
+
+  /// Runs `f` with the database, for queries this doesn't have
+  pub fn with_connection<T>(&self, f: impl FnOnce(&Connection) -> rusqlite::Result<T>) -> SpotifyResult<T> {
+    let db = self.db.lock().unwrap_or_else(PoisonError::into_inner);
+
+    f(&db).map_err(SpotifyError::History)
+  }
+
+  /// Adds `play` to the history and returns its id, [Play::id] is ignored
+  pub fn insert(&self, play: &Play) -> SpotifyResult<i64> {
+    let mut db = self.db.lock().unwrap_or_else(PoisonError::into_inner);
+    let tx = db.transaction().map_err(SpotifyError::History)?;
+
+    tx.execute(
+      "INSERT INTO plays (uri, title, album, album_uri, started_at, ended_at, played_ms, duration_ms, finished, scrobbled)
+       VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+      params![
+        play.uri.as_str(),
+        play.title,
+        play.album,
+        play.album_uri.as_str(),
+        to_millis(play.started_at),
+        to_millis(play.ended_at),
+        play.played.as_millis() as i64,
+        play.duration.as_millis() as i64,
+        play.finished,
+        play.scrobbled,
+      ],
+    ).map_err(SpotifyError::History)?;
+
+    let id = tx.last_insert_rowid();
+
+    for (position, artist) in play.artists.iter().enumerate() {
+      tx.execute(
+        "INSERT INTO play_artists (play_id, position, name, uri) VALUES (?1, ?2, ?3, ?4)",
+        params![id, position as i64, artist.name, artist.uri.as_str()],
+      ).map_err(SpotifyError::History)?;
+    }
+
+    tx.commit().map_err(SpotifyError::History)?;
+
+    Ok(id)
+  }
+
+  /// The play with `id`, [None] if there isn't one
+  pub fn get(&self, id: i64) -> SpotifyResult<Option<Play>> {
+    let db = self.db.lock().unwrap_or_else(PoisonError::into_inner);
+    let play = db
+      .query_row(&format!("SELECT {} FROM plays WHERE id = ?1", COLUMNS), [id], play_from_row)
+      .optional()
+      .map_err(SpotifyError::History)?;
+
+    match play {
+      Some(play) => Ok(Some(with_artists(&db, play).map_err(SpotifyError::History)?)),
+      None => Ok(None),
+    }
+  }
+
+  /// How many plays there are
+  pub fn len(&self) -> SpotifyResult<u64> {
+    self.with_connection(|db| db.query_row("SELECT COUNT(*) FROM plays", [], |row| row.get(0)))
+  }
+
+  pub fn is_empty(&self) -> SpotifyResult<bool> {
+    Ok(self.len()? == 0)
+  }
+
+  /// Inserts a [Play] on [SpotifyEvent::TrackFinished] and [SpotifyEvent::TrackSkipped],
+  /// the rest are only used for when it started and if it got scrobbled
+  ///
+  /// Returns the id of the play if one got inserted
+  pub fn record(&mut self, event: &SpotifyEvent) -> SpotifyResult<Option<i64>> {
+    match self.finish(event) {
+      Some(play) => self.insert(&play).map(Some),
+      None => Ok(None),
+    }
+  }
+
+  /// Records `events` until the listener is gone, same as [Self::record] for each of them,
+  /// inserts run with [tokio::task::spawn_blocking] so the runtime doesn't wait for the disk
+  ///
+  /// Errors get logged with the `log` crate
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  #[cfg(feature = "async")]
+  pub async fn run(mut self, events: crate::SpotifyEvents) {
+    use tokio::sync::broadcast::error::RecvError;
+
+    let mut events = events.into_receiver();
+
+    loop {
+      let event = match events.recv().await {
+        Ok(event) => event,
+        Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => break,
+      };
+
+      let play = match self.finish(&event) {
+        Some(play) => play,
+        None => continue,
+      };
+
+      let store = self.clone();
+
+      match tokio::task::spawn_blocking(move || store.insert(&play)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => log::warn!("failed to record {} to the history: {}", event.kind(), err),
+        Err(join) => log::warn!("recording {} to the history panicked: {}", event.kind(), join),
+      }
+    }
+  }
+
+  /// Keeps track of the current play, the [Play] that has to be inserted when `event` ends one
+  fn finish(&mut self, event: &SpotifyEvent) -> Option<Play> {
+    let play = match event {
+      SpotifyEvent::TrackChanged(info) => {
+        self.current = Some((info.uri.clone(), SystemTime::now(), false));
+        return None;
+      }
+      SpotifyEvent::StateSnapshot(snapshot) => {
+        let current = self.current.as_ref().map(|(uri, ..)| uri);
+
+        if let Some(info) = snapshot.track.as_ref().filter(|info| Some(&info.uri) != current) {
+          let started_at = SystemTime::now().checked_sub(snapshot.position).unwrap_or_else(SystemTime::now);
+          self.current = Some((info.uri.clone(), started_at, false));
+        }
+
+        return None;
+      }
+      SpotifyEvent::ScrobblePoint(info) => {
+        if let Some((uri, _, scrobbled)) = &mut self.current {
+          *scrobbled |= *uri == info.uri;
+        }
+
+        return None;
+      }
+      SpotifyEvent::TrackFinished(info) => Play::new(info, info.duration, true),
+      SpotifyEvent::TrackSkipped { track, at } => Play::new(track, *at, false),
+      _ => return None,
+    };
+
+    match self.current.take() {
+      Some((uri, started_at, scrobbled)) if uri == play.uri => Some(Play { started_at, scrobbled, ..play }),
+      _ => Some(play),
+    }
+  }
+}
+
+/// Queries for what's in the history, `range` is when the plays started, so `..` is everything
+/// and `from..to` compares with [Play::started_at]
+///
+/// Only plays that finished or got scrobbled count for the top lists, skipped ones are still in the rest
+impl HistoryStore {
+  /// Every play that started in `range`, oldest first
+  pub fn plays(&self, range: impl RangeBounds<SystemTime>) -> SpotifyResult<Vec<Play>> {
+    let (filter, values) = started_in(&range);
+
+    self.query_plays(&format!("SELECT {} FROM plays WHERE {} ORDER BY started_at, id", COLUMNS, filter), values)
+  }
+
+  /// The last `n` plays, newest first
+  pub fn last_plays(&self, n: usize) -> SpotifyResult<Vec<Play>> {
+    let sql = format!("SELECT {} FROM plays ORDER BY started_at DESC, id DESC LIMIT ?1", COLUMNS);
+
+    self.query_plays(&sql, vec![Value::Integer(limit(n))])
+  }
+
+  /// Plays with `title` anywhere in their title (ignoring case for ascii letters), newest first
+  pub fn search(&self, title: &str, n: usize) -> SpotifyResult<Vec<Play>> {
+    let pattern = format!("%{}%", title.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+    let sql = format!("SELECT {} FROM plays WHERE title LIKE ?1 ESCAPE '\\' ORDER BY started_at DESC, id DESC LIMIT ?2", COLUMNS);
+
+    self.query_plays(&sql, vec![Value::Text(pattern), Value::Integer(limit(n))])
+  }
+
+  /// The `n` most played tracks in `range`, then the ones that played longer go first
+  pub fn top_tracks(&self, range: impl RangeBounds<SystemTime>, n: usize) -> SpotifyResult<Vec<TopTrack>> {
+    let (filter, mut values) = started_in(&range);
+    values.push(Value::Integer(limit(n)));
+
+    let sql = format!(
+      "SELECT uri, title, COUNT(*), SUM(played_ms), MAX(id) FROM plays
+       WHERE (finished OR scrobbled) AND {}
+       GROUP BY uri, title ORDER BY 3 DESC, 4 DESC LIMIT ?{}",
+      filter,
+      values.len()
+    );
+
+    self.with_connection(|db| {
+      let mut query = db.prepare(&sql)?;
+      let rows = query.query_map(params_from_iter(values), |row| {
+        Ok((
+          TopTrack {
+            uri: SpotifyUri::from(row.get::<_, String>(0)?),
+            title: row.get(1)?,
+            artists: Vec::new(),
+            plays: row.get(2)?,
+            played: Duration::from_millis(row.get::<_, i64>(3)?.max(0) as u64),
+          },
+          row.get::<_, i64>(4)?,
+        ))
+      })?;
+
+      rows
+        .map(|row| {
+          let (mut track, latest) = row?;
+          track.artists = artists(db, latest)?;
+          Ok(track)
+        })
+        .collect()
+    })
+  }
+
+  /// The `n` most played artists in `range`, every artist of a track counts for it
+  pub fn top_artists(&self, range: impl RangeBounds<SystemTime>, n: usize) -> SpotifyResult<Vec<TopArtist>> {
+    let (filter, mut values) = started_in(&range);
+    values.push(Value::Integer(limit(n)));
+
+    let sql = format!(
+      "SELECT play_artists.name, MAX(play_artists.uri), COUNT(*), SUM(plays.played_ms) FROM play_artists
+       JOIN plays ON plays.id = play_artists.play_id
+       WHERE (finished OR scrobbled) AND {}
+       GROUP BY play_artists.name ORDER BY 3 DESC, 4 DESC LIMIT ?{}",
+      filter,
+      values.len()
+    );
+
+    self.with_connection(|db| {
+      let mut query = db.prepare(&sql)?;
+      let rows = query.query_map(params_from_iter(values), |row| {
+        Ok(TopArtist {
+          name: row.get(0)?,
+          uri: SpotifyUri::from(row.get::<_, String>(1)?),
+          plays: row.get(2)?,
+          played: Duration::from_millis(row.get::<_, i64>(3)?.max(0) as u64),
+        })
+      })?;
+
+      rows.collect()
+    })
+  }
+
+  fn query_plays(&self, sql: &str, values: Vec<Value>) -> SpotifyResult<Vec<Play>> {
+    self.with_connection(|db| {
+      let mut query = db.prepare(sql)?;
+      let plays = query.query_map(params_from_iter(values), play_from_row)?;
+
+      plays.map(|play| with_artists(db, play?)).collect()
+    })
+  }
+}
+
+/// Blocks while inserting, see [HistoryStore::run] for recording without that
+#[cfg(feature = "async")]
+impl crate::Interceptor for HistoryStore {
+  fn on_event(&mut self, event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+    if let Err(err) = self.record(&event) {
+      log::warn!("failed to record {} to the history: {}", event.kind(), err);
+    }
+
+    events.push(event);
+  }
+}
+
+/// Every column of `plays` in the order [play_from_row] reads them
+const COLUMNS: &str = "id, uri, title, album, album_uri, started_at, ended_at, played_ms, duration_ms, finished, scrobbled";
+
+/// A play without its artists, see [with_artists]
+fn play_from_row(row: &rusqlite::Row) -> rusqlite::Result<Play> {
+  Ok(Play {
+    id: row.get(0)?,
+    uri: SpotifyUri::from(row.get::<_, String>(1)?),
+    title: row.get(2)?,
+    artists: Vec::new(),
+    album: row.get(3)?,
+    album_uri: SpotifyUri::from(row.get::<_, String>(4)?),
+    started_at: from_millis(row.get(5)?),
+    ended_at: from_millis(row.get(6)?),
+    played: Duration::from_millis(row.get::<_, i64>(7)?.max(0) as u64),
+    duration: Duration::from_millis(row.get::<_, i64>(8)?.max(0) as u64),
+    finished: row.get(9)?,
+    scrobbled: row.get(10)?,
+  })
+}
+
+fn with_artists(db: &Connection, mut play: Play) -> rusqlite::Result<Play> {
+  play.artists = artists(db, play.id)?;
+
+  Ok(play)
+}
+
+fn artists(db: &Connection, play_id: i64) -> rusqlite::Result<Vec<Artist>> {
+  let mut query = db.prepare_cached("SELECT name, uri FROM play_artists WHERE play_id = ?1 ORDER BY position")?;
+
+  let artists = query.query_map([play_id], |row| {
+    Ok(Artist {
+      name: row.get(0)?,
+      uri: SpotifyUri::from(row.get::<_, String>(1)?),
+    })
+  })?;
+
+  artists.collect()
+}
+
+/// `WHERE` clause for plays that started in `range` and the values for it, numbered from 1
+pub(crate) fn started_in(range: &impl RangeBounds<SystemTime>) -> (String, Vec<Value>) {
+  let mut filter = vec!["1".to_string()];
+  let mut values = Vec::new();
+
+  let mut bound = |bound: Bound<&SystemTime>, inclusive: &str, exclusive: &str| {
+    let (op, time) = match bound {
+      Bound::Included(time) => (inclusive, time),
+      Bound::Excluded(time) => (exclusive, time),
+      Bound::Unbounded => return,
+    };
+
+    values.push(Value::Integer(to_millis(*time)));
+    filter.push(format!("started_at {} ?{}", op, values.len()));
+  };
+
+  bound(range.start_bound(), ">=", ">");
+  bound(range.end_bound(), "<=", "<");
+
+  (filter.join(" AND "), values)
+}
+
+/// SQLite limits are signed
+pub(crate) fn limit(n: usize) -> i64 {
+  n.try_into().unwrap_or(i64::MAX)
+}
+
+pub(crate) fn to_millis(time: SystemTime) -> i64 {
+  // times before 1970 don't come from spotify
+  time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as i64)
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+  UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::PlayerSnapshot;
+
+  fn track(name: &str) -> TrackInfo {
+    TrackInfo::builder()
+      .uid(name)
+      .uri(format!("spotify:track:{}", name))
+      .title(name)
+      .artist("artist")
+      .album("album")
+      .duration(Duration::from_secs(200))
+      .build()
+      .unwrap()
+  }
+
+  fn record(history: &mut HistoryStore, events: Vec<SpotifyEvent>) -> Vec<Play> {
+    let ids: Vec<_> = events.iter().filter_map(|event| history.record(event).unwrap()).collect();
+
+    ids.into_iter().map(|id| history.get(id).unwrap().unwrap()).collect()
+  }
+
+  #[test]
+  fn records_finished_and_skipped() {
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let plays = record(&mut history, vec![
+      SpotifyEvent::TrackChanged(track("a")),
+      SpotifyEvent::TrackFinished(track("a")),
+      SpotifyEvent::TrackChanged(track("b")),
+      SpotifyEvent::TrackSkipped { track: track("b"), at: Duration::from_secs(20) },
+    ]);
+
+    assert_eq!(plays.len(), 2);
+    assert_eq!((plays[0].title.as_str(), plays[0].played, plays[0].finished), ("a", Duration::from_secs(200), true));
+    assert_eq!((plays[1].title.as_str(), plays[1].played, plays[1].finished), ("b", Duration::from_secs(20), false));
+    assert_eq!(plays[1].artists[0].name, "artist");
+    assert_eq!(plays[1].album, "album");
+    assert_eq!(history.len().unwrap(), 2);
+  }
+
+  #[test]
+  fn nothing_else_gets_recorded() {
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let plays = record(&mut history, vec![
+      SpotifyEvent::TrackChanged(track("a")),
+      SpotifyEvent::StateChanged(crate::TrackState::Paused),
+      SpotifyEvent::ScrobblePoint(track("a")),
+    ]);
+
+    assert!(plays.is_empty());
+    assert!(history.is_empty().unwrap());
+  }
+
+  #[test]
+  fn started_when_the_track_changed() {
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let before = SystemTime::now();
+
+    history.record(&SpotifyEvent::TrackChanged(track("a"))).unwrap();
+    // finished right away, but it started when it changed and not the duration before now
+    let plays = record(&mut history, vec![SpotifyEvent::TrackFinished(track("a"))]);
+
+    assert!(plays[0].started_at + Duration::from_secs(1) >= before);
+  }
+
+  #[test]
+  fn scrobbled_only_for_the_same_track() {
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let plays = record(&mut history, vec![
+      SpotifyEvent::TrackChanged(track("a")),
+      SpotifyEvent::ScrobblePoint(track("a")),
+      SpotifyEvent::TrackFinished(track("a")),
+      SpotifyEvent::TrackChanged(track("b")),
+      SpotifyEvent::ScrobblePoint(track("a")),
+      SpotifyEvent::TrackFinished(track("b")),
+    ]);
+
+    assert!(plays[0].scrobbled);
+    assert!(!plays[1].scrobbled);
+  }
+
+  #[test]
+  fn started_from_the_snapshot_position() {
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let snapshot = PlayerSnapshot { track: Some(track("a")), position: Duration::from_secs(60), ..PlayerSnapshot::default() };
+    let plays = record(&mut history, vec![SpotifyEvent::StateSnapshot(snapshot), SpotifyEvent::TrackFinished(track("a"))]);
+
+    let ago = SystemTime::now().duration_since(plays[0].started_at).unwrap();
+    assert!(ago >= Duration::from_secs(59) && ago < Duration::from_secs(70));
+  }
+
+  #[test]
+  fn snapshot_of_the_same_track_keeps_the_start() {
+    // the snapshot after a reconnect doesn't start it over
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let snapshot = PlayerSnapshot { track: Some(track("a")), position: Duration::from_secs(60), ..PlayerSnapshot::default() };
+    let plays = record(&mut history, vec![
+      SpotifyEvent::TrackChanged(track("a")),
+      SpotifyEvent::ScrobblePoint(track("a")),
+      SpotifyEvent::StateSnapshot(snapshot),
+      SpotifyEvent::TrackFinished(track("a")),
+    ]);
+
+    assert!(SystemTime::now().duration_since(plays[0].started_at).unwrap() < Duration::from_secs(10));
+    assert!(plays[0].scrobbled);
+  }
+
+  #[test]
+  fn ended_without_a_start() {
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let plays = record(&mut history, vec![SpotifyEvent::TrackSkipped { track: track("a"), at: Duration::from_secs(30) }]);
+
+    assert_eq!(plays[0].ended_at.duration_since(plays[0].started_at).unwrap(), Duration::from_secs(30));
+  }
+
+  #[test]
+  fn clones_share_the_database() {
+    let mut history = HistoryStore::open_in_memory().unwrap();
+    let other = history.clone();
+
+    record(&mut history, vec![SpotifyEvent::TrackFinished(track("a"))]);
+
+    assert_eq!(other.len().unwrap(), 1);
+  }
+
+  #[cfg(feature = "async")]
+  #[tokio::test]
+  async fn run_records_events() {
+    use crate::SpotifyEvents;
+
+    let history = HistoryStore::open_in_memory().unwrap();
+    let (sender, _) = tokio::sync::broadcast::channel(16);
+    let (commands, _) = tokio::sync::mpsc::unbounded_channel();
+    let task = tokio::spawn(history.clone().run(SpotifyEvents { sender: sender.clone(), commands }));
+
+    tokio::task::yield_now().await;
+    sender.send(SpotifyEvent::TrackChanged(track("a"))).unwrap();
+    sender.send(SpotifyEvent::TrackFinished(track("a"))).unwrap();
+    drop(sender);
+
+    tokio::time::timeout(Duration::from_secs(5), task).await.expect("stops once the listener is gone").unwrap();
+    assert_eq!(history.len().unwrap(), 1);
+  }
+
+  /// A play of `title` that started `at` seconds after the epoch
+  fn play_at(title: &str, at: u64, finished: bool) -> Play {
+    let started_at = UNIX_EPOCH + Duration::from_secs(at);
+
+    Play {
+      uri: SpotifyUri::from(format!("spotify:track:{}", title.replace(|c: char| !c.is_ascii_alphanumeric(), ""))),
+      title: title.into(),
+      started_at,
+      ended_at: started_at + Duration::from_secs(10),
+      played: Duration::from_secs(10),
+      ..Play::new(&track("track"), Duration::from_secs(10), finished)
+    }
+  }
+
+  fn store(plays: &[Play]) -> HistoryStore {
+    let history = HistoryStore::open_in_memory().unwrap();
+
+    for play in plays {
+      history.insert(play).unwrap();
+    }
+
+    history
+  }
+
+  fn titles(plays: &[Play]) -> Vec<&str> {
+    plays.iter().map(|play| play.title.as_str()).collect()
+  }
+
+  fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+  }
+
+  #[test]
+  fn plays_in_a_range() {
+    let history = store(&[play_at("a", 100, true), play_at("b", 200, false), play_at("c", 300, true)]);
+
+    assert_eq!(titles(&history.plays(..).unwrap()), ["a", "b", "c"]);
+    assert_eq!(titles(&history.plays(at(200)..).unwrap()), ["b", "c"]);
+    assert_eq!(titles(&history.plays(..at(200)).unwrap()), ["a"]);
+    assert_eq!(titles(&history.plays(..=at(200)).unwrap()), ["a", "b"]);
+    assert_eq!(titles(&history.plays(at(150)..at(300)).unwrap()), ["b"]);
+    assert!(history.plays(at(400)..).unwrap().is_empty());
+  }
+
+  #[test]
+  fn last_plays_newest_first() {
+    let history = store(&[play_at("a", 100, true), play_at("b", 300, true), play_at("c", 200, true)]);
+
+    assert_eq!(titles(&history.last_plays(2).unwrap()), ["b", "c"]);
+    assert_eq!(titles(&history.last_plays(10).unwrap()), ["b", "c", "a"]);
+    assert!(history.last_plays(0).unwrap().is_empty());
+    // too big for sqlite is everything
+    assert_eq!(history.last_plays(usize::MAX).unwrap().len(), 3);
+  }
+
+  #[test]
+  fn search_by_title() {
+    let history = store(&[play_at("Hello", 100, true), play_at("hello world", 200, true), play_at("100%_done", 300, true), play_at("100 done", 400, true)]);
+
+    assert_eq!(titles(&history.search("HELLO", 10).unwrap()), ["hello world", "Hello"]);
+    assert_eq!(titles(&history.search("hello", 1).unwrap()), ["hello world"]);
+    // these mean something to LIKE
+    assert_eq!(titles(&history.search("%", 10).unwrap()), ["100%_done"]);
+    assert_eq!(titles(&history.search("_", 10).unwrap()), ["100%_done"]);
+    assert!(history.search("nope", 10).unwrap().is_empty());
+  }
+
+  #[test]
+  fn top_tracks_count_finished_or_scrobbled() {
+    let scrobbled = Play { scrobbled: true, ..play_at("b", 400, false) };
+    let history = store(&[
+      play_at("a", 100, true),
+      play_at("a", 200, true),
+      play_at("b", 300, false),
+      scrobbled,
+      play_at("c", 500, false),
+    ]);
+
+    let top = history.top_tracks(.., 10).unwrap();
+    let top: Vec<_> = top.iter().map(|track| (track.title.as_str(), track.plays)).collect();
+    assert_eq!(top, [("a", 2), ("b", 1)]);
+
+    assert_eq!(history.top_tracks(.., 1).unwrap().len(), 1);
+    assert_eq!(history.top_tracks(at(300).., 10).unwrap()[0].title, "b");
+    assert_eq!(history.top_tracks(.., 10).unwrap()[0].artists[0].name, "artist");
+  }
+
+  #[test]
+  fn top_artists_count_every_artist() {
+    let mut both = play_at("b", 200, true);
+    both.artists.push(Artist { name: "other".into(), uri: SpotifyUri::default() });
+    let history = store(&[play_at("a", 100, true), both, play_at("c", 300, false)]);
+
+    let top = history.top_artists(.., 10).unwrap();
+    let top: Vec<_> = top.iter().map(|artist| (artist.name.as_str(), artist.plays, artist.played.as_secs())).collect();
+    assert_eq!(top, [("artist", 2, 20), ("other", 1, 10)]);
+
+    assert!(history.top_artists(at(250).., 10).unwrap().is_empty());
+  }
+}
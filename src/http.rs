@@ -0,0 +1,155 @@
+//! Serving events over plain http, only with the `http` feature
+
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use hyper::header::{ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::{PlayerSnapshot, SpotifyError, SpotifyEvent, SpotifyEvents, SpotifyResult};
+
+/// Comments get sent this often so proxies and browsers don't give up on a quiet stream
+const SSE_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// Small http server for browser based things like overlays,
+/// events are sent as [server-sent events](https://developer.mozilla.org/en-US/docs/Web/API/Server-sent_events)
+/// so they can be read with `EventSource` without a websocket
+///
+/// There's also the current [PlayerSnapshot] as json, for scripts that'd rather poll
+///
+/// ```js
+/// new EventSource("http://127.0.0.1:19533/events").onmessage = (message) => {
+///   const event = JSON.parse(message.data); // same as what the extension sends
+/// };
+/// ```
+///
+/// Responses allow any origin, since overlays are usually loaded from a file or another port
+#[derive(Debug)]
+pub struct HttpServer {
+  listener: TcpListener,
+  events_route: String,
+  now_playing_route: String,
+}
+
+impl HttpServer {
+  /// Binds to the given address, this has to be a different port than the [SpotifyListener](crate::SpotifyListener)
+  pub async fn bind(addr: SocketAddr) -> SpotifyResult<Self> {
+    let listener = TcpListener::bind(addr).await.map_err(SpotifyError::Bind)?;
+
+    Ok(Self {
+      listener,
+      events_route: "/events".to_string(),
+      now_playing_route: "/now-playing".to_string(),
+    })
+  }
+
+  /// Path the event stream is served on
+  ///
+  /// Default: `/events`
+  pub fn events_route(mut self, route: impl Into<String>) -> Self {
+    self.events_route = route.into();
+    self
+  }
+
+  /// Path the current [PlayerSnapshot] is served on
+  ///
+  /// Default: `/now-playing`
+  pub fn now_playing_route(mut self, route: impl Into<String>) -> Self {
+    self.now_playing_route = route.into();
+    self
+  }
+
+  pub fn local_addr(&self) -> SpotifyResult<SocketAddr> {
+    self.listener.local_addr().map_err(SpotifyError::Bind)
+  }
+
+  /// Keeps serving requests, every client on the event stream gets every event from `events`
+  /// starting from when they connect, clients that read too slowly skip events
+  ///
+  /// The snapshot only knows about events sent after this gets called,
+  /// so it's best to call it before spotify connects
+  ///
+  /// Only returns when the listener stops accepting connections
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn serve(self, events: SpotifyEvents) -> SpotifyResult<()> {
+    let incoming = AddrIncoming::from_listener(self.listener).map_err(|err| SpotifyError::Accept(io::Error::other(err)))?;
+    let routes = Arc::new((self.events_route, self.now_playing_route));
+    let snapshot = Arc::new(RwLock::new(PlayerSnapshot::default()));
+
+    tokio::spawn(crate::track_snapshot(events.subscribe(), snapshot.clone()));
+
+    let make_service = make_service_fn(move |_| {
+      let events = events.clone();
+      let routes = routes.clone();
+      let snapshot = snapshot.clone();
+
+      async move {
+        Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+          let (events_route, now_playing_route) = &*routes;
+
+          let response = match (request.method(), request.uri().path()) {
+            (&Method::GET, path) if path == events_route => event_stream(events.subscribe()),
+            (&Method::GET, path) if path == now_playing_route => now_playing(&snapshot),
+            _ => not_found(),
+          };
+
+          async move { Ok::<_, Infallible>(response) }
+        }))
+      }
+    });
+
+    Server::builder(incoming)
+      .serve(make_service)
+      .await
+      .map_err(|err| SpotifyError::Accept(io::Error::other(err)))
+  }
+}
+
+fn event_stream(events: Receiver<SpotifyEvent>) -> Response<Body> {
+  let stream = futures_util::stream::unfold(events, |mut events| async move {
+    loop {
+      let chunk = match tokio::time::timeout(SSE_KEEPALIVE, events.recv()).await {
+        Ok(Ok(event)) => format!("data: {}\n\n", serde_json::to_string(&event).expect("events always serialize")),
+        Ok(Err(RecvError::Lagged(_))) => continue,
+        Ok(Err(RecvError::Closed)) => return None,
+        Err(_) => ": keepalive\n\n".to_string(),
+      };
+
+      return Some((Ok::<_, Infallible>(chunk), events));
+    }
+  });
+
+  Response::builder()
+    .header(CONTENT_TYPE, "text/event-stream")
+    .header(CACHE_CONTROL, "no-cache")
+    .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+    .body(Body::wrap_stream(stream))
+    .expect("response is always valid")
+}
+
+fn now_playing(snapshot: &RwLock<PlayerSnapshot>) -> Response<Body> {
+  let json = serde_json::to_string(&*snapshot.read().unwrap_or_else(PoisonError::into_inner)).expect("snapshots always serialize");
+
+  Response::builder()
+    .header(CONTENT_TYPE, "application/json")
+    .header(CACHE_CONTROL, "no-cache")
+    .header(ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+    .body(Body::from(json))
+    .expect("response is always valid")
+}
+
+fn not_found() -> Response<Body> {
+  Response::builder()
+    .status(StatusCode::NOT_FOUND)
+    .body(Body::empty())
+    .expect("response is always valid")
+}
@@ -0,0 +1,55 @@
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+
+/// Identifies a connection, ids are never reused while the program runs
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ConnectionId(u64);
+
+impl ConnectionId {
+  #[cfg(any(feature = "async", feature = "blocking"))]
+  pub(crate) fn next() -> Self {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    Self(NEXT.fetch_add(1, Ordering::Relaxed))
+  }
+
+  pub fn get(self) -> u64 {
+    self.0
+  }
+}
+
+impl Display for ConnectionId {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "#{}", self.0)
+  }
+}
+
+/// Everything known about a connection from accepting it and its hello,
+/// see [SpotifyConnection::info](crate::SpotifyConnection::info)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionInfo {
+  /// Id of the connection, same as the one [SpotifyServer](crate::SpotifyServer) yields with its events
+  pub id: ConnectionId,
+  /// Address the connection came from, [None] for unix sockets and named pipes
+  pub peer: Option<SocketAddr>,
+  /// Protocol version the extension said it speaks
+  pub protocol_version: u32,
+  /// Version of the extension itself, [None] for extensions that don't send it
+  pub extension_version: Option<String>,
+  /// What connected, like `Spotify 1.2.31`, [None] for extensions that don't send it
+  pub client: Option<String>,
+}
+
+impl Display for ConnectionInfo {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {}", self.id, self.client.as_deref().unwrap_or("unknown client"))?;
+
+    if let Some(peer) = self.peer {
+      write!(f, " from {}", peer)?;
+    }
+
+    Ok(())
+  }
+}
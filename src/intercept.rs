@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use crate::{SpotifyEvent, SpotifyMessage, TrackInfo};
+
+/// Sits between the extension and everything that reads from a connection,
+/// can look at, change or drop events before anything else sees them
+/// and messages before they get sent, added with [SpotifyListenerBuilder::interceptor](crate::SpotifyListenerBuilder::interceptor)
+///
+/// Both do nothing by default, so only implement the one you need
+///
+/// ```text
+/// #[derive(Clone)]
+/// struct NoExplicit;
+///
+/// impl Interceptor for NoExplicit {
+///   fn on_event(&mut self, event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+///     match &event {
+///       SpotifyEvent::TrackChanged(info) if info.explicit == Some(true) => {}
+///       _ => events.push(event),
+///     }
+///   }
+/// }
+/// ```
+pub trait Interceptor: Send + 'static {
+  /// Gets every event the connection receives, whatever gets pushed to `events` is passed on,
+  /// push nothing to drop it, something else to replace it, or more than one to add events
+  ///
+  /// Errors don't go through here, they're always passed on
+  fn on_event(&mut self, event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+    events.push(event);
+  }
+
+  /// Gets every message right before it's sent, [None] drops it
+  /// and sending it still succeeds
+  fn on_message(&mut self, message: SpotifyMessage) -> Option<SpotifyMessage> {
+    Some(message)
+  }
+}
+
+type MakeInterceptor = dyn Fn() -> Box<dyn Interceptor> + Send + Sync;
+
+/// Interceptors from the builder, every connection gets its own copy of them
+/// so state in one (like the last track) isn't shared between connections
+#[derive(Clone, Default)]
+pub(crate) struct Layers(Vec<Arc<MakeInterceptor>>);
+
+impl Debug for Layers {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("Layers").field(&self.0.len()).finish()
+  }
+}
+
+impl Layers {
+  pub(crate) fn push<I: Interceptor + Clone + Sync>(&mut self, interceptor: I) {
+    self.0.push(Arc::new(move || Box::new(interceptor.clone())));
+  }
+
+  /// Runs `other` after everything that's already there
+  pub(crate) fn extend(&mut self, other: Layers) {
+    self.0.extend(other.0);
+  }
+
+  pub(crate) fn build(&self) -> Interceptors {
+    Interceptors {
+      chain: self.0.iter().map(|make| make()).collect(),
+      pending: VecDeque::new(),
+    }
+  }
+}
+
+/// Interceptors of one connection in the order they were added,
+/// along with events that came out of them and haven't been yielded yet
+#[derive(Default)]
+pub(crate) struct Interceptors {
+  chain: Vec<Box<dyn Interceptor>>,
+  pending: VecDeque<SpotifyEvent>,
+}
+
+impl Debug for Interceptors {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Interceptors")
+      .field("chain", &self.chain.len())
+      .field("pending", &self.pending.len())
+      .finish()
+  }
+}
+
+impl Interceptors {
+  /// Adds one to the end of the chain
+  pub(crate) fn push(&mut self, interceptor: Box<dyn Interceptor>) {
+    self.chain.push(interceptor);
+  }
+
+  /// Next event that went through every interceptor
+  pub(crate) fn next(&mut self) -> Option<SpotifyEvent> {
+    self.pending.pop_front()
+  }
+
+  /// Runs `event` through the chain, whatever comes out the end waits in [Self::next]
+  pub(crate) fn event(&mut self, event: SpotifyEvent) {
+    if self.chain.is_empty() {
+      self.pending.push_back(event);
+      return;
+    }
+
+    let mut events = vec![event];
+
+    for interceptor in &mut self.chain {
+      let mut out = Vec::with_capacity(events.len());
+
+      for event in events {
+        interceptor.on_event(event, &mut out);
+      }
+
+      events = out;
+    }
+
+    self.pending.extend(events);
+  }
+
+  /// Runs `message` through the chain, [None] if one of them dropped it
+  pub(crate) fn message(&mut self, message: SpotifyMessage) -> Option<SpotifyMessage> {
+    self.chain.iter_mut().try_fold(message, |message, interceptor| interceptor.on_message(message))
+  }
+}
+
+/// Drops [SpotifyEvent::TrackChanged] when it's the same track as the last one,
+/// see [SpotifyListenerBuilder::dedup_tracks](crate::SpotifyListenerBuilder::dedup_tracks)
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DedupTracks {
+  last: Option<TrackInfo>,
+}
+
+impl DedupTracks {
+  fn is_same(&self, info: &TrackInfo) -> bool {
+    // local files can have an empty uid and uri, the title tells those apart
+    self.last.as_ref().is_some_and(|last| last.eq_ignore_state(info) && last.uri == info.uri && last.title == info.title)
+  }
+}
+
+impl Interceptor for DedupTracks {
+  fn on_event(&mut self, event: SpotifyEvent, events: &mut Vec<SpotifyEvent>) {
+    match &event {
+      SpotifyEvent::TrackChanged(info) if self.is_same(info) => return,
+      SpotifyEvent::TrackChanged(info) => self.last = Some(info.clone()),
+      SpotifyEvent::StateSnapshot(snapshot) => self.last = snapshot.track.clone(),
+      _ => {}
+    }
+
+    events.push(event);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{PlayerSnapshot, TrackState};
+
+  fn track(uid: &str, state: TrackState) -> TrackInfo {
+    let info = TrackInfo::builder().uid(uid).title(uid).build().unwrap();
+
+    TrackInfo { state, ..info }
+  }
+
+  /// What's left of `events` after going through [DedupTracks]
+  fn dedup(events: Vec<SpotifyEvent>) -> Vec<SpotifyEvent> {
+    let mut dedup = DedupTracks::default();
+    let mut out = Vec::new();
+
+    for event in events {
+      dedup.on_event(event, &mut out);
+    }
+
+    out
+  }
+
+  #[test]
+  fn drops_the_same_track() {
+    let out = dedup(vec![
+      SpotifyEvent::TrackChanged(track("a", TrackState::Playing)),
+      SpotifyEvent::TrackChanged(track("a", TrackState::Playing)),
+      SpotifyEvent::TrackChanged(track("b", TrackState::Playing)),
+      SpotifyEvent::TrackChanged(track("a", TrackState::Playing)),
+    ]);
+
+    let uids: Vec<_> = out
+      .iter()
+      .filter_map(|event| match event {
+        SpotifyEvent::TrackChanged(info) => Some(info.uid.as_str()),
+        _ => None,
+      })
+      .collect();
+    assert_eq!(uids, ["a", "b", "a"]);
+  }
+
+  #[test]
+  fn state_only_update_is_the_same_track() {
+    let out = dedup(vec![
+      SpotifyEvent::TrackChanged(track("a", TrackState::Playing)),
+      SpotifyEvent::StateChanged(TrackState::Paused),
+      SpotifyEvent::TrackChanged(track("a", TrackState::Paused)),
+    ]);
+
+    // the state still comes through on its own
+    assert!(matches!(&out[..], [SpotifyEvent::TrackChanged(_), SpotifyEvent::StateChanged(TrackState::Paused)]));
+  }
+
+  #[test]
+  fn other_fields_dont_count() {
+    let liked = TrackInfo { liked: Some(true), ..track("a", TrackState::Playing) };
+    let out = dedup(vec![SpotifyEvent::TrackChanged(track("a", TrackState::Playing)), SpotifyEvent::TrackChanged(liked)]);
+
+    assert_eq!(out.len(), 1);
+  }
+
+  #[test]
+  fn local_files_differ_by_title() {
+    // no uid or uri for local files
+    let local = |title: &str| TrackInfo::builder().title(title).build().unwrap();
+    let out = dedup(vec![SpotifyEvent::TrackChanged(local("one")), SpotifyEvent::TrackChanged(local("two"))]);
+
+    assert_eq!(out.len(), 2);
+  }
+
+  #[test]
+  fn snapshots_set_the_last_track() {
+    let snapshot = PlayerSnapshot { track: Some(track("a", TrackState::Playing)), ..PlayerSnapshot::default() };
+    let out = dedup(vec![SpotifyEvent::StateSnapshot(snapshot), SpotifyEvent::TrackChanged(track("a", TrackState::Playing))]);
+
+    assert!(matches!(&out[..], [SpotifyEvent::StateSnapshot(_)]));
+  }
+}
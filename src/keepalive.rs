@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::ready;
+
+use crate::runtime::{Delay, Runtime};
+
+/// How often to ping the extension and how long to wait for it to answer,
+/// so a connection that silently died (spotify crashed, the machine went to sleep)
+/// ends with [SpotifyError::Timeout](crate::SpotifyError::Timeout) instead of hanging forever
+///
+/// Default: ping after 5 seconds without hearing anything, give up after 10 more seconds
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Keepalive {
+  /// How long the connection can be quiet before a ping gets sent
+  pub interval: Duration,
+  /// How long to wait for anything to come back after a ping
+  pub timeout: Duration,
+}
+
+impl Default for Keepalive {
+  fn default() -> Self {
+    Self {
+      interval: Duration::from_secs(5),
+      timeout: Duration::from_secs(10),
+    }
+  }
+}
+
+/// Keeps track of when to ping and when to give up on a connection
+#[derive(Debug)]
+pub(crate) struct KeepaliveTimer {
+  config: Keepalive,
+  delay: Delay,
+  waiting: bool,
+  timed_out: bool,
+}
+
+impl KeepaliveTimer {
+  pub(crate) fn new(config: Keepalive, runtime: Arc<dyn Runtime>) -> Self {
+    Self {
+      config,
+      delay: Delay::new(runtime, config.interval),
+      waiting: false,
+      timed_out: false,
+    }
+  }
+
+  /// Something was received, so the connection is still alive
+  pub(crate) fn reset(&mut self) {
+    self.waiting = false;
+    self.delay.reset(self.config.interval);
+  }
+
+  pub(crate) fn timed_out(&self) -> bool {
+    self.timed_out
+  }
+
+  /// Ready with `true` when a ping should be sent,
+  /// `false` when the last ping didn't get an answer in time
+  pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+    ready!(self.delay.poll(cx));
+
+    if self.waiting {
+      self.timed_out = true;
+      return Poll::Ready(false);
+    }
+
+    self.waiting = true;
+    self.delay.reset(self.config.timeout);
+
+    Poll::Ready(true)
+  }
+}
+
+/// Ends a connection that hasn't sent any events for a while, pongs don't count
+#[derive(Debug)]
+pub(crate) struct IdleTimer {
+  timeout: Duration,
+  delay: Delay,
+  expired: bool,
+}
+
+impl IdleTimer {
+  pub(crate) fn new(timeout: Duration, runtime: Arc<dyn Runtime>) -> Self {
+    Self {
+      timeout,
+      delay: Delay::new(runtime, timeout),
+      expired: false,
+    }
+  }
+
+  /// The extension sent something that isn't a ping or pong
+  pub(crate) fn reset(&mut self) {
+    self.delay.reset(self.timeout);
+  }
+
+  pub(crate) fn expired(&self) -> bool {
+    self.expired
+  }
+
+  /// Ready once the connection was idle for too long
+  pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+    ready!(self.delay.poll(cx));
+
+    self.expired = true;
+
+    Poll::Ready(())
+  }
+}
@@ -0,0 +1,267 @@
+//! Scrobbling to Last.fm, only with the `lastfm` feature
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde_json::Value;
+
+use crate::{Scrobble, ScrobbleService, ServiceFuture, SpotifyError, SpotifyResult, TrackInfo};
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const AUTH_URL: &str = "https://www.last.fm/api/auth/";
+
+/// Error codes for problems on Last.fm's side, anything else won't work when tried again
+/// (8 operation failed, 11 service offline, 16 temporarily unavailable, 29 rate limited)
+const RETRY_ERRORS: &[u64] = &[8, 11, 16, 29];
+
+/// Last.fm's api as a [ScrobbleService] for a [Scrobbler](crate::Scrobbler)
+///
+/// Needs an api key and secret from https://www.last.fm/api/account/create, and a session key to scrobble
+/// that stays valid until the user revokes it, so getting it once and keeping it is enough:
+///
+/// ```text
+/// let mut lastfm = LastFm::new(API_KEY, SECRET);
+/// let token = lastfm.token().await?;
+///
+/// println!("allow access at {}", lastfm.auth_url(&token));
+/// // after the user allowed it
+/// let session_key = lastfm.session(&token).await?;
+/// ```
+///
+/// Tracks are sent with their first artist, that's what Last.fm matches best.
+/// Tracks without a title or artist (like some local files) are left out since Last.fm needs both
+///
+/// Default: 10 second timeout for every request
+#[derive(Debug, Clone)]
+pub struct LastFm {
+  client: reqwest::Client,
+  api_key: String,
+  secret: String,
+  session_key: Option<String>,
+  api_url: String,
+  timeout: Duration,
+}
+
+impl LastFm {
+  pub fn new(api_key: impl Into<String>, secret: impl Into<String>) -> Self {
+    Self {
+      client: reqwest::Client::new(),
+      api_key: api_key.into(),
+      secret: secret.into(),
+      session_key: None,
+      api_url: API_URL.to_string(),
+      timeout: Duration::from_secs(10),
+    }
+  }
+
+  /// Uses an existing client instead of making a new one, to share its connections and settings
+  pub fn client(mut self, client: reqwest::Client) -> Self {
+    self.client = client;
+    self
+  }
+
+  /// A session key from before, from [Self::session] or [Self::mobile_session]
+  pub fn session_key(mut self, key: impl Into<String>) -> Self {
+    self.session_key = Some(key.into());
+    self
+  }
+
+  /// Sends everything somewhere else instead, for a proxy or something that speaks the same api like Libre.fm
+  ///
+  /// Default: `https://ws.audioscrobbler.com/2.0/`
+  pub fn api_url(mut self, url: impl Into<String>) -> Self {
+    self.api_url = url.into();
+    self
+  }
+
+  /// How long a request can take before it fails, failed scrobbles get retried
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  pub fn is_signed_in(&self) -> bool {
+    self.session_key.is_some()
+  }
+
+  /// First step of signing in, the token is good for 60 minutes and has to be allowed at [Self::auth_url]
+  pub async fn token(&self) -> SpotifyResult<String> {
+    let answer = self.call("auth.getToken", Vec::new()).await?;
+
+    string(&answer["token"], "token")
+  }
+
+  /// Where the user allows `token` to scrobble for them
+  pub fn auth_url(&self, token: &str) -> String {
+    format!("{}?api_key={}&token={}", AUTH_URL, self.api_key, token)
+  }
+
+  /// Gets a session key once `token` was allowed, it's used from now on and should be kept for next time
+  pub async fn session(&mut self, token: &str) -> SpotifyResult<String> {
+    let answer = self.call("auth.getSession", vec![param("token", token)]).await?;
+
+    self.set_session(&answer)
+  }
+
+  /// Gets a session key with the user's password instead, only for apps where opening a browser doesn't work,
+  /// the password isn't kept
+  pub async fn mobile_session(&mut self, username: &str, password: &str) -> SpotifyResult<String> {
+    let params = vec![param("username", username), param("password", password)];
+    let answer = self.call("auth.getMobileSession", params).await?;
+
+    self.set_session(&answer)
+  }
+
+  fn set_session(&mut self, answer: &Value) -> SpotifyResult<String> {
+    let key = string(&answer["session"]["key"], "session key")?;
+    self.session_key = Some(key.clone());
+
+    Ok(key)
+  }
+
+  /// Calls `method` signed with the secret, the session key is added if there is one
+  async fn call(&self, method: &str, mut params: Vec<(String, String)>) -> SpotifyResult<Value> {
+    params.push(param("method", method));
+    params.push(param("api_key", &self.api_key));
+
+    if let Some(key) = &self.session_key {
+      params.push(param("sk", key));
+    }
+
+    params.push(param("api_sig", &sign(&params, &self.secret)));
+    params.push(param("format", "json"));
+
+    let response = self.client.post(&self.api_url)
+      .timeout(self.timeout)
+      .form(&params)
+      .send()
+      .await
+      .map_err(|err| error(err.to_string(), true))?;
+
+    let status = response.status();
+    let text = response.text().await.map_err(|err| error(err.to_string(), true))?;
+    let answer = serde_json::from_str::<Value>(&text).unwrap_or(Value::Null);
+
+    if let Some(code) = answer["error"].as_u64() {
+      let message = answer["message"].as_str().unwrap_or("unknown error");
+
+      return Err(error(format!("{} (error {})", message, code), RETRY_ERRORS.contains(&code)));
+    }
+
+    if !status.is_success() || answer.is_null() {
+      let retry = status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+
+      return Err(error(format!("unexpected answer with status {}", status), retry));
+    }
+
+    Ok(answer)
+  }
+
+  /// Everything that needs a session key fails right away without one, there's no point in retrying it
+  fn signed_in(&self) -> SpotifyResult<()> {
+    match self.session_key {
+      Some(_) => Ok(()),
+      None => Err(error("not signed in, see LastFm::session".to_string(), false)),
+    }
+  }
+}
+
+impl ScrobbleService for LastFm {
+  fn name(&self) -> &'static str {
+    "last.fm"
+  }
+
+  fn now_playing<'a>(&'a self, track: &'a TrackInfo) -> ServiceFuture<'a> {
+    Box::pin(async move {
+      self.signed_in()?;
+
+      let params = match track_params(track, None) {
+        Some(params) => params,
+        None => return Ok(()),
+      };
+
+      self.call("track.updateNowPlaying", params).await.map(drop)
+    })
+  }
+
+  fn scrobble<'a>(&'a self, scrobbles: &'a [Scrobble]) -> ServiceFuture<'a> {
+    Box::pin(async move {
+      self.signed_in()?;
+
+      let params = scrobbles.iter()
+        .filter_map(|scrobble| {
+          let timestamp = scrobble.started_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+          track_params(&scrobble.track, Some(timestamp))
+        })
+        .enumerate()
+        .flat_map(|(n, params)| params.into_iter().map(move |(key, value)| (format!("{}[{}]", key, n), value)))
+        .collect::<Vec<_>>();
+
+      if params.is_empty() {
+        return Ok(());
+      }
+
+      self.call("track.scrobble", params).await.map(drop)
+    })
+  }
+
+  fn max_batch(&self) -> usize {
+    // the most track.scrobble takes at once
+    50
+  }
+}
+
+/// [None] if Last.fm wouldn't take it
+fn track_params(track: &TrackInfo, timestamp: Option<u64>) -> Option<Vec<(String, String)>> {
+  let artist = track.artists.first().filter(|artist| !artist.name.is_empty())?;
+
+  if track.title.is_empty() {
+    return None;
+  }
+
+  let mut params = vec![param("artist", &artist.name), param("track", &track.title)];
+
+  if let Some(timestamp) = timestamp {
+    params.push(param("timestamp", &timestamp.to_string()));
+  }
+
+  if !track.album.name.is_empty() {
+    params.push(param("album", &track.album.name));
+  }
+
+  if !track.duration.is_zero() {
+    params.push(param("duration", &track.duration.as_secs().to_string()));
+  }
+
+  if let Some(number) = track.track_number {
+    params.push(param("trackNumber", &number.to_string()));
+  }
+
+  Some(params)
+}
+
+/// md5 of every parameter and its value sorted by name, then the secret
+fn sign(params: &[(String, String)], secret: &str) -> String {
+  let mut sorted = params.iter().collect::<Vec<_>>();
+  sorted.sort();
+
+  let mut text = sorted.into_iter().fold(String::new(), |text, (key, value)| text + key + value);
+  text.push_str(secret);
+
+  format!("{:x}", md5::compute(text))
+}
+
+fn param(key: &str, value: &str) -> (String, String) {
+  (key.to_string(), value.to_string())
+}
+
+fn string(value: &Value, what: &str) -> SpotifyResult<String> {
+  match value.as_str() {
+    Some(value) => Ok(value.to_string()),
+    None => Err(error(format!("answer is missing the {}", what), false)),
+  }
+}
+
+fn error(message: String, retry: bool) -> SpotifyError {
+  SpotifyError::Scrobble { service: "last.fm", message, retry }
+}
@@ -5,23 +5,271 @@
 //! More information can be found on https://github.com/Ricky12Awesome/spotify_info
 
 use std::fmt::{Display, Formatter};
-use std::io::ErrorKind;
-use std::net::SocketAddr;
-use std::time::Duration;
+#[cfg(feature = "async")]
+use std::future::Future;
+#[cfg(feature = "async")]
+use std::net::{Ipv6Addr, SocketAddr};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime};
 
-use futures_util::{SinkExt, StreamExt};
-use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::{accept_async, WebSocketStream};
-use tokio_tungstenite::tungstenite::{Error, Message};
+#[cfg(feature = "async")]
+use futures_util::{ready, SinkExt, Stream, StreamExt};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite};
+#[cfg(feature = "async")]
+use tokio::net::TcpListener;
+#[cfg(feature = "async")]
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
+#[cfg(feature = "async")]
+use tokio_tungstenite::WebSocketStream;
+#[cfg(feature = "async")]
+use tungstenite::Message;
+
+#[cfg(feature = "async")]
+pub use acceptor::SpotifyAcceptor;
+#[cfg(feature = "async")]
+pub use aggregate::Aggregation;
+#[cfg(feature = "async")]
+pub use backoff::Backoff;
+#[cfg(feature = "blocking")]
+pub use blocking::{Connection, Incoming, Listener};
+#[cfg(any(feature = "async", feature = "blocking"))]
+pub use ipnet::IpNet;
+#[cfg(feature = "async")]
+pub use builder::SpotifyListenerBuilder;
+#[cfg(feature = "http-client")]
+pub use cache::CoverCache;
+pub use clock::PlaybackClock;
+pub use codec::{Codec, EventCodec, ParseMode};
+pub use cover::{CoverArt, CoverSize};
+#[cfg(all(feature = "discord", any(unix, windows)))]
+pub use discord::DiscordPresence;
+pub use discovery::{Discovery, DiscoveryFile};
+pub use error::{SpotifyError, SpotifyResult};
+#[cfg(feature = "history")]
+pub use export::ExportFormat;
+#[cfg(feature = "http-client")]
+pub use fetch::{Image, ImageClient};
+pub use format::FormatSpec;
+#[cfg(feature = "async")]
+pub use handler::SpotifyEventHandler;
+pub use info::{ConnectionId, ConnectionInfo};
+#[cfg(feature = "async")]
+pub use intercept::Interceptor;
+#[cfg(feature = "async")]
+pub use keepalive::Keepalive;
+#[cfg(feature = "lastfm")]
+pub use lastfm::LastFm;
+#[cfg(feature = "listenbrainz")]
+pub use listenbrainz::ListenBrainz;
+pub use message::{EventMask, SpotifyMessage};
+#[cfg(feature = "async")]
+pub use mock::MockSpotifyClient;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+pub use mpris::MprisServer;
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcServer;
+#[cfg(feature = "history")]
+pub use history::{HistoryStore, Play, TopArtist, TopTrack};
+#[cfg(feature = "history")]
+pub use rusqlite;
+#[cfg(feature = "http")]
+pub use http::HttpServer;
+#[cfg(feature = "async")]
+pub use ndjson::NdjsonServer;
+#[cfg(feature = "notify")]
+pub use notify::Notifier;
+#[cfg(all(feature = "now-playing", target_os = "macos"))]
+pub use nowplaying::NowPlayingServer;
+pub use record::{EventRecorder, RecordedEvent};
+#[cfg(feature = "async")]
+pub use relay::RelayServer;
+#[cfg(feature = "async")]
+pub use replay::EventReplayer;
+#[cfg(feature = "async")]
+pub use runtime::{BoxFuture, Runtime, TokioRuntime};
+#[cfg(feature = "schemars")]
+pub use schema::schema;
+#[cfg(any(feature = "lastfm", feature = "listenbrainz"))]
+pub use scrobble::{Scrobble, ScrobbleService, Scrobbler, ServiceFuture};
+#[cfg(feature = "schemars")]
+pub use schemars;
+#[cfg(feature = "async")]
+pub use server::SpotifyServer;
+#[cfg(feature = "async")]
+pub use sink::FileSink;
+#[cfg(all(feature = "smtc", windows))]
+pub use smtc::SmtcServer;
+pub use state::{Device, PlayerState};
+#[cfg(feature = "history")]
+pub use stats::{DayStats, ListeningTime, SkipRate, Stats, StatsReport, Streaks};
+#[cfg(feature = "async")]
+pub use stream::SpotifyStream;
+pub use template::Template;
+pub use track::TrackInfoBuilder;
+#[cfg(feature = "ts-rs")]
+pub use typescript::typescript;
+pub use uri::{SpotifyUri, UriKind};
+#[cfg(feature = "tls")]
+pub use tokio_rustls::rustls;
+#[cfg(feature = "native-tls")]
+pub use tokio_native_tls::native_tls;
+
+#[cfg(feature = "async")]
+use backoff::BackoffTimer;
+#[cfg(feature = "async")]
+use intercept::Interceptors;
+#[cfg(feature = "async")]
+use keepalive::{IdleTimer, KeepaliveTimer};
+#[cfg(feature = "async")]
+use session::SessionTracker;
+#[cfg(feature = "async")]
+use throttle::ProgressThrottle;
+#[cfg(feature = "async")]
+use transport::Transport;
+
+#[cfg(feature = "async")]
+mod acceptor;
+#[cfg(feature = "async")]
+mod aggregate;
+#[cfg(feature = "async")]
+mod backoff;
+#[cfg(feature = "blocking")]
+mod blocking;
+#[cfg(feature = "async")]
+mod builder;
+#[cfg(feature = "http-client")]
+mod cache;
+mod clock;
+mod codec;
+mod cover;
+#[cfg(feature = "async")]
+mod derived;
+#[cfg(all(feature = "discord", any(unix, windows)))]
+mod discord;
+mod discovery;
+mod error;
+#[cfg(feature = "history")]
+mod export;
+#[cfg(feature = "http-client")]
+mod fetch;
+mod format;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "async")]
+mod handler;
+#[cfg(any(feature = "async", feature = "blocking"))]
+mod handshake;
+#[cfg(feature = "history")]
+mod history;
+#[cfg(feature = "http")]
+mod http;
+mod info;
+#[cfg(feature = "async")]
+mod intercept;
+#[cfg(feature = "async")]
+mod keepalive;
+#[cfg(feature = "lastfm")]
+mod lastfm;
+#[cfg(feature = "listenbrainz")]
+mod listenbrainz;
+mod message;
+#[cfg(feature = "async")]
+mod mock;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+mod mpris;
+#[cfg(feature = "async")]
+mod ndjson;
+#[cfg(feature = "notify")]
+mod notify;
+#[cfg(all(feature = "now-playing", target_os = "macos"))]
+mod nowplaying;
+mod record;
+#[cfg(feature = "async")]
+mod relay;
+#[cfg(feature = "async")]
+mod replay;
+#[cfg(feature = "async")]
+mod runtime;
+#[cfg(feature = "schemars")]
+mod schema;
+#[cfg(any(feature = "lastfm", feature = "listenbrainz"))]
+mod scrobble;
+mod serde_utils;
+#[cfg(feature = "async")]
+mod session;
+#[cfg(feature = "async")]
+mod server;
+#[cfg(feature = "async")]
+mod sink;
+#[cfg(all(feature = "smtc", windows))]
+mod smtc;
+mod state;
+#[cfg(feature = "history")]
+mod stats;
+#[cfg(feature = "async")]
+mod stream;
+mod template;
+mod track;
+#[cfg(feature = "async")]
+mod throttle;
+#[cfg(any(feature = "tls", feature = "native-tls"))]
+mod tls;
+#[cfg(feature = "async")]
+mod transport;
+#[cfg(feature = "ts-rs")]
+mod typescript;
+mod uri;
+
+/// Newest version of the protocol spoken with the extension,
+/// the extension says which version it speaks when it connects
+///
+/// - 1: progress was only a percentage
+/// - 2: progress has the position, percentage and when it was measured
+/// - 3: both ends say hello with their versions when connecting
+pub const PROTOCOL_VERSION: u32 = 3;
+
+/// Version of the JSON shape of events and messages, independent of [PROTOCOL_VERSION]
+/// which is about the handshake and what gets sent when
+///
+/// Events and messages are `{"type": "TrackChanged", "data": ...}`, fields are snake_case,
+/// durations are milliseconds, timestamps are unix milliseconds
+/// and [TrackState] and [RepeatMode] are their numbers
+///
+/// Adding fields or events doesn't change it, renaming or removing them does,
+/// the old names keep getting accepted as aliases so the extension and the crate can be updated separately
+///
+/// - 1: the current shape, camelCase names and the old `artist`, `cover` and `background` names are aliases
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Origin the spotify desktop client connects from,
+/// see [SpotifyListenerBuilder::allowed_origins]
+pub const SPOTIFY_ORIGIN: &str = "https://xpui.app.spotify.com";
+
+/// Named pipe to use with [SpotifyListener::bind_named_pipe] when there's no reason to pick another one
+#[cfg(all(windows, feature = "named-pipe"))]
+pub const DEFAULT_PIPE_NAME: &str = r"\\.\pipe\spotify_info";
+
+/// Oldest version of the protocol that's still supported,
+/// connections from older extensions fail with [SpotifyError::IncompatibleVersion]
+pub const MIN_PROTOCOL_VERSION: u32 = 3;
 
 /// The state of the track weather it's **Playing**, **Paused** or **Stopped**
 ///
 /// Default: Stopped
 #[repr(u32)]
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS), ts(type = "0 | 1 | 2"))]
 pub enum TrackState {
   Playing = 2,
   Paused = 1,
+  #[default]
   Stopped = 0,
 }
 
@@ -50,43 +298,578 @@ impl TrackState {
   }
 }
 
-impl Default for TrackState {
-  fn default() -> Self {
-    Self::Stopped
+/// Sent as its number on the wire
+impl Serialize for TrackState {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u32(*self as u32)
+  }
+}
+
+impl<'de> Deserialize<'de> for TrackState {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    u32::deserialize(deserializer).map(Self::from_u32)
+  }
+}
+
+/// The repeat mode of the player weather it's **Off**, repeating the **Context** or repeating the **Track**
+///
+/// Default: Off
+#[repr(u32)]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS), ts(type = "0 | 1 | 2"))]
+pub enum RepeatMode {
+  #[default]
+  Off = 0,
+  /// Repeats the playlist / album the track is playing from
+  Context = 1,
+  /// Repeats the current track
+  Track = 2,
+}
+
+impl Display for RepeatMode {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RepeatMode::Off => write!(f, "Off"),
+      RepeatMode::Context => write!(f, "Context"),
+      RepeatMode::Track => write!(f, "Track"),
+    }
+  }
+}
+
+impl RepeatMode {
+  /// 2 will be [Self::Track]
+  ///
+  /// 1 will be [Self::Context]
+  ///
+  /// anything else will be [Self::Off]
+  pub fn from_u32(n: u32) -> Self {
+    match n {
+      2 => Self::Track,
+      1 => Self::Context,
+      _ => Self::Off
+    }
+  }
+}
+
+/// Sent as its number on the wire, same as spicetify uses
+impl Serialize for RepeatMode {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u32(*self as u32)
+  }
+}
+
+impl<'de> Deserialize<'de> for RepeatMode {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    u32::deserialize(deserializer).map(Self::from_u32)
+  }
+}
+
+/// An artist of a track
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(from = "ArtistRepr", rename_all = "snake_case")]
+pub struct Artist {
+  /// Name of the artist
+  pub name: String,
+  /// URI of the artist, empty if it's not known (like for local files)
+  pub uri: SpotifyUri,
+}
+
+/// Older extensions only sent the name
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ArtistRepr {
+  Name(String),
+  Full { name: String, #[serde(default)] uri: SpotifyUri },
+}
+
+impl Artist {
+  /// Link to the artist on open.spotify.com, [None] if the URI isn't known
+  pub fn url(&self) -> Option<String> {
+    self.uri.to_url()
+  }
+}
+
+impl From<ArtistRepr> for Artist {
+  fn from(repr: ArtistRepr) -> Self {
+    match repr {
+      ArtistRepr::Name(name) => Self { name, uri: SpotifyUri::default() },
+      ArtistRepr::Full { name, uri } => Self { name, uri },
+    }
+  }
+}
+
+/// Cover art of an album at different sizes, each one may not exist
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub struct AlbumCovers {
+  /// Usually 64x64
+  pub small: Option<String>,
+  /// Usually 300x300
+  pub medium: Option<String>,
+  /// Usually 640x640
+  pub large: Option<String>,
+}
+
+/// The album of a track
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(from = "AlbumRepr", rename_all = "snake_case")]
+pub struct Album {
+  /// Name of the album
+  pub name: String,
+  /// URI of the album, empty if it's not known (like for local files)
+  pub uri: SpotifyUri,
+  /// Release date as spotify gives it, can be just the year (`2012`),
+  /// the month (`2012-03`) or the full date (`2012-03-14`)
+  pub release_date: Option<String>,
+  /// Cover art at different sizes
+  pub covers: AlbumCovers,
+}
+
+/// Older extensions only sent the name, local files may not have an album at all
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AlbumRepr {
+  Null,
+  Name(String),
+  Full {
+    name: String,
+    #[serde(default)]
+    uri: SpotifyUri,
+    #[serde(default, alias = "releaseDate")]
+    release_date: Option<String>,
+    #[serde(default)]
+    covers: AlbumCovers,
+  },
+}
+
+impl Album {
+  /// Link to the album on open.spotify.com, [None] if the URI isn't known
+  pub fn url(&self) -> Option<String> {
+    self.uri.to_url()
+  }
+}
+
+impl From<AlbumRepr> for Album {
+  fn from(repr: AlbumRepr) -> Self {
+    match repr {
+      AlbumRepr::Null => Self::default(),
+      AlbumRepr::Name(name) => Self { name, ..Self::default() },
+      AlbumRepr::Full { name, uri, release_date, covers } => Self { name, uri, release_date, covers },
+    }
+  }
+}
+
+/// What kind of item is playing
+///
+/// Default: Track
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+  /// A song
+  #[default]
+  Track,
+  /// A podcast episode
+  Episode,
+}
+
+/// Extra information only podcast episodes have
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub struct EpisodeInfo {
+  /// Name of the show (podcast) the episode belongs to
+  pub show: String,
+  /// URI of the show
+  #[serde(default, alias = "showUri")]
+  pub show_uri: SpotifyUri,
+  /// Publisher of the show
+  #[serde(default)]
+  pub publisher: Option<String>,
+  /// Description of the episode, plain text
+  #[serde(default)]
+  pub description: Option<String>,
+}
+
+impl EpisodeInfo {
+  /// Link to the show on open.spotify.com
+  pub fn show_url(&self) -> Option<String> {
+    self.show_uri.to_url()
   }
 }
 
 /// Stores information about the track
-#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd)]
+///
+/// Every field is optional on the wire since local files
+/// usually don't have most of them
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(default, rename_all = "snake_case")]
 pub struct TrackInfo {
   /// UID of track
   pub uid: String,
-  // URI of track
-  pub uri: String,
+  /// URI of track
+  pub uri: SpotifyUri,
   /// State of the track
   pub state: TrackState,
   /// Duration of the track
+  #[serde(with = "serde_utils::millis")]
+  #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+  #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
   pub duration: Duration,
   /// Title of the track
   pub title: String,
   /// Album of the track
-  pub album: String,
-  /// Vec since there can be multiple artists
-  pub artist: Vec<String>,
+  pub album: Album,
+  /// Vec since there can be multiple artists, in the order spotify lists them
+  #[serde(alias = "artist")]
+  pub artists: Vec<Artist>,
   /// Cover art of the track, option because it may not exist
+  #[serde(alias = "cover", alias = "coverUrl")]
   pub cover_url: Option<String>,
   /// Background art of the track, option because it may nto exist
   /// (when you hit the "full screen" thing in the bottom-right corner of spotify)
+  #[serde(alias = "background", alias = "backgroundUrl")]
   pub background_url: Option<String>,
+  /// Looping "Canvas" video of the track, option because most tracks don't have one
+  pub canvas: Option<String>,
+  /// If the track is in Liked Songs, option because older extensions don't send it
+  pub liked: Option<bool>,
+  /// Position of the track on its disc, starting at 1
+  #[serde(alias = "trackNumber")]
+  pub track_number: Option<u32>,
+  /// Disc of the album the track is on, starting at 1
+  #[serde(alias = "discNumber")]
+  pub disc_number: Option<u32>,
+  /// If the track is marked as explicit
+  pub explicit: Option<bool>,
+  /// Popularity between 0 and 100, higher is more popular
+  pub popularity: Option<u32>,
+  /// If it's a song or a podcast episode
+  pub kind: MediaKind,
+  /// If it's a local file instead of something from spotify,
+  /// local files don't have a cover, album or artist URIs
+  #[serde(alias = "isLocal")]
+  pub is_local: bool,
+  /// Only exists for podcast episodes,
+  /// [Self::album] is the show and [Self::artists] is the publisher for those
+  pub episode: Option<EpisodeInfo>,
 }
 
 impl TrackInfo {
+  /// For tests and made up events, see [TrackInfoBuilder]
+  pub fn builder() -> TrackInfoBuilder {
+    TrackInfoBuilder::default()
+  }
+
+  /// Names of all artists joined with `", "`
+  pub fn artist(&self) -> String {
+    self.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>().join(", ")
+  }
+
+  /// Best cover to show, falls back to the album covers
+  /// and then the background if there's no cover,
+  /// [None] if nothing exists (usually for local files)
+  pub fn cover(&self) -> Option<&str> {
+    let covers = &self.album.covers;
+
+    self.cover_url.as_ref()
+      .or(covers.large.as_ref())
+      .or(covers.medium.as_ref())
+      .or(covers.small.as_ref())
+      .or(self.background_url.as_ref())
+      .map(String::as_str)
+  }
+
+  /// Link to the track (or episode) on open.spotify.com for "copy link" buttons,
+  /// [None] for local files
+  pub fn url(&self) -> Option<String> {
+    self.uri.to_url()
+  }
+
+  /// Link to the album, or the show for podcast episodes
+  pub fn album_url(&self) -> Option<String> {
+    match &self.episode {
+      Some(episode) => episode.show_url(),
+      None => self.album.url(),
+    }
+  }
+
+  /// Link to the first artist, see [Artist::url] for the others
+  pub fn artist_url(&self) -> Option<String> {
+    self.artists.first().and_then(Artist::url)
+  }
+
+  /// [Self::cover] as a [CoverArt] to get it at other sizes,
+  /// [None] if there's no cover or it isn't from spotify's CDN
+  pub fn cover_art(&self) -> Option<CoverArt> {
+    self.cover().and_then(CoverArt::parse)
+  }
+
+  /// Downloads [Self::cover] with the default [ImageClient], [None] if there's no cover,
+  /// only with the `http-client` feature
+  #[cfg(feature = "http-client")]
+  pub async fn fetch_cover(&self) -> SpotifyResult<Option<Image>> {
+    fetch::shared().fetch_cover(self).await
+  }
+
+  /// Downloads [Self::background_url] with the default [ImageClient], [None] if there's no background,
+  /// only with the `http-client` feature
+  #[cfg(feature = "http-client")]
+  pub async fn fetch_background(&self) -> SpotifyResult<Option<Image>> {
+    fetch::shared().fetch_background(self).await
+  }
+
+  /// If it's a podcast episode instead of a song
+  pub fn is_episode(&self) -> bool {
+    self.kind == MediaKind::Episode
+  }
+
   pub fn eq_ignore_state(&self, other: &Self) -> bool {
     self.uid == other.uid
   }
 }
 
-#[derive(Debug)]
+/// Shared handle to the latest [TrackInfo],
+/// cheap to clone so multiple threads can read the current track
+/// without owning the connection
+#[derive(Debug, Clone, Default)]
+pub struct TrackHandle {
+  inner: Arc<RwLock<TrackInfo>>,
+}
+
+impl TrackHandle {
+  /// Locks the handle for reading, blocks while it's being updated
+  pub fn read(&self) -> RwLockReadGuard<'_, TrackInfo> {
+    self.inner.read().unwrap_or_else(PoisonError::into_inner)
+  }
+
+  /// Updates the stored track info with the given event
+  ///
+  /// **NOTE**: Only [SpotifyEvent::TrackChanged], [SpotifyEvent::StateChanged],
+  /// [SpotifyEvent::LikedChanged] and [SpotifyEvent::StateSnapshot] change anything
+  pub fn update(&self, event: &SpotifyEvent) {
+    let mut info = self.inner.write().unwrap_or_else(PoisonError::into_inner);
+
+    match event {
+      SpotifyEvent::TrackChanged(new) => *info = new.clone(),
+      SpotifyEvent::StateChanged(state) => info.state = *state,
+      SpotifyEvent::LikedChanged(liked) => info.liked = Some(*liked),
+      SpotifyEvent::StateSnapshot(snapshot) => {
+        if let Some(new) = &snapshot.track {
+          *info = new.clone();
+        }
+
+        info.state = snapshot.state;
+      }
+      _ => {}
+    }
+  }
+}
+
+/// What kind of thing the track is playing from
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum ContextKind {
+  Playlist,
+  Album,
+  Artist,
+  /// A podcast
+  Show,
+  /// Liked Songs
+  Collection,
+  /// Anything else, like search results or a single track
+  #[default]
+  #[serde(other)]
+  Other,
+}
+
+/// The playlist, album, artist, etc. the track is playing from
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub struct PlaybackContext {
+  pub kind: ContextKind,
+  /// URI of the context
+  pub uri: SpotifyUri,
+  /// Name of the context, like "Discover Weekly", may not exist
+  #[serde(default)]
+  pub name: Option<String>,
+}
+
+impl PlaybackContext {
+  /// Link to the playlist, album, etc. on open.spotify.com, [None] for contexts that don't have one
+  pub fn url(&self) -> Option<String> {
+    self.uri.to_url()
+  }
+}
+
+/// Everything about the player at the time it was requested
+/// with [SpotifyConnection::request_state]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub struct PlayerSnapshot {
+  /// Current track, [None] if nothing has played yet
+  pub track: Option<TrackInfo>,
+  /// State of the player
+  pub state: TrackState,
+  /// Position in the current track
+  #[serde(with = "serde_utils::millis")]
+  #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+  #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
+  pub position: Duration,
+  /// Volume between 0 and 1
+  pub volume: f64,
+  /// If shuffle is on
+  pub shuffle: bool,
+  /// Repeat mode
+  pub repeat: RepeatMode,
+  /// How fast it's playing, 1 is normal speed, only podcasts can change it
+  #[serde(default = "default_playback_rate", alias = "playbackRate")]
+  pub playback_rate: f32,
+  /// What the track is playing from
+  #[serde(default)]
+  pub context: Option<PlaybackContext>,
+}
+
+fn default_playback_rate() -> f32 {
+  1.0
+}
+
+/// Fastest rate that's believed, spotify itself only goes up to 3.5
+const MAX_PLAYBACK_RATE: f32 = 4.0;
+
+/// Keeps rates from the socket between 0 and [MAX_PLAYBACK_RATE], anything that isn't a number counts as normal speed
+pub(crate) fn clamp_playback_rate(rate: f32) -> f32 {
+  match rate.is_finite() {
+    true => rate.clamp(0.0, MAX_PLAYBACK_RATE),
+    false => default_playback_rate(),
+  }
+}
+
+/// How far `elapsed` gets at `rate`, saturates instead of panicking like [Duration::mul_f32]
+pub(crate) fn scale_by_rate(elapsed: Duration, rate: f32) -> Duration {
+  Duration::try_from_secs_f64(elapsed.as_secs_f64() * clamp_playback_rate(rate) as f64).unwrap_or(Duration::MAX)
+}
+
+/// About 30 years, same as what tokio's sleep clamps to, waiting any longer is as good as never
+pub(crate) const FAR_FUTURE: Duration = Duration::from_secs(30 * 365 * 24 * 60 * 60);
+
+/// `Instant::now() + after` without panicking, anything past [FAR_FUTURE] is [FAR_FUTURE] from now
+pub(crate) fn instant_after(after: Duration) -> Instant {
+  Instant::now() + after.min(FAR_FUTURE)
+}
+
+impl Default for PlayerSnapshot {
+  fn default() -> Self {
+    Self {
+      track: None,
+      state: TrackState::default(),
+      position: Duration::ZERO,
+      volume: 0.0,
+      shuffle: false,
+      repeat: RepeatMode::default(),
+      playback_rate: default_playback_rate(),
+      context: None,
+    }
+  }
+}
+
+impl PlayerSnapshot {
+  /// Updates the snapshot with the given event,
+  /// so it can be kept up to date without requesting it again
+  ///
+  /// The position only changes with [SpotifyEvent::ProgressChanged], use [PlaybackClock] for anything smoother
+  pub fn apply(&mut self, event: &SpotifyEvent) {
+    match event {
+      SpotifyEvent::TrackChanged(info) => {
+        self.state = info.state;
+        self.position = Duration::ZERO;
+        self.track = Some(info.clone());
+      }
+      SpotifyEvent::StateChanged(state) => {
+        self.state = *state;
+
+        if let Some(track) = &mut self.track {
+          track.state = *state;
+        }
+      }
+      SpotifyEvent::ProgressChanged { position, .. } => self.position = *position,
+      SpotifyEvent::VolumeChanged(volume) => self.volume = *volume,
+      SpotifyEvent::StateSnapshot(snapshot) => {
+        *self = snapshot.clone();
+        self.playback_rate = clamp_playback_rate(self.playback_rate);
+      }
+      SpotifyEvent::PlaybackRateChanged(rate) => self.playback_rate = clamp_playback_rate(*rate),
+      SpotifyEvent::ShuffleChanged(shuffle) => self.shuffle = *shuffle,
+      SpotifyEvent::RepeatChanged(repeat) => self.repeat = *repeat,
+      SpotifyEvent::ContextChanged(context) => self.context = Some(context.clone()),
+      SpotifyEvent::LikedChanged(liked) => {
+        if let Some(track) = &mut self.track {
+          track.liked = Some(*liked);
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+/// A single line of time-synced lyrics
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub struct LyricLine {
+  /// When the line starts in the track
+  #[serde(with = "serde_utils::millis")]
+  #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+  #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
+  pub start: Duration,
+  /// Text of the line, empty for instrumental breaks
+  pub text: String,
+}
+
+/// Colors spicetify extracted from the cover art, as hex strings like `#1db954`,
+/// each one may not exist
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(default, rename_all = "snake_case")]
+pub struct CoverColors {
+  pub vibrant: Option<String>,
+  #[serde(alias = "darkVibrant")]
+  pub dark_vibrant: Option<String>,
+  #[serde(alias = "lightVibrant")]
+  pub light_vibrant: Option<String>,
+  pub prominent: Option<String>,
+  pub desaturated: Option<String>,
+  /// Vibrant but less saturated, good for backgrounds behind text
+  #[serde(alias = "vibrantNonAlarming")]
+  pub vibrant_non_alarming: Option<String>,
+}
+
+/// Events sent by the spotify extension
+///
+/// New events can be added at any time, so matching on this always needs a `_` arm
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(tag = "type", content = "data", rename_all = "PascalCase", rename_all_fields = "snake_case")]
+#[non_exhaustive]
 pub enum SpotifyEvent {
   /// Gets called when user changes track
   TrackChanged(TrackInfo),
@@ -94,110 +877,1204 @@ pub enum SpotifyEvent {
   ///
   /// **NOTE**: Doesn't get called when user changes track
   StateChanged(TrackState),
-  /// Gets called on a set interval, wont get called if player is paused or stopped,
-  /// Value is a percentage of the position between 0 and 1
+  /// Gets called on a set interval, wont get called if player is paused or stopped
   ///
   /// **NOTE**: Doesn't get called when user changes track
-  ProgressChanged(f64),
+  ProgressChanged {
+    /// Position in the current track
+    #[serde(with = "serde_utils::millis")]
+    #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+    #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
+    position: Duration,
+    /// Percentage of the position between 0 and 1
+    percent: f64,
+    /// When the position was measured by the extension
+    #[serde(with = "serde_utils::timestamp_millis")]
+    #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+    #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
+    timestamp: SystemTime,
+  },
+  /// Gets called when user changes the volume, value is between 0 and 1
+  VolumeChanged(f64),
+  /// Response to [SpotifyMessage::RequestState] with everything about the player
+  StateSnapshot(PlayerSnapshot),
+  /// Gets called when the playback speed changes, 1 is normal speed,
+  /// only podcasts can be played at a different speed (0.5 to 3.5)
+  PlaybackRateChanged(f32),
+  /// Gets called when user turns shuffle on or off
+  ShuffleChanged(bool),
+  /// Gets called when user changes the repeat mode
+  RepeatChanged(RepeatMode),
+  /// Gets called when playback moves to another device with Spotify Connect
+  DeviceChanged {
+    /// Name of the device, like "Kitchen Speaker"
+    name: String,
+    /// ID of the device
+    id: String,
+    /// If it's the spotify client the extension runs in
+    #[serde(alias = "isLocal")]
+    is_local: bool,
+    /// Volume of the device between 0 and 1
+    volume: f64,
+  },
+  /// Gets called when the track starts playing from a different playlist, album, artist, etc.
+  ContextChanged(PlaybackContext),
+  /// Gets called after the track changes with time-synced lyrics of the new track,
+  /// empty if the track doesn't have any
+  LyricsChanged(Vec<LyricLine>),
+  /// Gets called when the current line of the lyrics changes,
+  /// value is the index of the line in the last [SpotifyEvent::LyricsChanged]
+  LyricLineChanged(usize),
+  /// Gets called after the track changes with colors extracted from the new cover art
+  ColorsChanged(CoverColors),
+  /// Gets called when user likes or unlikes the current track
+  ///
+  /// **NOTE**: Doesn't get called when user changes track, use [TrackInfo::liked] for that
+  LikedChanged(bool),
+  /// Made by the listener with [SpotifyListenerBuilder::track_lifecycle], not the extension,
+  /// the track played until the end (or close to it), comes right before the next [SpotifyEvent::TrackChanged]
+  TrackFinished(TrackInfo),
+  /// Made by the listener with [SpotifyListenerBuilder::track_lifecycle], not the extension,
+  /// the track changed before it got to the end, comes right before the next [SpotifyEvent::TrackChanged]
+  TrackSkipped {
+    /// The track that got skipped
+    track: TrackInfo,
+    /// How far into the track it got skipped
+    #[serde(with = "serde_utils::millis")]
+    #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+    #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
+    at: Duration,
+  },
+  /// Made by the listener with [SpotifyListenerBuilder::scrobble_points], not the extension,
+  /// the track has been played for long enough to scrobble it, at most once per play of a track
+  ScrobblePoint(TrackInfo),
+  /// Made by the listener with [SpotifyListenerBuilder::session_gap], not the extension,
+  /// something started playing after nothing played for a while (or ever)
+  SessionStarted,
+  /// Made by the listener with [SpotifyListenerBuilder::session_gap], not the extension,
+  /// nothing played for the gap or the connection ended
+  SessionEnded {
+    /// From when the session started to when playback stopped, without the gap
+    #[serde(with = "serde_utils::millis")]
+    #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+    #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
+    duration: Duration,
+    /// How many different tracks played in the session
+    #[serde(alias = "trackCount")]
+    track_count: usize,
+  },
+  /// An event this version of the crate doesn't know about,
+  /// usually because the extension is newer than the crate
+  ///
+  /// Not part of the JSON Schema from the `schemars` feature since it's whatever isn't in it
+  #[serde(untagged)]
+  #[cfg_attr(feature = "schemars", schemars(skip))]
+  #[cfg_attr(feature = "ts-rs", ts(skip))]
+  Unknown {
+    /// Type of the event
+    #[serde(rename = "type")]
+    kind: String,
+    /// Whatever data came with the event
+    #[serde(rename = "data", default)]
+    payload: serde_json::Value,
+  },
 }
 
-pub struct SpotifyListener {
-  pub listener: TcpListener,
+impl SpotifyEvent {
+  /// Types of every event this version of the crate knows about,
+  /// what [SpotifyEvent::kind] returns for anything that isn't [SpotifyEvent::Unknown]
+  pub const KINDS: &'static [&'static str] = &[
+    "TrackChanged",
+    "StateChanged",
+    "ProgressChanged",
+    "VolumeChanged",
+    "StateSnapshot",
+    "PlaybackRateChanged",
+    "ShuffleChanged",
+    "RepeatChanged",
+    "DeviceChanged",
+    "ContextChanged",
+    "LyricsChanged",
+    "LyricLineChanged",
+    "ColorsChanged",
+    "LikedChanged",
+    "TrackFinished",
+    "TrackSkipped",
+    "ScrobblePoint",
+    "SessionStarted",
+    "SessionEnded",
+  ];
+
+  /// Type of the event, same as it's sent on the wire
+  pub fn kind(&self) -> &str {
+    match self {
+      SpotifyEvent::TrackChanged(_) => "TrackChanged",
+      SpotifyEvent::StateChanged(_) => "StateChanged",
+      SpotifyEvent::ProgressChanged { .. } => "ProgressChanged",
+      SpotifyEvent::VolumeChanged(_) => "VolumeChanged",
+      SpotifyEvent::StateSnapshot(_) => "StateSnapshot",
+      SpotifyEvent::PlaybackRateChanged(_) => "PlaybackRateChanged",
+      SpotifyEvent::ShuffleChanged(_) => "ShuffleChanged",
+      SpotifyEvent::RepeatChanged(_) => "RepeatChanged",
+      SpotifyEvent::DeviceChanged { .. } => "DeviceChanged",
+      SpotifyEvent::ContextChanged(_) => "ContextChanged",
+      SpotifyEvent::LyricsChanged(_) => "LyricsChanged",
+      SpotifyEvent::LyricLineChanged(_) => "LyricLineChanged",
+      SpotifyEvent::ColorsChanged(_) => "ColorsChanged",
+      SpotifyEvent::LikedChanged(_) => "LikedChanged",
+      SpotifyEvent::TrackFinished(_) => "TrackFinished",
+      SpotifyEvent::TrackSkipped { .. } => "TrackSkipped",
+      SpotifyEvent::ScrobblePoint(_) => "ScrobblePoint",
+      SpotifyEvent::SessionStarted => "SessionStarted",
+      SpotifyEvent::SessionEnded { .. } => "SessionEnded",
+      SpotifyEvent::Unknown { kind, .. } => kind,
+    }
+  }
+
+  /// If it's made by the listener instead of sent by the extension, like [SpotifyEvent::TrackFinished]
+  pub fn is_from_listener(&self) -> bool {
+    matches!(
+      self,
+      SpotifyEvent::TrackFinished(_)
+        | SpotifyEvent::TrackSkipped { .. }
+        | SpotifyEvent::ScrobblePoint(_)
+        | SpotifyEvent::SessionStarted
+        | SpotifyEvent::SessionEnded { .. }
+    )
+  }
 }
 
+/// Why a connection to spotify ended
 #[derive(Debug)]
-pub struct SpotifyConnection {
-  pub ws: WebSocketStream<TcpStream>,
+#[non_exhaustive]
+pub enum DisconnectReason {
+  /// The extension closed the connection, usually because spotify closed
+  Closed,
+  /// The extension stopped answering pings, see [Keepalive]
+  Timeout,
+  /// The extension didn't send anything for too long,
+  /// see [SpotifyListenerBuilder::idle_timeout]
+  Idle,
+  /// The connection broke
+  Error(SpotifyError),
 }
 
-impl SpotifyConnection {
-  fn parse_track_info(data: &[&str]) -> TrackInfo {
-    TrackInfo {
-      uid: data[0].to_string(),
-      uri: data[1].to_string(),
-      state: TrackState::from_u32(data[2].parse().unwrap_or(0)),
-      duration: Duration::from_millis(data[3].parse().unwrap_or(0)),
-      title: data[4].to_string().replace("${#{#{SEMI_COLON}#}#}$", ";"),
-      album: data[5].to_string().replace("${#{#{SEMI_COLON}#}#}$", ";"),
-      artist: vec![data[6].to_string().replace("${#{#{SEMI_COLON}#}#}$", ";")],
-      cover_url: Some(data[7].to_string()).filter(|it| !it.contains("NONE")),
-      background_url: Some(data[8].to_string()).filter(|it| !it.contains("NONE")),
+impl Display for DisconnectReason {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      DisconnectReason::Closed => write!(f, "connection closed"),
+      DisconnectReason::Timeout => write!(f, "connection timed out"),
+      DisconnectReason::Idle => write!(f, "connection was idle for too long"),
+      DisconnectReason::Error(err) => write!(f, "{}", err),
     }
   }
+}
 
-  fn handle_message(message: String) -> Option<Result<SpotifyEvent, Error>> {
-    let mut data = message.split(';').collect::<Vec<_>>();
-    let invalid_data_err = Some(Err(Error::Io(std::io::Error::new(ErrorKind::InvalidData, "Invalid data"))));
+#[cfg(feature = "async")]
+impl DisconnectReason {
+  /// Why a connection ended going by the last thing it yielded,
+  /// only called once it wasn't an event or an error the connection gets past
+  fn from_next<T>(next: Option<SpotifyResult<T>>) -> Self {
+    match next {
+      Some(Err(SpotifyError::Timeout)) => DisconnectReason::Timeout,
+      Some(Err(SpotifyError::Idle)) => DisconnectReason::Idle,
+      Some(Err(err)) => DisconnectReason::Error(err),
+      Some(Ok(_)) | None => DisconnectReason::Closed,
+    }
+  }
+}
+
+/// Events from [SpotifyListener::connection_events],
+/// which includes when spotify connects and disconnects along with every [SpotifyEvent]
+#[derive(Debug)]
+// most of these are events, boxing them would only add an allocation
+#[allow(clippy::large_enum_variant)]
+pub enum ConnectionEvent {
+  /// Spotify connected
+  Connected,
+  /// Spotify disconnected, the listener is waiting for it to connect again
+  Disconnected { reason: DisconnectReason },
+  /// Spotify sent an event
+  Event(SpotifyEvent),
+}
+
+/// Items of [SpotifyListener::states], what the listener is doing right now
+/// along with every event while spotify is connected
+#[derive(Debug)]
+// most of these are events, boxing them would only add an allocation
+#[allow(clippy::large_enum_variant)]
+pub enum ListenerState {
+  /// Nothing is connected, the listener is waiting for spotify
+  WaitingForSpotify,
+  /// Spotify connected
+  Connected(ConnectionInfo),
+  /// Spotify sent an event, isn't a change of state
+  Event(SpotifyEvent),
+  /// Spotify disconnected, [Self::WaitingForSpotify] comes right after
+  Lost(DisconnectReason),
+}
+
+#[cfg(feature = "async")]
+/// A message for the connection behind [SpotifyEvents] and where to send how it went
+type Command = (SpotifyMessage, oneshot::Sender<SpotifyResult<()>>);
+
+#[cfg(feature = "async")]
+/// Fans out every event from the listener to any number of independent receivers,
+/// cheap to clone, created with [SpotifyListener::events]
+#[derive(Debug, Clone)]
+pub struct SpotifyEvents {
+  sender: broadcast::Sender<SpotifyEvent>,
+  commands: mpsc::UnboundedSender<Command>,
+}
+
+#[cfg(feature = "async")]
+impl SpotifyEvents {
+  /// Creates a new receiver that gets every event sent after this call
+  ///
+  /// If a receiver falls behind by more than the capacity,
+  /// it gets [broadcast::error::RecvError::Lagged] and skips the oldest events
+  pub fn subscribe(&self) -> broadcast::Receiver<SpotifyEvent> {
+    self.sender.subscribe()
+  }
 
-    if data.is_empty() {
-      return invalid_data_err;
+  /// [Self::subscribe] without holding on to the sender, so the receiver gets
+  /// [broadcast::error::RecvError::Closed] once the listener and every other clone are gone
+  pub(crate) fn into_receiver(self) -> broadcast::Receiver<SpotifyEvent> {
+    self.sender.subscribe()
+  }
+
+  /// How many receivers are currently subscribed
+  pub fn receiver_count(&self) -> usize {
+    self.sender.receiver_count()
+  }
+
+  /// Sends a message to spotify through the listener's current connection,
+  /// fails with [SpotifyError::NotConnected] if spotify isn't connected right now
+  pub async fn send(&self, message: SpotifyMessage) -> SpotifyResult<()> {
+    let (reply, result) = oneshot::channel();
+
+    self.commands.send((message, reply)).map_err(|_| SpotifyError::NotConnected)?;
+
+    result.await.unwrap_or(Err(SpotifyError::NotConnected))
+  }
+}
+
+#[cfg(feature = "async")]
+/// Keeps `snapshot` up to date with every event until the sender goes away,
+/// for relays that need the current state whenever someone asks
+async fn track_snapshot(mut events: broadcast::Receiver<SpotifyEvent>, snapshot: Arc<RwLock<PlayerSnapshot>>) {
+  loop {
+    match events.recv().await {
+      Ok(event) => snapshot.write().unwrap_or_else(PoisonError::into_inner).apply(&event),
+      // whatever got skipped is lost, the next events fix most of it
+      Err(broadcast::error::RecvError::Lagged(_)) => continue,
+      Err(broadcast::error::RecvError::Closed) => return,
     }
+  }
+}
 
-    match data.remove(0) {
-      "TRACK_CHANGED" if data.len() >= 9 => {
-        let info = Self::parse_track_info(&data);
+#[cfg(feature = "async")]
+pub struct SpotifyListener {
+  transport: Transport,
+  acceptor: Arc<SpotifyAcceptor>,
+  backoff: Backoff,
+  discovery: Option<DiscoveryFile>,
+  /// Every accepted stream does its handshake in its own task and sends the result here
+  finished: mpsc::UnboundedSender<SpotifyResult<SpotifyConnection>>,
+  handshakes: tokio::sync::Mutex<mpsc::UnboundedReceiver<SpotifyResult<SpotifyConnection>>>,
+}
 
-        Some(Ok(SpotifyEvent::TrackChanged(info)))
-      }
-      "STATE_CHANGED" if !data.is_empty() => {
-        let state = TrackState::from_u32(data[0].parse().unwrap_or(0));
+#[cfg(feature = "async")]
+/// One extension connected to the listener, `S` is only something other than [SpotifyStream]
+/// for streams from [SpotifyAcceptor::accept]
+#[derive(Debug)]
+pub struct SpotifyConnection<C = Codec, S = SpotifyStream> {
+  pub ws: WebSocketStream<S>,
+  info: ConnectionInfo,
+  codec: C,
+  parse_mode: ParseMode,
+  keepalive: Option<KeepaliveTimer>,
+  idle: Option<IdleTimer>,
+  interceptors: Interceptors,
+  throttle: Option<ProgressThrottle>,
+  sessions: Option<SessionTracker>,
+  _slot: ConnectionSlot,
+}
 
-        Some(Ok(SpotifyEvent::StateChanged(state)))
-      }
-      "PROGRESS_CHANGED" if !data.is_empty() => {
-        let progress = data[0].parse().unwrap_or(0f64);
+/// Counts towards the listener's connection count until it's dropped
+#[derive(Debug)]
+struct ConnectionSlot(Arc<AtomicUsize>);
 
-        Some(Ok(SpotifyEvent::ProgressChanged(progress)))
-      }
-      _ => invalid_data_err
+impl ConnectionSlot {
+  /// [None] when there are already `max` connections
+  fn reserve(connections: &Arc<AtomicUsize>, max: Option<usize>) -> Option<Self> {
+    let max = max.unwrap_or(usize::MAX);
+
+    connections
+      .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| (count < max).then_some(count + 1))
+      .ok()
+      .map(|_| ConnectionSlot(connections.clone()))
+  }
+}
+
+impl Drop for ConnectionSlot {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::AcqRel);
+  }
+}
+
+#[cfg(feature = "async")]
+impl<C: EventCodec + Unpin, S: AsyncRead + AsyncWrite + Unpin> SpotifyConnection<C, S> {
+  /// Protocol version the extension said it speaks when it connected
+  pub fn protocol_version(&self) -> u32 {
+    self.info.protocol_version
+  }
+
+  /// Id of the connection and what it said about itself when it connected
+  pub fn info(&self) -> &ConnectionInfo {
+    &self.info
+  }
+
+  /// Format everything after the hello is sent in, whatever the extension asked for
+  /// unless it got replaced with [Self::with_codec]
+  pub fn codec(&self) -> &C {
+    &self.codec
+  }
+
+  /// Uses a different codec from now on,
+  /// the extension has to agree on it some other way since the hello only knows about [Codec]
+  pub fn with_codec<T: EventCodec>(self, codec: T) -> SpotifyConnection<T, S> {
+    SpotifyConnection {
+      ws: self.ws,
+      info: self.info,
+      codec,
+      parse_mode: self.parse_mode,
+      keepalive: self.keepalive,
+      idle: self.idle,
+      interceptors: self.interceptors,
+      throttle: self.throttle,
+      sessions: self.sessions,
+      _slot: self._slot,
     }
   }
 
+  /// Decodes events with `mode` from now on instead of [SpotifyListenerBuilder::parse_mode]
+  pub fn with_parse_mode(mut self, mode: ParseMode) -> Self {
+    self.parse_mode = mode;
+    self
+  }
+
+  /// Adds an [Interceptor] after the ones from [SpotifyListenerBuilder::interceptor],
+  /// only for this connection
+  pub fn with_interceptor(mut self, interceptor: impl Interceptor) -> Self {
+    self.interceptors.push(Box::new(interceptor));
+    self
+  }
+
+  /// Sends a message to the spotify extension,
+  /// goes through every [Interceptor] first
+  pub async fn send(&mut self, message: SpotifyMessage) -> SpotifyResult<()> {
+    let message = match self.interceptors.message(message) {
+      Some(message) => message,
+      None => return Ok(()),
+    };
+    let message = self.codec.encode(&message)?;
+
+    Ok(self.ws.send(message).await?)
+  }
+
   /// Sets how often it should update the progress,
   ///
   /// by default it's set to 1 second
-  pub async fn set_progress_interval(&mut self, interval: Duration) -> Result<(), Error> {
-    let ms = interval.as_millis();
-    let text = format!("SET_PROGRESS_INTERVAL;{}", ms);
+  pub async fn set_progress_interval(&mut self, interval: Duration) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::SetProgressUpdateInterval(interval)).await
+  }
+
+  /// Resumes playback
+  pub async fn play(&mut self) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::Play).await
+  }
+
+  /// Pauses playback
+  pub async fn pause(&mut self) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::Pause).await
+  }
+
+  /// Pauses if playing, resumes if paused
+  pub async fn toggle_playback(&mut self) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::TogglePlayback).await
+  }
+
+  /// Skips to the next track
+  pub async fn skip_next(&mut self) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::Next).await
+  }
+
+  /// Goes back to the previous track
+  pub async fn skip_previous(&mut self) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::Previous).await
+  }
+
+  /// Seeks to an absolute position in the current track
+  pub async fn seek(&mut self, position: Duration) -> SpotifyResult<()> {
+    let position_ms = position.as_millis() as u64;
+
+    self.send(SpotifyMessage::Seek { position_ms }).await
+  }
+
+  /// Seeks to a percentage of the current track,
+  /// gets clamped between 0 and 1
+  pub async fn seek_percent(&mut self, percent: f64) -> SpotifyResult<()> {
+    let percent = percent.clamp(0.0, 1.0);
+
+    self.send(SpotifyMessage::SeekPercent { percent }).await
+  }
+
+  /// Sets the volume, gets clamped between 0 and 1
+  pub async fn set_volume(&mut self, volume: f64) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::SetVolume(volume.clamp(0.0, 1.0))).await
+  }
+
+  /// Turns shuffle on or off
+  pub async fn set_shuffle(&mut self, shuffle: bool) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::SetShuffle(shuffle)).await
+  }
+
+  /// Sets the repeat mode
+  pub async fn set_repeat(&mut self, mode: RepeatMode) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::SetRepeat(mode)).await
+  }
+
+  /// Likes (adds to Liked Songs) or unlikes the current track
+  pub async fn set_liked(&mut self, liked: bool) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::SetLiked(liked)).await
+  }
+
+  /// Asks the extension for everything about the player,
+  /// it responds with [SpotifyEvent::StateSnapshot]
+  ///
+  /// Useful right after connecting, since nothing gets sent until something changes
+  pub async fn request_state(&mut self) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::RequestState).await
+  }
+
+  /// Tells the extension to only send the given events,
+  /// for example `EventMask::TRACK_CHANGED | EventMask::STATE_CHANGED`
+  /// stops progress events from being sent at all
+  pub async fn set_subscriptions(&mut self, mask: EventMask) -> SpotifyResult<()> {
+    self.send(SpotifyMessage::SetSubscriptions(mask)).await
+  }
 
-    self.ws.send(Message::Text(text)).await
+  /// Likes (adds to Liked Songs) the current track, same as `set_liked(true)`
+  pub async fn like_current(&mut self) -> SpotifyResult<()> {
+    self.set_liked(true).await
   }
 
-  /// Waits for the next message to be received
-  pub async fn next(&mut self) -> Option<Result<SpotifyEvent, Error>> {
-    let message = self.ws.next().await?;
+  /// Waits for the next message to be received,
+  /// same as calling [StreamExt::next] on the connection
+  pub async fn next(&mut self) -> Option<SpotifyResult<SpotifyEvent>> {
+    StreamExt::next(self).await
+  }
+}
+
+#[cfg(feature = "async")]
+/// Yields every event received from the spotify extension,
+/// ends when the websocket connection closes
+///
+/// With a [Keepalive] it yields [SpotifyError::Timeout] once
+/// when the extension stops answering, then it ends,
+/// same with [SpotifyError::Idle] and [SpotifyListenerBuilder::idle_timeout]
+///
+/// Events go through every [Interceptor] before they're yielded,
+/// and then [SpotifyListenerBuilder::progress_throttle] if it's set,
+/// with [SpotifyListenerBuilder::session_gap] it yields [SpotifyEvent::SessionEnded] before it ends
+impl<C: EventCodec + Unpin, S: AsyncRead + AsyncWrite + Unpin> Stream for SpotifyConnection<C, S> {
+  type Item = SpotifyResult<SpotifyEvent>;
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    let this = &mut *self;
+
+    loop {
+      while let Some(event) = this.interceptors.next() {
+        if let Some(sessions) = &mut this.sessions {
+          sessions.ingest(&event);
+        }
+
+        match &mut this.throttle {
+          Some(throttle) => match throttle.filter(event) {
+            Some(event) => return Poll::Ready(Some(Ok(event))),
+            None => continue,
+          },
+          None => return Poll::Ready(Some(Ok(event))),
+        }
+      }
+
+      if let Some(Poll::Ready(event)) = this.sessions.as_mut().map(|sessions| sessions.poll(cx)) {
+        return Poll::Ready(Some(Ok(event)));
+      }
+
+      if let Some(Poll::Ready(event)) = this.throttle.as_mut().map(|throttle| throttle.poll(cx)) {
+        return Poll::Ready(Some(Ok(event)));
+      }
+
+      if this.keepalive.as_ref().is_some_and(KeepaliveTimer::timed_out) || this.idle.as_ref().is_some_and(IdleTimer::expired) {
+        return Poll::Ready(this.sessions.as_mut().and_then(SessionTracker::end).map(Ok));
+      }
+
+      match this.ws.poll_next_unpin(cx) {
+        Poll::Ready(Some(Ok(message))) => {
+          if let Some(keepalive) = &mut this.keepalive {
+            keepalive.reset();
+          }
+
+          if let (Some(idle), Message::Text(_) | Message::Binary(_)) = (&mut this.idle, &message) {
+            idle.reset();
+          }
+
+          match codec::decode_event(&this.codec, message, this.parse_mode) {
+            Some(Ok(event)) => this.interceptors.event(event),
+            Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+            None => {}
+          }
+
+          continue;
+        }
+        Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err.into()))),
+        // the session can't go on without the connection
+        Poll::Ready(None) => return Poll::Ready(this.sessions.as_mut().and_then(SessionTracker::end).map(Ok)),
+        Poll::Pending => {}
+      }
+
+      if let Some(Poll::Ready(())) = this.idle.as_mut().map(|idle| idle.poll(cx)) {
+        // starts the close handshake, the socket closes for good when the connection is dropped
+        let _ = this.ws.poll_close_unpin(cx);
+        return Poll::Ready(Some(Err(SpotifyError::Idle)));
+      }
+
+      let keepalive = match &mut this.keepalive {
+        Some(keepalive) => keepalive,
+        None => return Poll::Pending,
+      };
+
+      if !ready!(keepalive.poll(cx)) {
+        return Poll::Ready(Some(Err(SpotifyError::Timeout)));
+      }
+
+      // if the sink is busy there's already something on the way, which works just as well
+      if let Poll::Ready(Ok(())) = this.ws.poll_ready_unpin(cx) {
+        if let Err(err) = this.ws.start_send_unpin(Message::Ping(Vec::new())) {
+          return Poll::Ready(Some(Err(err.into())));
+        }
 
-    match message {
-      Ok(Message::Text(message)) => Self::handle_message(message),
-      Ok(_) => Some(Err(Error::Io(std::io::Error::new(ErrorKind::Unsupported, "Unsupported message type, only supports Text")))),
-      Err(err) => Some(Err(err))
+        // anything left over gets flushed by the next read
+        let _ = this.ws.poll_flush_unpin(cx);
+      }
     }
   }
 }
 
+#[cfg(feature = "async")]
 impl SpotifyListener {
-  /// Binds to 127.0.0.1:19532
-  pub async fn bind_default() -> std::io::Result<Self> {
-    Self::bind_local(19532).await
+  /// For anything other than the address, like [Keepalive]
+  pub fn builder() -> SpotifyListenerBuilder {
+    SpotifyListenerBuilder::new()
   }
 
-  /// Binds to 127.0.0.1 with a custom port
-  pub async fn bind_local(port: u16) -> std::io::Result<Self> {
-    Self::bind(format!("127.0.0.1:{}", port).parse().unwrap()).await
+  /// Binds to 127.0.0.1:19532, or \[::1\]:19532 on machines without IPv4
+  pub async fn bind_default() -> SpotifyResult<Self> {
+    Self::builder().bind().await
   }
 
-  /// Binds to the given address, same as calling [TcpListener::bind(addr)]
-  pub async fn bind(addr: SocketAddr) -> std::io::Result<Self> {
-    let listener = TcpListener::bind(addr).await?;
+  /// Binds to 127.0.0.1 with a custom port, or \[::1\] on machines without IPv4
+  pub async fn bind_local(port: u16) -> SpotifyResult<Self> {
+    Self::builder().port(port).bind().await
+  }
+
+  /// Binds to \[::1\] with a custom port
+  pub async fn bind_local_v6(port: u16) -> SpotifyResult<Self> {
+    Self::builder().addr(SocketAddr::from((Ipv6Addr::LOCALHOST, port))).bind().await
+  }
+
+  /// Binds to 127.0.0.1 on whatever port is free and writes it to [DiscoveryFile::default_path],
+  /// for when something else is already using 19532
+  pub async fn bind_free_port() -> SpotifyResult<Self> {
+    let builder = Self::builder().free_port();
+
+    match DiscoveryFile::default_path() {
+      Some(path) => builder.discovery_file(path).bind().await,
+      None => builder.bind().await,
+    }
+  }
+
+  /// Binds to a unix socket at `path` instead of a port,
+  /// for clients other than the extension since spotify can't connect to these
+  ///
+  /// A socket left behind by a listener that didn't shut down cleanly gets replaced,
+  /// the socket gets removed again when the listener is dropped
+  #[cfg(all(unix, feature = "unix"))]
+  pub async fn bind_unix(path: impl AsRef<std::path::Path>) -> SpotifyResult<Self> {
+    Self::builder().bind_unix(path).await
+  }
+
+  /// Creates a windows named pipe like [DEFAULT_PIPE_NAME] instead of binding to a port,
+  /// for clients other than the extension since spotify can't connect to these
+  ///
+  /// Fails if another process already created a pipe with the same name
+  #[cfg(all(windows, feature = "named-pipe"))]
+  pub async fn bind_named_pipe(name: impl AsRef<str>) -> SpotifyResult<Self> {
+    Self::builder().bind_named_pipe(name).await
+  }
+
+  /// The address the listener is bound to, useful after binding to port 0,
+  /// fails for unix sockets and named pipes
+  pub fn local_addr(&self) -> SpotifyResult<SocketAddr> {
+    match &self.transport {
+      Transport::Tcp(listener) => listener.local_addr().map_err(SpotifyError::Bind),
+      #[cfg(all(unix, feature = "unix"))]
+      Transport::Unix(_) => Err(SpotifyError::Bind(std::io::Error::new(std::io::ErrorKind::Unsupported, "listener is a unix socket"))),
+      #[cfg(all(windows, feature = "named-pipe"))]
+      Transport::NamedPipe(_) => Err(SpotifyError::Bind(std::io::Error::new(std::io::ErrorKind::Unsupported, "listener is a named pipe"))),
+    }
+  }
+
+  /// The tcp listener under it, [None] for unix sockets and named pipes
+  pub fn tcp_listener(&self) -> Option<&TcpListener> {
+    match &self.transport {
+      Transport::Tcp(listener) => Some(listener),
+      #[cfg(all(unix, feature = "unix"))]
+      Transport::Unix(_) => None,
+      #[cfg(all(windows, feature = "named-pipe"))]
+      Transport::NamedPipe(_) => None,
+    }
+  }
+
+  /// Name of the named pipe, [None] for tcp
+  #[cfg(all(windows, feature = "named-pipe"))]
+  pub fn pipe_name(&self) -> Option<&str> {
+    match &self.transport {
+      Transport::NamedPipe(pipe) => Some(pipe.name()),
+      Transport::Tcp(_) => None,
+    }
+  }
+
+  /// Path of the unix socket, [None] for tcp
+  #[cfg(all(unix, feature = "unix"))]
+  pub fn unix_path(&self) -> Option<&std::path::Path> {
+    match &self.transport {
+      Transport::Unix(socket) => Some(socket.path()),
+      Transport::Tcp(_) => None,
+    }
+  }
 
-    Ok(Self { listener })
+  /// The file the address was written to, if there is one
+  pub fn discovery_file(&self) -> Option<&DiscoveryFile> {
+    self.discovery.as_ref()
+  }
+
+  /// Binds to the given address, same as calling [TcpListener::bind(addr)]
+  pub async fn bind(addr: SocketAddr) -> SpotifyResult<Self> {
+    Self::builder().addr(addr).bind().await
   }
 
   /// Establishes a websocket connection to the spotify extension
-  pub async fn get_connection(&self) -> Result<SpotifyConnection, Error> {
-    let (stream, _) = self.listener.accept().await.map_err(|_| Error::ConnectionClosed)?;
-    let ws = accept_async(stream).await?;
+  /// and waits for it to say which protocol version it speaks
+  ///
+  /// Fails with [SpotifyError::PeerNotAllowed] or [SpotifyError::OriginNotAllowed] when they aren't allowed,
+  /// [SpotifyError::TooManyConnections] when there's already [SpotifyListenerBuilder::max_connections],
+  /// [SpotifyError::IncompatibleVersion] when the version isn't supported,
+  /// [SpotifyError::Unauthorized] when the token is wrong
+  /// and [SpotifyError::Timeout] when it takes longer than the handshake timeout
+  ///
+  /// Handshakes run in their own tasks, so a client that takes its time saying hello doesn't hold up the ones after it,
+  /// whichever finishes first gets returned
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime, unless [SpotifyListenerBuilder::runtime] was changed
+  pub async fn get_connection(&self) -> SpotifyResult<SpotifyConnection> {
+    use futures_util::future::{select, Either};
+
+    let mut handshakes = self.handshakes.lock().await;
 
-    Ok(SpotifyConnection { ws })
+    loop {
+      match select(std::pin::pin!(self.transport.accept()), std::pin::pin!(handshakes.recv())).await {
+        Either::Left((Ok(stream), _)) => {
+          let acceptor = self.acceptor.clone();
+          let finished = self.finished.clone();
+
+          self.acceptor.runtime.spawn(Box::pin(async move {
+            // only fails when the listener is gone
+            let _ = finished.send(acceptor.accept_transport(stream).await);
+          }));
+        }
+        Either::Left((Err(err), _)) => return Err(SpotifyError::Accept(err)),
+        Either::Right((Some(connection), _)) => return connection,
+        Either::Right((None, _)) => unreachable!("the listener keeps a sender"),
+      }
+    }
   }
-}
\ No newline at end of file
+
+  /// [Self::get_connection] until one works, connections that fail on their own get skipped
+  /// to wait for the extension to try again, so it only fails when the listener itself does
+  pub(crate) async fn next_connection(&self) -> SpotifyResult<SpotifyConnection> {
+    loop {
+      match self.get_connection().await {
+        Err(err) if err.is_connection_error() => continue,
+        connection => return connection,
+      }
+    }
+  }
+
+  /// How many connections are open right now,
+  /// including ones still in the handshake, they stop counting once they're dropped
+  pub fn connection_count(&self) -> usize {
+    self.acceptor.connection_count()
+  }
+
+  /// Handshakes for streams that didn't come from this listener,
+  /// they count towards the same [SpotifyListenerBuilder::max_connections]
+  pub fn acceptor(&self) -> &SpotifyAcceptor {
+    &self.acceptor
+  }
+
+  /// Keeps accepting connections and yields when spotify connects, disconnects and every event in between,
+  /// so you can show things like "Spotify not running" without having to nest loops
+  ///
+  /// Messages that couldn't be parsed are skipped,
+  /// the stream only ends when the listener stops accepting connections
+  pub fn connection_events(&self) -> impl Stream<Item = ConnectionEvent> + '_ {
+    futures_util::stream::unfold(None, move |connection: Option<SpotifyConnection>| async move {
+      let mut connection = match connection {
+        Some(connection) => connection,
+        None => match self.next_connection().await {
+          Ok(connection) => return Some((ConnectionEvent::Connected, Some(connection))),
+          Err(_) => return None,
+        },
+      };
+
+      loop {
+        let reason = match connection.next().await {
+          Some(Ok(event)) => return Some((ConnectionEvent::Event(event), Some(connection))),
+          Some(Err(err)) if err.is_message_error() => continue,
+          next => DisconnectReason::from_next(next),
+        };
+
+        return Some((ConnectionEvent::Disconnected { reason }, None));
+      }
+    })
+  }
+
+  /// Same as [Self::connection_events] but it never ends, meant for daemons that run forever
+  ///
+  /// Starts with [ListenerState::WaitingForSpotify], when the listener itself fails
+  /// (like running out of file descriptors) it keeps waiting and tries again after a [Backoff]
+  pub fn states(&self) -> impl Stream<Item = ListenerState> + '_ {
+    let runtime = &self.acceptor.runtime;
+
+    listener_states(move || self.next_connection(), move |delay| runtime.sleep(delay), self.backoff)
+  }
+
+  /// Keeps accepting connections and calls `f` for every event received,
+  /// only returns when the listener stops accepting connections
+  async fn for_each_event(&self, mut f: impl FnMut(SpotifyEvent)) -> SpotifyResult<()> {
+    loop {
+      let mut connection = self.next_connection().await?;
+
+      while let Some(event) = connection.next().await {
+        match event {
+          Ok(event) => f(event),
+          Err(err) if err.is_message_error() => continue,
+          Err(_) => break,
+        }
+      }
+
+      f(SpotifyEvent::StateChanged(TrackState::Stopped));
+    }
+  }
+
+  /// Keeps accepting connections and calls `handler` for every event from any of them,
+  /// every connection gets its own task so more than one spotify can be connected at once
+  ///
+  /// Events from the same connection are handled one at a time in order,
+  /// when a connection ends `handler` gets [SpotifyEvent::StateChanged] with [TrackState::Stopped]
+  ///
+  /// Only returns when the listener stops accepting connections
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime, unless [SpotifyListenerBuilder::runtime] was changed
+  pub async fn serve<F, Fut>(&self, handler: F) -> SpotifyResult<()>
+  where
+    F: Fn(SpotifyEvent) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send,
+  {
+    let handler = Arc::new(handler);
+
+    loop {
+      let mut connection = self.next_connection().await?;
+
+      let handler = handler.clone();
+
+      self.acceptor.runtime.spawn(Box::pin(async move {
+        while let Some(event) = connection.next().await {
+          match event {
+            Ok(event) => handler(event).await,
+            Err(err) if err.is_message_error() => continue,
+            Err(_) => break,
+          }
+        }
+
+        handler(SpotifyEvent::StateChanged(TrackState::Stopped)).await;
+      }));
+    }
+  }
+
+  /// Keeps accepting connections and calls `handler` for everything that happens,
+  /// one connection at a time, same as [Self::connection_events] but with callbacks
+  ///
+  /// Only returns when the listener stops accepting connections
+  pub async fn run(&self, mut handler: impl SpotifyEventHandler) -> SpotifyResult<()> {
+    loop {
+      let mut connection = self.next_connection().await?;
+
+      handler.on_connect(connection.info());
+
+      let reason = loop {
+        match connection.next().await {
+          Some(Ok(event)) => handler::dispatch(&mut handler, &event),
+          Some(Err(err)) if err.is_message_error() => continue,
+          next => break DisconnectReason::from_next(next),
+        }
+      };
+
+      handler.on_disconnect(&reason);
+    }
+  }
+
+  /// Keeps listening for connections and updates the handle with every event,
+  /// so other threads can read the current track from their own clone of the handle
+  ///
+  /// When spotify disconnects the state gets set to [TrackState::Stopped]
+  /// until it connects again
+  pub async fn listen(&self, handle: TrackHandle) -> SpotifyResult<()> {
+    self.for_each_event(|event| handle.update(&event)).await
+  }
+
+  /// Keeps listening for connections in a background task
+  /// and returns receivers that always hold the latest track and state,
+  /// so you can wait on [watch::Receiver::changed] instead of caching events yourself
+  ///
+  /// The track is [None] until spotify sends one,
+  /// when spotify disconnects the state gets set to [TrackState::Stopped]
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime, unless [SpotifyListenerBuilder::runtime] was changed
+  pub fn watch(self) -> (watch::Receiver<Option<TrackInfo>>, watch::Receiver<TrackState>) {
+    let (track_tx, track_rx) = watch::channel(None);
+    let (state_tx, state_rx) = watch::channel(TrackState::Stopped);
+
+    let runtime = self.acceptor.runtime.clone();
+
+    runtime.spawn(Box::pin(async move {
+      let _ = self.for_each_event(|event| match event {
+        SpotifyEvent::TrackChanged(info) => {
+          state_tx.send_if_modified(|state| std::mem::replace(state, info.state) != info.state);
+          track_tx.send_replace(Some(info));
+        }
+        SpotifyEvent::StateChanged(new) => {
+          state_tx.send_if_modified(|state| std::mem::replace(state, new) != new);
+          track_tx.send_if_modified(|info| match info {
+            Some(info) if info.state != new => {
+              info.state = new;
+              true
+            }
+            _ => false,
+          });
+        }
+        SpotifyEvent::LikedChanged(liked) => {
+          track_tx.send_if_modified(|info| match info {
+            Some(info) if info.liked != Some(liked) => {
+              info.liked = Some(liked);
+              true
+            }
+            _ => false,
+          });
+        }
+        SpotifyEvent::StateSnapshot(snapshot) => {
+          state_tx.send_if_modified(|state| std::mem::replace(state, snapshot.state) != snapshot.state);
+
+          if let Some(info) = snapshot.track {
+            track_tx.send_replace(Some(info));
+          }
+        }
+        _ => {}
+      }).await;
+    }));
+
+    (track_rx, state_rx)
+  }
+
+  /// Keeps listening for connections in a background task
+  /// and sends every event to all receivers subscribed with [SpotifyEvents::subscribe]
+  ///
+  /// `capacity` is how many events can be queued for a receiver before it starts lagging behind,
+  /// when spotify disconnects it sends [SpotifyEvent::StateChanged] with [TrackState::Stopped]
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime, unless [SpotifyListenerBuilder::runtime] was changed
+  pub fn events(self, capacity: usize) -> SpotifyEvents {
+    let (sender, _) = broadcast::channel(capacity);
+    let (commands, receiver) = mpsc::unbounded_channel();
+    let events = SpotifyEvents { sender: sender.clone(), commands };
+
+    let runtime = self.acceptor.runtime.clone();
+
+    runtime.spawn(Box::pin(async move {
+      let _ = self.relay_events(sender, receiver).await;
+    }));
+
+    events
+  }
+
+  /// Keeps listening for connections in a background task and sends every event to the returned receiver,
+  /// messages sent with the returned sender go to the connection, for apps that pass everything around with channels
+  ///
+  /// Unlike [Self::events] no events get skipped, it stops reading from spotify until the receiver catches up,
+  /// messages sent while spotify isn't connected get dropped, use [SpotifyEvents::send] to know when that happens
+  ///
+  /// When spotify disconnects it sends [SpotifyEvent::StateChanged] with [TrackState::Stopped],
+  /// the task stops once the receiver is dropped
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime, unless [SpotifyListenerBuilder::runtime] was changed
+  pub fn into_channel(self, capacity: usize) -> (mpsc::Receiver<SpotifyEvent>, mpsc::Sender<SpotifyMessage>) {
+    use futures_util::future::select;
+
+    let (events, receiver) = mpsc::channel(capacity);
+    let (messages, commands) = mpsc::channel(capacity);
+    let runtime = self.acceptor.runtime.clone();
+
+    runtime.spawn(Box::pin(async move {
+      let relay = std::pin::pin!(self.relay_channel(&events, commands));
+
+      select(relay, std::pin::pin!(events.closed())).await;
+    }));
+
+    (receiver, messages)
+  }
+
+  /// Same as [Self::relay_events] but with channels, only returns when the listener stops accepting connections
+  async fn relay_channel(&self, events: &mpsc::Sender<SpotifyEvent>, mut commands: mpsc::Receiver<SpotifyMessage>) {
+    use futures_util::future::{select, Either};
+
+    // events are still wanted after every sender is dropped, so it waits forever instead of ending
+    async fn next_command(commands: &mut mpsc::Receiver<SpotifyMessage>) -> SpotifyMessage {
+      match commands.recv().await {
+        Some(message) => message,
+        None => std::future::pending().await,
+      }
+    }
+
+    loop {
+      let mut accept = std::pin::pin!(self.next_connection());
+
+      let connection = loop {
+        match select(accept.as_mut(), std::pin::pin!(next_command(&mut commands))).await {
+          Either::Left((connection, _)) => break connection,
+          // nothing is connected to send it to
+          Either::Right(_) => continue,
+        }
+      };
+
+      let mut connection = match connection {
+        Ok(connection) => connection,
+        Err(_) => return,
+      };
+
+      loop {
+        let event = match select(StreamExt::next(&mut connection), std::pin::pin!(next_command(&mut commands))).await {
+          Either::Left((Some(event), _)) => event,
+          Either::Left((None, _)) => break,
+          Either::Right((message, _)) => {
+            // if it failed the connection is broken, which the next read finds out
+            let _ = connection.send(message).await;
+            continue;
+          }
+        };
+
+        match event {
+          Ok(event) => {
+            if events.send(event).await.is_err() {
+              return;
+            }
+          }
+          Err(err) if err.is_message_error() => continue,
+          Err(_) => break,
+        }
+      }
+
+      if events.send(SpotifyEvent::StateChanged(TrackState::Stopped)).await.is_err() {
+        return;
+      }
+    }
+  }
+
+  /// Same as [Self::for_each_event] but also sends commands from [SpotifyEvents::send] to the connection,
+  /// commands sent while spotify isn't connected fail instead of waiting for it
+  async fn relay_events(&self, sender: broadcast::Sender<SpotifyEvent>, mut commands: mpsc::UnboundedReceiver<Command>) -> SpotifyResult<()> {
+    use futures_util::future::{select, Either};
+
+    loop {
+      let mut accept = std::pin::pin!(self.next_connection());
+
+      let connection = loop {
+        match select(accept.as_mut(), std::pin::pin!(commands.recv())).await {
+          Either::Left((connection, _)) => break connection,
+          Either::Right((Some((_, reply)), _)) => drop(reply.send(Err(SpotifyError::NotConnected))),
+          // every SpotifyEvents is gone, nobody is left to send events to
+          Either::Right((None, _)) => return Ok(()),
+        }
+      };
+
+      let mut connection = connection?;
+
+      loop {
+        let event = match select(StreamExt::next(&mut connection), std::pin::pin!(commands.recv())).await {
+          Either::Left((Some(event), _)) => event,
+          Either::Left((None, _)) => break,
+          Either::Right((Some((message, reply)), _)) => {
+            drop(reply.send(connection.send(message).await));
+            continue;
+          }
+          Either::Right((None, _)) => return Ok(()),
+        };
+
+        match event {
+          // errors only mean nobody is subscribed right now
+          Ok(event) => drop(sender.send(event)),
+          Err(err) if err.is_message_error() => continue,
+          Err(_) => break,
+        }
+      }
+
+      drop(sender.send(SpotifyEvent::StateChanged(TrackState::Stopped)));
+    }
+  }
+}
+/// Everything [SpotifyListener::states] does, with where connections come from and how it waits passed in so tests can fake them
+#[cfg(feature = "async")]
+fn listener_states<'a, C, S, F>(
+  accept: impl Fn() -> F + 'a,
+  sleep: impl Fn(Duration) -> BoxFuture + 'a,
+  backoff: Backoff,
+) -> impl Stream<Item = ListenerState> + 'a
+where
+  C: EventCodec + Unpin + 'a,
+  S: AsyncRead + AsyncWrite + Unpin + 'a,
+  F: Future<Output = SpotifyResult<SpotifyConnection<C, S>>> + 'a,
+{
+  let start = (None, BackoffTimer::new(backoff), false, accept, sleep);
+
+  futures_util::stream::unfold(start, |(connection, mut backoff, waiting, accept, sleep)| async move {
+    let mut connection: SpotifyConnection<C, S> = match connection {
+      Some(connection) => connection,
+      None if !waiting => return Some((ListenerState::WaitingForSpotify, (None, backoff, true, accept, sleep))),
+      None => loop {
+        match accept().await {
+          Ok(connection) => {
+            backoff.reset();
+            let info = connection.info().clone();
+            return Some((ListenerState::Connected(info), (Some(connection), backoff, false, accept, sleep)));
+          }
+          Err(_) => sleep(backoff.next_delay()).await,
+        }
+      },
+    };
+
+    loop {
+      let reason = match connection.next().await {
+        Some(Ok(event)) => return Some((ListenerState::Event(event), (Some(connection), backoff, false, accept, sleep))),
+        Some(Err(err)) if err.is_message_error() => continue,
+        next => DisconnectReason::from_next(next),
+      };
+
+      return Some((ListenerState::Lost(reason), (None, backoff, false, accept, sleep)));
+    }
+  })
+}
+
+#[cfg(all(test, feature = "async"))]
+mod tests {
+  use std::collections::VecDeque;
+  use std::sync::Mutex;
+
+  use tokio::io::DuplexStream;
+
+  use super::*;
+
+  type Accepted = SpotifyResult<SpotifyConnection<Codec, DuplexStream>>;
+
+  fn accept_failed() -> Accepted {
+    Err(SpotifyError::Accept(std::io::Error::other("out of file descriptors")))
+  }
+
+  async fn next_state(states: &mut (impl Stream<Item = ListenerState> + Unpin)) -> ListenerState {
+    tokio::time::timeout(Duration::from_secs(5), states.next()).await.expect("state in time").expect("never ends")
+  }
+
+  #[tokio::test]
+  async fn states_back_off_and_reset() {
+    let acceptor = SpotifyListener::builder().keepalive(None).acceptor().unwrap();
+    let (mut spotify, connection) = MockSpotifyClient::pair(&acceptor).await.unwrap();
+
+    let accepted = Mutex::new(VecDeque::from([accept_failed(), accept_failed(), accept_failed(), Ok(connection), accept_failed()]));
+    let slept = Arc::new(Mutex::new(Vec::new()));
+
+    let accept = || {
+      let next = accepted.lock().unwrap().pop_front();
+      // nothing left to accept, the test is over by then
+      async move {
+        match next {
+          Some(next) => next,
+          None => std::future::pending().await,
+        }
+      }
+    };
+    let sleep = {
+      let slept = slept.clone();
+      move |delay| -> BoxFuture {
+        slept.lock().unwrap().push(delay);
+        Box::pin(std::future::ready(()))
+      }
+    };
+
+    let backoff = Backoff { initial: Duration::from_secs(1), max: Duration::from_secs(3) };
+    let mut states = std::pin::pin!(listener_states(accept, sleep, backoff));
+
+    assert!(matches!(next_state(&mut states).await, ListenerState::WaitingForSpotify));
+    assert!(matches!(next_state(&mut states).await, ListenerState::Connected(_)));
+
+    // doubled every time, capped at max, each somewhere between half and all of it
+    let delays = std::mem::take(&mut *slept.lock().unwrap());
+    assert_eq!(delays.len(), 3);
+    for (delay, max) in delays.iter().zip([1, 2, 3]) {
+      let max = Duration::from_secs(max);
+      assert!(*delay >= max / 2 && *delay <= max, "{:?} for {:?}", delay, max);
+    }
+
+    spotify.send_state(TrackState::Playing).await.unwrap();
+    assert!(matches!(next_state(&mut states).await, ListenerState::Event(SpotifyEvent::StateChanged(TrackState::Playing))));
+
+    spotify.close().await.unwrap();
+    assert!(matches!(next_state(&mut states).await, ListenerState::Lost(DisconnectReason::Closed)));
+    assert!(matches!(next_state(&mut states).await, ListenerState::WaitingForSpotify));
+
+    // connecting started it over from the initial delay
+    let wait = tokio::time::timeout(Duration::from_millis(100), states.next()).await;
+    assert!(wait.is_err());
+    let delays = slept.lock().unwrap().clone();
+    assert_eq!(delays.len(), 1);
+    assert!(delays[0] <= Duration::from_secs(1), "{:?}", delays[0]);
+  }
+
+  #[test]
+  fn disconnect_reasons() {
+    assert!(matches!(DisconnectReason::from_next::<()>(None), DisconnectReason::Closed));
+    assert!(matches!(DisconnectReason::from_next::<()>(Some(Err(SpotifyError::Timeout))), DisconnectReason::Timeout));
+    assert!(matches!(DisconnectReason::from_next::<()>(Some(Err(SpotifyError::Idle))), DisconnectReason::Idle));
+    assert!(matches!(DisconnectReason::from_next::<()>(Some(Err(SpotifyError::NotConnected))), DisconnectReason::Error(SpotifyError::NotConnected)));
+  }
+}
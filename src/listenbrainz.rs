@@ -0,0 +1,196 @@
+//! Submitting listens to ListenBrainz, only with the `listenbrainz` feature
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use serde_json::{json, Map, Value};
+
+use crate::{Scrobble, ScrobbleService, ServiceFuture, SpotifyError, SpotifyResult, TrackInfo};
+
+const API_URL: &str = "https://api.listenbrainz.org";
+
+/// ListenBrainz's api as a [ScrobbleService] for a [Scrobbler](crate::Scrobbler)
+///
+/// Needs the user token from https://listenbrainz.org/settings/, there's no other way to sign in
+///
+/// ```text
+/// let listenbrainz = ListenBrainz::new(USER_TOKEN);
+/// println!("signed in as {}", listenbrainz.validate_token().await?);
+///
+/// tokio::spawn(Scrobbler::new(listenbrainz).run(listener.events(64)));
+/// ```
+///
+/// Tracks are sent with every artist (joined with `, ` like spotify shows them) and a link to spotify so ListenBrainz can match them,
+/// tracks without a title or artist (like some local files) are left out since ListenBrainz needs both
+///
+/// Default: 10 second timeout for every request
+#[derive(Debug, Clone)]
+pub struct ListenBrainz {
+  client: reqwest::Client,
+  token: String,
+  api_url: String,
+  timeout: Duration,
+}
+
+impl ListenBrainz {
+  pub fn new(token: impl Into<String>) -> Self {
+    Self {
+      client: reqwest::Client::new(),
+      token: token.into(),
+      api_url: API_URL.to_string(),
+      timeout: Duration::from_secs(10),
+    }
+  }
+
+  /// Uses an existing client instead of making a new one, to share its connections and settings
+  pub fn client(mut self, client: reqwest::Client) -> Self {
+    self.client = client;
+    self
+  }
+
+  /// Sends everything to another server that speaks the same api, like a self-hosted one
+  ///
+  /// Default: `https://api.listenbrainz.org`
+  pub fn api_url(mut self, url: impl Into<String>) -> Self {
+    self.api_url = url.into().trim_end_matches('/').to_string();
+    self
+  }
+
+  /// How long a request can take before it fails, failed listens get retried
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.timeout = timeout;
+    self
+  }
+
+  /// Checks the token, returns the name of the user it belongs to
+  pub async fn validate_token(&self) -> SpotifyResult<String> {
+    let request = self.client.get(format!("{}/1/validate-token", self.api_url));
+    let answer = self.send(request).await?;
+
+    match (answer["valid"].as_bool(), answer["user_name"].as_str()) {
+      (Some(true), Some(name)) => Ok(name.to_string()),
+      _ => Err(error("invalid user token".to_string(), false)),
+    }
+  }
+
+  /// `listen_type` is `playing_now`, `single` or `import`
+  async fn submit(&self, listen_type: &str, payload: Vec<Value>) -> SpotifyResult<()> {
+    let body = json!({ "listen_type": listen_type, "payload": payload });
+    let request = self.client.post(format!("{}/1/submit-listens", self.api_url))
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(body.to_string());
+
+    self.send(request).await.map(drop)
+  }
+
+  async fn send(&self, request: reqwest::RequestBuilder) -> SpotifyResult<Value> {
+    let response = request
+      .header(reqwest::header::AUTHORIZATION, format!("Token {}", self.token))
+      .timeout(self.timeout)
+      .send()
+      .await
+      .map_err(|err| error(err.to_string(), true))?;
+
+    let status = response.status();
+    let text = response.text().await.map_err(|err| error(err.to_string(), true))?;
+    let answer = serde_json::from_str::<Value>(&text).unwrap_or(Value::Null);
+
+    if !status.is_success() {
+      let message = answer["error"].as_str().map_or_else(|| format!("unexpected answer with status {}", status), str::to_string);
+      let retry = status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+
+      return Err(error(message, retry));
+    }
+
+    Ok(answer)
+  }
+}
+
+impl ScrobbleService for ListenBrainz {
+  fn name(&self) -> &'static str {
+    "listenbrainz"
+  }
+
+  fn now_playing<'a>(&'a self, track: &'a TrackInfo) -> ServiceFuture<'a> {
+    Box::pin(async move {
+      match listen(track, None) {
+        Some(listen) => self.submit("playing_now", vec![listen]).await,
+        None => Ok(()),
+      }
+    })
+  }
+
+  fn scrobble<'a>(&'a self, scrobbles: &'a [Scrobble]) -> ServiceFuture<'a> {
+    Box::pin(async move {
+      let listens = scrobbles.iter()
+        .filter_map(|scrobble| {
+          let listened_at = scrobble.started_at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+          listen(&scrobble.track, Some(listened_at))
+        })
+        .collect::<Vec<_>>();
+
+      // `single` only takes one, queued ones go as `import` which takes more
+      match listens.len() {
+        0 => Ok(()),
+        1 => self.submit("single", listens).await,
+        _ => self.submit("import", listens).await,
+      }
+    })
+  }
+
+  fn max_batch(&self) -> usize {
+    // ListenBrainz takes up to 1000 at once but limits the size of a request too
+    100
+  }
+}
+
+/// [None] if ListenBrainz wouldn't take it
+fn listen(track: &TrackInfo, listened_at: Option<u64>) -> Option<Value> {
+  let artists = track.artists.iter().map(|artist| artist.name.as_str()).filter(|name| !name.is_empty()).collect::<Vec<_>>();
+
+  if artists.is_empty() || track.title.is_empty() {
+    return None;
+  }
+
+  let mut info = Map::new();
+  info.insert("media_player".into(), "Spotify".into());
+  info.insert("submission_client".into(), env!("CARGO_PKG_NAME").into());
+  info.insert("submission_client_version".into(), env!("CARGO_PKG_VERSION").into());
+  info.insert("music_service".into(), "spotify.com".into());
+  info.insert("artist_names".into(), artists.iter().copied().collect());
+
+  if !track.duration.is_zero() {
+    info.insert("duration_ms".into(), (track.duration.as_millis() as u64).into());
+  }
+
+  if let Some(number) = track.track_number {
+    info.insert("tracknumber".into(), number.into());
+  }
+
+  if let Some(url) = track.uri.to_url() {
+    info.insert("spotify_id".into(), url.as_str().into());
+    info.insert("origin_url".into(), url.into());
+  }
+
+  let mut metadata = json!({
+    "artist_name": artists.join(", "),
+    "track_name": track.title,
+    "additional_info": info,
+  });
+
+  if !track.album.name.is_empty() {
+    metadata["release_name"] = track.album.name.as_str().into();
+  }
+
+  let mut listen = json!({ "track_metadata": metadata });
+
+  if let Some(listened_at) = listened_at {
+    listen["listened_at"] = listened_at.into();
+  }
+
+  Some(listen)
+}
+
+fn error(message: String, retry: bool) -> SpotifyError {
+  SpotifyError::Scrobble { service: "listenbrainz", message, retry }
+}
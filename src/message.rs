@@ -0,0 +1,168 @@
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{RepeatMode, SpotifyEvent};
+
+/// Which events the extension should send, combine them with `|`
+///
+/// [SpotifyEvent::StateSnapshot] is always sent since it's only a response to
+/// [SpotifyMessage::RequestState], same with events the listener makes itself
+/// like [SpotifyEvent::TrackFinished]
+///
+/// Default: [Self::ALL]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(transparent)]
+pub struct EventMask(u32);
+
+impl EventMask {
+  /// No events at all
+  pub const NONE: Self = Self(0);
+  /// [SpotifyEvent::TrackChanged]
+  pub const TRACK_CHANGED: Self = Self(1 << 0);
+  /// [SpotifyEvent::StateChanged]
+  pub const STATE_CHANGED: Self = Self(1 << 1);
+  /// [SpotifyEvent::ProgressChanged]
+  pub const PROGRESS_CHANGED: Self = Self(1 << 2);
+  /// [SpotifyEvent::VolumeChanged]
+  pub const VOLUME_CHANGED: Self = Self(1 << 3);
+  /// [SpotifyEvent::PlaybackRateChanged]
+  pub const PLAYBACK_RATE_CHANGED: Self = Self(1 << 4);
+  /// [SpotifyEvent::ShuffleChanged]
+  pub const SHUFFLE_CHANGED: Self = Self(1 << 5);
+  /// [SpotifyEvent::RepeatChanged]
+  pub const REPEAT_CHANGED: Self = Self(1 << 6);
+  /// [SpotifyEvent::DeviceChanged]
+  pub const DEVICE_CHANGED: Self = Self(1 << 7);
+  /// [SpotifyEvent::ContextChanged]
+  pub const CONTEXT_CHANGED: Self = Self(1 << 8);
+  /// [SpotifyEvent::LyricsChanged]
+  pub const LYRICS_CHANGED: Self = Self(1 << 9);
+  /// [SpotifyEvent::LyricLineChanged]
+  pub const LYRIC_LINE_CHANGED: Self = Self(1 << 10);
+  /// [SpotifyEvent::ColorsChanged]
+  pub const COLORS_CHANGED: Self = Self(1 << 11);
+  /// [SpotifyEvent::LikedChanged]
+  pub const LIKED_CHANGED: Self = Self(1 << 12);
+  /// Every event, including ones added in the future
+  pub const ALL: Self = Self(u32::MAX);
+
+  pub fn from_bits(bits: u32) -> Self {
+    Self(bits)
+  }
+
+  pub fn bits(self) -> u32 {
+    self.0
+  }
+
+  /// If every event in `other` is also in this mask
+  pub fn contains(self, other: Self) -> bool {
+    self.0 & other.0 == other.0
+  }
+
+  /// If this mask lets the given event through
+  pub fn matches(self, event: &SpotifyEvent) -> bool {
+    match event {
+      SpotifyEvent::TrackChanged(_) => self.contains(Self::TRACK_CHANGED),
+      SpotifyEvent::StateChanged(_) => self.contains(Self::STATE_CHANGED),
+      SpotifyEvent::ProgressChanged { .. } => self.contains(Self::PROGRESS_CHANGED),
+      SpotifyEvent::VolumeChanged(_) => self.contains(Self::VOLUME_CHANGED),
+      SpotifyEvent::PlaybackRateChanged(_) => self.contains(Self::PLAYBACK_RATE_CHANGED),
+      SpotifyEvent::ShuffleChanged(_) => self.contains(Self::SHUFFLE_CHANGED),
+      SpotifyEvent::RepeatChanged(_) => self.contains(Self::REPEAT_CHANGED),
+      SpotifyEvent::DeviceChanged { .. } => self.contains(Self::DEVICE_CHANGED),
+      SpotifyEvent::ContextChanged(_) => self.contains(Self::CONTEXT_CHANGED),
+      SpotifyEvent::LyricsChanged(_) => self.contains(Self::LYRICS_CHANGED),
+      SpotifyEvent::LyricLineChanged(_) => self.contains(Self::LYRIC_LINE_CHANGED),
+      SpotifyEvent::ColorsChanged(_) => self.contains(Self::COLORS_CHANGED),
+      SpotifyEvent::LikedChanged(_) => self.contains(Self::LIKED_CHANGED),
+      SpotifyEvent::StateSnapshot(_) | SpotifyEvent::Unknown { .. } => true,
+      SpotifyEvent::TrackFinished(_) | SpotifyEvent::TrackSkipped { .. } | SpotifyEvent::ScrobblePoint(_) => true,
+      SpotifyEvent::SessionStarted | SpotifyEvent::SessionEnded { .. } => true,
+    }
+  }
+}
+
+impl Default for EventMask {
+  fn default() -> Self {
+    Self::ALL
+  }
+}
+
+impl BitOr for EventMask {
+  type Output = Self;
+
+  fn bitor(self, rhs: Self) -> Self {
+    Self(self.0 | rhs.0)
+  }
+}
+
+impl BitOrAssign for EventMask {
+  fn bitor_assign(&mut self, rhs: Self) {
+    self.0 |= rhs.0;
+  }
+}
+
+impl BitAnd for EventMask {
+  type Output = Self;
+
+  fn bitand(self, rhs: Self) -> Self {
+    Self(self.0 & rhs.0)
+  }
+}
+
+impl Not for EventMask {
+  type Output = Self;
+
+  fn not(self) -> Self {
+    Self(!self.0)
+  }
+}
+
+/// Messages that can be sent to the spotify extension
+/// with [SpotifyConnection::send](crate::SpotifyConnection::send)
+///
+/// New messages can be added at any time, so matching on this always needs a `_` arm
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(tag = "type", content = "data", rename_all = "PascalCase", rename_all_fields = "snake_case")]
+#[non_exhaustive]
+pub enum SpotifyMessage {
+  /// Sets how often the extension sends [SpotifyEvent::ProgressChanged](crate::SpotifyEvent::ProgressChanged)
+  SetProgressUpdateInterval(
+    #[serde(with = "crate::serde_utils::millis")]
+    #[cfg_attr(feature = "schemars", schemars(with = "u64"))]
+    #[cfg_attr(feature = "ts-rs", ts(type = "number"))]
+    Duration,
+  ),
+  /// Resumes playback
+  Play,
+  /// Pauses playback
+  Pause,
+  /// Pauses if playing, resumes if paused
+  TogglePlayback,
+  /// Skips to the next track
+  Next,
+  /// Goes back to the previous track
+  Previous,
+  /// Seeks to an absolute position in the current track
+  Seek { #[serde(alias = "positionMs")] position_ms: u64 },
+  /// Seeks to a percentage of the current track between 0 and 1
+  SeekPercent { percent: f64 },
+  /// Sets the volume between 0 and 1
+  SetVolume(f64),
+  /// Turns shuffle on or off
+  SetShuffle(bool),
+  /// Sets the repeat mode
+  SetRepeat(RepeatMode),
+  /// Likes (adds to Liked Songs) or unlikes the current track
+  SetLiked(bool),
+  /// Asks for [SpotifyEvent::StateSnapshot](crate::SpotifyEvent::StateSnapshot)
+  RequestState,
+  /// Tells the extension which events to send, so it doesn't send events you don't care about
+  SetSubscriptions(EventMask),
+}
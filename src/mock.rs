@@ -0,0 +1,135 @@
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream};
+use tokio::net::TcpStream;
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::Message;
+
+use crate::{handshake, scale_by_rate, Codec, PlayerSnapshot, SpotifyAcceptor, SpotifyConnection, SpotifyError, SpotifyEvent, SpotifyMessage, SpotifyResult, TrackInfo, TrackState};
+
+/// Pretends to be the extension, for testing code that uses this crate without spotify
+///
+/// Keeps track of the player like the extension does, so [Self::send_progress] and [Self::advance_time]
+/// send the same positions the extension would, there's no real time involved so tests stay fast
+///
+/// Only speaks json, messages from the listener can be checked with [Self::recv],
+/// pings only get answered while reading so turn off the [Keepalive](crate::Keepalive) for tests that never call it
+///
+/// ```text
+/// let (mut spotify, mut connection) = MockSpotifyClient::pair(&acceptor).await?;
+///
+/// spotify.send_track_changed(TrackInfo::builder().title("Song").build()?).await?;
+/// spotify.advance_time(Duration::from_secs(30)).await?;
+/// ```
+#[derive(Debug)]
+pub struct MockSpotifyClient<S = TcpStream> {
+  ws: WebSocketStream<S>,
+  snapshot: PlayerSnapshot,
+}
+
+impl MockSpotifyClient<TcpStream> {
+  /// Connects to a [SpotifyListener](crate::SpotifyListener) at `addr` like the extension does,
+  /// from spotify's origin and with `token` if the listener needs one
+  pub async fn connect(addr: SocketAddr, token: Option<&str>) -> SpotifyResult<Self> {
+    let stream = TcpStream::connect(addr).await.map_err(|err| SpotifyError::from(tungstenite::Error::Io(err)))?;
+
+    let ws = handshake::connect_client(stream, &format!("ws://{}/", addr), &[], token, "mock", Codec::Json).await?;
+
+    Ok(Self { ws, snapshot: PlayerSnapshot::default() })
+  }
+}
+
+impl MockSpotifyClient<DuplexStream> {
+  /// Connects to `acceptor` over an in-memory stream instead of a socket,
+  /// so there's no ports that can be taken already
+  ///
+  /// Uses the acceptor's token and allowed origins, so it always gets accepted
+  pub async fn pair(acceptor: &SpotifyAcceptor) -> SpotifyResult<(Self, SpotifyConnection<Codec, DuplexStream>)> {
+    let (connection, ws) = acceptor.duplex(Codec::Json).await?;
+
+    Ok((Self { ws, snapshot: PlayerSnapshot::default() }, connection))
+  }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> MockSpotifyClient<S> {
+  /// Everything the mock thinks the player is doing, what [Self::send_snapshot] sends
+  pub fn snapshot(&self) -> &PlayerSnapshot {
+    &self.snapshot
+  }
+
+  /// Sends any event, also updates the mock's player with it
+  pub async fn send(&mut self, event: SpotifyEvent) -> SpotifyResult<()> {
+    self.snapshot.apply(&event);
+
+    let text = serde_json::to_string(&event).expect("events always serialize");
+
+    Ok(self.ws.send(Message::Text(text)).await?)
+  }
+
+  /// Changes the track and starts it from the beginning, with the state in `info`
+  pub async fn send_track_changed(&mut self, info: TrackInfo) -> SpotifyResult<()> {
+    self.send(SpotifyEvent::TrackChanged(info)).await
+  }
+
+  pub async fn send_state(&mut self, state: TrackState) -> SpotifyResult<()> {
+    self.send(SpotifyEvent::StateChanged(state)).await
+  }
+
+  /// Moves to `position` in the current track, with the percent and timestamp the extension would send
+  pub async fn send_progress(&mut self, position: Duration) -> SpotifyResult<()> {
+    let duration = self.snapshot.track.as_ref().map_or(Duration::ZERO, |info| info.duration);
+    let percent = match duration.is_zero() {
+      true => 0.0,
+      false => position.as_secs_f64() / duration.as_secs_f64(),
+    };
+
+    self.send(SpotifyEvent::ProgressChanged {
+      position,
+      percent,
+      timestamp: SystemTime::now(),
+    }).await
+  }
+
+  /// Pretends `by` passed, while playing the position moves forward (at most to the end of the track)
+  /// and gets sent with [Self::send_progress], nothing happens while paused like with the extension
+  pub async fn advance_time(&mut self, by: Duration) -> SpotifyResult<()> {
+    if self.snapshot.state != TrackState::Playing {
+      return Ok(());
+    }
+
+    let mut position = self.snapshot.position.saturating_add(scale_by_rate(by, self.snapshot.playback_rate));
+
+    if let Some(info) = self.snapshot.track.as_ref().filter(|info| !info.duration.is_zero()) {
+      position = position.min(info.duration);
+    }
+
+    self.send_progress(position).await
+  }
+
+  /// Sends [Self::snapshot] like the extension answers [SpotifyMessage::RequestState]
+  pub async fn send_snapshot(&mut self) -> SpotifyResult<()> {
+    self.send(SpotifyEvent::StateSnapshot(self.snapshot.clone())).await
+  }
+
+  /// Waits for the next message from the listener, [None] once the connection is closed
+  pub async fn recv(&mut self) -> Option<SpotifyResult<SpotifyMessage>> {
+    loop {
+      match self.ws.next().await? {
+        Ok(Message::Text(text)) => return Some(serde_json::from_str(&text).map_err(SpotifyError::Deserialize)),
+        Ok(Message::Close(_)) => return None,
+        Ok(_) => continue,
+        Err(err) => return Some(Err(err.into())),
+      }
+    }
+  }
+
+  /// Closes the connection like spotify closing, the listener's side ends after everything sent before it
+  ///
+  /// Doesn't wait for the listener to answer since it's usually not being read at the same time,
+  /// so keep this around until the connection ended or it ends with an error instead
+  pub async fn close(&mut self) -> SpotifyResult<()> {
+    Ok(self.ws.close(None).await?)
+  }
+}
@@ -0,0 +1,434 @@
+//! Publishing the player over D-Bus as MPRIS, only with the `mpris` feature
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use tokio::sync::broadcast::error::RecvError;
+use zbus::fdo;
+use zbus::object_server::SignalContext;
+use zbus::zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value};
+
+use crate::{PlayerState, RepeatMode, SpotifyError, SpotifyEvent, SpotifyEvents, SpotifyMessage, SpotifyResult, TrackInfo, TrackState};
+
+const PATH: &str = "/org/mpris/MediaPlayer2";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+/// Positions further off than this from where it should be mean it was seeked
+const SEEK_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// Publishes what the extension sends as an MPRIS player on the session bus,
+/// so `playerctl`, waybar and desktop media widgets work without spotify's own MPRIS
+/// (which is missing in the flatpak and breaks now and then)
+///
+/// Play, pause, next, previous, seeking, volume, shuffle and repeat from them go to spotify as [SpotifyMessage]s,
+/// raising, quitting and opening uris aren't supported
+///
+/// Default: `org.mpris.MediaPlayer2.spotify_info` named `Spotify`,
+/// the name is different from spotify's own so both can be there at the same time
+///
+/// ```text
+/// tokio::spawn(MprisServer::new().serve(listener.events(64)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MprisServer {
+  name: String,
+  identity: String,
+}
+
+impl Default for MprisServer {
+  fn default() -> Self {
+    Self {
+      name: "spotify_info".to_string(),
+      identity: "Spotify".to_string(),
+    }
+  }
+}
+
+impl MprisServer {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// What comes after `org.mpris.MediaPlayer2.`, only letters, digits and `_`,
+  /// this is what `playerctl --player` takes
+  pub fn name(mut self, name: impl Into<String>) -> Self {
+    self.name = name.into();
+    self
+  }
+
+  /// Name that widgets show for the player
+  pub fn identity(mut self, identity: impl Into<String>) -> Self {
+    self.identity = identity.into();
+    self
+  }
+
+  /// Takes the name on the session bus and keeps it up to date with `events` until the listener is gone,
+  /// commands go to the connection behind `events`
+  ///
+  /// Fails with [SpotifyError::Mpris] if there's no session bus or the name is taken
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn serve(self, events: SpotifyEvents) -> SpotifyResult<()> {
+    // before connecting so nothing gets missed while it does
+    let mut receiver = events.subscribe();
+    let state = Arc::new(Mutex::new(PlayerState::new()));
+    let player = Player { state: state.clone(), events: events.clone() };
+
+    let connection = zbus::connection::Builder::session()
+      .and_then(|builder| builder.name(format!("org.mpris.MediaPlayer2.{}", self.name)))
+      .and_then(|builder| builder.serve_at(PATH, Root { identity: self.identity }))
+      .and_then(|builder| builder.serve_at(PATH, player))
+      .map_err(SpotifyError::Mpris)?
+      .build()
+      .await
+      .map_err(SpotifyError::Mpris)?;
+
+    let player = connection.object_server().interface::<_, Player>(PATH).await.map_err(SpotifyError::Mpris)?;
+
+    loop {
+      let event = match receiver.recv().await {
+        Ok(event) => event,
+        Err(RecvError::Lagged(_)) => {
+          // the state missed events, the snapshot that comes back has all of it,
+          // failing means nothing is connected, so there is nothing to catch up on
+          let _ = events.send(SpotifyMessage::RequestState).await;
+          continue;
+        }
+        Err(RecvError::Closed) => return Ok(()),
+      };
+
+      let seeked = {
+        let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+        let expected = state.position();
+
+        state.apply(&event);
+
+        matches!(event, SpotifyEvent::ProgressChanged { .. }) && state.position().abs_diff(expected) > SEEK_TOLERANCE
+      };
+
+      // a bus that went away will fail everything after, so it's only logged
+      if let Err(err) = changed(&player, &event, seeked).await {
+        log::warn!("couldn't send mpris update: {}", err);
+      }
+    }
+  }
+}
+
+/// Lets everything that's watching know what `event` changed
+async fn changed(player: &zbus::object_server::InterfaceRef<Player>, event: &SpotifyEvent, seeked: bool) -> zbus::Result<()> {
+  let context = player.signal_context();
+  let player = player.get().await;
+
+  match event {
+    SpotifyEvent::TrackChanged(_) => {
+      player.metadata_changed(context).await?;
+      player.playback_status_changed(context).await?;
+    }
+    SpotifyEvent::StateSnapshot(_) => {
+      player.metadata_changed(context).await?;
+      player.playback_status_changed(context).await?;
+      player.volume_changed(context).await?;
+      player.shuffle_changed(context).await?;
+      player.loop_status_changed(context).await?;
+      player.rate_changed(context).await?;
+    }
+    SpotifyEvent::StateChanged(_) => player.playback_status_changed(context).await?,
+    SpotifyEvent::VolumeChanged(_) | SpotifyEvent::DeviceChanged { .. } => player.volume_changed(context).await?,
+    SpotifyEvent::ShuffleChanged(_) => player.shuffle_changed(context).await?,
+    SpotifyEvent::RepeatChanged(_) => player.loop_status_changed(context).await?,
+    SpotifyEvent::PlaybackRateChanged(_) => player.rate_changed(context).await?,
+    SpotifyEvent::LikedChanged(_) => player.metadata_changed(context).await?,
+    _ => {}
+  }
+
+  if seeked {
+    let position = player.position();
+
+    Player::seeked(context, position).await?;
+  }
+
+  Ok(())
+}
+
+/// `org.mpris.MediaPlayer2`, about the app itself
+struct Root {
+  identity: String,
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl Root {
+  fn raise(&self) {}
+
+  fn quit(&self) {}
+
+  #[zbus(property)]
+  fn can_quit(&self) -> bool {
+    false
+  }
+
+  #[zbus(property)]
+  fn can_raise(&self) -> bool {
+    false
+  }
+
+  #[zbus(property)]
+  fn has_track_list(&self) -> bool {
+    false
+  }
+
+  #[zbus(property)]
+  fn identity(&self) -> String {
+    self.identity.clone()
+  }
+
+  #[zbus(property)]
+  fn desktop_entry(&self) -> String {
+    "spotify".to_string()
+  }
+
+  #[zbus(property)]
+  fn supported_uri_schemes(&self) -> Vec<String> {
+    Vec::new()
+  }
+
+  #[zbus(property)]
+  fn supported_mime_types(&self) -> Vec<String> {
+    Vec::new()
+  }
+}
+
+/// `org.mpris.MediaPlayer2.Player`, the track and controls
+struct Player {
+  state: Arc<Mutex<PlayerState>>,
+  events: SpotifyEvents,
+}
+
+impl Player {
+  fn with<T>(&self, f: impl FnOnce(&PlayerState) -> T) -> T {
+    f(&self.state.lock().unwrap_or_else(PoisonError::into_inner))
+  }
+
+  async fn send(&self, message: SpotifyMessage) -> fdo::Result<()> {
+    self.events.send(message).await.map_err(|err| fdo::Error::Failed(err.to_string()))
+  }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+  async fn next(&self) -> fdo::Result<()> {
+    self.send(SpotifyMessage::Next).await
+  }
+
+  async fn previous(&self) -> fdo::Result<()> {
+    self.send(SpotifyMessage::Previous).await
+  }
+
+  async fn pause(&self) -> fdo::Result<()> {
+    self.send(SpotifyMessage::Pause).await
+  }
+
+  async fn play_pause(&self) -> fdo::Result<()> {
+    self.send(SpotifyMessage::TogglePlayback).await
+  }
+
+  /// Spotify can't stop, only pause
+  async fn stop(&self) -> fdo::Result<()> {
+    self.send(SpotifyMessage::Pause).await
+  }
+
+  async fn play(&self) -> fdo::Result<()> {
+    self.send(SpotifyMessage::Play).await
+  }
+
+  /// `offset` in microseconds from the current position
+  async fn seek(&self, offset: i64) -> fdo::Result<()> {
+    let position = self.with(|state| state.position().as_micros() as i64).saturating_add(offset);
+
+    self.send(SpotifyMessage::Seek { position_ms: (position.max(0) / 1000) as u64 }).await
+  }
+
+  /// Ignored if `track_id` isn't the current track anymore, like the spec says
+  async fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+    if self.with(|state| track_id.as_str() != track_path(state.current_track()).as_str()) || position < 0 {
+      return Ok(());
+    }
+
+    self.send(SpotifyMessage::Seek { position_ms: (position / 1000) as u64 }).await
+  }
+
+  fn open_uri(&self, _uri: &str) -> fdo::Result<()> {
+    Err(fdo::Error::NotSupported("opening uris isn't supported".to_string()))
+  }
+
+  #[zbus(signal)]
+  async fn seeked(context: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+  #[zbus(property)]
+  fn playback_status(&self) -> String {
+    let status = match self.with(PlayerState::state) {
+      TrackState::Playing => "Playing",
+      TrackState::Paused => "Paused",
+      TrackState::Stopped => "Stopped",
+    };
+
+    status.to_string()
+  }
+
+  #[zbus(property)]
+  fn loop_status(&self) -> String {
+    let status = match self.with(PlayerState::repeat) {
+      RepeatMode::Off => "None",
+      RepeatMode::Context => "Playlist",
+      RepeatMode::Track => "Track",
+    };
+
+    status.to_string()
+  }
+
+  #[zbus(property)]
+  async fn set_loop_status(&mut self, status: String) -> fdo::Result<()> {
+    let mode = match status.as_str() {
+      "None" => RepeatMode::Off,
+      "Playlist" => RepeatMode::Context,
+      "Track" => RepeatMode::Track,
+      _ => return Err(fdo::Error::InvalidArgs(format!("unknown loop status {}", status))),
+    };
+
+    self.send(SpotifyMessage::SetRepeat(mode)).await
+  }
+
+  #[zbus(property)]
+  fn rate(&self) -> f64 {
+    self.with(PlayerState::playback_rate) as f64
+  }
+
+  #[zbus(property)]
+  fn minimum_rate(&self) -> f64 {
+    0.5
+  }
+
+  #[zbus(property)]
+  fn maximum_rate(&self) -> f64 {
+    3.5
+  }
+
+  #[zbus(property)]
+  fn shuffle(&self) -> bool {
+    self.with(PlayerState::shuffle)
+  }
+
+  #[zbus(property)]
+  async fn set_shuffle(&mut self, shuffle: bool) -> fdo::Result<()> {
+    self.send(SpotifyMessage::SetShuffle(shuffle)).await
+  }
+
+  #[zbus(property)]
+  fn metadata(&self) -> HashMap<String, OwnedValue> {
+    self.with(|state| metadata(state.current_track()))
+  }
+
+  #[zbus(property)]
+  fn volume(&self) -> f64 {
+    self.with(PlayerState::volume)
+  }
+
+  #[zbus(property)]
+  async fn set_volume(&mut self, volume: f64) -> fdo::Result<()> {
+    self.send(SpotifyMessage::SetVolume(volume.clamp(0.0, 1.0))).await
+  }
+
+  /// In microseconds, widgets ask for it instead of getting told since it changes all the time
+  #[zbus(property(emits_changed_signal = "false"))]
+  fn position(&self) -> i64 {
+    self.with(|state| state.position().as_micros() as i64)
+  }
+
+  #[zbus(property)]
+  fn can_go_next(&self) -> bool {
+    true
+  }
+
+  #[zbus(property)]
+  fn can_go_previous(&self) -> bool {
+    true
+  }
+
+  #[zbus(property)]
+  fn can_play(&self) -> bool {
+    true
+  }
+
+  #[zbus(property)]
+  fn can_pause(&self) -> bool {
+    true
+  }
+
+  #[zbus(property)]
+  fn can_seek(&self) -> bool {
+    true
+  }
+
+  #[zbus(property(emits_changed_signal = "const"))]
+  fn can_control(&self) -> bool {
+    true
+  }
+}
+
+/// `/org/mpris/MediaPlayer2/Track/spotify_track_4uLU6hMCjMI75M1A2tKUQC`, ids can only have letters, digits and `_`
+fn track_path(track: Option<&TrackInfo>) -> OwnedObjectPath {
+  let id = match track {
+    Some(track) if !track.uri.is_empty() => track.uri.as_str(),
+    Some(track) if !track.uid.is_empty() => track.uid.as_str(),
+    _ => return OwnedObjectPath::try_from(NO_TRACK).expect("valid path"),
+  };
+
+  let path = format!("{}/Track/{}", PATH, id.replace(|c: char| !c.is_ascii_alphanumeric(), "_"));
+
+  OwnedObjectPath::try_from(path).expect("only valid characters")
+}
+
+fn metadata(track: Option<&TrackInfo>) -> HashMap<String, OwnedValue> {
+  let mut metadata = HashMap::new();
+  let mut insert = |key: &str, value: Value<'_>| {
+    metadata.insert(key.to_string(), value.try_to_owned().expect("no file descriptors"));
+  };
+
+  insert("mpris:trackid", Value::from(track_path(track)));
+
+  let track = match track {
+    Some(track) => track,
+    None => return metadata,
+  };
+
+  insert("mpris:length", Value::from(track.duration.as_micros() as i64));
+  insert("xesam:title", Value::from(track.title.as_str()));
+  insert("xesam:artist", Value::from(track.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>()));
+
+  if !track.album.name.is_empty() {
+    insert("xesam:album", Value::from(track.album.name.as_str()));
+  }
+
+  if let Some(cover) = track.cover() {
+    insert("mpris:artUrl", Value::from(cover));
+  }
+
+  if let Some(url) = track.uri.to_url() {
+    insert("xesam:url", Value::from(url));
+  }
+
+  if let Some(number) = track.track_number {
+    insert("xesam:trackNumber", Value::from(number as i32));
+  }
+
+  if let Some(number) = track.disc_number {
+    insert("xesam:discNumber", Value::from(number as i32));
+  }
+
+  // the closest thing MPRIS has to liked
+  if let Some(liked) = track.liked {
+    insert("xesam:userRating", Value::from(if liked { 1.0 } else { 0.0 }));
+  }
+
+  metadata
+}
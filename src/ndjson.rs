@@ -0,0 +1,62 @@
+use std::net::SocketAddr;
+
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+use crate::{SpotifyError, SpotifyEvent, SpotifyEvents, SpotifyResult};
+
+/// Sends every event as one line of json to anything that connects over plain tcp,
+/// for things that can't speak websockets, like `nc 127.0.0.1 19533` in a shell script
+///
+/// Lines look exactly like what the extension sends, for example `{"type":"StateChanged","data":2}`
+#[derive(Debug)]
+pub struct NdjsonServer {
+  listener: TcpListener,
+}
+
+impl NdjsonServer {
+  /// Binds to the given address, this has to be a different port than the [SpotifyListener](crate::SpotifyListener)
+  pub async fn bind(addr: SocketAddr) -> SpotifyResult<Self> {
+    let listener = TcpListener::bind(addr).await.map_err(SpotifyError::Bind)?;
+
+    Ok(Self { listener })
+  }
+
+  pub fn local_addr(&self) -> SpotifyResult<SocketAddr> {
+    self.listener.local_addr().map_err(SpotifyError::Bind)
+  }
+
+  /// Keeps accepting clients and sends them every event from `events` starting from when they connect,
+  /// clients that read too slowly skip events instead of slowing down everyone else
+  ///
+  /// Only returns when the listener stops accepting connections
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn serve(self, events: SpotifyEvents) -> SpotifyResult<()> {
+    loop {
+      let (stream, _) = self.listener.accept().await.map_err(SpotifyError::Accept)?;
+
+      tokio::spawn(send_lines(stream, events.subscribe()));
+    }
+  }
+}
+
+async fn send_lines(mut stream: TcpStream, mut events: Receiver<SpotifyEvent>) {
+  loop {
+    let event = match events.recv().await {
+      Ok(event) => event,
+      Err(RecvError::Lagged(_)) => continue,
+      Err(RecvError::Closed) => return,
+    };
+
+    let mut line = serde_json::to_vec(&event).expect("events always serialize");
+    line.push(b'\n');
+
+    // the client went away
+    if stream.write_all(&line).await.is_err() {
+      return;
+    }
+  }
+}
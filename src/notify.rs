@@ -0,0 +1,273 @@
+//! Desktop notifications when the track changes, only with the `notify` feature
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use notify_rust::Notification;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{Image, ImageClient, PlayerState, SpotifyEvent, SpotifyEvents, Template, TrackInfo};
+
+/// Shows a desktop notification with the title, artist and cover whenever the track changes,
+/// through the notification daemon on linux and bsd, notification center on macos and toasts on windows
+///
+/// A notification only shows once the track played for [Self::delay], so skipping through a few tracks
+/// only shows the one that stayed. On linux and bsd every notification replaces the one before it
+///
+/// Default: `{title}` and `{artist}` as the summary and body, 1 second delay, with covers,
+/// `{title}` etc. are [Template]s with everything from [PlayerState]
+///
+/// ```text
+/// tokio::spawn(Notifier::new().run(listener.events(64)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Notifier {
+  summary: Template,
+  body: Template,
+  app_name: String,
+  delay: Duration,
+  covers: bool,
+  client: ImageClient,
+}
+
+impl Default for Notifier {
+  fn default() -> Self {
+    Self {
+      summary: Template::parse("{title}").expect("valid template"),
+      body: Template::parse("{artist}").expect("valid template"),
+      app_name: "Spotify".to_string(),
+      delay: Duration::from_secs(1),
+      covers: true,
+      client: ImageClient::default(),
+    }
+  }
+}
+
+impl Notifier {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// First line, usually in bold
+  pub fn summary(mut self, template: Template) -> Self {
+    self.summary = template;
+    self
+  }
+
+  /// Everything under [Self::summary], some notification daemons understand a bit of html in it
+  pub fn body(mut self, template: Template) -> Self {
+    self.body = template;
+    self
+  }
+
+  /// Who the notification is from, doesn't do anything on macos
+  ///
+  /// Default: `Spotify`
+  pub fn app_name(mut self, name: impl Into<String>) -> Self {
+    self.app_name = name.into();
+    self
+  }
+
+  /// How long a track has to stay before it gets a notification, 0 shows every track right away
+  pub fn delay(mut self, delay: Duration) -> Self {
+    self.delay = delay;
+    self
+  }
+
+  /// Turn off to leave the cover out, so nothing gets downloaded
+  pub fn covers(mut self, covers: bool) -> Self {
+    self.covers = covers;
+    self
+  }
+
+  /// Downloads covers with this client instead of the default one
+  pub fn client(mut self, client: ImageClient) -> Self {
+    self.client = client;
+    self
+  }
+
+  /// Shows notifications for `events` until the listener is gone
+  ///
+  /// Errors get logged with the `log` crate, a cover that can't be downloaded only leaves the cover out
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn run(self, events: SpotifyEvents) {
+    let mut events = events.into_receiver();
+    let mut player = PlayerState::new();
+    // when the track that's waiting for [Self::delay] gets shown
+    let mut due: Option<Instant> = None;
+    let mut shown: Option<TrackInfo> = None;
+    let mut replaces: Option<u32> = None;
+
+    loop {
+      let event = match due {
+        Some(at) => match tokio::time::timeout(at.saturating_duration_since(Instant::now()), events.recv()).await {
+          Ok(event) => event,
+          Err(_) => {
+            due = None;
+
+            if let Some(track) = player.current_track() {
+              shown = Some(track.clone());
+              replaces = self.show(&player, replaces).await;
+            }
+
+            continue;
+          }
+        },
+        None => events.recv().await,
+      };
+
+      match event {
+        Ok(SpotifyEvent::TrackChanged(info)) => {
+          // the same track again, like after reconnecting, only counts when another one is waiting
+          if due.is_some() || !shown.as_ref().is_some_and(|track| track.eq_ignore_state(&info) && track.uri == info.uri) {
+            due = Some(crate::instant_after(self.delay));
+          }
+
+          player.apply(&SpotifyEvent::TrackChanged(info));
+        }
+        Ok(SpotifyEvent::SessionEnded { .. }) => {
+          player = PlayerState::new();
+          due = None;
+        }
+        Ok(event) => player.apply(&event),
+        Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => break,
+      }
+    }
+  }
+
+  /// Returns the id to replace it with the next one, when the platform has one
+  async fn show(&self, player: &PlayerState, replaces: Option<u32>) -> Option<u32> {
+    let mut notification = Notification::new();
+    notification.appname(&self.app_name).summary(&self.summary.render(player)).body(&self.body.render(player));
+
+    if let Some(path) = self.cover(player).await {
+      notification.image_path(&path.to_string_lossy());
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    if let Some(id) = replaces {
+      notification.id(id);
+    }
+
+    // every platform's `show` blocks
+    let shown = tokio::task::spawn_blocking(move || notification.show().map(|handle| handle_id(&handle))).await;
+
+    match shown {
+      Ok(Ok(id)) => id,
+      Ok(Err(err)) => {
+        log::warn!("couldn't show notification: {}", err);
+        replaces
+      }
+      Err(_) => replaces,
+    }
+  }
+
+  /// Downloads the cover into a file, that's the only way every platform takes it
+  async fn cover(&self, player: &PlayerState) -> Option<PathBuf> {
+    let url = player.current_track()?.cover().filter(|_| self.covers)?;
+
+    let image = match self.client.fetch(url).await {
+      Ok(image) => image,
+      Err(err) => {
+        log::warn!("couldn't download cover for notification: {}", err);
+        return None;
+      }
+    };
+
+    let written = tokio::task::spawn_blocking(move || write_cover(&cover_dir(), &image)).await;
+
+    match written {
+      Ok(Ok(path)) => Some(path),
+      Ok(Err(err)) => {
+        log::warn!("couldn't save cover for notification: {}", err);
+        None
+      }
+      Err(_) => None,
+    }
+  }
+}
+
+/// `spotify_info/notify` in the user's cache directory so other users can't put anything there,
+/// the temp directory only on platforms without one
+fn cover_dir() -> PathBuf {
+  dirs::cache_dir().map_or_else(std::env::temp_dir, |dir| dir.join("spotify_info").join("notify"))
+}
+
+/// Always the same file in `dir` per process, the last notification already has the image by the time it's written again
+fn write_cover(dir: &Path, image: &Image) -> io::Result<PathBuf> {
+  // windows only shows it with the right extension
+  let extension = match image.content_type.as_deref() {
+    Some("image/png") => "png",
+    _ => "jpg",
+  };
+
+  fs::create_dir_all(dir)?;
+
+  let path = dir.join(format!("spotify_info-cover-{}.{}", std::process::id(), extension));
+
+  // somewhere else first so the notification never gets half a file,
+  // made new every time so it never writes through a symlink someone left there
+  let tmp = path.with_extension("tmp");
+
+  match fs::remove_file(&tmp) {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+    _ => {}
+  }
+
+  OpenOptions::new().write(true).create_new(true).open(&tmp)?.write_all(&image.bytes)?;
+  // replaces whatever `path` is, even a symlink, instead of following it
+  fs::rename(&tmp, &path)?;
+
+  Ok(path)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn handle_id(handle: &notify_rust::NotificationHandle) -> Option<u32> {
+  Some(handle.id())
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn handle_id<T>(_: &T) -> Option<u32> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn image() -> Image {
+    Image { bytes: b"cover".to_vec(), content_type: Some("image/png".to_string()) }
+  }
+
+  #[test]
+  fn writes_the_cover() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_cover(&dir.path().join("new"), &image()).unwrap();
+
+    assert_eq!(path.extension().unwrap(), "png");
+    assert_eq!(fs::read(&path).unwrap(), b"cover");
+    assert!(!path.with_extension("tmp").exists());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn doesnt_follow_symlinks() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("target");
+    fs::write(&target, "someone else's").unwrap();
+
+    let name = format!("spotify_info-cover-{}", std::process::id());
+    std::os::unix::fs::symlink(&target, dir.path().join(format!("{}.tmp", name))).unwrap();
+    std::os::unix::fs::symlink(&target, dir.path().join(format!("{}.png", name))).unwrap();
+
+    let path = write_cover(dir.path(), &image()).unwrap();
+
+    assert_eq!(fs::read_to_string(&target).unwrap(), "someone else's");
+    assert_eq!(fs::read(&path).unwrap(), b"cover");
+    assert!(!fs::symlink_metadata(&path).unwrap().file_type().is_symlink());
+  }
+}
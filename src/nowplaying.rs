@@ -0,0 +1,232 @@
+//! macOS' Now Playing widget and media keys, only with the `now-playing` feature
+
+use std::pin::pin;
+use std::ptr::NonNull;
+use std::sync::mpsc;
+
+use block2::RcBlock;
+use futures_util::future::{select, Either};
+use objc2::rc::Retained;
+use objc2::runtime::AnyObject;
+use objc2_foundation::{NSDefaultRunLoopMode, NSDictionary, NSNumber, NSPort, NSRunLoop, NSString};
+use objc2_media_player::{
+  MPChangePlaybackPositionCommandEvent, MPMediaItemPropertyAlbumTitle, MPMediaItemPropertyAlbumTrackNumber, MPMediaItemPropertyArtist,
+  MPMediaItemPropertyPlaybackDuration, MPMediaItemPropertyTitle, MPNowPlayingInfoCenter, MPNowPlayingInfoPropertyElapsedPlaybackTime,
+  MPNowPlayingInfoPropertyPlaybackRate, MPNowPlayingPlaybackState, MPRemoteCommand, MPRemoteCommandCenter, MPRemoteCommandEvent,
+  MPRemoteCommandHandlerStatus,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc as tokio_mpsc;
+
+use crate::{PlayerSnapshot, PlayerState, SpotifyEvent, SpotifyEvents, SpotifyMessage, TrackState};
+
+/// Shows what's playing in macos' Now Playing widget (in the menu bar and control center)
+/// and sends the media keys and the widget's buttons to spotify
+///
+/// Play, pause, next, previous and seeking go to spotify as [SpotifyMessage]s,
+/// stop pauses since spotify can't stop
+///
+/// macos only shows one app there at a time, spotify's own integration might still win over this one while it's running.
+/// There's no cover, macos wants the image itself instead of a link to it
+///
+/// The commands arrive on the main thread, so nothing comes in unless it runs a run loop,
+/// [Self::run_main_loop] does that for programs that don't have one already (like an `NSApplication`):
+///
+/// ```text
+/// std::thread::spawn(move || runtime.block_on(NowPlayingServer::new().serve(listener.events(64))));
+/// NowPlayingServer::run_main_loop();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NowPlayingServer {}
+
+impl NowPlayingServer {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Keeps the widget up to date with `events` until the listener is gone, then clears it,
+  /// commands go to the connection behind `events`
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn serve(self, events: SpotifyEvents) {
+    let mut receiver = events.subscribe();
+    let (updates, updates_rx) = mpsc::channel();
+    let (commands_tx, mut commands) = tokio_mpsc::unbounded_channel();
+
+    // none of it can leave the thread that made it
+    std::thread::spawn(move || {
+      let controls = Controls::new(commands_tx);
+
+      for snapshot in updates_rx {
+        controls.update(&snapshot);
+      }
+
+      controls.remove();
+    });
+
+    let mut state = PlayerState::new();
+
+    loop {
+      match select(pin!(receiver.recv()), pin!(commands.recv())).await {
+        Either::Left((Ok(event), _)) => {
+          // only what the widget shows
+          if !matches!(event, SpotifyEvent::TrackChanged(_) | SpotifyEvent::StateChanged(_) | SpotifyEvent::StateSnapshot(_)
+            | SpotifyEvent::ProgressChanged { .. } | SpotifyEvent::PlaybackRateChanged(_)) {
+            continue;
+          }
+
+          state.apply(&event);
+
+          if updates.send(state.snapshot()).is_err() {
+            return;
+          }
+        }
+        Either::Left((Err(RecvError::Lagged(_)), _)) => continue,
+        Either::Left((Err(RecvError::Closed), _)) => return,
+        Either::Right((Some(message), _)) => {
+          if let Err(err) = events.send(message).await {
+            log::warn!("couldn't send media key to spotify: {}", err);
+          }
+        }
+        // only ends with the thread, which only ends when this is gone
+        Either::Right((None, _)) => return,
+      }
+    }
+  }
+
+  /// Runs the main thread's run loop forever so the commands from [Self::serve] arrive,
+  /// tokio has to run on another thread then
+  ///
+  /// **NOTE**: Must be called from the main thread
+  pub fn run_main_loop() -> ! {
+    unsafe {
+      let main = NSRunLoop::mainRunLoop();
+      // a run loop without anything in it returns right away, the port never gets anything
+      main.addPort_forMode(&NSPort::port(), NSDefaultRunLoopMode);
+
+      loop {
+        main.run();
+      }
+    }
+  }
+}
+
+struct Controls {
+  center: Retained<MPNowPlayingInfoCenter>,
+  /// Every command along with what it has to be given back to be removed
+  targets: Vec<(Retained<MPRemoteCommand>, Retained<AnyObject>)>,
+}
+
+impl Controls {
+  fn new(commands: tokio_mpsc::UnboundedSender<SpotifyMessage>) -> Self {
+    let center = unsafe { MPNowPlayingInfoCenter::defaultCenter() };
+    let remote = unsafe { MPRemoteCommandCenter::sharedCommandCenter() };
+
+    let mut controls = Self { center, targets: Vec::new() };
+
+    unsafe {
+      controls.on(remote.playCommand(), &commands, |_| Some(SpotifyMessage::Play));
+      controls.on(remote.pauseCommand(), &commands, |_| Some(SpotifyMessage::Pause));
+      controls.on(remote.stopCommand(), &commands, |_| Some(SpotifyMessage::Pause));
+      controls.on(remote.togglePlayPauseCommand(), &commands, |_| Some(SpotifyMessage::TogglePlayback));
+      controls.on(remote.nextTrackCommand(), &commands, |_| Some(SpotifyMessage::Next));
+      controls.on(remote.previousTrackCommand(), &commands, |_| Some(SpotifyMessage::Previous));
+      controls.on(Retained::into_super(remote.changePlaybackPositionCommand()), &commands, |event| {
+        // it's only ever called with its own kind of event
+        let event = event.cast::<MPChangePlaybackPositionCommandEvent>().as_ref();
+        let position = event.positionTime().max(0.0);
+
+        Some(SpotifyMessage::Seek { position_ms: (position * 1000.0) as u64 })
+      });
+    }
+
+    controls
+  }
+
+  /// Enables `command` and sends what `message` makes of its events
+  unsafe fn on<F>(&mut self, command: Retained<MPRemoteCommand>, commands: &tokio_mpsc::UnboundedSender<SpotifyMessage>, message: F)
+  where
+    F: Fn(NonNull<MPRemoteCommandEvent>) -> Option<SpotifyMessage> + 'static,
+  {
+    let commands = commands.clone();
+    let handler = RcBlock::new(move |event: NonNull<MPRemoteCommandEvent>| {
+      match message(event).map(|message| commands.send(message)) {
+        Some(Ok(())) => MPRemoteCommandHandlerStatus::Success,
+        _ => MPRemoteCommandHandlerStatus::CommandFailed,
+      }
+    });
+
+    command.setEnabled(true);
+    let target = command.addTargetWithHandler(&handler);
+
+    self.targets.push((command, target));
+  }
+
+  fn update(&self, snapshot: &PlayerSnapshot) {
+    let track = match &snapshot.track {
+      Some(track) => track,
+      None => return self.clear(),
+    };
+
+    let mut keys = Vec::<&NSString>::new();
+    let mut values = Vec::<Retained<AnyObject>>::new();
+    let mut add = |key: &'static NSString, value: Retained<AnyObject>| {
+      keys.push(key);
+      values.push(value);
+    };
+
+    let artists = track.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>().join(", ");
+
+    unsafe {
+      add(MPMediaItemPropertyTitle, string(&track.title));
+      add(MPMediaItemPropertyArtist, string(&artists));
+      add(MPMediaItemPropertyAlbumTitle, string(&track.album.name));
+      add(MPMediaItemPropertyPlaybackDuration, number(NSNumber::new_f64(track.duration.as_secs_f64())));
+      add(MPNowPlayingInfoPropertyElapsedPlaybackTime, number(NSNumber::new_f64(snapshot.position.as_secs_f64())));
+      // 0 while paused, otherwise macos keeps counting the position up
+      add(MPNowPlayingInfoPropertyPlaybackRate, number(NSNumber::new_f64(match snapshot.state {
+        TrackState::Playing => snapshot.playback_rate as f64,
+        _ => 0.0,
+      })));
+
+      if let Some(track_number) = track.track_number {
+        add(MPMediaItemPropertyAlbumTrackNumber, number(NSNumber::new_u32(track_number)));
+      }
+
+      let info = NSDictionary::from_vec(&keys, values);
+      self.center.setNowPlayingInfo(Some(&info));
+      self.center.setPlaybackState(match snapshot.state {
+        TrackState::Playing => MPNowPlayingPlaybackState::Playing,
+        TrackState::Paused => MPNowPlayingPlaybackState::Paused,
+        TrackState::Stopped => MPNowPlayingPlaybackState::Stopped,
+      });
+    }
+  }
+
+  fn clear(&self) {
+    unsafe {
+      self.center.setNowPlayingInfo(None);
+      self.center.setPlaybackState(MPNowPlayingPlaybackState::Stopped);
+    }
+  }
+
+  fn remove(self) {
+    self.clear();
+
+    for (command, target) in self.targets {
+      unsafe {
+        command.removeTarget(Some(&target));
+        command.setEnabled(false);
+      }
+    }
+  }
+}
+
+fn string(text: &str) -> Retained<AnyObject> {
+  Retained::into_super(Retained::into_super(NSString::from_str(text)))
+}
+
+fn number(number: Retained<NSNumber>) -> Retained<AnyObject> {
+  // NSNumber, NSValue, NSObject
+  Retained::into_super(Retained::into_super(Retained::into_super(number)))
+}
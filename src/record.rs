@@ -0,0 +1,257 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{serde_utils, SpotifyEvent};
+
+/// One line of a recording from [EventRecorder]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedEvent {
+  /// When it was recorded, since the recorder was created,
+  /// keeps counting up across rotated files and never jumps when the system clock changes
+  #[serde(with = "serde_utils::millis")]
+  pub at: Duration,
+  pub event: SpotifyEvent,
+}
+
+/// Writes every event it gets to a file as one line of json each, for capturing a real listening session
+/// to debug it or use it in tests later
+///
+/// Lines look like `{"at":1520,"event":{"type":"StateChanged","data":2}}`,
+/// every line gets written right away so nothing is lost when the program crashes
+///
+/// Default: one file that grows forever, see [Self::rotate]
+#[derive(Debug)]
+pub struct EventRecorder {
+  path: PathBuf,
+  file: File,
+  started: Instant,
+  /// Bytes in the current file
+  written: u64,
+  rotate: Option<(u64, usize)>,
+}
+
+impl EventRecorder {
+  /// Records to `path`, replacing what was there and creating missing directories
+  pub fn create(path: impl Into<PathBuf>) -> io::Result<Self> {
+    let path = path.into();
+
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+
+    Ok(Self {
+      file: File::create(&path)?,
+      path,
+      started: Instant::now(),
+      written: 0,
+      rotate: None,
+    })
+  }
+
+  /// Once the file is bigger than `max_size` bytes it gets renamed to `path.1` (the one before that to `path.2` and so on)
+  /// and a new one is started, only the newest `max_files` old files are kept
+  pub fn rotate(mut self, max_size: u64, max_files: usize) -> Self {
+    self.rotate = Some((max_size, max_files));
+    self
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Writes `event` to the file, with how long it's been since this was created
+  pub fn record(&mut self, event: &SpotifyEvent) -> io::Result<()> {
+    let recorded = Recorded {
+      at: self.started.elapsed(),
+      event,
+    };
+
+    let mut line = serde_json::to_vec(&recorded).expect("events always serialize");
+    line.push(b'\n');
+
+    if let Some((max_size, max_files)) = self.rotate {
+      if self.written > 0 && self.written + line.len() as u64 > max_size {
+        self.rotate_files(max_files)?;
+      }
+    }
+
+    // one write per line so other programs tailing it never see half of one
+    self.file.write_all(&line)?;
+    self.written += line.len() as u64;
+
+    Ok(())
+  }
+
+  fn rotate_files(&mut self, max_files: usize) -> io::Result<()> {
+    let rotated = self.shift_files(max_files);
+
+    // even when shifting failed part of the way, so the next line doesn't go to a file that got renamed,
+    // whatever is still in `path` stays there and rotating gets tried again with the next line
+    self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+    self.written = self.file.metadata()?.len();
+
+    rotated
+  }
+
+  /// Moves `path` to `path.1` and every older file up by one
+  fn shift_files(&self, max_files: usize) -> io::Result<()> {
+    let numbered = |n: usize| {
+      let mut path = self.path.clone().into_os_string();
+      path.push(format!(".{}", n));
+      PathBuf::from(path)
+    };
+
+    if max_files == 0 {
+      fs::remove_file(&self.path)?;
+    } else {
+      let _ = fs::remove_file(numbered(max_files));
+
+      for n in (1..max_files).rev() {
+        match fs::rename(numbered(n), numbered(n + 1)) {
+          Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+          _ => {}
+        }
+      }
+
+      fs::rename(&self.path, numbered(1))?;
+    }
+
+    Ok(())
+  }
+}
+
+/// [RecordedEvent] without cloning the event
+#[derive(Serialize)]
+struct Recorded<'a> {
+  #[serde(with = "serde_utils::millis")]
+  at: Duration,
+  event: &'a SpotifyEvent,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::TrackState;
+
+  fn event() -> SpotifyEvent {
+    SpotifyEvent::StateChanged(TrackState::Playing)
+  }
+
+  /// Big enough for one line but not two
+  fn line_size() -> u64 {
+    let recorded = Recorded { at: Duration::from_secs(1), event: &event() };
+
+    serde_json::to_vec(&recorded).unwrap().len() as u64 + 1
+  }
+
+  fn lines(path: &Path) -> usize {
+    fs::read_to_string(path).unwrap().lines().count()
+  }
+
+  fn numbered(path: &Path, n: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}", path.display(), n))
+  }
+
+  #[test]
+  fn records_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested/events.jsonl");
+    let mut recorder = EventRecorder::create(&path).unwrap();
+
+    recorder.record(&event()).unwrap();
+    recorder.record(&SpotifyEvent::VolumeChanged(0.5)).unwrap();
+
+    let text = fs::read_to_string(&path).unwrap();
+    let recorded: Vec<RecordedEvent> = text.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+    assert!(matches!(recorded[..], [RecordedEvent { event: SpotifyEvent::StateChanged(_), .. }, RecordedEvent { event: SpotifyEvent::VolumeChanged(_), .. }]));
+    assert!(recorded[0].at <= recorded[1].at);
+  }
+
+  #[test]
+  fn shifts_old_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+    let mut recorder = EventRecorder::create(&path).unwrap().rotate(line_size() + 10, 2);
+
+    for _ in 0..4 {
+      recorder.record(&event()).unwrap();
+    }
+
+    // the newest line in path, the one before in .1 and so on, the oldest one is gone
+    assert_eq!(lines(&path), 1);
+    assert_eq!(lines(&numbered(&path, 1)), 1);
+    assert_eq!(lines(&numbered(&path, 2)), 1);
+    assert!(!numbered(&path, 3).exists());
+  }
+
+  #[test]
+  fn no_old_files_with_max_files_0() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+    let mut recorder = EventRecorder::create(&path).unwrap().rotate(line_size() + 10, 0);
+
+    for _ in 0..3 {
+      recorder.record(&event()).unwrap();
+    }
+
+    assert_eq!(lines(&path), 1);
+    assert!(!numbered(&path, 1).exists());
+  }
+
+  #[test]
+  fn lines_bigger_than_max_size_still_get_written() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+    let mut recorder = EventRecorder::create(&path).unwrap().rotate(1, 1);
+
+    recorder.record(&event()).unwrap();
+    recorder.record(&event()).unwrap();
+
+    assert_eq!(lines(&path), 1);
+    assert_eq!(lines(&numbered(&path, 1)), 1);
+  }
+
+  #[test]
+  fn keeps_rotating_after_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+
+    for _ in 0..2 {
+      let mut recorder = EventRecorder::create(&path).unwrap().rotate(line_size() + 10, 3);
+
+      recorder.record(&event()).unwrap();
+      recorder.record(&event()).unwrap();
+    }
+
+    // the files from before got shifted instead of replaced, only path itself starts over
+    assert_eq!(lines(&path), 1);
+    assert_eq!(lines(&numbered(&path, 1)), 1);
+    assert_eq!(lines(&numbered(&path, 2)), 1);
+    assert!(!numbered(&path, 3).exists());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn keeps_writing_to_path_when_rotating_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("events.jsonl");
+    let mut recorder = EventRecorder::create(&path).unwrap().rotate(line_size() + 10, 2);
+
+    recorder.record(&event()).unwrap();
+    recorder.record(&event()).unwrap();
+
+    // .1 can't be moved to .2 when .2 is a directory that isn't empty
+    fs::create_dir(numbered(&path, 2)).unwrap();
+    fs::write(numbered(&path, 2).join("file"), "").unwrap();
+
+    assert!(recorder.record(&event()).is_err());
+    // the line that failed isn't written but the next one goes to path instead of the renamed file
+    recorder.record(&event()).ok();
+    assert!(lines(&path) >= 1);
+    assert_eq!(lines(&numbered(&path, 1)), 1);
+  }
+}
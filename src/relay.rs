@@ -0,0 +1,88 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use futures_util::{SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio_tungstenite::accept_async;
+use tungstenite::Message;
+
+use crate::{PlayerSnapshot, SpotifyError, SpotifyEvent, SpotifyEvents, SpotifyResult};
+
+/// Websocket server that sends every event to everything connected to it,
+/// for things like an OBS browser source that want the same events spotify sends
+///
+/// Clients get a [SpotifyEvent::StateSnapshot] when they connect so they don't start out empty,
+/// after that they get every event in the same json as the extension sends
+#[derive(Debug)]
+pub struct RelayServer {
+  listener: TcpListener,
+}
+
+impl RelayServer {
+  /// Binds to the given address, this has to be a different port than the [SpotifyListener](crate::SpotifyListener)
+  pub async fn bind(addr: SocketAddr) -> SpotifyResult<Self> {
+    let listener = TcpListener::bind(addr).await.map_err(SpotifyError::Bind)?;
+
+    Ok(Self { listener })
+  }
+
+  pub fn local_addr(&self) -> SpotifyResult<SocketAddr> {
+    self.listener.local_addr().map_err(SpotifyError::Bind)
+  }
+
+  /// Keeps accepting clients and sends them every event from `events`,
+  /// clients that read too slowly skip events instead of slowing down everyone else
+  ///
+  /// The snapshot only knows about events sent after this gets called,
+  /// so it's best to call it before spotify connects
+  ///
+  /// Only returns when the listener stops accepting connections
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn serve(self, events: SpotifyEvents) -> SpotifyResult<()> {
+    let snapshot = Arc::new(RwLock::new(PlayerSnapshot::default()));
+
+    tokio::spawn(crate::track_snapshot(events.subscribe(), snapshot.clone()));
+
+    loop {
+      let (stream, _) = self.listener.accept().await.map_err(SpotifyError::Accept)?;
+      // subscribe before reading the snapshot so nothing falls in between
+      let receiver = events.subscribe();
+      let snapshot = snapshot.read().unwrap_or_else(PoisonError::into_inner).clone();
+
+      tokio::spawn(relay(stream, receiver, snapshot));
+    }
+  }
+}
+
+async fn relay(stream: TcpStream, mut events: Receiver<SpotifyEvent>, snapshot: PlayerSnapshot) {
+  let (mut sink, mut incoming) = match accept_async(stream).await {
+    Ok(ws) => ws.split(),
+    Err(_) => return,
+  };
+
+  // nothing clients send matters, this only answers pings and notices when they leave
+  tokio::spawn(async move { while let Some(Ok(_)) = incoming.next().await {} });
+
+  let mut next = Some(SpotifyEvent::StateSnapshot(snapshot));
+
+  loop {
+    let event = match next.take() {
+      Some(event) => event,
+      None => match events.recv().await {
+        Ok(event) => event,
+        Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => return,
+      },
+    };
+
+    let text = serde_json::to_string(&event).expect("events always serialize");
+
+    // the client went away
+    if sink.send(Message::Text(text)).await.is_err() {
+      return;
+    }
+  }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use futures_util::future::{select, Either};
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::DuplexStream;
+use tokio_tungstenite::WebSocketStream;
+use tungstenite::Message;
+
+use crate::runtime::Runtime;
+use crate::{handshake, Codec, PlayerSnapshot, RecordedEvent, SpotifyAcceptor, SpotifyConnection, SpotifyEvent, SpotifyListener, SpotifyMessage, SpotifyResult};
+
+/// Plays a recording from [EventRecorder](crate::EventRecorder) back as if spotify was running,
+/// for working on a UI without having to play music
+///
+/// The connection is a normal [SpotifyConnection] that went through the handshake and everything set on the listener,
+/// only it's over an in-memory stream to a fake extension instead of a socket
+///
+/// The fake extension answers [SpotifyMessage::RequestState] with the state so far and ignores every other message,
+/// events the listener makes itself (like [SpotifyEvent::TrackFinished]) get left out since the listener makes them again
+///
+/// Default: original timing, plays once then closes the connection
+#[derive(Debug, Clone)]
+pub struct EventReplayer {
+  events: Vec<RecordedEvent>,
+  speed: f64,
+  repeat: bool,
+}
+
+impl EventReplayer {
+  /// Reads a recording, fails with [io::ErrorKind::InvalidData] if a line isn't a [RecordedEvent]
+  pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+    let text = fs::read_to_string(path)?;
+    let events = text.lines()
+      .enumerate()
+      .filter(|(_, line)| !line.trim().is_empty())
+      .map(|(n, line)| {
+        serde_json::from_str(line).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", n + 1, err)))
+      })
+      .collect::<io::Result<Vec<_>>>()?;
+
+    Ok(Self::new(events))
+  }
+
+  /// Plays `events` instead of a file, see [RecordedEvent::at] for the timing
+  pub fn new(events: Vec<RecordedEvent>) -> Self {
+    Self {
+      events,
+      speed: 1.0,
+      repeat: false,
+    }
+  }
+
+  /// How much faster than the recording to play it, `2.0` is twice as fast,
+  /// [f64::INFINITY] is the only speed that sends everything right away
+  ///
+  /// 0, negative speeds and NaN play it at the original speed instead, they can't be played
+  pub fn speed(mut self, speed: f64) -> Self {
+    self.speed = match speed > 0.0 {
+      true => speed,
+      false => 1.0,
+    };
+    self
+  }
+
+  /// Starts over from the beginning after the last event instead of closing the connection,
+  /// with [f64::INFINITY] as the [Self::speed] it keeps sending the recording as fast as the connection reads it
+  pub fn repeat(mut self, repeat: bool) -> Self {
+    self.repeat = repeat;
+    self
+  }
+
+  pub fn events(&self) -> &[RecordedEvent] {
+    &self.events
+  }
+
+  /// Connects to a default listener, see [Self::connect] for anything else
+  pub async fn connection(self) -> SpotifyResult<SpotifyConnection<Codec, DuplexStream>> {
+    self.connect(&SpotifyListener::builder().acceptor()?).await
+  }
+
+  /// Starts playing it on `acceptor`'s [Runtime] and returns the connection once the handshake is done,
+  /// the builder's token and allowed origins are used so the fake extension gets accepted
+  pub async fn connect(self, acceptor: &SpotifyAcceptor) -> SpotifyResult<SpotifyConnection<Codec, DuplexStream>> {
+    let (listener, extension) = tokio::io::duplex(64 * 1024);
+
+    let allowed_origins = acceptor.allowed_origins.clone();
+    let token = acceptor.auth_token.clone();
+    let runtime = acceptor.runtime.clone();
+
+    acceptor.runtime.spawn(Box::pin(async move {
+      let connected = handshake::connect_client(extension, "ws://replay/", &allowed_origins, token.as_deref(), "replay", Codec::Json);
+      let mut ws = match connected.await {
+        Ok(ws) => ws,
+        Err(_) => return,
+      };
+
+      self.play(&mut ws, runtime).await;
+
+      // waits for the close to be answered so the connection ends cleanly instead of with a broken pipe
+      if ws.close(None).await.is_ok() {
+        while let Some(Ok(_)) = ws.next().await {}
+      }
+    }));
+
+    acceptor.accept(listener, None).await
+  }
+
+  async fn play(self, ws: &mut WebSocketStream<DuplexStream>, runtime: Arc<dyn Runtime>) {
+    let events = self.events.into_iter().filter(|recorded| !recorded.event.is_from_listener()).collect::<Vec<_>>();
+    let mut snapshot = PlayerSnapshot::default();
+    let mut started = Instant::now();
+    let mut next = 0;
+
+    loop {
+      if next == events.len() {
+        if !self.repeat || events.is_empty() {
+          return;
+        }
+
+        started = Instant::now();
+        next = 0;
+      }
+
+      // only too big to be a duration after `speed`, so it's never
+      let at = Duration::try_from_secs_f64(events[next].at.as_secs_f64() / self.speed).unwrap_or(Duration::MAX);
+      let wait = at.saturating_sub(started.elapsed());
+
+      let event = match select(ws.next(), runtime.sleep(wait)).await {
+        Either::Left((Some(Ok(Message::Text(text))), _)) => match serde_json::from_str(&text) {
+          Ok(SpotifyMessage::RequestState) => SpotifyEvent::StateSnapshot(snapshot.clone()),
+          _ => continue,
+        },
+        Either::Left((Some(Ok(_)), _)) => continue,
+        // the connection got dropped
+        Either::Left((_, _)) => return,
+        Either::Right(_) => {
+          let mut event = events[next].event.clone();
+          next += 1;
+
+          // it's when the position was measured, which is now and not when it was recorded
+          if let SpotifyEvent::ProgressChanged { timestamp, .. } = &mut event {
+            *timestamp = SystemTime::now();
+          }
+
+          snapshot.apply(&event);
+          event
+        }
+      };
+
+      let text = serde_json::to_string(&event).expect("events always serialize");
+
+      if ws.send(Message::Text(text)).await.is_err() {
+        return;
+      }
+    }
+  }
+}
@@ -0,0 +1,119 @@
+//! The only things the listener needs from an async runtime,
+//! so it can run on something other than tokio
+
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::ready;
+
+/// A task or a sleep from a [Runtime]
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+/// Spawns tasks and sleeps, everything else works on any runtime since
+/// the websocket only needs [AsyncRead](tokio::io::AsyncRead) and [AsyncWrite](tokio::io::AsyncWrite)
+/// and the channels don't need a runtime at all
+///
+/// Set it with [SpotifyListenerBuilder::runtime](crate::SpotifyListenerBuilder::runtime),
+/// an adapter for another runtime is usually two lines, see `examples/smol.rs`
+///
+/// Streams from other runtimes usually implement the `futures` io traits instead,
+/// `tokio_util::compat` turns them into ones [SpotifyAcceptor::accept](crate::SpotifyAcceptor::accept) takes
+pub trait Runtime: Send + Sync + 'static {
+  /// Runs `task` in the background until it's done
+  fn spawn(&self, task: BoxFuture);
+
+  /// Completes after `duration`
+  fn sleep(&self, duration: Duration) -> BoxFuture;
+}
+
+impl Debug for dyn Runtime {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str("Runtime")
+  }
+}
+
+/// Default: spawns with [tokio::spawn] and sleeps with [tokio::time::sleep],
+/// so it has to be used from within a tokio runtime
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct TokioRuntime;
+
+impl Runtime for TokioRuntime {
+  fn spawn(&self, task: BoxFuture) {
+    tokio::spawn(task);
+  }
+
+  fn sleep(&self, duration: Duration) -> BoxFuture {
+    Box::pin(tokio::time::sleep(duration))
+  }
+}
+
+/// A deadline that can be moved without allocating a new sleep every time,
+/// the sleep only gets replaced when it finishes before the deadline it's for
+pub(crate) struct Delay {
+  runtime: Arc<dyn Runtime>,
+  deadline: Instant,
+  sleep: Option<BoxFuture>,
+}
+
+impl Debug for Delay {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Delay").field("deadline", &self.deadline).finish_non_exhaustive()
+  }
+}
+
+impl Delay {
+  pub(crate) fn new(runtime: Arc<dyn Runtime>, after: Duration) -> Self {
+    Self {
+      runtime,
+      deadline: crate::instant_after(after),
+      sleep: None,
+    }
+  }
+
+  pub(crate) fn reset(&mut self, after: Duration) {
+    let deadline = crate::instant_after(after);
+
+    // sleeps can't be moved earlier, only replaced
+    if deadline < self.deadline {
+      self.sleep = None;
+    }
+
+    self.deadline = deadline;
+  }
+
+  pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+    loop {
+      let now = Instant::now();
+
+      if now >= self.deadline {
+        self.sleep = None;
+        return Poll::Ready(());
+      }
+
+      let sleep = self.sleep.get_or_insert_with(|| self.runtime.sleep(self.deadline - now));
+
+      ready!(sleep.as_mut().poll(cx));
+
+      // it was for an older deadline, the loop sleeps again for the rest
+      self.sleep = None;
+    }
+  }
+}
+
+/// Sleeps that never finish, for polling by hand in tests,
+/// [Delay] still sees the deadline passed on the next poll
+#[cfg(test)]
+pub(crate) struct NeverWakes;
+
+#[cfg(test)]
+impl Runtime for NeverWakes {
+  fn spawn(&self, _task: BoxFuture) {}
+
+  fn sleep(&self, _duration: Duration) -> BoxFuture {
+    Box::pin(std::future::pending())
+  }
+}
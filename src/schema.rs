@@ -0,0 +1,69 @@
+//! JSON Schema of the wire format, only with the `schemars` feature
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Metadata, RootSchema, Schema, SchemaObject, SubschemaValidation};
+use schemars::JsonSchema;
+
+use crate::{RepeatMode, SpotifyEvent, SpotifyMessage, TrackState, SCHEMA_VERSION};
+
+/// Schema of everything that goes over the websocket, events from the extension or messages to it,
+/// [TrackInfo](crate::TrackInfo) and everything else in them are in its definitions
+///
+/// For only one of them use `schemars::schema_for!(SpotifyEvent)`,
+/// [Artist](crate::Artist) and [Album](crate::Album) are the full objects even though their names alone are accepted too
+pub fn schema() -> RootSchema {
+  let mut generator = SchemaGenerator::default();
+  let any_of = vec![generator.subschema_for::<SpotifyEvent>(), generator.subschema_for::<SpotifyMessage>()];
+
+  let schema = SchemaObject {
+    metadata: Some(Box::new(Metadata {
+      title: Some(format!("spotify_info schema version {}", SCHEMA_VERSION)),
+      description: Some("An event from the extension or a message to it".to_string()),
+      ..Metadata::default()
+    })),
+    subschemas: Some(Box::new(SubschemaValidation {
+      any_of: Some(any_of),
+      ..SubschemaValidation::default()
+    })),
+    ..SchemaObject::default()
+  };
+
+  RootSchema {
+    meta_schema: generator.settings().meta_schema.clone(),
+    definitions: generator.take_definitions(),
+    schema,
+  }
+}
+
+/// [TrackState] and [RepeatMode] are sent as their numbers
+fn numbers(description: &str, values: &[u32]) -> Schema {
+  Schema::Object(SchemaObject {
+    metadata: Some(Box::new(Metadata {
+      description: Some(description.to_string()),
+      ..Metadata::default()
+    })),
+    instance_type: Some(InstanceType::Integer.into()),
+    enum_values: Some(values.iter().map(|value| (*value).into()).collect()),
+    ..SchemaObject::default()
+  })
+}
+
+impl JsonSchema for TrackState {
+  fn schema_name() -> String {
+    "TrackState".to_string()
+  }
+
+  fn json_schema(_: &mut SchemaGenerator) -> Schema {
+    numbers("0 is stopped, 1 is paused and 2 is playing", &[0, 1, 2])
+  }
+}
+
+impl JsonSchema for RepeatMode {
+  fn schema_name() -> String {
+    "RepeatMode".to_string()
+  }
+
+  fn json_schema(_: &mut SchemaGenerator) -> Schema {
+    numbers("0 is off, 1 repeats the context and 2 repeats the track", &[0, 1, 2])
+  }
+}
@@ -0,0 +1,517 @@
+//! What's the same for every scrobbling service, only with the `lastfm` or `listenbrainz` feature
+
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::backoff::BackoffTimer;
+use crate::derived::ScrobbleTimer;
+use crate::{serde_utils, Backoff, Interceptor, SpotifyError, SpotifyEvent, SpotifyEvents, SpotifyResult, TrackInfo, TrackState};
+
+/// What [ScrobbleService] returns
+pub type ServiceFuture<'a> = Pin<Box<dyn Future<Output = SpotifyResult<()>> + Send + 'a>>;
+
+/// A track that played long enough to count
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scrobble {
+  pub track: TrackInfo,
+  /// When it started playing, what the services want as the time of the scrobble
+  #[serde(with = "serde_utils::timestamp_millis")]
+  pub started_at: SystemTime,
+}
+
+/// Somewhere to send scrobbles to, like `LastFm` or `ListenBrainz`, used by a [Scrobbler]
+///
+/// Errors should be [SpotifyError::Scrobble] with `retry` set when trying again later could work (like without a network),
+/// the [Scrobbler] keeps those in its queue and drops the ones that can never work
+pub trait ScrobbleService: Send + Sync + 'static {
+  /// For logs, like `last.fm`
+  fn name(&self) -> &'static str;
+
+  /// Shows `track` as playing right now, doesn't get retried since it'd be outdated by then
+  fn now_playing<'a>(&'a self, track: &'a TrackInfo) -> ServiceFuture<'a>;
+
+  /// Submits every one of `scrobbles`, at most [Self::max_batch] at once
+  fn scrobble<'a>(&'a self, scrobbles: &'a [Scrobble]) -> ServiceFuture<'a>;
+
+  fn max_batch(&self) -> usize {
+    50
+  }
+}
+
+/// Sends what's playing and everything that played long enough to a [ScrobbleService],
+/// keeps scrobbles that couldn't be sent in a queue and tries again later
+///
+/// Decides when a track counts by itself the same way as
+/// [SpotifyListenerBuilder::scrobble_points](crate::SpotifyListenerBuilder::scrobble_points)
+/// (half of it or 4 minutes, tracks under 30 seconds never count), so that doesn't have to be on
+///
+/// Default: sends now playing, keeps at most 1000 scrobbles only in memory,
+/// waits 10 seconds before retrying and up to 30 minutes when it keeps failing
+///
+/// ```text
+/// let lastfm = LastFm::new(API_KEY, SECRET).session_key(session_key);
+///
+/// tokio::spawn(Scrobbler::new(lastfm).queue_file("scrobbles.json").run(listener.events(64)));
+/// ```
+#[derive(Debug)]
+pub struct Scrobbler<S> {
+  service: S,
+  timer: ScrobbleTimer,
+  send_now_playing: bool,
+  queue: VecDeque<Scrobble>,
+  queue_file: Option<PathBuf>,
+  /// If what was in [Self::queue_file] already got read into the queue
+  loaded: bool,
+  max_queued: usize,
+  backoff: BackoffTimer,
+  /// Not before this after sending failed
+  retry_at: Option<Instant>,
+}
+
+impl<S: ScrobbleService> Scrobbler<S> {
+  pub fn new(service: S) -> Self {
+    Self {
+      service,
+      timer: ScrobbleTimer::default(),
+      send_now_playing: true,
+      queue: VecDeque::new(),
+      queue_file: None,
+      loaded: true,
+      max_queued: 1000,
+      backoff: BackoffTimer::new(Backoff {
+        initial: Duration::from_secs(10),
+        max: Duration::from_secs(30 * 60),
+      }),
+      retry_at: None,
+    }
+  }
+
+  pub fn service(&self) -> &S {
+    &self.service
+  }
+
+  /// Turns now playing updates on or off, scrobbles still get sent
+  pub fn now_playing(mut self, enabled: bool) -> Self {
+    self.send_now_playing = enabled;
+    self
+  }
+
+  /// Keeps the queue in `path` too so scrobbles aren't lost when the program exits while offline,
+  /// what's already there gets sent first (a file that can't be read gets logged and replaced)
+  ///
+  /// The file gets read with the first event (or [Self::flush]), so it's not in [Self::queued] before that
+  pub fn queue_file(mut self, path: impl Into<PathBuf>) -> Self {
+    self.queue_file = Some(path.into());
+    self.loaded = false;
+    self
+  }
+
+  /// Most scrobbles to keep while they can't be sent, the oldest get dropped after that
+  pub fn max_queued(mut self, max: usize) -> Self {
+    self.max_queued = max;
+    self
+  }
+
+  /// How long to wait before sending the queue again after it failed
+  pub fn backoff(mut self, backoff: Backoff) -> Self {
+    self.backoff = BackoffTimer::new(backoff);
+    self
+  }
+
+  /// Scrobbles waiting to be sent, oldest first
+  pub fn queued(&self) -> impl Iterator<Item = &Scrobble> {
+    self.queue.iter()
+  }
+
+  /// Handles every event from `events` until the listener is gone,
+  /// also sends the queue again once it's time to even when no events come in
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn run(mut self, events: SpotifyEvents) {
+    let mut events = events.into_receiver();
+
+    self.flush_if_due().await;
+
+    loop {
+      let wait = self.retry_at.map(|at| at.saturating_duration_since(Instant::now()));
+
+      let event = match wait {
+        Some(wait) => match tokio::time::timeout(wait, events.recv()).await {
+          Ok(event) => event,
+          Err(_) => {
+            self.flush_if_due().await;
+            continue;
+          }
+        },
+        None => events.recv().await,
+      };
+
+      match event {
+        Ok(event) => self.handle(&event).await,
+        Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => return,
+      }
+    }
+  }
+
+  /// Handles one event, for when the events come from somewhere other than [SpotifyEvents],
+  /// errors get logged with the `log` crate since most of them get retried anyway
+  pub async fn handle(&mut self, event: &SpotifyEvent) {
+    self.load_queue().await;
+
+    let now_playing = match event {
+      // it makes its own, these would count tracks twice
+      SpotifyEvent::ScrobblePoint(_) => return,
+      SpotifyEvent::TrackChanged(info) => !self.timer.is_resent(info),
+      SpotifyEvent::StateSnapshot(snapshot) => snapshot.track.as_ref().is_some_and(|info| !self.timer.is_resent(info)),
+      // it runs out on its own after a while, so it gets sent again when playback resumes
+      SpotifyEvent::StateChanged(TrackState::Playing) => !self.timer.is_playing(),
+      _ => false,
+    };
+
+    let mut events = Vec::new();
+    self.timer.on_event(event.clone(), &mut events);
+
+    if now_playing && self.timer.is_playing() {
+      if let Some(track) = self.timer.track().cloned() {
+        self.send_now_playing(&track).await;
+      }
+    }
+
+    for event in events {
+      if let SpotifyEvent::ScrobblePoint(track) = event {
+        let now = SystemTime::now();
+        let started_at = now.checked_sub(self.timer.played()).unwrap_or(now);
+
+        self.enqueue(Scrobble { track, started_at }).await;
+      }
+    }
+
+    self.flush_if_due().await;
+  }
+
+  /// Sends everything in the queue now, even if it's not time to retry yet
+  pub async fn flush(&mut self) -> SpotifyResult<()> {
+    self.load_queue().await;
+
+    while !self.queue.is_empty() {
+      let batch = self.queue.len().min(self.service.max_batch().max(1));
+      let scrobbles = self.queue.range(..batch).cloned().collect::<Vec<_>>();
+
+      match self.service.scrobble(&scrobbles).await {
+        Ok(()) => {
+          self.backoff.reset();
+          self.retry_at = None;
+        }
+        Err(err @ SpotifyError::Scrobble { retry: true, .. }) => {
+          self.retry_at = Some(crate::instant_after(self.backoff.next_delay()));
+          return Err(err);
+        }
+        Err(err) => log::warn!("dropping {} scrobbles {} won't take: {}", batch, self.service.name(), err),
+      }
+
+      self.queue.drain(..batch);
+      self.save_queue().await;
+    }
+
+    Ok(())
+  }
+
+  async fn flush_if_due(&mut self) {
+    self.load_queue().await;
+
+    if self.queue.is_empty() || self.retry_at.is_some_and(|at| at > Instant::now()) {
+      return;
+    }
+
+    if let Err(err) = self.flush().await {
+      log::warn!("couldn't send scrobbles to {}, {} are queued: {}", self.service.name(), self.queue.len(), err);
+    }
+  }
+
+  async fn send_now_playing(&mut self, track: &TrackInfo) {
+    if !self.send_now_playing {
+      return;
+    }
+
+    if let Err(err) = self.service.now_playing(track).await {
+      log::warn!("couldn't send now playing to {}: {}", self.service.name(), err);
+    }
+  }
+
+  async fn enqueue(&mut self, scrobble: Scrobble) {
+    self.queue.push_back(scrobble);
+    self.trim_queue();
+    self.save_queue().await;
+  }
+
+  /// Drops the oldest ones over [Self::max_queued]
+  fn trim_queue(&mut self) {
+    while self.queue.len() > self.max_queued {
+      self.queue.pop_front();
+    }
+  }
+
+  /// Puts what's in [Self::queue_file] in front of the queue, only the first time
+  async fn load_queue(&mut self) {
+    let path = match &self.queue_file {
+      Some(path) if !self.loaded => path.clone(),
+      _ => return,
+    };
+
+    self.loaded = true;
+
+    let read = tokio::task::spawn_blocking({
+      let path = path.clone();
+      move || fs::read(path)
+    }).await;
+
+    let saved = match read {
+      Ok(Ok(bytes)) => serde_json::from_slice::<VecDeque<Scrobble>>(&bytes).map_err(|err| err.to_string()),
+      Ok(Err(err)) if err.kind() == io::ErrorKind::NotFound => Ok(VecDeque::new()),
+      Ok(Err(err)) => Err(err.to_string()),
+      Err(err) => Err(err.to_string()),
+    };
+
+    match saved {
+      Ok(mut saved) => {
+        saved.append(&mut self.queue);
+        self.queue = saved;
+        // it could be from when more were allowed
+        self.trim_queue();
+      }
+      Err(err) => log::warn!("ignoring {} scrobble queue {}: {}", self.service.name(), path.display(), err),
+    }
+  }
+
+  async fn save_queue(&self) {
+    let path = match &self.queue_file {
+      Some(path) => path.clone(),
+      None => return,
+    };
+
+    let bytes = serde_json::to_vec(&self.queue).expect("scrobbles always serialize");
+
+    let saved = tokio::task::spawn_blocking({
+      let path = path.clone();
+
+      move || {
+        // write somewhere else first so a crash never leaves half a file
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bytes).and_then(|_| fs::rename(&tmp, &path))
+      }
+    }).await;
+
+    match saved {
+      Ok(Ok(())) => {}
+      Ok(Err(err)) => log::warn!("couldn't save {} scrobble queue {}: {}", self.service.name(), path.display(), err),
+      Err(err) => log::warn!("couldn't save {} scrobble queue {}: {}", self.service.name(), path.display(), err),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+
+  use super::*;
+
+  /// Answers with the next of `answers` (Ok once there are none left) and keeps every batch it got
+  #[derive(Clone)]
+  struct FakeService {
+    answers: Arc<Mutex<VecDeque<SpotifyResult<()>>>>,
+    batches: Arc<Mutex<Vec<Vec<Scrobble>>>>,
+  }
+
+  impl FakeService {
+    fn new(answers: Vec<SpotifyResult<()>>) -> Self {
+      Self {
+        answers: Arc::new(Mutex::new(answers.into())),
+        batches: Arc::default(),
+      }
+    }
+
+    fn batches(&self) -> Vec<Vec<String>> {
+      let batches = self.batches.lock().unwrap();
+
+      batches.iter().map(|batch| uids(batch.iter())).collect()
+    }
+  }
+
+  impl ScrobbleService for FakeService {
+    fn name(&self) -> &'static str {
+      "fake"
+    }
+
+    fn now_playing<'a>(&'a self, _: &'a TrackInfo) -> ServiceFuture<'a> {
+      Box::pin(std::future::ready(Ok(())))
+    }
+
+    fn scrobble<'a>(&'a self, scrobbles: &'a [Scrobble]) -> ServiceFuture<'a> {
+      self.batches.lock().unwrap().push(scrobbles.to_vec());
+      let answer = self.answers.lock().unwrap().pop_front().unwrap_or(Ok(()));
+
+      Box::pin(std::future::ready(answer))
+    }
+
+    fn max_batch(&self) -> usize {
+      2
+    }
+  }
+
+  fn scrobble(uid: &str) -> Scrobble {
+    Scrobble {
+      track: TrackInfo::builder().uid(uid).title(uid).build().unwrap(),
+      started_at: SystemTime::UNIX_EPOCH,
+    }
+  }
+
+  fn uids<'a>(scrobbles: impl Iterator<Item = &'a Scrobble>) -> Vec<String> {
+    scrobbles.map(|scrobble| scrobble.track.uid.clone()).collect()
+  }
+
+  fn error(retry: bool) -> SpotifyError {
+    SpotifyError::Scrobble { service: "fake", message: "nope".to_string(), retry }
+  }
+
+  #[tokio::test]
+  async fn sends_in_batches() {
+    let service = FakeService::new(Vec::new());
+    let mut scrobbler = Scrobbler::new(service.clone());
+
+    for uid in ["a", "b", "c", "d", "e"] {
+      scrobbler.enqueue(scrobble(uid)).await;
+    }
+
+    scrobbler.flush().await.unwrap();
+
+    assert_eq!(service.batches(), vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+    assert_eq!(scrobbler.queued().count(), 0);
+  }
+
+  #[tokio::test]
+  async fn keeps_the_queue_when_it_can_retry() {
+    let service = FakeService::new(vec![Err(error(true))]);
+    let mut scrobbler = Scrobbler::new(service.clone());
+
+    scrobbler.enqueue(scrobble("a")).await;
+    scrobbler.enqueue(scrobble("b")).await;
+    scrobbler.enqueue(scrobble("c")).await;
+
+    assert!(scrobbler.flush().await.is_err());
+    assert_eq!(uids(scrobbler.queued()), vec!["a", "b", "c"]);
+    assert!(scrobbler.retry_at.is_some());
+
+    // not time yet
+    scrobbler.flush_if_due().await;
+    assert_eq!(service.batches().len(), 1);
+
+    scrobbler.flush().await.unwrap();
+    assert_eq!(scrobbler.queued().count(), 0);
+    assert!(scrobbler.retry_at.is_none());
+    assert_eq!(service.batches(), vec![vec!["a", "b"], vec!["a", "b"], vec!["c"]]);
+  }
+
+  #[tokio::test]
+  async fn drops_what_cant_be_sent() {
+    let service = FakeService::new(vec![Err(error(false))]);
+    let mut scrobbler = Scrobbler::new(service.clone());
+
+    scrobbler.enqueue(scrobble("a")).await;
+    scrobbler.enqueue(scrobble("b")).await;
+    scrobbler.enqueue(scrobble("c")).await;
+
+    scrobbler.flush().await.unwrap();
+
+    assert_eq!(service.batches(), vec![vec!["a", "b"], vec!["c"]]);
+    assert_eq!(scrobbler.queued().count(), 0);
+  }
+
+  #[tokio::test]
+  async fn backs_off_and_resets() {
+    let service = FakeService::new(vec![Err(error(true)), Err(error(true)), Err(error(true))]);
+    let mut scrobbler = Scrobbler::new(service).backoff(Backoff {
+      initial: Duration::from_secs(10),
+      max: Duration::from_secs(20),
+    });
+
+    scrobbler.enqueue(scrobble("a")).await;
+
+    // every wait is between half and all of the delay
+    for delay in [10, 20, 20] {
+      let delay = Duration::from_secs(delay);
+      assert!(scrobbler.flush().await.is_err());
+
+      let wait = scrobbler.retry_at.unwrap().saturating_duration_since(Instant::now());
+      assert!(wait <= delay && wait >= delay / 2 - Duration::from_secs(1), "{:?} isn't around {:?}", wait, delay);
+    }
+
+    scrobbler.flush().await.unwrap();
+    assert!(scrobbler.retry_at.is_none());
+    // starts from the first delay again
+    assert!(scrobbler.backoff.next_delay() <= Duration::from_secs(10));
+  }
+
+  #[tokio::test]
+  async fn drops_the_oldest_over_max_queued() {
+    let service = FakeService::new(Vec::new());
+    let mut scrobbler = Scrobbler::new(service).max_queued(2);
+
+    for uid in ["a", "b", "c"] {
+      scrobbler.enqueue(scrobble(uid)).await;
+    }
+
+    assert_eq!(uids(scrobbler.queued()), vec!["b", "c"]);
+  }
+
+  #[tokio::test]
+  async fn queue_file_is_loaded_trimmed_and_saved() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("scrobbles.json");
+    fs::write(&path, serde_json::to_vec(&[scrobble("a"), scrobble("b"), scrobble("c")]).unwrap()).unwrap();
+
+    let service = FakeService::new(vec![Err(error(true))]);
+    let mut scrobbler = Scrobbler::new(service.clone()).queue_file(&path).max_queued(2);
+
+    scrobbler.handle(&SpotifyEvent::StateChanged(TrackState::Paused)).await;
+
+    assert_eq!(uids(scrobbler.queued()), vec!["b", "c"]);
+    assert_eq!(service.batches(), vec![vec!["b", "c"]]);
+
+    scrobbler.enqueue(scrobble("d")).await;
+
+    let saved = serde_json::from_slice::<Vec<Scrobble>>(&fs::read(&path).unwrap()).unwrap();
+    assert_eq!(uids(saved.iter()), vec!["c", "d"]);
+  }
+
+  #[tokio::test]
+  async fn broken_queue_file_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("scrobbles.json");
+    fs::write(&path, "not json").unwrap();
+
+    let mut scrobbler = Scrobbler::new(FakeService::new(Vec::new())).queue_file(&path);
+    scrobbler.flush().await.unwrap();
+
+    assert_eq!(scrobbler.queued().count(), 0);
+  }
+
+  #[tokio::test]
+  async fn ignores_scrobble_points_from_the_listener() {
+    let service = FakeService::new(Vec::new());
+    let mut scrobbler = Scrobbler::new(service.clone());
+
+    scrobbler.handle(&SpotifyEvent::ScrobblePoint(scrobble("a").track)).await;
+
+    assert_eq!(scrobbler.queued().count(), 0);
+    assert!(service.batches().is_empty());
+  }
+}
@@ -0,0 +1,36 @@
+/// (De)serializes a [Duration](std::time::Duration) as whole milliseconds
+pub mod millis {
+  use std::time::Duration;
+
+  use serde::{Deserialize, Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_millis() as u64)
+  }
+
+  /// `null` is treated as 0 since local files may not have a duration
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    // spotify sometimes gives fractional milliseconds
+    let ms = Option::<f64>::deserialize(deserializer)?.unwrap_or(0.0);
+
+    Ok(Duration::from_millis(ms.max(0.0) as u64))
+  }
+}
+
+/// (De)serializes a [SystemTime](std::time::SystemTime) as milliseconds since the unix epoch,
+/// same as `Date.now()` in javascript
+pub mod timestamp_millis {
+  use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+  use serde::{Deserializer, Serializer};
+
+  pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+
+    super::millis::serialize(&since_epoch, serializer)
+  }
+
+  pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+    super::millis::deserialize(deserializer).map(|since_epoch| UNIX_EPOCH + since_epoch)
+  }
+}
@@ -0,0 +1,187 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+
+use futures_util::future::{abortable, select, AbortHandle, Either};
+use futures_util::{Stream, StreamExt};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::aggregate::Aggregator;
+use crate::{Aggregation, Command, ConnectionId, PlayerSnapshot, SpotifyConnection, SpotifyError, SpotifyEvent, SpotifyListener, SpotifyMessage, SpotifyResult, TrackState};
+
+/// How many events can wait in [SpotifyServer] before connections wait for it to catch up
+const EVENT_BUFFER: usize = 64;
+
+type Connections = Arc<Mutex<HashMap<ConnectionId, mpsc::UnboundedSender<Command>>>>;
+type SharedAggregator = Arc<Mutex<Aggregator>>;
+
+/// Keeps every connection open at the same time instead of one at a time,
+/// for when more than one spotify connects, or a new one connects before the old one is gone
+///
+/// Yields events from every connection with the id of the connection it came from,
+/// when a connection ends it yields [SpotifyEvent::StateChanged] with [TrackState::Stopped] for it
+///
+/// [Self::current] picks one of them with [Aggregation] for things that only show one track
+///
+/// Dropping it stops accepting connections
+#[derive(Debug)]
+pub struct SpotifyServer {
+  events: mpsc::Receiver<(ConnectionId, SpotifyEvent)>,
+  connections: Connections,
+  aggregator: SharedAggregator,
+  accept: AbortHandle,
+}
+
+impl SpotifyServer {
+  /// Starts accepting connections from `listener` in a background task
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime, unless [SpotifyListenerBuilder::runtime](crate::SpotifyListenerBuilder::runtime) was changed
+  pub fn new(listener: SpotifyListener) -> Self {
+    let (sender, events) = mpsc::channel(EVENT_BUFFER);
+    let connections = Connections::default();
+    let aggregator = SharedAggregator::default();
+    let runtime = listener.acceptor.runtime.clone();
+    let (task, accept) = abortable(accept(listener, sender, connections.clone(), aggregator.clone()));
+
+    runtime.spawn(Box::pin(async move {
+      let _ = task.await;
+    }));
+
+    Self { events, connections, aggregator, accept }
+  }
+
+  /// Changes how [Self::current] picks a connection, takes effect right away
+  ///
+  /// Default: [Aggregation::LastActive]
+  pub fn set_aggregation(&self, policy: Aggregation) {
+    self.aggregator.lock().unwrap_or_else(PoisonError::into_inner).policy = policy;
+  }
+
+  /// The connection picked by [Aggregation] and everything known about its player,
+  /// [None] when nothing is connected (or the pinned connection isn't)
+  ///
+  /// Already includes events that haven't been read from the server yet
+  pub fn current(&self) -> Option<(ConnectionId, PlayerSnapshot)> {
+    let aggregator = self.aggregator.lock().unwrap_or_else(PoisonError::into_inner);
+
+    aggregator.current().map(|(id, snapshot)| (id, snapshot.clone()))
+  }
+
+  /// Ids of every connection that's open right now
+  pub fn connections(&self) -> Vec<ConnectionId> {
+    let mut ids = self.connections.lock().unwrap_or_else(PoisonError::into_inner).keys().copied().collect::<Vec<_>>();
+    ids.sort();
+    ids
+  }
+
+  /// Sends a message to one connection,
+  /// fails with [SpotifyError::NotConnected] if it's already closed
+  pub async fn send(&self, id: ConnectionId, message: SpotifyMessage) -> SpotifyResult<()> {
+    let commands = self.connections.lock().unwrap_or_else(PoisonError::into_inner).get(&id).cloned();
+    let commands = commands.ok_or(SpotifyError::NotConnected)?;
+    let (reply, result) = oneshot::channel();
+
+    commands.send((message, reply)).map_err(|_| SpotifyError::NotConnected)?;
+
+    result.await.unwrap_or(Err(SpotifyError::NotConnected))
+  }
+
+  /// Sends a message to every connection that's open right now,
+  /// returns how many of them it got sent to
+  pub async fn broadcast(&self, message: SpotifyMessage) -> usize {
+    let commands = self.connections.lock().unwrap_or_else(PoisonError::into_inner).values().cloned().collect::<Vec<_>>();
+
+    let results = commands.into_iter().map(|commands| {
+      let message = message.clone();
+
+      async move {
+        let (reply, result) = oneshot::channel();
+
+        commands.send((message, reply)).is_ok() && matches!(result.await, Ok(Ok(())))
+      }
+    });
+
+    futures_util::future::join_all(results).await.into_iter().filter(|sent| *sent).count()
+  }
+
+  /// Waits for the next event from any connection,
+  /// same as calling [StreamExt::next] on the server
+  pub async fn next(&mut self) -> Option<(ConnectionId, SpotifyEvent)> {
+    self.events.recv().await
+  }
+}
+
+/// Only ends when the listener stops accepting connections and every connection is closed
+impl Stream for SpotifyServer {
+  type Item = (ConnectionId, SpotifyEvent);
+
+  fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    self.events.poll_recv(cx)
+  }
+}
+
+impl Drop for SpotifyServer {
+  fn drop(&mut self) {
+    self.accept.abort();
+  }
+}
+
+async fn accept(listener: SpotifyListener, events: mpsc::Sender<(ConnectionId, SpotifyEvent)>, connections: Connections, aggregator: SharedAggregator) {
+  loop {
+    let connection = match listener.next_connection().await {
+      Ok(connection) => connection,
+      Err(_) => return,
+    };
+
+    let id = connection.info().id;
+    let (commands, receiver) = mpsc::unbounded_channel();
+
+    connections.lock().unwrap_or_else(PoisonError::into_inner).insert(id, commands);
+    aggregator.lock().unwrap_or_else(PoisonError::into_inner).insert(id);
+
+    let relay = relay(id, connection, receiver, events.clone(), connections.clone(), aggregator.clone());
+
+    listener.acceptor.runtime.spawn(Box::pin(relay));
+  }
+}
+
+/// Sends every event of one connection to the server and every command for it to the connection
+async fn relay(
+  id: ConnectionId,
+  mut connection: SpotifyConnection,
+  mut commands: mpsc::UnboundedReceiver<Command>,
+  events: mpsc::Sender<(ConnectionId, SpotifyEvent)>,
+  connections: Connections,
+  aggregator: SharedAggregator,
+) {
+  loop {
+    let event = match select(StreamExt::next(&mut connection), std::pin::pin!(commands.recv())).await {
+      Either::Left((Some(event), _)) => event,
+      Either::Left((None, _)) => break,
+      Either::Right((Some((message, reply)), _)) => {
+        drop(reply.send(connection.send(message).await));
+        continue;
+      }
+      // the sender stays in the map until the end of this task, so this doesn't happen
+      Either::Right((None, _)) => break,
+    };
+
+    match event {
+      Ok(event) => {
+        aggregator.lock().unwrap_or_else(PoisonError::into_inner).apply(id, &event);
+
+        if events.send((id, event)).await.is_err() {
+          return;
+        }
+      }
+      Err(err) if err.is_message_error() => continue,
+      Err(_) => break,
+    }
+  }
+
+  connections.lock().unwrap_or_else(PoisonError::into_inner).remove(&id);
+  aggregator.lock().unwrap_or_else(PoisonError::into_inner).remove(id);
+
+  let _ = events.send((id, SpotifyEvent::StateChanged(TrackState::Stopped))).await;
+}
@@ -0,0 +1,258 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::ready;
+
+use crate::runtime::{Delay, Runtime};
+use crate::{SpotifyEvent, SpotifyUri, TrackInfo, TrackState};
+
+/// Groups playback into listening sessions, a session starts when something starts playing
+/// and ends once nothing played for `gap`,
+/// see [SpotifyListenerBuilder::session_gap](crate::SpotifyListenerBuilder::session_gap)
+#[derive(Debug)]
+pub(crate) struct SessionTracker {
+  gap: Duration,
+  session: Option<Session>,
+  /// uid and uri of the current track
+  track: Option<(String, SpotifyUri)>,
+  /// If the current track was already counted in the session
+  counted: bool,
+  playing: bool,
+  /// [SpotifyEvent::SessionStarted] waiting to be yielded
+  started: bool,
+  delay: Delay,
+}
+
+#[derive(Debug)]
+struct Session {
+  started: Instant,
+  /// When playback stopped, [None] while playing
+  stopped: Option<Instant>,
+  track_count: usize,
+}
+
+impl SessionTracker {
+  pub(crate) fn new(gap: Duration, runtime: Arc<dyn Runtime>) -> Self {
+    Self {
+      gap,
+      session: None,
+      track: None,
+      counted: false,
+      playing: false,
+      started: false,
+      delay: Delay::new(runtime, gap),
+    }
+  }
+
+  /// Updates the session with an event that's about to be yielded,
+  /// [SpotifyEvent::SessionStarted] comes from [Self::poll] right after it
+  pub(crate) fn ingest(&mut self, event: &SpotifyEvent) {
+    self.ingest_at(event, Instant::now());
+  }
+
+  /// Same as [Self::ingest] with `now` as the time it arrived
+  fn ingest_at(&mut self, event: &SpotifyEvent, now: Instant) {
+    let playing = match event {
+      SpotifyEvent::TrackChanged(info) => {
+        self.set_track(info);
+        Some(info.state == TrackState::Playing)
+      }
+      SpotifyEvent::StateChanged(state) => Some(*state == TrackState::Playing),
+      // only sent while playing
+      SpotifyEvent::ProgressChanged { .. } => Some(true),
+      SpotifyEvent::StateSnapshot(snapshot) => {
+        if let Some(info) = &snapshot.track {
+          self.set_track(info);
+        }
+
+        Some(snapshot.state == TrackState::Playing)
+      }
+      _ => None,
+    };
+
+    match playing {
+      Some(true) => self.play(now),
+      Some(false) => self.stop(now),
+      None => {}
+    }
+
+    if let Some(session) = &mut self.session {
+      if self.playing && !self.counted {
+        self.counted = true;
+        session.track_count += 1;
+      }
+    }
+  }
+
+  fn set_track(&mut self, info: &TrackInfo) {
+    let same = self.track.as_ref().is_some_and(|(uid, uri)| *uid == info.uid && *uri == info.uri);
+
+    if !same {
+      self.track = Some((info.uid.clone(), info.uri.clone()));
+      self.counted = false;
+    }
+  }
+
+  fn play(&mut self, now: Instant) {
+    self.playing = true;
+
+    match &mut self.session {
+      Some(session) => session.stopped = None,
+      None => {
+        self.session = Some(Session {
+          started: now,
+          stopped: None,
+          track_count: 0,
+        });
+        self.started = true;
+      }
+    }
+  }
+
+  fn stop(&mut self, now: Instant) {
+    self.playing = false;
+
+    if let Some(session) = self.session.as_mut().filter(|session| session.stopped.is_none()) {
+      session.stopped = Some(now);
+      self.delay.reset(self.gap);
+    }
+  }
+
+  /// [SpotifyEvent::SessionEnded] for the current session, if there is one
+  pub(crate) fn end(&mut self) -> Option<SpotifyEvent> {
+    self.started = false;
+
+    self.session.take().map(|session| {
+      // the gap at the end isn't part of it
+      let ended = session.stopped.unwrap_or_else(Instant::now);
+
+      SpotifyEvent::SessionEnded {
+        duration: ended.duration_since(session.started),
+        track_count: session.track_count,
+      }
+    })
+  }
+
+  /// Ready with [SpotifyEvent::SessionStarted] after [Self::ingest] started one,
+  /// or [SpotifyEvent::SessionEnded] once nothing played for the gap
+  pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<SpotifyEvent> {
+    if std::mem::take(&mut self.started) {
+      return Poll::Ready(SpotifyEvent::SessionStarted);
+    }
+
+    if self.session.as_ref().is_none_or(|session| session.stopped.is_none()) {
+      // only [Self::ingest] stops it, which happens right before this gets polled again
+      return Poll::Pending;
+    }
+
+    ready!(self.delay.poll(cx));
+
+    Poll::Ready(self.end().expect("checked above"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::task::Waker;
+
+  use super::*;
+  use crate::runtime::NeverWakes;
+
+  const GAP: Duration = Duration::from_millis(100);
+
+  fn tracker() -> SessionTracker {
+    SessionTracker::new(GAP, Arc::new(NeverWakes))
+  }
+
+  fn poll(tracker: &mut SessionTracker) -> Poll<SpotifyEvent> {
+    tracker.poll(&mut Context::from_waker(Waker::noop()))
+  }
+
+  fn playing(uid: &str) -> SpotifyEvent {
+    let info = TrackInfo::builder().uid(uid).duration(Duration::from_secs(60)).build().unwrap();
+
+    SpotifyEvent::TrackChanged(TrackInfo { state: TrackState::Playing, ..info })
+  }
+
+  fn state(state: TrackState) -> SpotifyEvent {
+    SpotifyEvent::StateChanged(state)
+  }
+
+  #[test]
+  fn starts_when_something_plays() {
+    let mut tracker = tracker();
+
+    tracker.ingest(&state(TrackState::Paused));
+    assert!(poll(&mut tracker).is_pending());
+
+    tracker.ingest(&playing("a"));
+    assert!(matches!(poll(&mut tracker), Poll::Ready(SpotifyEvent::SessionStarted)));
+    assert!(poll(&mut tracker).is_pending());
+  }
+
+  #[test]
+  fn ends_after_the_gap() {
+    let mut tracker = tracker();
+
+    tracker.ingest(&playing("a"));
+    let _ = poll(&mut tracker);
+    tracker.ingest(&state(TrackState::Paused));
+    assert!(poll(&mut tracker).is_pending());
+
+    std::thread::sleep(GAP + GAP / 2);
+    assert!(matches!(poll(&mut tracker), Poll::Ready(SpotifyEvent::SessionEnded { track_count: 1, .. })));
+    assert!(poll(&mut tracker).is_pending());
+  }
+
+  #[test]
+  fn playing_within_the_gap_keeps_it_going() {
+    let mut tracker = tracker();
+
+    tracker.ingest(&playing("a"));
+    let _ = poll(&mut tracker);
+    tracker.ingest(&state(TrackState::Paused));
+    std::thread::sleep(GAP / 2);
+    tracker.ingest(&playing("b"));
+
+    // the gap was still running when it started playing again
+    std::thread::sleep(GAP);
+    assert!(poll(&mut tracker).is_pending());
+
+    // stopping again starts the gap over
+    tracker.ingest(&state(TrackState::Stopped));
+    assert!(poll(&mut tracker).is_pending());
+    std::thread::sleep(GAP + GAP / 2);
+    assert!(matches!(poll(&mut tracker), Poll::Ready(SpotifyEvent::SessionEnded { track_count: 2, .. })));
+  }
+
+  #[test]
+  fn gap_isnt_part_of_the_duration() {
+    let start = Instant::now();
+    let mut tracker = tracker();
+
+    tracker.ingest_at(&playing("a"), start);
+    tracker.ingest_at(&state(TrackState::Paused), start + Duration::from_secs(30));
+    tracker.ingest_at(&state(TrackState::Playing), start + Duration::from_secs(40));
+    tracker.ingest_at(&state(TrackState::Stopped), start + Duration::from_secs(50));
+
+    let ended = tracker.end();
+    assert!(matches!(ended, Some(SpotifyEvent::SessionEnded { duration, track_count: 1 }) if duration == Duration::from_secs(50)));
+    assert!(tracker.end().is_none());
+  }
+
+  #[test]
+  fn counts_each_track_once() {
+    let mut tracker = tracker();
+
+    tracker.ingest(&playing("a"));
+    tracker.ingest(&SpotifyEvent::ProgressChanged { position: Duration::from_secs(1), percent: 0.0, timestamp: std::time::SystemTime::now() });
+    tracker.ingest(&state(TrackState::Paused));
+    tracker.ingest(&state(TrackState::Playing));
+    // sent again after a reconnect
+    tracker.ingest(&playing("a"));
+    tracker.ingest(&playing("b"));
+
+    assert!(matches!(tracker.end(), Some(SpotifyEvent::SessionEnded { track_count: 2, .. })));
+  }
+}
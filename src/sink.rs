@@ -0,0 +1,192 @@
+//! Writing what's playing to files, for OBS' "Read from file" text sources and image sources
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use tokio::sync::broadcast::error::RecvError;
+
+#[cfg(feature = "http-client")]
+use crate::ImageClient;
+use crate::{PlayerState, SpotifyEvent, SpotifyEvents, Template, TrackState};
+
+/// Keeps a text file with the now playing line up to date, and a file with the cover with the `http-client` feature
+///
+/// Files are replaced all at once so whatever reads them never sees half of one,
+/// they're only written when the text (or cover) actually changed.
+/// Missing directories get created
+///
+/// Default: `{?artist}{artist} - {/artist}{title}`, emptied while paused or stopped,
+/// the [Template] has everything from [PlayerState] so `{position}` works too but writes the file every progress update
+///
+/// ```text
+/// tokio::spawn(FileSink::new("nowplaying.txt").cover("nowplaying.jpg").run(listener.events(64)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileSink {
+  path: PathBuf,
+  template: Template,
+  clear_on_pause: bool,
+  #[cfg(feature = "http-client")]
+  cover: Option<PathBuf>,
+  #[cfg(feature = "http-client")]
+  client: ImageClient,
+}
+
+impl FileSink {
+  pub fn new(path: impl Into<PathBuf>) -> Self {
+    Self {
+      path: path.into(),
+      template: Template::parse("{?artist}{artist} - {/artist}{title}").expect("valid template"),
+      clear_on_pause: true,
+      #[cfg(feature = "http-client")]
+      cover: None,
+      #[cfg(feature = "http-client")]
+      client: ImageClient::default(),
+    }
+  }
+
+  /// What gets written to the file
+  pub fn template(mut self, template: Template) -> Self {
+    self.template = template;
+    self
+  }
+
+  /// Turn off to keep the text (and cover) while paused or stopped
+  pub fn clear_on_pause(mut self, clear: bool) -> Self {
+    self.clear_on_pause = clear;
+    self
+  }
+
+  /// Also writes the cover to `path` as it was downloaded (usually a jpeg),
+  /// the file gets removed while there's no cover so image sources show nothing
+  ///
+  /// Only with the `http-client` feature
+  #[cfg(feature = "http-client")]
+  pub fn cover(mut self, path: impl Into<PathBuf>) -> Self {
+    self.cover = Some(path.into());
+    self
+  }
+
+  /// Downloads covers with this client instead of the default one
+  ///
+  /// Only with the `http-client` feature
+  #[cfg(feature = "http-client")]
+  pub fn client(mut self, client: ImageClient) -> Self {
+    self.client = client;
+    self
+  }
+
+  /// Keeps the files up to date with `events` until the listener is gone, then empties them
+  ///
+  /// Errors get logged with the `log` crate, a file that couldn't be written is tried again with the next event
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn run(self, events: SpotifyEvents) {
+    let mut events = events.into_receiver();
+    let mut player = PlayerState::new();
+    // [None] while it's unknown what's in the file, so the first one always gets written
+    let mut written: Option<String> = None;
+    #[cfg(feature = "http-client")]
+    let mut cover: Option<Option<String>> = None;
+
+    loop {
+      match events.recv().await {
+        Ok(SpotifyEvent::SessionEnded { .. }) => player = PlayerState::new(),
+        Ok(event) => player.apply(&event),
+        Err(RecvError::Lagged(_)) => continue,
+        Err(RecvError::Closed) => break,
+      }
+
+      let shown = self.is_shown(&player);
+      let text = match shown {
+        true => self.template.render(&player),
+        false => String::new(),
+      };
+
+      if written.as_ref() != Some(&text) {
+        written = write(&self.path, text.clone().into_bytes()).await.then_some(text);
+      }
+
+      #[cfg(feature = "http-client")]
+      if let Some(path) = &self.cover {
+        let url = player.current_track().and_then(|track| track.cover()).filter(|_| shown).map(str::to_string);
+
+        if cover.as_ref() != Some(&url) {
+          cover = self.write_cover(path, url.as_deref()).await.then_some(url);
+        }
+      }
+    }
+
+    write(&self.path, Vec::new()).await;
+
+    #[cfg(feature = "http-client")]
+    if let Some(path) = &self.cover {
+      self.write_cover(path, None).await;
+    }
+  }
+
+  fn is_shown(&self, player: &PlayerState) -> bool {
+    player.current_track().is_some() && (!self.clear_on_pause || player.state() == TrackState::Playing)
+  }
+
+  /// Removes the file for [None], false if it didn't work
+  #[cfg(feature = "http-client")]
+  async fn write_cover(&self, path: &Path, url: Option<&str>) -> bool {
+    let url = match url {
+      Some(url) => url,
+      None => return remove(path).await,
+    };
+
+    match self.client.fetch(url).await {
+      Ok(image) => write(path, image.bytes).await,
+      Err(err) => {
+        log::warn!("couldn't download cover for {}: {}", path.display(), err);
+        // so it doesn't keep showing the last one, it's only tried again for the next track
+        remove(path).await
+      }
+    }
+  }
+}
+
+/// Writes next to it first and then replaces it, false if it didn't work
+async fn write(path: &Path, bytes: Vec<u8>) -> bool {
+  let path = path.to_path_buf();
+
+  blocking(path.clone(), move || {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+      fs::create_dir_all(dir)?;
+    }
+
+    // after the whole name, the text and cover are often only different in the extension
+    let mut tmp = OsString::from(path.as_os_str());
+    tmp.push(".tmp");
+
+    fs::write(&tmp, bytes)?;
+    fs::rename(&tmp, &path)
+  }).await
+}
+
+/// A file that's already gone is fine too
+#[cfg(feature = "http-client")]
+async fn remove(path: &Path) -> bool {
+  let path = path.to_path_buf();
+
+  blocking(path.clone(), move || match fs::remove_file(&path) {
+    Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+    removed => removed,
+  }).await
+}
+
+/// Runs file system stuff without blocking the runtime, errors get logged
+async fn blocking(path: PathBuf, task: impl FnOnce() -> io::Result<()> + Send + 'static) -> bool {
+  match tokio::task::spawn_blocking(task).await {
+    Ok(Ok(())) => true,
+    Ok(Err(err)) => {
+      log::warn!("couldn't write {}: {}", path.display(), err);
+      false
+    }
+    Err(_) => false,
+  }
+}
@@ -0,0 +1,253 @@
+//! Windows' media overlay and media keys, only with the `smtc` feature
+
+use std::pin::pin;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use futures_util::future::{select, Either};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{mpsc as tokio_mpsc, oneshot};
+use windows::core::HSTRING;
+use windows::Foundation::{TimeSpan, TypedEventHandler, Uri};
+use windows::Media::Playback::MediaPlayer;
+use windows::Media::{
+  AutoRepeatModeChangeRequestedEventArgs, MediaPlaybackAutoRepeatMode, MediaPlaybackStatus, MediaPlaybackType,
+  PlaybackPositionChangeRequestedEventArgs, ShuffleEnabledChangeRequestedEventArgs, SystemMediaTransportControls,
+  SystemMediaTransportControlsButton, SystemMediaTransportControlsButtonPressedEventArgs,
+  SystemMediaTransportControlsTimelineProperties,
+};
+use windows::Storage::Streams::RandomAccessStreamReference;
+use windows::Win32::System::WinRT::{RoInitialize, RO_INIT_MULTITHREADED};
+
+use crate::{PlayerSnapshot, PlayerState, RepeatMode, SpotifyError, SpotifyEvent, SpotifyEvents, SpotifyMessage, SpotifyResult, TrackInfo, TrackState};
+
+/// Shows what's playing in windows' media overlay (the one that comes up with the volume keys)
+/// and on the lock screen, and sends the media keys and the overlay's buttons to spotify
+///
+/// Play, pause, next, previous, seeking, shuffle and repeat go to spotify as [SpotifyMessage]s,
+/// stop pauses since spotify can't stop
+///
+/// The controls belong to this process, so they show up under its name and icon instead of spotify's
+///
+/// ```text
+/// tokio::spawn(SmtcServer::new().serve(listener.events(64)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SmtcServer {}
+
+impl SmtcServer {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Keeps the controls up to date with `events` until the listener is gone, then removes them,
+  /// commands go to the connection behind `events`
+  ///
+  /// Fails with [SpotifyError::Smtc] if windows doesn't give out the controls
+  ///
+  /// **NOTE**: Must be called from within a tokio runtime
+  pub async fn serve(self, events: SpotifyEvents) -> SpotifyResult<()> {
+    let mut receiver = events.subscribe();
+    let (updates, updates_rx) = mpsc::channel();
+    let (commands_tx, mut commands) = tokio_mpsc::unbounded_channel();
+    let (ready_tx, ready) = oneshot::channel();
+
+    // the controls are only used from one thread, they'd need more care to be used from tokio's
+    std::thread::spawn(move || {
+      let mut controls = match Controls::new(commands_tx) {
+        Ok(controls) => {
+          let _ = ready_tx.send(Ok(()));
+          controls
+        }
+        Err(err) => return drop(ready_tx.send(Err(err))),
+      };
+
+      for snapshot in updates_rx {
+        if let Err(err) = controls.update(&snapshot) {
+          log::warn!("couldn't update media controls: {}", err);
+        }
+      }
+
+      let _ = controls.controls.SetIsEnabled(false);
+    });
+
+    ready.await.expect("the thread always answers").map_err(SpotifyError::Smtc)?;
+
+    let mut state = PlayerState::new();
+
+    loop {
+      match select(pin!(receiver.recv()), pin!(commands.recv())).await {
+        Either::Left((Ok(event), _)) => {
+          // only what the overlay shows
+          if !matches!(event, SpotifyEvent::TrackChanged(_) | SpotifyEvent::StateChanged(_) | SpotifyEvent::StateSnapshot(_)
+            | SpotifyEvent::ProgressChanged { .. } | SpotifyEvent::ShuffleChanged(_) | SpotifyEvent::RepeatChanged(_)
+            | SpotifyEvent::PlaybackRateChanged(_) | SpotifyEvent::LikedChanged(_)) {
+            continue;
+          }
+
+          state.apply(&event);
+
+          if updates.send(state.snapshot()).is_err() {
+            return Ok(());
+          }
+        }
+        Either::Left((Err(RecvError::Lagged(_)), _)) => continue,
+        Either::Left((Err(RecvError::Closed), _)) => return Ok(()),
+        Either::Right((Some(message), _)) => {
+          if let Err(err) = events.send(message).await {
+            log::warn!("couldn't send media key to spotify: {}", err);
+          }
+        }
+        // only ends with the thread, which only ends when this is gone
+        Either::Right((None, _)) => return Ok(()),
+      }
+    }
+  }
+}
+
+struct Controls {
+  /// Never used, but the controls go away with it
+  _player: MediaPlayer,
+  controls: SystemMediaTransportControls,
+  /// What the overlay shows, so it only gets redrawn when the track changed
+  track: Option<TrackInfo>,
+}
+
+impl Controls {
+  fn new(commands: tokio_mpsc::UnboundedSender<SpotifyMessage>) -> windows::core::Result<Self> {
+    // fails when it's already initialized, which is fine too
+    let _ = unsafe { RoInitialize(RO_INIT_MULTITHREADED) };
+
+    let player = MediaPlayer::new()?;
+    // nothing is played through it, it's only there for the controls
+    player.CommandManager()?.SetIsEnabled(false)?;
+
+    let controls = player.SystemMediaTransportControls()?;
+    controls.SetIsEnabled(true)?;
+    controls.SetIsPlayEnabled(true)?;
+    controls.SetIsPauseEnabled(true)?;
+    controls.SetIsStopEnabled(true)?;
+    controls.SetIsNextEnabled(true)?;
+    controls.SetIsPreviousEnabled(true)?;
+
+    let sender = commands.clone();
+    controls.ButtonPressed(&TypedEventHandler::new(
+      move |_, args: &Option<SystemMediaTransportControlsButtonPressedEventArgs>| {
+        let message = match args.as_ref().map(|args| args.Button()).transpose()? {
+          Some(SystemMediaTransportControlsButton::Play) => SpotifyMessage::Play,
+          Some(SystemMediaTransportControlsButton::Pause | SystemMediaTransportControlsButton::Stop) => SpotifyMessage::Pause,
+          Some(SystemMediaTransportControlsButton::Next) => SpotifyMessage::Next,
+          Some(SystemMediaTransportControlsButton::Previous) => SpotifyMessage::Previous,
+          _ => return Ok(()),
+        };
+
+        let _ = sender.send(message);
+        Ok(())
+      },
+    ))?;
+
+    let sender = commands.clone();
+    controls.PlaybackPositionChangeRequested(&TypedEventHandler::new(
+      move |_, args: &Option<PlaybackPositionChangeRequestedEventArgs>| {
+        if let Some(position) = args.as_ref().map(|args| args.RequestedPlaybackPosition()).transpose()? {
+          let _ = sender.send(SpotifyMessage::Seek { position_ms: (position.Duration.max(0) / 10_000) as u64 });
+        }
+
+        Ok(())
+      },
+    ))?;
+
+    let sender = commands.clone();
+    controls.ShuffleEnabledChangeRequested(&TypedEventHandler::new(
+      move |_, args: &Option<ShuffleEnabledChangeRequestedEventArgs>| {
+        if let Some(shuffle) = args.as_ref().map(|args| args.RequestedShuffleEnabled()).transpose()? {
+          let _ = sender.send(SpotifyMessage::SetShuffle(shuffle));
+        }
+
+        Ok(())
+      },
+    ))?;
+
+    controls.AutoRepeatModeChangeRequested(&TypedEventHandler::new(
+      move |_, args: &Option<AutoRepeatModeChangeRequestedEventArgs>| {
+        let mode = match args.as_ref().map(|args| args.RequestedAutoRepeatMode()).transpose()? {
+          Some(MediaPlaybackAutoRepeatMode::Track) => RepeatMode::Track,
+          Some(MediaPlaybackAutoRepeatMode::List) => RepeatMode::Context,
+          Some(_) => RepeatMode::Off,
+          None => return Ok(()),
+        };
+
+        let _ = commands.send(SpotifyMessage::SetRepeat(mode));
+        Ok(())
+      },
+    ))?;
+
+    Ok(Self { _player: player, controls, track: None })
+  }
+
+  fn update(&mut self, snapshot: &PlayerSnapshot) -> windows::core::Result<()> {
+    let status = match (&snapshot.track, snapshot.state) {
+      (None, _) => MediaPlaybackStatus::Closed,
+      (_, TrackState::Playing) => MediaPlaybackStatus::Playing,
+      (_, TrackState::Paused) => MediaPlaybackStatus::Paused,
+      (_, TrackState::Stopped) => MediaPlaybackStatus::Stopped,
+    };
+
+    self.controls.SetPlaybackStatus(status)?;
+    self.controls.SetShuffleEnabled(snapshot.shuffle)?;
+    self.controls.SetAutoRepeatMode(match snapshot.repeat {
+      RepeatMode::Off => MediaPlaybackAutoRepeatMode::None,
+      RepeatMode::Context => MediaPlaybackAutoRepeatMode::List,
+      RepeatMode::Track => MediaPlaybackAutoRepeatMode::Track,
+    })?;
+    self.controls.SetPlaybackRate(snapshot.playback_rate as f64)?;
+
+    if self.track != snapshot.track {
+      self.track = snapshot.track.clone();
+      self.display(snapshot.track.as_ref())?;
+    }
+
+    if let Some(track) = &snapshot.track {
+      let timeline = SystemMediaTransportControlsTimelineProperties::new()?;
+      timeline.SetStartTime(time_span(Duration::ZERO))?;
+      timeline.SetMinSeekTime(time_span(Duration::ZERO))?;
+      timeline.SetEndTime(time_span(track.duration))?;
+      timeline.SetMaxSeekTime(time_span(track.duration))?;
+      timeline.SetPosition(time_span(snapshot.position))?;
+
+      self.controls.UpdateTimelineProperties(&timeline)?;
+    }
+
+    Ok(())
+  }
+
+  fn display(&self, track: Option<&TrackInfo>) -> windows::core::Result<()> {
+    let updater = self.controls.DisplayUpdater()?;
+    updater.ClearAll()?;
+
+    if let Some(track) = track {
+      updater.SetType(MediaPlaybackType::Music)?;
+
+      let music = updater.MusicProperties()?;
+      music.SetTitle(&HSTRING::from(&track.title))?;
+      music.SetArtist(&HSTRING::from(track.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>().join(", ")))?;
+      music.SetAlbumTitle(&HSTRING::from(&track.album.name))?;
+
+      if let Some(number) = track.track_number {
+        music.SetTrackNumber(number)?;
+      }
+
+      // windows downloads it by itself
+      if let Some(cover) = track.cover() {
+        updater.SetThumbnail(&RandomAccessStreamReference::CreateFromUri(&Uri::CreateUri(&HSTRING::from(cover))?)?)?;
+      }
+    }
+
+    updater.Update()
+  }
+}
+
+/// In 100 nanosecond ticks
+fn time_span(duration: Duration) -> TimeSpan {
+  TimeSpan { Duration: (duration.as_nanos() / 100) as i64 }
+}
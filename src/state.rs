@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{PlaybackClock, PlaybackContext, PlayerSnapshot, RepeatMode, SpotifyEvent, TrackInfo, TrackState};
+
+/// The device playback is on, from [SpotifyEvent::DeviceChanged]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Device {
+  /// Name of the device, like "Kitchen Speaker"
+  pub name: String,
+  /// ID of the device
+  pub id: String,
+  /// If it's the spotify client the extension runs in
+  #[serde(alias = "isLocal")]
+  pub is_local: bool,
+  /// Volume of the device between 0 and 1
+  pub volume: f64,
+}
+
+/// Everything known about the player, for when an app needs the current track or volume
+/// at any time instead of reacting to single events
+///
+/// Feed it every event with [PlayerState::apply], the position moves on its own
+/// between progress events like with [PlaybackClock]
+#[derive(Debug, Clone, Default)]
+pub struct PlayerState {
+  snapshot: PlayerSnapshot,
+  clock: PlaybackClock,
+  device: Option<Device>,
+}
+
+impl PlayerState {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Updates the state with an event, events that don't change the player are ignored
+  pub fn apply(&mut self, event: &SpotifyEvent) {
+    self.snapshot.apply(event);
+    self.clock.ingest(event);
+
+    if let SpotifyEvent::DeviceChanged { name, id, is_local, volume } = event {
+      self.snapshot.volume = *volume;
+      self.device = Some(Device {
+        name: name.clone(),
+        id: id.clone(),
+        is_local: *is_local,
+        volume: *volume,
+      });
+    }
+  }
+
+  /// Current track, [None] if nothing has played yet
+  pub fn current_track(&self) -> Option<&TrackInfo> {
+    self.snapshot.track.as_ref()
+  }
+
+  pub fn state(&self) -> TrackState {
+    self.snapshot.state
+  }
+
+  pub fn is_playing(&self) -> bool {
+    self.snapshot.state == TrackState::Playing
+  }
+
+  /// Estimated position in the current track, see [PlaybackClock::estimated_position]
+  pub fn position(&self) -> Duration {
+    self.clock.estimated_position()
+  }
+
+  /// Volume between 0 and 1
+  pub fn volume(&self) -> f64 {
+    self.snapshot.volume
+  }
+
+  pub fn shuffle(&self) -> bool {
+    self.snapshot.shuffle
+  }
+
+  pub fn repeat(&self) -> RepeatMode {
+    self.snapshot.repeat
+  }
+
+  /// How fast it's playing, 1 is normal speed
+  pub fn playback_rate(&self) -> f32 {
+    self.snapshot.playback_rate
+  }
+
+  /// What the track is playing from, [None] until the extension says
+  pub fn context(&self) -> Option<&PlaybackContext> {
+    self.snapshot.context.as_ref()
+  }
+
+  /// Device playback is on, [None] until it moves to another device for the first time
+  pub fn device(&self) -> Option<&Device> {
+    self.device.as_ref()
+  }
+
+  /// Everything but the device as a [PlayerSnapshot], with the estimated position
+  pub fn snapshot(&self) -> PlayerSnapshot {
+    PlayerSnapshot {
+      position: self.position(),
+      ..self.snapshot.clone()
+    }
+  }
+}
+
+impl From<PlayerSnapshot> for PlayerState {
+  fn from(snapshot: PlayerSnapshot) -> Self {
+    let mut state = Self::default();
+    state.apply(&SpotifyEvent::StateSnapshot(snapshot));
+    state
+  }
+}
@@ -0,0 +1,428 @@
+//! Listening statistics from a [HistoryStore], only with the `history` feature
+
+use std::ops::RangeBounds;
+use std::time::{Duration, SystemTime};
+
+use rusqlite::params_from_iter;
+use serde::{Deserialize, Serialize};
+
+use crate::history::{limit, started_in, to_millis};
+use crate::{serde_utils, HistoryStore, SpotifyResult, SpotifyUri};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// How long was listened to on one day
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DayStats {
+  /// Like `2024-03-14`
+  pub date: String,
+  #[serde(with = "serde_utils::millis")]
+  pub played: Duration,
+  pub plays: u64,
+}
+
+/// How long one artist or album was listened to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ListeningTime {
+  pub name: String,
+  /// Empty if no play had it (like local files)
+  pub uri: SpotifyUri,
+  #[serde(with = "serde_utils::millis")]
+  pub played: Duration,
+  pub plays: u64,
+}
+
+/// Days in a row with anything played
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Streaks {
+  /// Days up to today, or yesterday if nothing played today yet
+  pub current: u32,
+  pub longest: u32,
+  /// First day of the longest streak, [None] if nothing ever played
+  pub longest_start: Option<String>,
+}
+
+/// How many plays got skipped before the end
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SkipRate {
+  pub plays: u64,
+  pub skipped: u64,
+  /// `skipped / plays`, 0 without any plays
+  pub rate: f64,
+}
+
+/// Everything from [Stats] at once, for a dashboard or a yearly summary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatsReport {
+  #[serde(with = "serde_utils::millis")]
+  pub played: Duration,
+  pub skip_rate: SkipRate,
+  pub streaks: Streaks,
+  pub days: Vec<DayStats>,
+  pub artists: Vec<ListeningTime>,
+  pub albums: Vec<ListeningTime>,
+}
+
+/// Adds up what's in a [HistoryStore], made with [HistoryStore::stats]
+///
+/// Unlike the top lists in [HistoryStore] every play counts here, skipped ones for as long as they played,
+/// `range` is when the plays started like with [HistoryStore::plays]
+///
+/// Default: days are in UTC, see [Self::utc_offset]
+#[derive(Debug, Clone)]
+pub struct Stats<'a> {
+  history: &'a HistoryStore,
+  /// Milliseconds to add to unix time to get local time
+  offset: i64,
+}
+
+impl HistoryStore {
+  /// Listening time, streaks and skips of everything in the history
+  pub fn stats(&self) -> Stats<'_> {
+    Stats { history: self, offset: 0 }
+  }
+}
+
+impl Stats<'_> {
+  /// Where days start, `3600` is UTC+1, the crate doesn't know about time zones
+  /// so it's up to the caller to get the right one
+  pub fn utc_offset(mut self, seconds: i32) -> Self {
+    self.offset = seconds as i64 * 1000;
+    self
+  }
+
+  /// How long everything in `range` played
+  pub fn total(&self, range: impl RangeBounds<SystemTime>) -> SpotifyResult<Duration> {
+    let (filter, values) = started_in(&range);
+    let sql = format!("SELECT COALESCE(SUM(played_ms), 0) FROM plays WHERE {}", filter);
+
+    self.history.with_connection(|db| {
+      db.query_row(&sql, params_from_iter(values), |row| Ok(millis(row.get(0)?)))
+    })
+  }
+
+  /// Every day in `range` something played, oldest first
+  pub fn per_day(&self, range: impl RangeBounds<SystemTime>) -> SpotifyResult<Vec<DayStats>> {
+    let days = self.days(range)?.into_iter().map(|(day, played, plays)| DayStats { date: date(day), played, plays });
+
+    Ok(days.collect())
+  }
+
+  /// Days since 1970-01-01 in local time with how long and how many times something played on them
+  fn days(&self, range: impl RangeBounds<SystemTime>) -> SpotifyResult<Vec<(i64, Duration, u64)>> {
+    let (filter, values) = started_in(&range);
+    // sqlite's / rounds towards 0, this rounds down like div_euclid
+    let sql = format!(
+      "SELECT (started_at + {offset}) / {day} - ((started_at + {offset}) % {day} < 0), SUM(played_ms), COUNT(*) FROM plays
+       WHERE {filter} GROUP BY 1 ORDER BY 1",
+      offset = self.offset,
+      day = DAY_MILLIS,
+      filter = filter,
+    );
+
+    self.history.with_connection(|db| {
+      let mut query = db.prepare(&sql)?;
+      let days = query.query_map(params_from_iter(values), |row| Ok((row.get(0)?, millis(row.get(1)?), row.get(2)?)))?;
+
+      days.collect()
+    })
+  }
+
+  /// The `n` artists that played the longest in `range`, every artist of a track gets its whole time
+  pub fn per_artist(&self, range: impl RangeBounds<SystemTime>, n: usize) -> SpotifyResult<Vec<ListeningTime>> {
+    self.listening_time(
+      "SELECT play_artists.name, MAX(play_artists.uri), SUM(plays.played_ms), COUNT(*) FROM play_artists
+       JOIN plays ON plays.id = play_artists.play_id",
+      None,
+      "play_artists.name",
+      range,
+      n,
+    )
+  }
+
+  /// The `n` albums that played the longest in `range`, plays without an album aren't counted
+  pub fn per_album(&self, range: impl RangeBounds<SystemTime>, n: usize) -> SpotifyResult<Vec<ListeningTime>> {
+    self.listening_time(
+      "SELECT album, MAX(album_uri), SUM(played_ms), COUNT(*) FROM plays",
+      Some("album != ''"),
+      "album",
+      range,
+      n,
+    )
+  }
+
+  /// `select` grouped by `group`, only the rows in `range` that match `only` (if there is one) get added up
+  fn listening_time(
+    &self,
+    select: &str,
+    only: Option<&str>,
+    group: &str,
+    range: impl RangeBounds<SystemTime>,
+    n: usize,
+  ) -> SpotifyResult<Vec<ListeningTime>> {
+    let (mut filter, mut values) = started_in(&range);
+    values.push(rusqlite::types::Value::Integer(limit(n)));
+
+    if let Some(only) = only {
+      filter = format!("{} AND {}", filter, only);
+    }
+
+    let sql = format!("{} WHERE {} GROUP BY {} ORDER BY 3 DESC, 4 DESC LIMIT ?{}", select, filter, group, values.len());
+
+    self.history.with_connection(|db| {
+      let mut query = db.prepare(&sql)?;
+      let rows = query.query_map(params_from_iter(values), |row| {
+        Ok(ListeningTime {
+          name: row.get(0)?,
+          uri: SpotifyUri::from(row.get::<_, String>(1)?),
+          played: millis(row.get(2)?),
+          plays: row.get(3)?,
+        })
+      })?;
+
+      rows.collect()
+    })
+  }
+
+  /// Current and longest streak of days with anything played, out of the whole history
+  pub fn streaks(&self) -> SpotifyResult<Streaks> {
+    let days = self.days(..)?.into_iter().map(|(day, ..)| day).collect::<Vec<_>>();
+    let today = (to_millis(SystemTime::now()) + self.offset).div_euclid(DAY_MILLIS);
+
+    Ok(streaks(&days, today))
+  }
+
+  /// How many plays in `range` got skipped
+  pub fn skip_rate(&self, range: impl RangeBounds<SystemTime>) -> SpotifyResult<SkipRate> {
+    let (filter, values) = started_in(&range);
+    let sql = format!("SELECT COUNT(*), COALESCE(SUM(NOT finished), 0) FROM plays WHERE {}", filter);
+
+    self.history.with_connection(|db| {
+      db.query_row(&sql, params_from_iter(values), |row| {
+        let (plays, skipped) = (row.get::<_, u64>(0)?, row.get::<_, u64>(1)?);
+        let rate = if plays == 0 { 0.0 } else { skipped as f64 / plays as f64 };
+
+        Ok(SkipRate { plays, skipped, rate })
+      })
+    })
+  }
+
+  /// Everything for `range` with the top `n` artists and albums, streaks are still for the whole history
+  pub fn report(&self, range: impl RangeBounds<SystemTime> + Clone, n: usize) -> SpotifyResult<StatsReport> {
+    Ok(StatsReport {
+      played: self.total(range.clone())?,
+      skip_rate: self.skip_rate(range.clone())?,
+      streaks: self.streaks()?,
+      days: self.per_day(range.clone())?,
+      artists: self.per_artist(range.clone(), n)?,
+      albums: self.per_album(range, n)?,
+    })
+  }
+}
+
+/// `days` are sorted and without duplicates, like from [Stats::days]
+fn streaks(days: &[i64], today: i64) -> Streaks {
+  let mut streaks = Streaks::default();
+  let mut start = 0;
+
+  for (n, day) in days.iter().enumerate() {
+    if n > 0 && days[n - 1] + 1 != *day {
+      start = n;
+    }
+
+    let length = (n - start + 1) as u32;
+
+    if length > streaks.longest {
+      streaks.longest = length;
+      streaks.longest_start = Some(date(days[start]));
+    }
+
+    // a streak that ended yesterday still counts until today is over
+    if *day >= today - 1 {
+      streaks.current = length;
+    }
+  }
+
+  streaks
+}
+
+fn millis(millis: i64) -> Duration {
+  Duration::from_millis(millis.max(0) as u64)
+}
+
+/// `2024-03-14` for days since 1970-01-01, from http://howardhinnant.github.io/date_algorithms.html
+pub(crate) fn date(days: i64) -> String {
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + (month <= 2) as i64;
+
+  format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::UNIX_EPOCH;
+
+  use super::*;
+  use crate::history::Play;
+  use crate::Artist;
+
+  const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+  /// 2024-03-14 00:00 UTC
+  fn day(n: u32) -> SystemTime {
+    UNIX_EPOCH + DAY * (19796 + n)
+  }
+
+  fn artist(name: &str) -> Artist {
+    Artist { name: name.into(), uri: SpotifyUri::from(format!("spotify:artist:{}", name)) }
+  }
+
+  fn play(started_at: SystemTime, played: u64, artists: &[&str], album: &str, finished: bool) -> Play {
+    Play {
+      id: 0,
+      uri: SpotifyUri::from("spotify:track:a"),
+      title: "a".into(),
+      artists: artists.iter().map(|name| artist(name)).collect(),
+      album: album.into(),
+      album_uri: match album {
+        "" => SpotifyUri::default(),
+        album => SpotifyUri::from(format!("spotify:album:{}", album)),
+      },
+      started_at,
+      ended_at: started_at + Duration::from_secs(played),
+      played: Duration::from_secs(played),
+      duration: Duration::from_secs(200),
+      finished,
+      scrobbled: false,
+    }
+  }
+
+  fn history(plays: &[Play]) -> HistoryStore {
+    let history = HistoryStore::open_in_memory().unwrap();
+
+    for play in plays {
+      history.insert(play).unwrap();
+    }
+
+    history
+  }
+
+  fn names(times: &[ListeningTime]) -> Vec<(&str, u64, u64)> {
+    times.iter().map(|time| (time.name.as_str(), time.played.as_secs(), time.plays)).collect()
+  }
+
+  #[test]
+  fn adds_up_artists() {
+    let history = history(&[
+      play(day(0), 100, &["one", "two"], "x", true),
+      play(day(0), 50, &["two"], "x", false),
+      play(day(1), 30, &["three"], "y", true),
+    ]);
+    let stats = history.stats();
+
+    // every artist of a track gets its whole time
+    assert_eq!(names(&stats.per_artist(.., 10).unwrap()), [("two", 150, 2), ("one", 100, 1), ("three", 30, 1)]);
+    assert_eq!(names(&stats.per_artist(.., 1).unwrap()), [("two", 150, 2)]);
+    assert_eq!(names(&stats.per_artist(day(1).., 10).unwrap()), [("three", 30, 1)]);
+  }
+
+  #[test]
+  fn adds_up_albums_with_a_name() {
+    let history = history(&[
+      play(day(0), 100, &["one"], "x", true),
+      play(day(0), 300, &["one"], "", true),
+      play(day(1), 30, &["one"], "y", true),
+      play(day(2), 80, &["one"], "y", true),
+    ]);
+    let stats = history.stats();
+
+    let albums = stats.per_album(.., 10).unwrap();
+    assert_eq!(names(&albums), [("y", 110, 2), ("x", 100, 1)]);
+    assert_eq!(albums[0].uri, SpotifyUri::from("spotify:album:y"));
+
+    // the limit counts only albums with a name
+    assert_eq!(names(&stats.per_album(.., 1).unwrap()), [("y", 110, 2)]);
+    assert_eq!(names(&stats.per_album(..day(1), 10).unwrap()), [("x", 100, 1)]);
+  }
+
+  #[test]
+  fn totals_and_skips() {
+    let history = history(&[
+      play(day(0), 100, &["one"], "x", true),
+      play(day(0), 20, &["one"], "x", false),
+      play(day(1), 30, &["one"], "x", false),
+    ]);
+    let stats = history.stats();
+
+    assert_eq!(stats.total(..).unwrap(), Duration::from_secs(150));
+    assert_eq!(stats.total(day(1)..).unwrap(), Duration::from_secs(30));
+
+    let skips = stats.skip_rate(..).unwrap();
+    assert_eq!((skips.plays, skips.skipped), (3, 2));
+    assert!((skips.rate - 2.0 / 3.0).abs() < 1e-9);
+    assert_eq!(stats.skip_rate(day(5)..).unwrap(), SkipRate::default());
+  }
+
+  #[test]
+  fn buckets_by_day() {
+    let late = day(0) + DAY - Duration::from_secs(60 * 60);
+    let history = history(&[play(day(0), 10, &["one"], "x", true), play(late, 20, &["one"], "x", true), play(day(2), 30, &["one"], "x", true)]);
+
+    let days = history.stats().per_day(..).unwrap();
+    let days: Vec<_> = days.iter().map(|day| (day.date.as_str(), day.played.as_secs(), day.plays)).collect();
+    assert_eq!(days, [("2024-03-14", 30, 2), ("2024-03-16", 30, 1)]);
+
+    // 23:00 UTC is the next day in UTC+2
+    let days = history.stats().utc_offset(2 * 60 * 60).per_day(..).unwrap();
+    let days: Vec<_> = days.iter().map(|day| day.date.as_str()).collect();
+    assert_eq!(days, ["2024-03-14", "2024-03-15", "2024-03-16"]);
+
+    // and early morning UTC is the day before in UTC-2
+    let days = history.stats().utc_offset(-2 * 60 * 60).per_day(..).unwrap();
+    let days: Vec<_> = days.iter().map(|day| day.date.as_str()).collect();
+    assert_eq!(days, ["2024-03-13", "2024-03-14", "2024-03-15"]);
+  }
+
+  #[test]
+  fn buckets_before_1970() {
+    // still 1969 in UTC-2, sqlite has to round down instead of towards 0
+    let history = history(&[play(UNIX_EPOCH + Duration::from_secs(60 * 60), 10, &["one"], "x", true)]);
+
+    assert_eq!(history.stats().utc_offset(-2 * 60 * 60).per_day(..).unwrap()[0].date, "1969-12-31");
+  }
+
+  #[test]
+  fn finds_streaks() {
+    let days = [19796, 19797, 19798, 19800, 19801];
+
+    assert_eq!(streaks(&days, 19801), Streaks { current: 2, longest: 3, longest_start: Some("2024-03-14".into()) });
+    // yesterday still counts
+    assert_eq!(streaks(&days, 19802).current, 2);
+    assert_eq!(streaks(&days, 19803).current, 0);
+    assert_eq!(streaks(&[], 19801), Streaks::default());
+  }
+
+  #[test]
+  fn streaks_from_the_history() {
+    let today = SystemTime::now();
+    let history = history(&[play(today - DAY * 3, 10, &["one"], "x", true), play(today - DAY, 10, &["one"], "x", true), play(today, 10, &["one"], "x", true)]);
+
+    let streaks = history.stats().streaks().unwrap();
+    assert_eq!((streaks.current, streaks.longest), (2, 2));
+  }
+
+  #[test]
+  fn formats_dates() {
+    assert_eq!(date(0), "1970-01-01");
+    assert_eq!(date(-1), "1969-12-31");
+    assert_eq!(date(19796), "2024-03-14");
+    assert_eq!(date(11016), "2000-02-29");
+  }
+}
@@ -0,0 +1,107 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+#[cfg(all(unix, feature = "unix"))]
+use tokio::net::UnixStream;
+#[cfg(all(windows, feature = "named-pipe"))]
+use tokio::net::windows::named_pipe::NamedPipeServer;
+
+/// The stream under a [SpotifyConnection](crate::SpotifyConnection)'s websocket
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SpotifyStream {
+  /// Plain `ws://`
+  Plain(TcpStream),
+  /// `wss://`, see [SpotifyListenerBuilder::tls_pem](crate::SpotifyListenerBuilder::tls_pem)
+  #[cfg(feature = "tls")]
+  Tls(Box<tokio_rustls::server::TlsStream<TcpStream>>),
+  /// `wss://` with the platform tls stack, see [SpotifyListenerBuilder::native_tls_pem](crate::SpotifyListenerBuilder::native_tls_pem)
+  #[cfg(feature = "native-tls")]
+  NativeTls(Box<tokio_native_tls::TlsStream<TcpStream>>),
+  /// Unix socket, see [SpotifyListener::bind_unix](crate::SpotifyListener::bind_unix)
+  #[cfg(all(unix, feature = "unix"))]
+  Unix(UnixStream),
+  /// Windows named pipe, see [SpotifyListener::bind_named_pipe](crate::SpotifyListener::bind_named_pipe)
+  #[cfg(all(windows, feature = "named-pipe"))]
+  NamedPipe(NamedPipeServer),
+}
+
+impl SpotifyStream {
+  /// The tcp stream under any encryption, [None] for unix sockets and named pipes
+  pub fn tcp(&self) -> Option<&TcpStream> {
+    match self {
+      SpotifyStream::Plain(stream) => Some(stream),
+      #[cfg(feature = "tls")]
+      SpotifyStream::Tls(stream) => Some(stream.get_ref().0),
+      #[cfg(feature = "native-tls")]
+      SpotifyStream::NativeTls(stream) => Some(stream.get_ref().get_ref().get_ref()),
+      #[cfg(all(unix, feature = "unix"))]
+      SpotifyStream::Unix(_) => None,
+      #[cfg(all(windows, feature = "named-pipe"))]
+      SpotifyStream::NamedPipe(_) => None,
+    }
+  }
+}
+
+impl AsyncRead for SpotifyStream {
+  fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      SpotifyStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+      #[cfg(feature = "tls")]
+      SpotifyStream::Tls(stream) => Pin::new(stream).poll_read(cx, buf),
+      #[cfg(feature = "native-tls")]
+      SpotifyStream::NativeTls(stream) => Pin::new(stream).poll_read(cx, buf),
+      #[cfg(all(unix, feature = "unix"))]
+      SpotifyStream::Unix(stream) => Pin::new(stream).poll_read(cx, buf),
+      #[cfg(all(windows, feature = "named-pipe"))]
+      SpotifyStream::NamedPipe(stream) => Pin::new(stream).poll_read(cx, buf),
+    }
+  }
+}
+
+impl AsyncWrite for SpotifyStream {
+  fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+    match self.get_mut() {
+      SpotifyStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+      #[cfg(feature = "tls")]
+      SpotifyStream::Tls(stream) => Pin::new(stream).poll_write(cx, buf),
+      #[cfg(feature = "native-tls")]
+      SpotifyStream::NativeTls(stream) => Pin::new(stream).poll_write(cx, buf),
+      #[cfg(all(unix, feature = "unix"))]
+      SpotifyStream::Unix(stream) => Pin::new(stream).poll_write(cx, buf),
+      #[cfg(all(windows, feature = "named-pipe"))]
+      SpotifyStream::NamedPipe(stream) => Pin::new(stream).poll_write(cx, buf),
+    }
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      SpotifyStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+      #[cfg(feature = "tls")]
+      SpotifyStream::Tls(stream) => Pin::new(stream).poll_flush(cx),
+      #[cfg(feature = "native-tls")]
+      SpotifyStream::NativeTls(stream) => Pin::new(stream).poll_flush(cx),
+      #[cfg(all(unix, feature = "unix"))]
+      SpotifyStream::Unix(stream) => Pin::new(stream).poll_flush(cx),
+      #[cfg(all(windows, feature = "named-pipe"))]
+      SpotifyStream::NamedPipe(stream) => Pin::new(stream).poll_flush(cx),
+    }
+  }
+
+  fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+    match self.get_mut() {
+      SpotifyStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+      #[cfg(feature = "tls")]
+      SpotifyStream::Tls(stream) => Pin::new(stream).poll_shutdown(cx),
+      #[cfg(feature = "native-tls")]
+      SpotifyStream::NativeTls(stream) => Pin::new(stream).poll_shutdown(cx),
+      #[cfg(all(unix, feature = "unix"))]
+      SpotifyStream::Unix(stream) => Pin::new(stream).poll_shutdown(cx),
+      #[cfg(all(windows, feature = "named-pipe"))]
+      SpotifyStream::NamedPipe(stream) => Pin::new(stream).poll_shutdown(cx),
+    }
+  }
+}
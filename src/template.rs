@@ -0,0 +1,364 @@
+//! Placeholder templates for now-playing strings, like `{artist} - {title} ({position}/{duration})`
+
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+use std::time::Duration;
+
+use crate::{PlayerState, SpotifyError, SpotifyResult, TrackInfo};
+
+/// A parsed template, parse it once with [Template::parse] and render it for every event
+///
+/// - `{name}` gets replaced with the value, unknown names are empty
+/// - `{name:20}` pads it to 20 characters, `{name:>20}` pads on the left, `{name:^20}` centers it
+/// - `{name:.20}` cuts it off after 20 characters, can be combined like `{name:<20.30}`
+/// - `{?name}...{/name}` is only there when `name` isn't empty, `{!name}...{/name}` only when it is
+/// - `{{` and `}}` are plain braces
+///
+/// Everything [TrackInfo] and [PlayerState] know about is a value:
+/// `title`, `artist`, `album`, `uri`, `url`, `cover`, `duration`, `position`, `remaining`,
+/// `percent` (0 to 100), `state`, `volume` (0 to 100), `shuffle`, `repeat`, `liked`, `explicit`, `device` and `context`
+///
+/// Durations look like `3:07` (`1:02:03` past an hour), flags are `true` or empty so they work with `{?name}`
+///
+/// ```text
+/// {?artist}{artist} - {/artist}{title:.40}{?liked} ♥{/liked} ({position}/{duration})
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+  parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+  Text(String),
+  Value { name: String, spec: Spec },
+  Section { name: String, negate: bool, parts: Vec<Part> },
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+enum Align {
+  #[default]
+  Left,
+  Right,
+  Center,
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+struct Spec {
+  align: Align,
+  width: Option<usize>,
+  max: Option<usize>,
+}
+
+impl Template {
+  /// Fails with [SpotifyError::Template] for unclosed placeholders or sections,
+  /// a lone `}` or a spec that isn't a number
+  pub fn parse(template: &str) -> SpotifyResult<Self> {
+    let mut chars = template.chars().peekable();
+    let parts = parse_parts(&mut chars, None)?;
+
+    Ok(Self { parts })
+  }
+
+  /// Renders it with everything from `state`
+  pub fn render(&self, state: &PlayerState) -> String {
+    self.render_with(|name| state_value(state, name))
+  }
+
+  /// Renders it with only the track, its `position` is 0
+  pub fn render_track(&self, info: &TrackInfo) -> String {
+    self.render_with(|name| track_value(info, Duration::ZERO, name))
+  }
+
+  /// Renders it with custom values, [None] is the same as empty
+  pub fn render_with(&self, values: impl Fn(&str) -> Option<String>) -> String {
+    let mut out = String::new();
+
+    render_parts(&self.parts, &values, &mut out);
+
+    out
+  }
+}
+
+impl FromStr for Template {
+  type Err = SpotifyError;
+
+  fn from_str(template: &str) -> SpotifyResult<Self> {
+    Self::parse(template)
+  }
+}
+
+fn invalid(message: impl Into<String>) -> SpotifyError {
+  SpotifyError::Template(message.into())
+}
+
+/// Parses until the end, or until `{/end}` when inside a section
+fn parse_parts(chars: &mut Peekable<Chars<'_>>, end: Option<&str>) -> SpotifyResult<Vec<Part>> {
+  let mut parts = Vec::new();
+  let mut text = String::new();
+
+  while let Some(char) = chars.next() {
+    match char {
+      '{' if chars.peek() == Some(&'{') => {
+        chars.next();
+        text.push('{');
+      }
+      '}' if chars.peek() == Some(&'}') => {
+        chars.next();
+        text.push('}');
+      }
+      '}' => return Err(invalid("`}` without `{`, use `}}` for a plain brace")),
+      '{' => {
+        let mut placeholder = String::new();
+
+        loop {
+          match chars.next() {
+            Some('}') => break,
+            Some(char) => placeholder.push(char),
+            None => return Err(invalid(format!("`{{{}` isn't closed", placeholder))),
+          }
+        }
+
+        if !text.is_empty() {
+          parts.push(Part::Text(std::mem::take(&mut text)));
+        }
+
+        if let Some(name) = placeholder.strip_prefix('/') {
+          return match end {
+            Some(end) if end == name => Ok(parts),
+            Some(end) => Err(invalid(format!("`{{/{}}}` closes `{}` instead", name, end))),
+            None => Err(invalid(format!("`{{/{}}}` without a section to close", name))),
+          };
+        }
+
+        let part = match placeholder.chars().next() {
+          Some(prefix @ ('?' | '!')) => {
+            let name = placeholder[1..].trim().to_string();
+            let inner = parse_parts(chars, Some(&name))?;
+
+            Part::Section { name, negate: prefix == '!', parts: inner }
+          }
+          _ => {
+            let (name, spec) = match placeholder.split_once(':') {
+              Some((name, spec)) => (name, parse_spec(spec)?),
+              None => (placeholder.as_str(), Spec::default()),
+            };
+
+            Part::Value { name: name.trim().to_string(), spec }
+          }
+        };
+
+        parts.push(part);
+      }
+      char => text.push(char),
+    }
+  }
+
+  if let Some(end) = end {
+    return Err(invalid(format!("`{}` isn't closed with `{{/{}}}`", end, end)));
+  }
+
+  if !text.is_empty() {
+    parts.push(Part::Text(text));
+  }
+
+  Ok(parts)
+}
+
+/// `[<>^][width][.max]`
+fn parse_spec(spec: &str) -> SpotifyResult<Spec> {
+  let (align, rest) = match spec.chars().next() {
+    Some('<') => (Align::Left, &spec[1..]),
+    Some('>') => (Align::Right, &spec[1..]),
+    Some('^') => (Align::Center, &spec[1..]),
+    _ => (Align::Left, spec),
+  };
+
+  let (width, max) = match rest.split_once('.') {
+    Some((width, max)) => (width, Some(max)),
+    None => (rest, None),
+  };
+
+  let number = |number: &str| number.parse::<usize>().map_err(|_| invalid(format!("`{}` isn't a valid spec", spec)));
+
+  Ok(Spec {
+    align,
+    width: Some(width).filter(|width| !width.is_empty()).map(number).transpose()?,
+    max: max.map(number).transpose()?,
+  })
+}
+
+fn render_parts(parts: &[Part], values: &impl Fn(&str) -> Option<String>, out: &mut String) {
+  for part in parts {
+    match part {
+      Part::Text(text) => out.push_str(text),
+      Part::Value { name, spec } => out.push_str(&apply_spec(values(name).unwrap_or_default(), *spec)),
+      Part::Section { name, negate, parts } => {
+        let present = values(name).is_some_and(|value| !value.is_empty());
+
+        if present != *negate {
+          render_parts(parts, values, out);
+        }
+      }
+    }
+  }
+}
+
+fn apply_spec(mut value: String, spec: Spec) -> String {
+  if let Some(max) = spec.max {
+    if value.chars().count() > max {
+      value = value.chars().take(max.saturating_sub(1)).collect::<String>();
+      value.push('…');
+    }
+  }
+
+  let width = spec.width.unwrap_or(0);
+  let len = value.chars().count();
+
+  if len >= width {
+    return value;
+  }
+
+  let pad = width - len;
+
+  match spec.align {
+    Align::Left => format!("{}{}", value, " ".repeat(pad)),
+    Align::Right => format!("{}{}", " ".repeat(pad), value),
+    Align::Center => format!("{}{}{}", " ".repeat(pad / 2), value, " ".repeat(pad - pad / 2)),
+  }
+}
+
+/// `3:07`, or `1:02:03` past an hour
+fn duration(duration: Duration) -> String {
+  let secs = duration.as_secs();
+
+  match secs >= 3600 {
+    true => format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60),
+    false => format!("{}:{:02}", secs / 60, secs % 60),
+  }
+}
+
+fn flag(value: bool) -> Option<String> {
+  value.then(|| "true".to_string())
+}
+
+fn percent(value: f64) -> String {
+  format!("{}", (value * 100.0).round() as i64)
+}
+
+fn track_value(info: &TrackInfo, position: Duration, name: &str) -> Option<String> {
+  match name {
+    "title" => Some(info.title.clone()),
+    "artist" => Some(info.artist()),
+    "album" => Some(info.album.name.clone()),
+    "uri" => Some(info.uri.to_string()),
+    "url" => info.url(),
+    "cover" => info.cover().map(str::to_string),
+    "duration" => Some(duration(info.duration)),
+    "position" => Some(duration(position)),
+    "remaining" => Some(duration(info.duration.saturating_sub(position))),
+    "percent" if !info.duration.is_zero() => Some(percent(position.as_secs_f64() / info.duration.as_secs_f64())),
+    "state" => Some(info.state.to_string()),
+    "liked" => info.liked.and_then(flag),
+    "explicit" => info.explicit.and_then(flag),
+    _ => None,
+  }
+}
+
+fn state_value(state: &PlayerState, name: &str) -> Option<String> {
+  match name {
+    "state" => Some(state.state().to_string()),
+    "volume" => Some(percent(state.volume())),
+    "shuffle" => flag(state.shuffle()),
+    "repeat" => Some(state.repeat().to_string()),
+    "device" => state.device().map(|device| device.name.clone()),
+    "context" => state.context().and_then(|context| context.name.clone()),
+    name => state.current_track().and_then(|info| track_value(info, state.position(), name)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let template = Template::parse(template).unwrap_or_else(|err| panic!("{:?} didn't parse: {}", template, err));
+
+    template.render_with(|name| values.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string()))
+  }
+
+  #[test]
+  fn values() {
+    let values = [("artist", "Artist"), ("title", "Title")];
+
+    assert_eq!(render("{artist} - {title}", &values), "Artist - Title");
+    assert_eq!(render("{ title }", &values), "Title");
+    assert_eq!(render("{unknown}|", &values), "|");
+    assert_eq!(render("", &values), "");
+    assert_eq!(render("just text", &values), "just text");
+  }
+
+  #[test]
+  fn escaping() {
+    let values = [("title", "Title")];
+
+    assert_eq!(render("{{title}}", &values), "{title}");
+    assert_eq!(render("{{{title}}}", &values), "{Title}");
+    assert_eq!(render("a }} b {{", &values), "a } b {");
+  }
+
+  #[test]
+  fn padding() {
+    let values = [("v", "abc"), ("wide", "日本語")];
+
+    assert_eq!(render("{v:6}|", &values), "abc   |");
+    assert_eq!(render("{v:<6}|", &values), "abc   |");
+    assert_eq!(render("{v:>6}|", &values), "   abc|");
+    assert_eq!(render("{v:^6}|", &values), " abc  |");
+    assert_eq!(render("{v:2}|", &values), "abc|");
+    // counts characters, not bytes
+    assert_eq!(render("{wide:4}|", &values), "日本語 |");
+  }
+
+  #[test]
+  fn cutting_off() {
+    let values = [("v", "abcdef"), ("wide", "日本語です")];
+
+    assert_eq!(render("{v:.4}", &values), "abc…");
+    assert_eq!(render("{v:.6}", &values), "abcdef");
+    assert_eq!(render("{v:>6.4}|", &values), "  abc…|");
+    assert_eq!(render("{wide:.3}", &values), "日本…");
+  }
+
+  #[test]
+  fn sections() {
+    let values = [("artist", "Artist"), ("empty", ""), ("title", "Title")];
+
+    assert_eq!(render("{?artist}{artist} - {/artist}{title}", &values), "Artist - Title");
+    assert_eq!(render("{?empty}hidden{/empty}{title}", &values), "Title");
+    assert_eq!(render("{?missing}hidden{/missing}{title}", &values), "Title");
+    assert_eq!(render("{!empty}shown{/empty}", &values), "shown");
+    assert_eq!(render("{!artist}hidden{/artist}", &values), "");
+    assert_eq!(render("{?artist}a{?title}b{!title}c{/title}{/title}{/artist}", &values), "ab");
+  }
+
+  #[test]
+  fn invalid() {
+    for template in ["{title", "title}", "{?artist}no end", "{?a}{/b}", "{/a}", "{v:x}", "{v:5.x}", "{v:.}"] {
+      assert!(Template::parse(template).is_err(), "{:?} parsed", template);
+    }
+  }
+
+  #[test]
+  fn tracks() {
+    let info = TrackInfo::builder()
+      .title("Title")
+      .artist("Artist")
+      .duration(Duration::from_secs(3725))
+      .build()
+      .unwrap();
+    let template = Template::parse("{title} by {artist} ({position}/{duration}, {remaining} left){?liked} ♥{/liked}").unwrap();
+
+    assert_eq!(template.render_track(&info), "Title by Artist (0:00/1:02:05, 1:02:05 left)");
+  }
+}
@@ -0,0 +1,172 @@
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures_util::ready;
+
+use crate::runtime::{Delay, Runtime};
+use crate::SpotifyEvent;
+
+/// Lets through at most one [SpotifyEvent::ProgressChanged] per `spacing`,
+/// the newest one that came in too early waits until it's allowed and the rest get dropped,
+/// see [SpotifyListenerBuilder::progress_throttle](crate::SpotifyListenerBuilder::progress_throttle)
+#[derive(Debug)]
+pub(crate) struct ProgressThrottle {
+  spacing: Duration,
+  last: Option<Instant>,
+  held: Option<SpotifyEvent>,
+  delay: Delay,
+}
+
+impl ProgressThrottle {
+  pub(crate) fn new(spacing: Duration, runtime: Arc<dyn Runtime>) -> Self {
+    Self {
+      // so `last + spacing` can't overflow
+      spacing: spacing.min(crate::FAR_FUTURE),
+      last: None,
+      held: None,
+      delay: Delay::new(runtime, spacing),
+    }
+  }
+
+  /// [None] when `event` has to wait or got replaced by a newer one
+  pub(crate) fn filter(&mut self, event: SpotifyEvent) -> Option<SpotifyEvent> {
+    match event {
+      SpotifyEvent::ProgressChanged { .. } => {
+        let now = Instant::now();
+
+        match self.last.map(|last| last + self.spacing) {
+          Some(allowed) if now < allowed => {
+            self.delay.reset(allowed - now);
+            self.held = Some(event);
+            None
+          }
+          _ => {
+            self.last = Some(now);
+            Some(event)
+          }
+        }
+      }
+      // the position from before is for the old track, or the snapshot already has a newer one
+      SpotifyEvent::TrackChanged(_) | SpotifyEvent::StateSnapshot(_) => {
+        self.held = None;
+        Some(event)
+      }
+      event => Some(event),
+    }
+  }
+
+  /// Ready with the held progress once it's allowed through
+  pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<SpotifyEvent> {
+    if self.held.is_none() {
+      // only [Self::filter] holds something, which happens right before this gets polled again
+      return Poll::Pending;
+    }
+
+    ready!(self.delay.poll(cx));
+
+    self.last = Some(Instant::now());
+
+    Poll::Ready(self.held.take().expect("checked above"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::task::Waker;
+  use std::time::SystemTime;
+
+  use super::*;
+  use crate::runtime::NeverWakes;
+  use crate::{TrackInfo, TrackState};
+
+  const SPACING: Duration = Duration::from_millis(100);
+
+  fn throttle() -> ProgressThrottle {
+    ProgressThrottle::new(SPACING, Arc::new(NeverWakes))
+  }
+
+  fn poll(throttle: &mut ProgressThrottle) -> Poll<SpotifyEvent> {
+    throttle.poll(&mut Context::from_waker(Waker::noop()))
+  }
+
+  fn progress(secs: u64) -> SpotifyEvent {
+    SpotifyEvent::ProgressChanged { position: Duration::from_secs(secs), percent: 0.0, timestamp: SystemTime::now() }
+  }
+
+  fn position(event: &SpotifyEvent) -> u64 {
+    match event {
+      SpotifyEvent::ProgressChanged { position, .. } => position.as_secs(),
+      event => panic!("not progress: {event:?}"),
+    }
+  }
+
+  #[test]
+  fn first_one_goes_through() {
+    let mut throttle = throttle();
+
+    assert_eq!(throttle.filter(progress(1)).as_ref().map(position), Some(1));
+    assert!(poll(&mut throttle).is_pending());
+  }
+
+  #[test]
+  fn newest_one_comes_after_the_spacing() {
+    let mut throttle = throttle();
+
+    let _ = throttle.filter(progress(1));
+    assert!(throttle.filter(progress(2)).is_none());
+    assert!(throttle.filter(progress(3)).is_none());
+    assert!(poll(&mut throttle).is_pending());
+
+    std::thread::sleep(SPACING + SPACING / 2);
+    assert!(matches!(poll(&mut throttle), Poll::Ready(event) if position(&event) == 3));
+    // the ones it replaced are gone
+    assert!(poll(&mut throttle).is_pending());
+  }
+
+  #[test]
+  fn trailing_one_counts_as_the_last() {
+    let mut throttle = throttle();
+
+    let _ = throttle.filter(progress(1));
+    let _ = throttle.filter(progress(2));
+    std::thread::sleep(SPACING + SPACING / 2);
+    assert!(poll(&mut throttle).is_ready());
+
+    // too soon after the one that was held back
+    assert!(throttle.filter(progress(3)).is_none());
+  }
+
+  #[test]
+  fn goes_through_once_the_spacing_passed() {
+    let mut throttle = throttle();
+
+    let _ = throttle.filter(progress(1));
+    std::thread::sleep(SPACING + SPACING / 2);
+
+    assert_eq!(throttle.filter(progress(2)).as_ref().map(position), Some(2));
+    assert!(poll(&mut throttle).is_pending());
+  }
+
+  #[test]
+  fn track_changes_drop_the_held_one() {
+    let mut throttle = throttle();
+    let info = TrackInfo::builder().uid("a").build().unwrap();
+
+    let _ = throttle.filter(progress(1));
+    let _ = throttle.filter(progress(2));
+    assert!(throttle.filter(SpotifyEvent::TrackChanged(info)).is_some());
+
+    std::thread::sleep(SPACING + SPACING / 2);
+    assert!(poll(&mut throttle).is_pending());
+  }
+
+  #[test]
+  fn lets_everything_else_through() {
+    let mut throttle = throttle();
+
+    let _ = throttle.filter(progress(1));
+    assert!(throttle.filter(SpotifyEvent::StateChanged(TrackState::Paused)).is_some());
+    assert!(throttle.filter(SpotifyEvent::VolumeChanged(0.5)).is_some());
+  }
+}
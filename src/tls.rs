@@ -0,0 +1,139 @@
+//! Loading certificates for `wss://`, only with the `tls` or `native-tls` feature
+
+#[cfg(feature = "tls")]
+use std::fs::File;
+#[cfg(feature = "tls")]
+use std::io::BufReader;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+
+use tokio::net::TcpStream;
+#[cfg(feature = "tls")]
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+
+use crate::{SpotifyError, SpotifyResult, SpotifyStream};
+
+/// Where the certificate comes from, resolved when the listener binds
+#[derive(Clone)]
+pub(crate) enum TlsSource {
+  #[cfg(feature = "tls")]
+  Config(Arc<ServerConfig>),
+  #[cfg(feature = "tls")]
+  Pem { cert: PathBuf, key: PathBuf },
+  #[cfg(feature = "native-tls")]
+  NativeAcceptor(tokio_native_tls::native_tls::TlsAcceptor),
+  #[cfg(feature = "native-tls")]
+  NativePem { cert: PathBuf, key: PathBuf },
+  #[cfg(feature = "native-tls")]
+  NativePkcs12 { path: PathBuf, password: String },
+}
+
+impl std::fmt::Debug for TlsSource {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      #[cfg(feature = "tls")]
+      TlsSource::Config(_) => f.write_str("Config"),
+      #[cfg(feature = "tls")]
+      TlsSource::Pem { cert, key } => f.debug_struct("Pem").field("cert", cert).field("key", key).finish(),
+      #[cfg(feature = "native-tls")]
+      TlsSource::NativeAcceptor(_) => f.write_str("NativeAcceptor"),
+      #[cfg(feature = "native-tls")]
+      TlsSource::NativePem { cert, key } => f.debug_struct("NativePem").field("cert", cert).field("key", key).finish(),
+      // no password in logs
+      #[cfg(feature = "native-tls")]
+      TlsSource::NativePkcs12 { path, .. } => f.debug_struct("NativePkcs12").field("path", path).finish_non_exhaustive(),
+    }
+  }
+}
+
+impl TlsSource {
+  pub(crate) fn acceptor(self) -> SpotifyResult<TlsAcceptor> {
+    let acceptor = match self {
+      #[cfg(feature = "tls")]
+      TlsSource::Config(config) => TlsAcceptor::Rustls(config.into()),
+      #[cfg(feature = "tls")]
+      TlsSource::Pem { cert, key } => TlsAcceptor::Rustls(Arc::new(load_pem(&cert, &key).map_err(SpotifyError::Tls)?).into()),
+      #[cfg(feature = "native-tls")]
+      TlsSource::NativeAcceptor(acceptor) => TlsAcceptor::Native(acceptor.into()),
+      #[cfg(feature = "native-tls")]
+      TlsSource::NativePem { cert, key } => TlsAcceptor::Native(load_native_pem(&cert, &key).map_err(SpotifyError::Tls)?.into()),
+      #[cfg(feature = "native-tls")]
+      TlsSource::NativePkcs12 { path, password } => {
+        TlsAcceptor::Native(load_native_pkcs12(&path, &password).map_err(SpotifyError::Tls)?.into())
+      }
+    };
+
+    Ok(acceptor)
+  }
+}
+
+/// Whichever tls stack the listener was built with
+pub(crate) enum TlsAcceptor {
+  #[cfg(feature = "tls")]
+  Rustls(tokio_rustls::TlsAcceptor),
+  #[cfg(feature = "native-tls")]
+  Native(tokio_native_tls::TlsAcceptor),
+}
+
+impl TlsAcceptor {
+  pub(crate) async fn accept(&self, stream: TcpStream) -> SpotifyResult<SpotifyStream> {
+    let stream = match self {
+      #[cfg(feature = "tls")]
+      TlsAcceptor::Rustls(acceptor) => SpotifyStream::Tls(Box::new(acceptor.accept(stream).await.map_err(SpotifyError::Tls)?)),
+      #[cfg(feature = "native-tls")]
+      TlsAcceptor::Native(acceptor) => {
+        let stream = acceptor.accept(stream).await.map_err(|err| SpotifyError::Tls(io::Error::other(err)))?;
+        SpotifyStream::NativeTls(Box::new(stream))
+      }
+    };
+
+    Ok(stream)
+  }
+}
+
+#[cfg(feature = "tls")]
+fn load_pem(cert: &Path, key: &Path) -> io::Result<ServerConfig> {
+  let certs = rustls_pemfile::certs(&mut BufReader::new(File::open(cert)?))?
+    .into_iter()
+    .map(Certificate)
+    .collect::<Vec<_>>();
+
+  // pkcs8 is what most tools generate, older ones still use rsa keys
+  let key = rustls_pemfile::read_all(&mut BufReader::new(File::open(key)?))?
+    .into_iter()
+    .find_map(|item| match item {
+      rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+      _ => None,
+    })
+    .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "no private key found"))?;
+
+  ServerConfig::builder()
+    .with_safe_defaults()
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+/// native-tls only takes pkcs8 keys from PEM files, others have to be converted or put in a pkcs12 file
+#[cfg(feature = "native-tls")]
+fn load_native_pem(cert: &Path, key: &Path) -> io::Result<tokio_native_tls::native_tls::TlsAcceptor> {
+  let identity = tokio_native_tls::native_tls::Identity::from_pkcs8(&std::fs::read(cert)?, &std::fs::read(key)?)
+    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+
+  native_acceptor(identity)
+}
+
+#[cfg(feature = "native-tls")]
+fn load_native_pkcs12(path: &Path, password: &str) -> io::Result<tokio_native_tls::native_tls::TlsAcceptor> {
+  let identity = tokio_native_tls::native_tls::Identity::from_pkcs12(&std::fs::read(path)?, password)
+    .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))?;
+
+  native_acceptor(identity)
+}
+
+#[cfg(feature = "native-tls")]
+fn native_acceptor(identity: tokio_native_tls::native_tls::Identity) -> io::Result<tokio_native_tls::native_tls::TlsAcceptor> {
+  tokio_native_tls::native_tls::TlsAcceptor::new(identity).map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use crate::{Album, Artist, EpisodeInfo, MediaKind, SpotifyError, SpotifyResult, SpotifyUri, TrackInfo, TrackState, UriKind};
+
+/// Builds a [TrackInfo] for tests and made up events without listing every field,
+/// created with [TrackInfo::builder]
+///
+/// Default: a 3 minute track that's playing, without a title, URI or anything else
+///
+/// [Self::build] checks that it's something the extension could send, a struct literal still works for anything it doesn't allow
+#[derive(Debug, Clone)]
+pub struct TrackInfoBuilder {
+  info: TrackInfo,
+}
+
+impl Default for TrackInfoBuilder {
+  fn default() -> Self {
+    Self {
+      info: TrackInfo {
+        state: TrackState::Playing,
+        duration: Duration::from_secs(180),
+        ..TrackInfo::default()
+      },
+    }
+  }
+}
+
+impl TrackInfoBuilder {
+  pub fn uid(mut self, uid: impl Into<String>) -> Self {
+    self.info.uid = uid.into();
+    self
+  }
+
+  /// Track, episode or local file URI, episodes and local files also set [TrackInfo::kind] and [TrackInfo::is_local]
+  pub fn uri(mut self, uri: impl Into<SpotifyUri>) -> Self {
+    self.info.uri = uri.into();
+    self
+  }
+
+  pub fn state(mut self, state: TrackState) -> Self {
+    self.info.state = state;
+    self
+  }
+
+  pub fn duration(mut self, duration: Duration) -> Self {
+    self.info.duration = duration;
+    self
+  }
+
+  pub fn title(mut self, title: impl Into<String>) -> Self {
+    self.info.title = title.into();
+    self
+  }
+
+  /// Only the name, use [Self::album_info] for the rest
+  pub fn album(mut self, name: impl Into<String>) -> Self {
+    self.info.album.name = name.into();
+    self
+  }
+
+  pub fn album_info(mut self, album: Album) -> Self {
+    self.info.album = album;
+    self
+  }
+
+  /// Adds an artist without a URI, can be called multiple times
+  pub fn artist(self, name: impl Into<String>) -> Self {
+    self.artist_info(Artist { name: name.into(), uri: SpotifyUri::default() })
+  }
+
+  /// Adds an artist, can be called multiple times
+  pub fn artist_info(mut self, artist: Artist) -> Self {
+    self.info.artists.push(artist);
+    self
+  }
+
+  pub fn cover_url(mut self, url: impl Into<String>) -> Self {
+    self.info.cover_url = Some(url.into());
+    self
+  }
+
+  pub fn background_url(mut self, url: impl Into<String>) -> Self {
+    self.info.background_url = Some(url.into());
+    self
+  }
+
+  pub fn canvas(mut self, url: impl Into<String>) -> Self {
+    self.info.canvas = Some(url.into());
+    self
+  }
+
+  pub fn liked(mut self, liked: bool) -> Self {
+    self.info.liked = Some(liked);
+    self
+  }
+
+  /// Position on the disc and the disc, both starting at 1
+  pub fn track_number(mut self, track: u32, disc: u32) -> Self {
+    self.info.track_number = Some(track);
+    self.info.disc_number = Some(disc);
+    self
+  }
+
+  pub fn explicit(mut self, explicit: bool) -> Self {
+    self.info.explicit = Some(explicit);
+    self
+  }
+
+  /// Between 0 and 100
+  pub fn popularity(mut self, popularity: u32) -> Self {
+    self.info.popularity = Some(popularity);
+    self
+  }
+
+  /// Makes it a podcast episode
+  pub fn episode(mut self, episode: EpisodeInfo) -> Self {
+    self.info.kind = MediaKind::Episode;
+    self.info.episode = Some(episode);
+    self
+  }
+
+  /// Makes it a local file
+  pub fn local(mut self) -> Self {
+    self.info.is_local = true;
+    self
+  }
+
+  /// Fails with [SpotifyError::InvalidTrack] if the duration is 0,
+  /// a URI isn't the right kind (or doesn't match [Self::episode] and [Self::local]),
+  /// or the popularity is over 100
+  pub fn build(self) -> SpotifyResult<TrackInfo> {
+    let mut info = self.info;
+    let invalid = |message: String| Err(SpotifyError::InvalidTrack(message));
+
+    if info.duration.is_zero() {
+      return invalid("duration is 0".to_string());
+    }
+
+    if !info.uri.is_empty() {
+      match info.uri.kind() {
+        Some(UriKind::Track) if info.episode.is_none() && !info.is_local => {}
+        Some(UriKind::Episode) => info.kind = MediaKind::Episode,
+        Some(UriKind::Local) => info.is_local = true,
+        _ => return invalid(format!("`{}` isn't {} uri", info.uri, expected(&info))),
+      }
+
+      if info.kind == MediaKind::Episode && info.is_local {
+        return invalid("it can't be a local file and an episode".to_string());
+      }
+    }
+
+    check_uri(&info.album.uri, UriKind::Album)?;
+
+    if let Some(episode) = &info.episode {
+      check_uri(&episode.show_uri, UriKind::Show)?;
+    }
+
+    for artist in &info.artists {
+      check_uri(&artist.uri, UriKind::Artist)?;
+    }
+
+    if let Some(popularity) = info.popularity.filter(|popularity| *popularity > 100) {
+      return invalid(format!("popularity is {}, it's at most 100", popularity));
+    }
+
+    Ok(info)
+  }
+}
+
+fn expected(info: &TrackInfo) -> &'static str {
+  match (info.is_local, &info.episode) {
+    (true, _) => "a local file",
+    (false, Some(_)) => "an episode",
+    (false, None) => "a track",
+  }
+}
+
+/// Empty ones are fine, they're empty for local files
+fn check_uri(uri: &SpotifyUri, kind: UriKind) -> SpotifyResult<()> {
+  match uri.is_empty() || uri.kind() == Some(kind) {
+    true => Ok(()),
+    false => Err(SpotifyError::InvalidTrack(format!("`{}` isn't {} uri", uri, match kind {
+      UriKind::Album => "an album",
+      UriKind::Artist => "an artist",
+      _ => "a show",
+    }))),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ID: &str = "4uLU6hMCjMI75M1A2tKUQC";
+
+  fn uri(kind: &str) -> String {
+    format!("spotify:{}:{}", kind, ID)
+  }
+
+  fn episode() -> EpisodeInfo {
+    EpisodeInfo { show: "Show".to_string(), ..EpisodeInfo::default() }
+  }
+
+  fn invalid(builder: TrackInfoBuilder) -> bool {
+    matches!(builder.build(), Err(SpotifyError::InvalidTrack(_)))
+  }
+
+  #[test]
+  fn defaults() {
+    let info = TrackInfo::builder().build().unwrap();
+
+    assert_eq!(info.state, TrackState::Playing);
+    assert_eq!(info.duration, Duration::from_secs(180));
+    assert_eq!(info.kind, MediaKind::Track);
+    assert!(info.uri.is_empty() && info.title.is_empty() && info.artists.is_empty());
+  }
+
+  #[test]
+  fn sets_fields() {
+    let info = TrackInfo::builder()
+      .uri(uri("track"))
+      .title("Title")
+      .album("Album")
+      .artist("First")
+      .artist_info(Artist { name: "Second".to_string(), uri: uri("artist").into() })
+      .liked(true)
+      .track_number(3, 2)
+      .popularity(100)
+      .build()
+      .unwrap();
+
+    assert_eq!(info.title, "Title");
+    assert_eq!(info.album.name, "Album");
+    assert_eq!(info.artists.iter().map(|artist| artist.name.as_str()).collect::<Vec<_>>(), ["First", "Second"]);
+    assert_eq!(info.liked, Some(true));
+    assert_eq!((info.track_number, info.disc_number), (Some(3), Some(2)));
+    assert_eq!(info.popularity, Some(100));
+  }
+
+  #[test]
+  fn kind_follows_the_uri() {
+    let from_uri = TrackInfo::builder().uri(uri("episode")).build().unwrap();
+    assert_eq!(from_uri.kind, MediaKind::Episode);
+
+    let local = TrackInfo::builder().uri("spotify:local:Artist:Album:Title:180").build().unwrap();
+    assert!(local.is_local);
+    assert_eq!(local.kind, MediaKind::Track);
+
+    let with_info = TrackInfo::builder().uri(uri("episode")).episode(episode()).build().unwrap();
+    assert_eq!(with_info.episode.unwrap().show, "Show");
+  }
+
+  #[test]
+  fn rejects_invalid_tracks() {
+    assert!(invalid(TrackInfo::builder().duration(Duration::ZERO)));
+    assert!(invalid(TrackInfo::builder().popularity(101)));
+    assert!(invalid(TrackInfo::builder().uri(uri("album"))));
+    assert!(invalid(TrackInfo::builder().uri("not a uri")));
+  }
+
+  #[test]
+  fn rejects_uris_that_dont_match() {
+    assert!(invalid(TrackInfo::builder().uri(uri("track")).episode(episode())));
+    assert!(invalid(TrackInfo::builder().uri(uri("track")).local()));
+    assert!(invalid(TrackInfo::builder().uri(uri("episode")).local()));
+    assert!(invalid(TrackInfo::builder().album_info(Album { uri: uri("track").into(), ..Album::default() })));
+    assert!(invalid(TrackInfo::builder().artist_info(Artist { name: "Artist".to_string(), uri: uri("album").into() })));
+    assert!(invalid(TrackInfo::builder().episode(EpisodeInfo { show_uri: uri("artist").into(), ..episode() })));
+  }
+}
@@ -0,0 +1,134 @@
+use std::io;
+#[cfg(all(unix, feature = "unix"))]
+use std::path::{Path, PathBuf};
+
+use tokio::net::{TcpListener, TcpStream};
+#[cfg(all(unix, feature = "unix"))]
+use tokio::net::{UnixListener, UnixStream};
+#[cfg(all(windows, feature = "named-pipe"))]
+use tokio::net::windows::named_pipe::{NamedPipeServer, ServerOptions};
+
+/// What a [SpotifyListener](crate::SpotifyListener) accepts connections on
+#[derive(Debug)]
+pub(crate) enum Transport {
+  Tcp(TcpListener),
+  #[cfg(all(unix, feature = "unix"))]
+  Unix(UnixSocket),
+  #[cfg(all(windows, feature = "named-pipe"))]
+  NamedPipe(NamedPipe),
+}
+
+/// A stream that was just accepted, before the websocket handshake
+pub(crate) enum Accepted {
+  Tcp(TcpStream, std::net::SocketAddr),
+  #[cfg(all(unix, feature = "unix"))]
+  Unix(UnixStream),
+  #[cfg(all(windows, feature = "named-pipe"))]
+  NamedPipe(NamedPipeServer),
+}
+
+impl Transport {
+  pub(crate) async fn accept(&self) -> io::Result<Accepted> {
+    match self {
+      Transport::Tcp(listener) => listener.accept().await.map(|(stream, peer)| Accepted::Tcp(stream, peer)),
+      #[cfg(all(unix, feature = "unix"))]
+      Transport::Unix(socket) => socket.listener.accept().await.map(|(stream, _)| Accepted::Unix(stream)),
+      #[cfg(all(windows, feature = "named-pipe"))]
+      Transport::NamedPipe(pipe) => pipe.accept().await.map(Accepted::NamedPipe),
+    }
+  }
+}
+
+impl Accepted {
+  /// Address of the other end, [None] for unix sockets
+  pub(crate) fn peer(&self) -> Option<std::net::SocketAddr> {
+    match self {
+      Accepted::Tcp(_, peer) => Some(*peer),
+      #[cfg(all(unix, feature = "unix"))]
+      Accepted::Unix(_) => None,
+      #[cfg(all(windows, feature = "named-pipe"))]
+      Accepted::NamedPipe(_) => None,
+    }
+  }
+}
+
+/// Removes the socket file when dropped, so the next bind doesn't fail
+#[cfg(all(unix, feature = "unix"))]
+#[derive(Debug)]
+pub(crate) struct UnixSocket {
+  pub(crate) listener: UnixListener,
+  path: PathBuf,
+}
+
+#[cfg(all(unix, feature = "unix"))]
+impl UnixSocket {
+  pub(crate) fn bind(path: &Path) -> io::Result<Self> {
+    let listener = match UnixListener::bind(path) {
+      // left behind by a listener that crashed, nothing is listening if connecting fails
+      Err(err) if err.kind() == io::ErrorKind::AddrInUse && is_stale_socket(path) => {
+        std::fs::remove_file(path)?;
+        UnixListener::bind(path)?
+      }
+      listener => listener?,
+    };
+
+    Ok(Self { listener, path: path.to_path_buf() })
+  }
+
+  pub(crate) fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+/// Only removes sockets, never some other file that happens to be there
+#[cfg(all(unix, feature = "unix"))]
+fn is_stale_socket(path: &Path) -> bool {
+  use std::os::unix::fs::FileTypeExt;
+
+  let is_socket = std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket());
+
+  is_socket && std::os::unix::net::UnixStream::connect(path).is_err()
+}
+
+#[cfg(all(unix, feature = "unix"))]
+impl Drop for UnixSocket {
+  fn drop(&mut self) {
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+/// Every client gets its own instance of the pipe,
+/// so a new one gets created as soon as the last one is connected to
+#[cfg(all(windows, feature = "named-pipe"))]
+#[derive(Debug)]
+pub(crate) struct NamedPipe {
+  name: String,
+  next: tokio::sync::Mutex<NamedPipeServer>,
+}
+
+#[cfg(all(windows, feature = "named-pipe"))]
+impl NamedPipe {
+  pub(crate) fn bind(name: &str) -> io::Result<Self> {
+    // fails if another process already owns the name instead of sharing it with them
+    let server = ServerOptions::new().first_pipe_instance(true).create(name)?;
+
+    Ok(Self {
+      name: name.to_string(),
+      next: tokio::sync::Mutex::new(server),
+    })
+  }
+
+  async fn accept(&self) -> io::Result<NamedPipeServer> {
+    let mut next = self.next.lock().await;
+
+    next.connect().await?;
+
+    let server = ServerOptions::new().create(&self.name)?;
+
+    Ok(std::mem::replace(&mut *next, server))
+  }
+
+  pub(crate) fn name(&self) -> &str {
+    &self.name
+  }
+}
@@ -0,0 +1,47 @@
+//! TypeScript definitions of the wire format, only with the `ts-rs` feature
+
+use ts_rs::{Config, TS};
+
+use crate::{
+  Album, AlbumCovers, Artist, ContextKind, CoverColors, EpisodeInfo, EventMask, LyricLine, MediaKind, PlaybackContext, PlayerSnapshot,
+  RepeatMode, SpotifyEvent, SpotifyMessage, SpotifyUri, TrackInfo, TrackState, SCHEMA_VERSION,
+};
+
+/// TypeScript definitions of every event and message and everything in them, as one `.d.ts` file,
+/// [examples/typescript.rs](https://github.com/Ricky12Awesome/spotify_info/blob/main/examples/typescript.rs)
+/// writes it next to the extension so it can't silently drift from the crate
+///
+/// Numbers that could be bigger than 2^53 are still `number` since that's what the extension gets from `JSON.parse`
+pub fn typescript() -> String {
+  let config = Config::new().with_large_int("number");
+
+  let decls = [
+    SpotifyEvent::decl(&config),
+    SpotifyMessage::decl(&config),
+    TrackInfo::decl(&config),
+    TrackState::decl(&config),
+    RepeatMode::decl(&config),
+    MediaKind::decl(&config),
+    Artist::decl(&config),
+    Album::decl(&config),
+    AlbumCovers::decl(&config),
+    EpisodeInfo::decl(&config),
+    PlayerSnapshot::decl(&config),
+    PlaybackContext::decl(&config),
+    ContextKind::decl(&config),
+    LyricLine::decl(&config),
+    CoverColors::decl(&config),
+    SpotifyUri::decl(&config),
+    EventMask::decl(&config),
+  ];
+
+  let mut out = format!("// Generated by spotify_info {} for schema version {}, don't edit it by hand\n", env!("CARGO_PKG_VERSION"), SCHEMA_VERSION);
+
+  for decl in decls {
+    out.push_str("\nexport ");
+    out.push_str(&decl);
+    out.push('\n');
+  }
+
+  out
+}
@@ -0,0 +1,301 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{SpotifyError, SpotifyResult};
+
+/// Where share links point to
+const OPEN_URL: &str = "https://open.spotify.com";
+
+/// What a [SpotifyUri] points to
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum UriKind {
+  Track,
+  Album,
+  Artist,
+  Playlist,
+  /// A podcast episode
+  Episode,
+  /// A podcast
+  Show,
+  User,
+  /// Liked Songs
+  Collection,
+  /// A local file, these don't have an id or a link
+  Local,
+}
+
+impl UriKind {
+  /// Same as it's written in the URI, like `track`
+  pub fn as_str(self) -> &'static str {
+    match self {
+      UriKind::Track => "track",
+      UriKind::Album => "album",
+      UriKind::Artist => "artist",
+      UriKind::Playlist => "playlist",
+      UriKind::Episode => "episode",
+      UriKind::Show => "show",
+      UriKind::User => "user",
+      UriKind::Collection => "collection",
+      UriKind::Local => "local",
+    }
+  }
+
+  /// Kinds that are followed by a base62 id
+  fn with_id(kind: &str) -> Option<Self> {
+    match kind {
+      "track" => Some(UriKind::Track),
+      "album" => Some(UriKind::Album),
+      "artist" => Some(UriKind::Artist),
+      "playlist" => Some(UriKind::Playlist),
+      "episode" => Some(UriKind::Episode),
+      "show" => Some(UriKind::Show),
+      _ => None,
+    }
+  }
+}
+
+impl Display for UriKind {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// A spotify URI like `spotify:track:4uLU6hMCjMI75M1A2tKUQC`
+///
+/// (De)serializes as the plain string, whatever the extension sends is kept as is
+/// since local files and older extensions send ones that aren't valid (or empty ones),
+/// [Self::kind] is [None] for those
+///
+/// [Self::parse] is for URIs and open.spotify.com links from users, it only accepts valid ones
+#[derive(Debug, Clone, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "ts-rs", derive(ts_rs::TS))]
+#[serde(transparent)]
+pub struct SpotifyUri(String);
+
+impl SpotifyUri {
+  /// Parses a URI or an open.spotify.com link (with or without `https://`, `?si=...` is ignored),
+  /// links get turned into the URI they point to
+  ///
+  /// Fails with [SpotifyError::InvalidUri] if it's neither, or the id isn't base62
+  pub fn parse(input: &str) -> SpotifyResult<Self> {
+    let input = input.trim();
+
+    let uri = match input.starts_with("spotify:") {
+      true => Some(Self(input.to_string())),
+      false => from_url(input).map(Self),
+    };
+
+    uri.filter(|uri| uri.kind().is_some()).ok_or_else(|| SpotifyError::InvalidUri(input.to_string()))
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  /// Local files and tracks from older extensions can have an empty URI
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+
+  /// What it points to, [None] if it isn't a valid URI
+  pub fn kind(&self) -> Option<UriKind> {
+    self.parts().map(|(kind, _)| kind)
+  }
+
+  /// The base62 id, or the name for [UriKind::User],
+  /// [None] for Liked Songs, local files and anything that isn't valid
+  pub fn id(&self) -> Option<&str> {
+    self.parts().and_then(|(_, id)| id)
+  }
+
+  /// Link like `https://open.spotify.com/track/4uLU6hMCjMI75M1A2tKUQC`,
+  /// [None] for local files and anything that isn't valid
+  pub fn to_url(&self) -> Option<String> {
+    let path = match self.parts()? {
+      (UriKind::Local, _) => return None,
+      (UriKind::Collection, _) => "collection/tracks".to_string(),
+      (kind, Some(id)) => format!("{}/{}", kind, id),
+      (_, None) => return None,
+    };
+
+    Some(format!("{}/{}", OPEN_URL, path))
+  }
+
+  fn parts(&self) -> Option<(UriKind, Option<&str>)> {
+    let parts = self.0.strip_prefix("spotify:")?.split(':').collect::<Vec<_>>();
+
+    match parts[..] {
+      // older playlist URIs have the owner in them
+      ["user", _, "playlist", id] if is_id(id) => Some((UriKind::Playlist, Some(id))),
+      ["user", _, "collection"] | ["collection"] | ["collection", "tracks"] => Some((UriKind::Collection, None)),
+      ["user", user] if !user.is_empty() => Some((UriKind::User, Some(user))),
+      ["local", ..] => Some((UriKind::Local, None)),
+      // after the others, `tracks` and user names look like ids too
+      [kind, id] if is_id(id) => UriKind::with_id(kind).map(|kind| (kind, Some(id))),
+      _ => None,
+    }
+  }
+}
+
+fn is_id(id: &str) -> bool {
+  !id.is_empty() && id.bytes().all(|byte| byte.is_ascii_alphanumeric())
+}
+
+/// `open.spotify.com/track/...` to `spotify:track:...`, the kind and id get checked after
+fn from_url(url: &str) -> Option<String> {
+  let url = url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")).unwrap_or(url);
+  let path = url.strip_prefix("open.spotify.com/")?;
+  let path = path.split(['?', '#']).next()?;
+  // localized links look like /intl-de/track/...
+  let mut segments = path.split('/').filter(|segment| !segment.is_empty()).skip_while(|segment| segment.starts_with("intl-"));
+
+  match (segments.next()?, segments.next(), segments.next()) {
+    ("collection", Some("tracks") | None, None) => Some("spotify:collection".to_string()),
+    (kind, Some(id), None) => Some(format!("spotify:{}:{}", kind, id)),
+    _ => None,
+  }
+}
+
+impl Display for SpotifyUri {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.0)
+  }
+}
+
+impl FromStr for SpotifyUri {
+  type Err = SpotifyError;
+
+  fn from_str(input: &str) -> SpotifyResult<Self> {
+    Self::parse(input)
+  }
+}
+
+/// Kept as is without checking it, same as deserializing, use [SpotifyUri::parse] to check it
+impl From<String> for SpotifyUri {
+  fn from(uri: String) -> Self {
+    Self(uri)
+  }
+}
+
+/// Kept as is without checking it, same as deserializing, use [SpotifyUri::parse] to check it
+impl From<&str> for SpotifyUri {
+  fn from(uri: &str) -> Self {
+    Self(uri.to_string())
+  }
+}
+
+impl From<SpotifyUri> for String {
+  fn from(uri: SpotifyUri) -> Self {
+    uri.0
+  }
+}
+
+impl AsRef<str> for SpotifyUri {
+  fn as_ref(&self) -> &str {
+    &self.0
+  }
+}
+
+impl PartialEq<str> for SpotifyUri {
+  fn eq(&self, other: &str) -> bool {
+    self.0 == other
+  }
+}
+
+impl PartialEq<&str> for SpotifyUri {
+  fn eq(&self, other: &&str) -> bool {
+    self.0 == *other
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ID: &str = "4uLU6hMCjMI75M1A2tKUQC";
+
+  fn parse(input: &str) -> SpotifyUri {
+    SpotifyUri::parse(input).unwrap_or_else(|err| panic!("{:?} didn't parse: {}", input, err))
+  }
+
+  #[test]
+  fn uris() {
+    let uri = parse(&format!("spotify:track:{}", ID));
+
+    assert_eq!(uri.kind(), Some(UriKind::Track));
+    assert_eq!(uri.id(), Some(ID));
+    assert_eq!(uri.to_url().as_deref(), Some(format!("https://open.spotify.com/track/{}", ID).as_str()));
+    assert_eq!(parse(&format!("  spotify:episode:{} ", ID)).kind(), Some(UriKind::Episode));
+  }
+
+  #[test]
+  fn links() {
+    for link in [
+      format!("https://open.spotify.com/track/{}", ID),
+      format!("http://open.spotify.com/track/{}", ID),
+      format!("open.spotify.com/track/{}", ID),
+      format!("https://open.spotify.com/track/{}?si=abc123&context=spotify", ID),
+      format!("https://open.spotify.com/track/{}#fragment", ID),
+      format!("https://open.spotify.com/track/{}/", ID),
+      format!("https://open.spotify.com/intl-de/track/{}", ID),
+      format!("https://open.spotify.com/intl-pt/track/{}?si=abc", ID),
+    ] {
+      assert_eq!(parse(&link), format!("spotify:track:{}", ID).as_str(), "{}", link);
+    }
+  }
+
+  #[test]
+  fn legacy_playlists() {
+    let uri = parse(&format!("spotify:user:someone:playlist:{}", ID));
+
+    assert_eq!(uri.kind(), Some(UriKind::Playlist));
+    assert_eq!(uri.id(), Some(ID));
+    // the owner isn't part of links anymore
+    assert_eq!(uri.to_url().as_deref(), Some(format!("https://open.spotify.com/playlist/{}", ID).as_str()));
+  }
+
+  #[test]
+  fn without_ids() {
+    for uri in ["spotify:collection", "spotify:collection:tracks", "spotify:user:someone:collection"] {
+      assert_eq!(parse(uri).kind(), Some(UriKind::Collection), "{}", uri);
+      assert_eq!(parse(uri).to_url().as_deref(), Some("https://open.spotify.com/collection/tracks"), "{}", uri);
+    }
+
+    assert_eq!(parse("https://open.spotify.com/collection/tracks").kind(), Some(UriKind::Collection));
+
+    let local = parse("spotify:local:Artist:Album:Title:180");
+    assert_eq!(local.kind(), Some(UriKind::Local));
+    assert_eq!(local.id(), None);
+    assert_eq!(local.to_url(), None);
+
+    let user = parse("spotify:user:someone");
+    assert_eq!(user.kind(), Some(UriKind::User));
+    assert_eq!(user.id(), Some("someone"));
+  }
+
+  #[test]
+  fn invalid() {
+    for input in [
+      "",
+      "spotify:",
+      "spotify:track",
+      "spotify:track:",
+      "spotify:track:not-base62",
+      "spotify:song:4uLU6hMCjMI75M1A2tKUQC",
+      "spotify:user:",
+      "https://open.spotify.com/",
+      "https://open.spotify.com/intl-de/",
+      "https://open.spotify.com/track/a/b",
+      "https://example.com/track/4uLU6hMCjMI75M1A2tKUQC",
+    ] {
+      assert!(SpotifyUri::parse(input).is_err(), "{:?} parsed", input);
+    }
+
+    // kept as is, only parse checks it
+    assert_eq!(SpotifyUri::from("nonsense").kind(), None);
+  }
+}
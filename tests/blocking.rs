@@ -0,0 +1,122 @@
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use spotify_info::{Codec, IpNet, Listener, SpotifyError, PROTOCOL_VERSION, SPOTIFY_ORIGIN};
+use tungstenite::client::IntoClientRequest;
+use tungstenite::http::header;
+use tungstenite::Message;
+
+fn listener() -> Listener {
+  Listener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).unwrap()
+}
+
+/// Upgrades from another thread with `origin` without saying hello,
+/// the socket stays open until the sender is dropped
+fn connect(listener: &Listener, origin: &'static str) -> mpsc::Sender<()> {
+  connect_with(listener, origin, None)
+}
+
+/// Same as [connect] but sends `hello` after upgrading
+fn connect_with(listener: &Listener, origin: &'static str, hello: Option<String>) -> mpsc::Sender<()> {
+  let addr = listener.listener.local_addr().unwrap();
+  let (done, wait) = mpsc::channel::<()>();
+
+  thread::spawn(move || {
+    let mut request = format!("ws://{}/", addr).into_client_request().unwrap();
+    request.headers_mut().insert(header::ORIGIN, origin.parse().unwrap());
+
+    let mut ws = tungstenite::client(request, TcpStream::connect(addr).unwrap()).map(|(ws, _)| ws);
+
+    if let (Ok(ws), Some(hello)) = (&mut ws, hello) {
+      let _ = ws.write_message(Message::Text(hello));
+    }
+
+    let _ = wait.recv();
+  });
+
+  done
+}
+
+fn hello(codecs: &[&str]) -> Option<String> {
+  Some(format!(r#"{{"type":"Hello","data":{{"protocol_version":{},"codecs":{:?}}}}}"#, PROTOCOL_VERSION, codecs))
+}
+
+#[test]
+fn handshake_times_out() {
+  let listener = listener().handshake_timeout(Duration::from_millis(200));
+  // connects but never upgrades
+  let _stream = TcpStream::connect(listener.listener.local_addr().unwrap()).unwrap();
+
+  assert!(matches!(listener.accept(), Err(SpotifyError::Timeout)));
+}
+
+#[test]
+fn hello_times_out() {
+  let listener = listener().handshake_timeout(Duration::from_millis(200));
+  // upgrades but never says hello
+  let _client = connect(&listener, SPOTIFY_ORIGIN);
+
+  assert!(matches!(listener.accept(), Err(SpotifyError::Timeout)));
+}
+
+#[test]
+fn origin_not_allowed() {
+  let listener = listener().allowed_origins([SPOTIFY_ORIGIN]);
+  let _client = connect(&listener, "https://example.com");
+
+  match listener.accept() {
+    Err(SpotifyError::OriginNotAllowed(origin)) => assert_eq!(origin.as_deref(), Some("https://example.com")),
+    result => panic!("expected OriginNotAllowed, got {:?}", result),
+  }
+}
+
+#[test]
+fn peer_not_allowed() {
+  let listener = listener().allowed_peers(["10.0.0.0/8".parse::<IpNet>().unwrap()]);
+  let _stream = TcpStream::connect(listener.listener.local_addr().unwrap()).unwrap();
+
+  assert!(matches!(listener.accept(), Err(SpotifyError::PeerNotAllowed(_))));
+}
+
+#[test]
+fn huge_handshake_timeout() {
+  let listener = listener().handshake_timeout(Duration::MAX);
+  let _client = connect_with(&listener, SPOTIFY_ORIGIN, hello(&["json"]));
+
+  listener.accept().unwrap();
+}
+
+#[test]
+fn codecs_limit_what_gets_picked() {
+  let listener = listener().codecs([Codec::Json]);
+  let _client = connect_with(&listener, SPOTIFY_ORIGIN, hello(&["msgpack", "cbor", "json"]));
+
+  assert_eq!(listener.accept().unwrap().codec(), Codec::Json);
+}
+
+#[test]
+fn max_connections() {
+  let listener = listener().max_connections(Some(1));
+  let _first = connect_with(&listener, SPOTIFY_ORIGIN, hello(&["json"]));
+  let connection = listener.accept().unwrap();
+  assert_eq!(listener.connection_count(), 1);
+
+  let _second = connect_with(&listener, SPOTIFY_ORIGIN, hello(&["json"]));
+  assert!(matches!(listener.accept(), Err(SpotifyError::TooManyConnections)));
+
+  drop(connection);
+  assert_eq!(listener.connection_count(), 0);
+
+  let _third = connect_with(&listener, SPOTIFY_ORIGIN, hello(&["json"]));
+  listener.accept().unwrap();
+}
+
+#[test]
+fn max_message_size() {
+  let listener = listener().max_message_size(Some(16));
+  let _client = connect_with(&listener, SPOTIFY_ORIGIN, hello(&["json"]));
+
+  assert!(matches!(listener.accept(), Err(SpotifyError::WebSocket(_))));
+}
@@ -0,0 +1,115 @@
+use std::time::Duration;
+
+use spotify_info::{Codec, Keepalive, MockSpotifyClient, SpotifyConnection, SpotifyEvent, SpotifyListener, SpotifyMessage, TrackInfo, TrackState};
+use tokio::io::DuplexStream;
+use tokio::net::TcpStream;
+
+async fn next(connection: &mut SpotifyConnection<Codec, DuplexStream>) -> SpotifyEvent {
+  tokio::time::timeout(Duration::from_secs(5), connection.next()).await
+    .expect("event in time")
+    .expect("connection open")
+    .expect("valid event")
+}
+
+#[tokio::test]
+async fn events_arrive_in_order() {
+  let acceptor = SpotifyListener::builder().keepalive(None).acceptor().unwrap();
+  let (mut spotify, mut connection) = MockSpotifyClient::pair(&acceptor).await.unwrap();
+
+  let track = TrackInfo::builder().uid("a").title("Song").artist("Artist").duration(Duration::from_secs(60)).build().unwrap();
+  spotify.send_track_changed(track.clone()).await.unwrap();
+  spotify.send_state(TrackState::Playing).await.unwrap();
+
+  match next(&mut connection).await {
+    SpotifyEvent::TrackChanged(info) => assert_eq!(info, track),
+    event => panic!("expected TrackChanged, got {:?}", event),
+  }
+
+  assert!(matches!(next(&mut connection).await, SpotifyEvent::StateChanged(TrackState::Playing)));
+}
+
+#[tokio::test]
+async fn advance_time_moves_the_position() {
+  let acceptor = SpotifyListener::builder().keepalive(None).acceptor().unwrap();
+  let (mut spotify, mut connection) = MockSpotifyClient::pair(&acceptor).await.unwrap();
+
+  spotify.send_track_changed(TrackInfo::builder().uid("a").title("Song").duration(Duration::from_secs(60)).build().unwrap()).await.unwrap();
+  spotify.send_state(TrackState::Playing).await.unwrap();
+  spotify.advance_time(Duration::from_secs(30)).await.unwrap();
+  // only ever goes up to the end of the track
+  spotify.advance_time(Duration::from_secs(60)).await.unwrap();
+
+  let mut positions = Vec::new();
+
+  while positions.len() < 2 {
+    if let SpotifyEvent::ProgressChanged { position, percent, .. } = next(&mut connection).await {
+      positions.push((position, percent));
+    }
+  }
+
+  assert_eq!(positions, vec![(Duration::from_secs(30), 0.5), (Duration::from_secs(60), 1.0)]);
+}
+
+#[tokio::test]
+async fn advance_time_with_odd_rates() {
+  let acceptor = SpotifyListener::builder().keepalive(None).acceptor().unwrap();
+  let (mut spotify, _connection) = MockSpotifyClient::pair(&acceptor).await.unwrap();
+
+  spotify.send_track_changed(TrackInfo::builder().uid("a").title("Song").build().unwrap()).await.unwrap();
+  spotify.send_state(TrackState::Playing).await.unwrap();
+
+  for rate in [f32::INFINITY, f32::NAN, -1.0, 1e30] {
+    spotify.send(SpotifyEvent::PlaybackRateChanged(rate)).await.unwrap();
+    spotify.advance_time(Duration::MAX).await.unwrap();
+  }
+}
+
+#[tokio::test]
+async fn messages_reach_the_mock() {
+  let acceptor = SpotifyListener::builder().keepalive(None).acceptor().unwrap();
+  let (mut spotify, mut connection) = MockSpotifyClient::pair(&acceptor).await.unwrap();
+
+  connection.send(SpotifyMessage::Next).await.unwrap();
+
+  assert_eq!(spotify.recv().await.unwrap().unwrap(), SpotifyMessage::Next);
+
+  spotify.close().await.unwrap();
+  assert!(connection.next().await.is_none());
+}
+
+#[tokio::test]
+async fn slow_handshakes_dont_hold_up_others() {
+  let listener = SpotifyListener::builder().addr(([127, 0, 0, 1], 0).into()).handshake_timeout(Duration::from_secs(30)).bind().await.unwrap();
+  let addr = listener.local_addr().unwrap();
+
+  // connects but never says anything
+  let _slow = TcpStream::connect(addr).await.unwrap();
+  let accept = tokio::time::timeout(Duration::from_secs(5), listener.get_connection());
+  let (connection, spotify) = tokio::join!(accept, MockSpotifyClient::connect(addr, None));
+
+  connection.expect("connection before the slow one times out").unwrap();
+  spotify.unwrap();
+}
+
+#[tokio::test]
+async fn huge_timeouts_never_fire() {
+  let acceptor = SpotifyListener::builder()
+    .keepalive(Some(Keepalive { interval: Duration::MAX, timeout: Duration::MAX }))
+    .idle_timeout(Some(Duration::MAX))
+    .progress_throttle(Some(Duration::MAX))
+    .session_gap(Some(Duration::MAX))
+    .acceptor()
+    .unwrap();
+  let (mut spotify, mut connection) = MockSpotifyClient::pair(&acceptor).await.unwrap();
+
+  spotify.send_track_changed(TrackInfo::builder().uid("a").title("Song").duration(Duration::from_secs(60)).build().unwrap()).await.unwrap();
+  spotify.send_state(TrackState::Playing).await.unwrap();
+  spotify.send_progress(Duration::from_secs(1)).await.unwrap();
+  // held back by the throttle
+  spotify.send_progress(Duration::from_secs(2)).await.unwrap();
+  spotify.send_state(TrackState::Paused).await.unwrap();
+
+  while !matches!(next(&mut connection).await, SpotifyEvent::StateChanged(TrackState::Paused)) {}
+
+  assert!(tokio::time::timeout(Duration::from_millis(100), connection.next()).await.is_err());
+}
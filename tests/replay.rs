@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use spotify_info::{EventReplayer, RecordedEvent, SpotifyEvent, TrackState};
+
+fn recording() -> Vec<RecordedEvent> {
+  vec![
+    RecordedEvent { at: Duration::from_secs(3600), event: SpotifyEvent::StateChanged(TrackState::Playing) },
+    RecordedEvent { at: Duration::from_secs(7200), event: SpotifyEvent::StateChanged(TrackState::Paused) },
+  ]
+}
+
+#[tokio::test]
+async fn infinite_speed_sends_everything() {
+  let mut connection = EventReplayer::new(recording()).speed(f64::INFINITY).connection().await.unwrap();
+
+  for state in [TrackState::Playing, TrackState::Paused] {
+    let event = tokio::time::timeout(Duration::from_secs(5), connection.next()).await.expect("event in time");
+    assert!(matches!(event, Some(Ok(SpotifyEvent::StateChanged(got))) if got == state));
+  }
+
+  assert!(connection.next().await.is_none());
+}
+
+#[tokio::test]
+async fn invalid_speeds_keep_the_timing() {
+  for speed in [0.0, -1.0, f64::NAN, f64::NEG_INFINITY] {
+    let mut connection = EventReplayer::new(recording()).speed(speed).repeat(true).connection().await.unwrap();
+
+    assert!(tokio::time::timeout(Duration::from_millis(200), connection.next()).await.is_err(), "speed {} sent right away", speed);
+  }
+}